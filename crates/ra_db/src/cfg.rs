@@ -0,0 +1,226 @@
+//! Conditional compilation: the set of cfg flags a crate is compiled with,
+//! and evaluation of `#[cfg(...)]` predicates against that set.
+use rustc_hash::FxHashSet;
+use ra_syntax::{
+    SmolStr, SyntaxNode,
+    SyntaxKind::*,
+    ast::{self, AstNode, AttrsOwner},
+};
+
+/// The set of cfg flags enabled for a crate: plain atoms like `unix` or
+/// `test`, and key-value pairs like `target_os = "linux"` or
+/// `feature = "serde"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CfgOptions {
+    atoms: FxHashSet<SmolStr>,
+    key_values: FxHashSet<(SmolStr, SmolStr)>,
+}
+
+impl CfgOptions {
+    pub fn insert_atom(&mut self, key: SmolStr) {
+        self.atoms.insert(key);
+    }
+
+    pub fn insert_key_value(&mut self, key: SmolStr, value: SmolStr) {
+        self.key_values.insert((key, value));
+    }
+
+    /// Evaluates a cfg predicate. Returns `None` if the predicate is
+    /// malformed, in which case we can't say anything about it.
+    pub fn check(&self, cfg: &CfgExpr) -> Option<bool> {
+        cfg.fold(&|key, value| match value {
+            None => self.atoms.contains(key),
+            Some(value) => self.key_values.contains(&(key.clone(), value.clone())),
+        })
+    }
+
+    /// Checks the `#[cfg]` attributes of a node. Malformed predicates are
+    /// treated as enabled, so that we don't hide code we don't understand.
+    pub fn is_cfg_enabled(&self, node: &impl AttrsOwner) -> bool {
+        match CfgExpr::from_attrs(node) {
+            Some(cfg) => self.check(&cfg) != Some(false),
+            None => true,
+        }
+    }
+}
+
+/// A parsed cfg predicate, the argument of `#[cfg(...)]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CfgExpr {
+    Invalid,
+    Atom(SmolStr),
+    KeyValue { key: SmolStr, value: SmolStr },
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Parses the predicate of a `#[cfg(...)]` attribute, if `attr` is one.
+    pub fn from_attr(attr: &ast::Attr) -> Option<CfgExpr> {
+        match attr.as_call()? {
+            (ref name, tt) if name == "cfg" => Some(CfgExpr::parse(tt)),
+            _ => None,
+        }
+    }
+
+    /// Combines all `#[cfg]` attributes of a node into a single predicate.
+    /// Returns `None` if the node is not cfg'd at all.
+    pub fn from_attrs(node: &impl AttrsOwner) -> Option<CfgExpr> {
+        let mut cfgs: Vec<CfgExpr> = node.attrs().filter_map(CfgExpr::from_attr).collect();
+        match cfgs.len() {
+            0 => None,
+            1 => cfgs.pop(),
+            _ => Some(CfgExpr::All(cfgs)),
+        }
+    }
+
+    /// Parses the contents of the token tree `(...)` of a `cfg` attribute.
+    pub fn parse(tt: &ast::TokenTree) -> CfgExpr {
+        let mut preds = parse_list(tt);
+        if preds.len() == 1 {
+            preds.pop().unwrap()
+        } else {
+            CfgExpr::Invalid
+        }
+    }
+
+    /// Evaluates the predicate, using `query` to check individual atoms and
+    /// key-value pairs. Malformed parts evaluate to `None`, which propagates
+    /// unless the result is already decided by the other operands.
+    pub fn fold(&self, query: &dyn Fn(&SmolStr, Option<&SmolStr>) -> bool) -> Option<bool> {
+        match self {
+            CfgExpr::Invalid => None,
+            CfgExpr::Atom(key) => Some(query(key, None)),
+            CfgExpr::KeyValue { key, value } => Some(query(key, Some(value))),
+            CfgExpr::All(preds) => {
+                let results: Vec<_> = preds.iter().map(|pred| pred.fold(query)).collect();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else if results.contains(&None) {
+                    None
+                } else {
+                    Some(true)
+                }
+            }
+            CfgExpr::Any(preds) => {
+                let results: Vec<_> = preds.iter().map(|pred| pred.fold(query)).collect();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else if results.contains(&None) {
+                    None
+                } else {
+                    Some(false)
+                }
+            }
+            CfgExpr::Not(pred) => pred.fold(query).map(|it| !it),
+        }
+    }
+}
+
+fn parse_list(tt: &ast::TokenTree) -> Vec<CfgExpr> {
+    let tokens: Vec<&SyntaxNode> = tt
+        .syntax()
+        .children()
+        .filter(|it| !it.kind().is_trivia())
+        .collect();
+    // Strip the delimiters.
+    if tokens.len() < 2 {
+        return Vec::new();
+    }
+    let tokens = &tokens[1..tokens.len() - 1];
+    tokens
+        .split(|it| it.kind() == COMMA)
+        .filter(|pred| !pred.is_empty())
+        .map(parse_pred)
+        .collect()
+}
+
+fn parse_pred(tokens: &[&SyntaxNode]) -> CfgExpr {
+    let name = match tokens.first() {
+        Some(it) if it.kind() == IDENT => it.leaf_text().unwrap().clone(),
+        _ => return CfgExpr::Invalid,
+    };
+    match &tokens[1..] {
+        [] => CfgExpr::Atom(name),
+        [eq, value] if eq.kind() == EQ && value.kind() == STRING => {
+            let value = value.leaf_text().unwrap();
+            let value = value.trim_start_matches('"').trim_end_matches('"');
+            CfgExpr::KeyValue {
+                key: name,
+                value: value.into(),
+            }
+        }
+        [args] if args.kind() == TOKEN_TREE => {
+            let args = ast::TokenTree::cast(args).unwrap();
+            let mut preds = parse_list(args);
+            match name.as_str() {
+                "all" => CfgExpr::All(preds),
+                "any" => CfgExpr::Any(preds),
+                "not" if preds.len() == 1 => CfgExpr::Not(Box::new(preds.pop().unwrap())),
+                _ => CfgExpr::Invalid,
+            }
+        }
+        _ => CfgExpr::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::{
+        SourceFile,
+        ast::{self, AstNode},
+    };
+
+    use super::*;
+
+    fn check(cfg: &str, expected: Option<bool>) {
+        let source = format!("#[cfg({})] fn f() {{}}", cfg);
+        let file = SourceFile::parse(&source);
+        let attr = file
+            .syntax()
+            .descendants()
+            .find_map(ast::Attr::cast)
+            .unwrap();
+        let cfg = CfgExpr::from_attr(attr).unwrap();
+
+        let mut opts = CfgOptions::default();
+        opts.insert_atom("unix".into());
+        opts.insert_atom("test".into());
+        opts.insert_key_value("target_os".into(), "linux".into());
+        opts.insert_key_value("feature".into(), "std".into());
+        assert_eq!(opts.check(&cfg), expected, "{}", source);
+    }
+
+    #[test]
+    fn cfg_atoms_and_key_values() {
+        check("unix", Some(true));
+        check("windows", Some(false));
+        check(r#"target_os = "linux""#, Some(true));
+        check(r#"target_os = "macos""#, Some(false));
+        check(r#"feature = "std""#, Some(true));
+    }
+
+    #[test]
+    fn cfg_combinators() {
+        check("not(windows)", Some(true));
+        check("not(test)", Some(false));
+        check(r#"all(unix, feature = "std")"#, Some(true));
+        check("all(unix, windows)", Some(false));
+        check("all()", Some(true));
+        check("any(unix, windows)", Some(true));
+        check("any(windows, not(unix))", Some(false));
+        check("any()", Some(false));
+    }
+
+    #[test]
+    fn cfg_invalid() {
+        check("", None);
+        check("unix, windows", None);
+        check("not(unix, windows)", None);
+        check("foo(unix)", None);
+        check("all(unix, foo(bar))", None);
+        check("all(windows, foo(bar))", Some(false));
+        check("any(unix, foo(bar))", Some(true));
+    }
+}
@@ -5,12 +5,16 @@
 /// Note that neither this module, nor any other part of the analyzer's core do
 /// actual IO. See `vfs` and `project_model` in the `ra_lsp_server` crate for how
 /// actual IO is done and lowered to input.
+use std::path::PathBuf;
+
 use relative_path::RelativePathBuf;
 use rustc_hash::FxHashMap;
 
 use ra_syntax::SmolStr;
 use rustc_hash::FxHashSet;
 
+use crate::cfg::CfgOptions;
+
 /// `FileId` is an integer which uniquely identifies a file. File paths are
 /// messy and system-dependent, so most of the code should work directly with
 /// `FileId`, without inspecting the path. The mapping between `FileId` and path
@@ -23,20 +27,23 @@ pub struct FileId(pub u32);
 /// file systems which is watched for changes. Typically it corresponds to a
 /// Rust crate. Source roots *might* be nested: in this case, a file belongs to
 /// the nearest enclosing source root. Paths to files are always relative to a
-/// source root, and the analyzer does not know the root path of the source root at
-/// all. So, a file from one source root can't refer to a file in another source
-/// root by path.
+/// source root. The analyzer might know the path of the source root itself
+/// (see `SourceRoot::path`), but it is used only for the rare cases where a
+/// file from one source root refers to a file in another source root by path,
+/// like `#[path = "../../other/foo.rs"] mod foo;`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct SourceRootId(pub u32);
 
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct SourceRoot {
     pub files: FxHashMap<RelativePathBuf, FileId>,
+    /// Absolute path to the directory of the source root, if known.
+    pub path: Option<PathBuf>,
 }
 
 /// `CrateGraph` is a bit of information which turns a set of text files into a
 /// number of Rust crates. Each crate is defined by the `FileId` of its root module,
-/// the set of cfg flags (not yet implemented) and the set of dependencies. Note
+/// the edition, the set of cfg flags and the set of dependencies. Note
 /// that, due to cfg's, there might be several crates for a single `FileId`! As
 /// in the rust-lang proper, a crate does not have a name. Instead, names are
 /// specified on dependency edges. That is, a crate might be known under
@@ -56,9 +63,29 @@ pub struct CyclicDependencies;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CrateId(pub u32);
 
+/// The edition of a crate. It changes the meaning of paths: in 2015, `use`
+/// paths and `::foo` are relative to the crate root, in 2018 `::foo` refers to
+/// an extern crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edition {
+    Edition2015,
+    Edition2018,
+}
+
+impl Edition {
+    pub fn from_string(s: &str) -> Edition {
+        match s {
+            "2015" => Edition::Edition2015,
+            _ => Edition::Edition2018,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CrateData {
     file_id: FileId,
+    edition: Edition,
+    cfg_options: CfgOptions,
     dependencies: Vec<Dependency>,
 }
 
@@ -66,6 +93,8 @@ impl CrateData {
     fn new(file_id: FileId) -> CrateData {
         CrateData {
             file_id,
+            edition: Edition::Edition2018,
+            cfg_options: CfgOptions::default(),
             dependencies: Vec::new(),
         }
     }
@@ -94,6 +123,12 @@ impl CrateGraph {
         assert!(prev.is_none());
         crate_id
     }
+    pub fn set_edition(&mut self, crate_id: CrateId, edition: Edition) {
+        self.arena.get_mut(&crate_id).unwrap().edition = edition;
+    }
+    pub fn set_cfg_options(&mut self, crate_id: CrateId, cfg_options: CfgOptions) {
+        self.arena.get_mut(&crate_id).unwrap().cfg_options = cfg_options;
+    }
     pub fn add_dep(
         &mut self,
         from: CrateId,
@@ -108,9 +143,18 @@ impl CrateGraph {
     pub fn is_empty(&self) -> bool {
         self.arena.is_empty()
     }
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = CrateId> + 'a {
+        self.arena.keys().cloned()
+    }
     pub fn crate_root(&self, crate_id: CrateId) -> FileId {
         self.arena[&crate_id].file_id
     }
+    pub fn edition(&self, crate_id: CrateId) -> Edition {
+        self.arena[&crate_id].edition
+    }
+    pub fn cfg_options(&self, crate_id: CrateId) -> &CfgOptions {
+        &self.arena[&crate_id].cfg_options
+    }
     pub fn crate_id_for_crate_root(&self, file_id: FileId) -> Option<CrateId> {
         let (&crate_id, _) = self
             .arena
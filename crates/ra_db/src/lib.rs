@@ -1,5 +1,6 @@
 //! ra_db defines basic database traits. The concrete DB is defined by ra_ide_api.
 mod cancellation;
+mod cfg;
 mod input;
 mod loc2id;
 pub mod mock;
@@ -14,8 +15,9 @@ use relative_path::RelativePathBuf;
 pub use ::salsa as salsa;
 pub use crate::{
     cancellation::Canceled,
+    cfg::{CfgOptions, CfgExpr},
     input::{
-        FileId, CrateId, SourceRoot, SourceRootId, CrateGraph, Dependency,
+        FileId, CrateId, SourceRoot, SourceRootId, CrateGraph, Dependency, Edition,
     },
     loc2id::LocationIntener,
 };
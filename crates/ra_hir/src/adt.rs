@@ -4,13 +4,14 @@
 use std::sync::Arc;
 
 use ra_arena::{RawId, Arena, impl_arena_id};
+use ra_db::CfgOptions;
 use ra_syntax::{
     TreeArc,
     ast::{self, NameOwner, StructFlavor}
 };
 
 use crate::{
-    Name, AsName, Struct, Enum, EnumVariant, Crate,
+    Name, AsName, Struct, Enum, EnumVariant, Crate, Module,
     HirDatabase, HirFileId, StructField, FieldSource,
     type_ref::TypeRef,
 };
@@ -42,27 +43,38 @@ impl Struct {
 pub struct StructData {
     pub(crate) name: Option<Name>,
     pub(crate) variant_data: Arc<VariantData>,
+    pub(crate) is_union: bool,
 }
 
 impl StructData {
-    fn new(struct_def: &ast::StructDef) -> StructData {
+    fn new(struct_def: &ast::StructDef, cfg_options: &CfgOptions) -> StructData {
         let name = struct_def.name().map(|n| n.as_name());
-        let variant_data = VariantData::new(struct_def.flavor());
+        let variant_data = VariantData::new(struct_def.flavor(), cfg_options);
         let variant_data = Arc::new(variant_data);
-        StructData { name, variant_data }
+        StructData {
+            name,
+            variant_data,
+            is_union: struct_def.is_union(),
+        }
     }
 
     pub(crate) fn struct_data_query(db: &impl HirDatabase, struct_: Struct) -> Arc<StructData> {
         let (_, struct_def) = struct_.source(db);
-        Arc::new(StructData::new(&*struct_def))
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(struct_.module(db).krate);
+        Arc::new(StructData::new(&*struct_def, cfg_options))
     }
 }
 
-fn variants(enum_def: &ast::EnumDef) -> impl Iterator<Item = &ast::EnumVariant> {
+fn variants<'a>(
+    enum_def: &'a ast::EnumDef,
+    cfg_options: &'a CfgOptions,
+) -> impl Iterator<Item = &'a ast::EnumVariant> + 'a {
     enum_def
         .variant_list()
         .into_iter()
         .flat_map(|it| it.variants())
+        .filter(move |it| cfg_options.is_cfg_enabled(*it))
 }
 
 impl EnumVariant {
@@ -71,7 +83,9 @@ impl EnumVariant {
         db: &impl HirDatabase,
     ) -> (HirFileId, TreeArc<ast::EnumVariant>) {
         let (file_id, enum_def) = self.parent.source(db);
-        let var = variants(&*enum_def)
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(self.module(db).krate);
+        let var = variants(&*enum_def, cfg_options)
             .zip(db.enum_data(self.parent).variants.iter())
             .find(|(_syntax, (id, _))| *id == self.id)
             .unwrap()
@@ -96,10 +110,12 @@ impl EnumData {
     pub(crate) fn enum_data_query(db: &impl HirDatabase, e: Enum) -> Arc<EnumData> {
         let (_file_id, enum_def) = e.source(db);
         let name = enum_def.name().map(|n| n.as_name());
-        let variants = variants(&*enum_def)
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(e.module(db).krate);
+        let variants = variants(&*enum_def, cfg_options)
             .map(|var| EnumVariantData {
                 name: var.name().map(|it| it.as_name()),
-                variant_data: Arc::new(VariantData::new(var.flavor())),
+                variant_data: Arc::new(VariantData::new(var.flavor(), cfg_options)),
             })
             .collect();
         Arc::new(EnumData { name, variants })
@@ -145,14 +161,23 @@ impl VariantData {
             _ => None,
         }
     }
+
+    /// Whether the fields have names, like in `struct S { a: u32 }`.
+    pub(crate) fn is_struct(&self) -> bool {
+        match self.0 {
+            VariantDataInner::Struct(_) => true,
+            VariantDataInner::Tuple(_) | VariantDataInner::Unit => false,
+        }
+    }
 }
 
 impl VariantData {
-    fn new(flavor: StructFlavor) -> Self {
+    fn new(flavor: StructFlavor, cfg_options: &CfgOptions) -> Self {
         let inner = match flavor {
             ast::StructFlavor::Tuple(fl) => {
                 let fields = fl
                     .fields()
+                    .filter(|fd| cfg_options.is_cfg_enabled(*fd))
                     .enumerate()
                     .map(|(i, fd)| StructFieldData {
                         name: Name::tuple_field_name(i),
@@ -164,6 +189,7 @@ impl VariantData {
             ast::StructFlavor::Named(fl) => {
                 let fields = fl
                     .fields()
+                    .filter(|fd| cfg_options.is_cfg_enabled(*fd))
                     .map(|fd| StructFieldData {
                         name: fd.name().map(|n| n.as_name()).unwrap_or_else(Name::missing),
                         type_ref: TypeRef::from_ast_opt(fd.type_ref()),
@@ -197,6 +223,12 @@ impl VariantDef {
             VariantDef::EnumVariant(it) => it.variant_data(db),
         }
     }
+    pub(crate) fn module(self, db: &impl HirDatabase) -> Module {
+        match self {
+            VariantDef::Struct(it) => it.module(db),
+            VariantDef::EnumVariant(it) => it.module(db),
+        }
+    }
 }
 
 impl StructField {
@@ -218,13 +250,17 @@ impl StructField {
             }
         };
 
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(self.parent.module(db).krate);
         let field_sources = match struct_flavor {
             ast::StructFlavor::Tuple(fl) => fl
                 .fields()
+                .filter(|it| cfg_options.is_cfg_enabled(*it))
                 .map(|it| FieldSource::Pos(it.to_owned()))
                 .collect(),
             ast::StructFlavor::Named(fl) => fl
                 .fields()
+                .filter(|it| cfg_options.is_cfg_enabled(*it))
                 .map(|it| FieldSource::Named(it.to_owned()))
                 .collect(),
             ast::StructFlavor::Unit => Vec::new(),
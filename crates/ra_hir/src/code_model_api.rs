@@ -2,20 +2,25 @@ use std::sync::Arc;
 
 use relative_path::RelativePathBuf;
 use ra_db::{CrateId, FileId};
-use ra_syntax::{ast::self, TreeArc, SyntaxNode};
+use ra_syntax::{ast::{self, NameOwner}, TreeArc, SyntaxNode};
 
 use crate::{
-    Name, Path, PerNs, ScopesWithSyntaxMapping, Ty, HirFileId,
+    Name, AsName, Path, PerNs, ScopesWithSyntaxMapping, Ty, HirFileId,
     type_ref::TypeRef,
-    nameres::{ModuleScope, lower::ImportId},
+    nameres::{ModuleScope, lower::{ImportId, ImportSource}},
     db::HirDatabase,
-    expr::BodySyntaxMapping,
-    ty::InferenceResult,
+    expr::{Body, BodySyntaxMapping},
+    ty::{InferenceResult, traits::Environment},
     adt::{EnumVariantId, StructFieldId, VariantDef},
     generics::GenericParams,
+    traits::TraitItem,
+    impl_block::ImplBlock,
+    const_eval::ConstValue,
+    unsafe_check::UnsafeExpr,
+    mutability_check::{self, MutabilityDiagnostic},
     docs::{Documentation, Docs, docs_from_ast},
     module_tree::ModuleId,
-    ids::{FunctionId, StructId, EnumId, AstItemDef, ConstId, StaticId, TraitId, TypeId},
+    ids::{FunctionId, StructId, EnumId, AstItemDef, ConstId, StaticId, TraitId, TypeId, BlockId},
 };
 
 /// hir::Crate describes a single crate. It's the main interface with which
@@ -53,6 +58,10 @@ pub enum Def {
 pub struct Module {
     pub(crate) krate: CrateId,
     pub(crate) module_id: ModuleId,
+    /// Like in rustc, a block which declares items is an anonymous module
+    /// inside of the module `module_id`. Names are first looked up in the
+    /// scopes of the enclosing blocks, innermost first.
+    pub(crate) block: Option<BlockId>,
 }
 
 /// The defs which can be visible in the module.
@@ -81,6 +90,60 @@ impl_froms!(
     Type
 );
 
+/// The defs which have a body of expressions: functions, and the initializers
+/// of consts and statics and the discriminants of enum variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DefWithBody {
+    Function(Function),
+    Const(Const),
+    Static(Static),
+    EnumVariant(EnumVariant),
+}
+impl_froms!(DefWithBody: Function, Const, Static, EnumVariant);
+
+impl DefWithBody {
+    pub fn module(&self, db: &impl HirDatabase) -> Module {
+        match self {
+            DefWithBody::Function(it) => it.module(db),
+            DefWithBody::Const(it) => it.module(db),
+            DefWithBody::Static(it) => it.module(db),
+            DefWithBody::EnumVariant(it) => it.module(db),
+        }
+    }
+
+    pub fn body_syntax_mapping(&self, db: &impl HirDatabase) -> Arc<BodySyntaxMapping> {
+        db.body_syntax_mapping(*self)
+    }
+
+    pub fn scopes(&self, db: &impl HirDatabase) -> ScopesWithSyntaxMapping {
+        let scopes = db.fn_scopes(*self);
+        let syntax_mapping = db.body_syntax_mapping(*self);
+        ScopesWithSyntaxMapping {
+            scopes,
+            syntax_mapping,
+        }
+    }
+
+    pub fn infer(&self, db: &impl HirDatabase) -> Arc<InferenceResult> {
+        db.infer(*self)
+    }
+
+    /// The operations in the body which are only allowed in unsafe code.
+    pub fn unsafe_exprs(&self, db: &impl HirDatabase) -> Arc<Vec<UnsafeExpr>> {
+        db.unsafe_exprs(*self)
+    }
+
+    /// The bindings which are mutated without being declared `mut`, and the
+    /// `mut` ones which are never mutated.
+    pub fn mutability_diagnostics(&self, db: &impl HirDatabase) -> Vec<MutabilityDiagnostic> {
+        mutability_check::mutability_diagnostics(db, *self)
+    }
+
+    pub(crate) fn body(&self, db: &impl HirDatabase) -> Arc<Body> {
+        db.body_hir(*self)
+    }
+}
+
 pub enum ModuleSource {
     SourceFile(TreeArc<ast::SourceFile>),
     Module(TreeArc<ast::Module>),
@@ -117,12 +180,9 @@ impl Module {
         self.declaration_source_impl(db)
     }
 
-    /// Returns the syntax of the last path segment corresponding to this import
-    pub fn import_source(
-        &self,
-        db: &impl HirDatabase,
-        import: ImportId,
-    ) -> TreeArc<ast::PathSegment> {
+    /// Returns the syntax of the last path segment corresponding to this import,
+    /// or the `extern crate` item.
+    pub fn import_source(&self, db: &impl HirDatabase, import: ImportId) -> ImportSource {
         self.import_source_impl(db, import)
     }
 
@@ -148,7 +208,8 @@ impl Module {
         self.children_impl(db)
     }
 
-    /// Finds a parent module.
+    /// Finds a parent module. The parent of the anonymous module of a block is
+    /// the module containing the block.
     pub fn parent(&self, db: &impl HirDatabase) -> Option<Module> {
         self.parent_impl(db)
     }
@@ -163,9 +224,14 @@ impl Module {
         res
     }
 
-    /// Returns a `ModuleScope`: a set of items, visible in this module.
+    /// Returns a `ModuleScope`: a set of items, visible in this module. For
+    /// the anonymous module of a block, these are the items and imports
+    /// declared in the block.
     pub fn scope(&self, db: &impl HirDatabase) -> ModuleScope {
-        db.item_map(self.krate)[self.module_id].clone()
+        match self.block {
+            Some(block) => ModuleScope::clone(&db.block_scope(block)),
+            None => db.item_map(self.krate)[self.module_id].clone(),
+        }
     }
 
     pub fn resolve_path(&self, db: &impl HirDatabase, path: &Path) -> PerNs<ModuleDef> {
@@ -243,6 +309,12 @@ impl Struct {
         db.struct_data(*self).name.clone()
     }
 
+    /// Whether this is a `union` rather than a `struct`. Reading a union field
+    /// is unsafe.
+    pub fn is_union(&self, db: &impl HirDatabase) -> bool {
+        db.struct_data(*self).is_union
+    }
+
     pub fn fields(&self, db: &impl HirDatabase) -> Vec<StructField> {
         db.struct_data(*self)
             .variant_data
@@ -393,6 +465,8 @@ pub struct FnSignature {
     /// True if the first param is `self`. This is relevant to decide whether this
     /// can be called as a method.
     pub(crate) has_self_param: bool,
+    /// True for `unsafe fn`s, which can only be called in unsafe code.
+    pub(crate) is_unsafe: bool,
 }
 
 impl FnSignature {
@@ -413,6 +487,11 @@ impl FnSignature {
     pub fn has_self_param(&self) -> bool {
         self.has_self_param
     }
+
+    /// True for `unsafe fn`s, which can only be called in unsafe code.
+    pub fn is_unsafe(&self) -> bool {
+        self.is_unsafe
+    }
 }
 
 impl Function {
@@ -425,16 +504,11 @@ impl Function {
     }
 
     pub fn body_syntax_mapping(&self, db: &impl HirDatabase) -> Arc<BodySyntaxMapping> {
-        db.body_syntax_mapping(*self)
+        DefWithBody::from(*self).body_syntax_mapping(db)
     }
 
     pub fn scopes(&self, db: &impl HirDatabase) -> ScopesWithSyntaxMapping {
-        let scopes = db.fn_scopes(*self);
-        let syntax_mapping = db.body_syntax_mapping(*self);
-        ScopesWithSyntaxMapping {
-            scopes,
-            syntax_mapping,
-        }
+        DefWithBody::from(*self).scopes(db)
     }
 
     pub fn signature(&self, db: &impl HirDatabase) -> Arc<FnSignature> {
@@ -442,12 +516,17 @@ impl Function {
     }
 
     pub fn infer(&self, db: &impl HirDatabase) -> Arc<InferenceResult> {
-        db.infer(*self)
+        DefWithBody::from(*self).infer(db)
     }
 
     pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
         db.generic_params((*self).into())
     }
+
+    /// The where clauses which hold in the body of this function.
+    pub fn environment(&self, db: &impl HirDatabase) -> Arc<Environment> {
+        Environment::for_def(db, (*self).into())
+    }
 }
 
 impl Docs for Function {
@@ -465,6 +544,30 @@ impl Const {
     pub fn source(&self, db: &impl HirDatabase) -> (HirFileId, TreeArc<ast::ConstDef>) {
         self.id.source(db)
     }
+
+    pub fn module(&self, db: &impl HirDatabase) -> Module {
+        self.id.module(db)
+    }
+
+    pub fn infer(&self, db: &impl HirDatabase) -> Arc<InferenceResult> {
+        DefWithBody::from(*self).infer(db)
+    }
+
+    /// The value of the initializer, if it can be evaluated at compile time.
+    pub fn value(&self, db: &impl HirDatabase) -> Option<ConstValue> {
+        db.const_value(*self)
+    }
+
+    /// The declared type, e.g. `usize` in `const N: usize = 4;`.
+    pub(crate) fn type_ref(&self, db: &impl HirDatabase) -> TypeRef {
+        TypeRef::from_ast_opt(self.source(db).1.type_ref())
+    }
+
+    /// The containing impl block, if this is an associated const.
+    pub(crate) fn impl_block(&self, db: &impl HirDatabase) -> Option<ImplBlock> {
+        let module_impls = db.impls_in_module(self.module(db));
+        ImplBlock::containing(module_impls, (*self).into())
+    }
 }
 
 impl Docs for Const {
@@ -482,6 +585,25 @@ impl Static {
     pub fn source(&self, db: &impl HirDatabase) -> (HirFileId, TreeArc<ast::StaticDef>) {
         self.id.source(db)
     }
+
+    pub fn module(&self, db: &impl HirDatabase) -> Module {
+        self.id.module(db)
+    }
+
+    pub fn infer(&self, db: &impl HirDatabase) -> Arc<InferenceResult> {
+        DefWithBody::from(*self).infer(db)
+    }
+
+    /// The declared type, e.g. `u32` in `static COUNT: u32 = 0;`.
+    pub(crate) fn type_ref(&self, db: &impl HirDatabase) -> TypeRef {
+        TypeRef::from_ast_opt(self.source(db).1.type_ref())
+    }
+
+    /// Whether this is a `static mut`, which can only be accessed in unsafe
+    /// code.
+    pub fn is_mut(&self, db: &impl HirDatabase) -> bool {
+        self.source(db).1.is_mut()
+    }
 }
 
 impl Docs for Static {
@@ -500,6 +622,24 @@ impl Trait {
         self.id.source(db)
     }
 
+    pub fn module(&self, db: &impl HirDatabase) -> Module {
+        self.id.module(db)
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Option<Name> {
+        self.source(db).1.name().map(|name| name.as_name())
+    }
+
+    pub fn items(&self, db: &impl HirDatabase) -> Vec<TraitItem> {
+        db.trait_data(*self).items().to_vec()
+    }
+
+    /// Whether this is an `unsafe trait`, which can only be implemented by an
+    /// `unsafe impl`.
+    pub fn is_unsafe(&self, db: &impl HirDatabase) -> bool {
+        db.trait_data(*self).is_unsafe()
+    }
+
     pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
         db.generic_params((*self).into())
     }
@@ -521,6 +661,20 @@ impl Type {
         self.id.source(db)
     }
 
+    pub fn module(&self, db: &impl HirDatabase) -> Module {
+        self.id.module(db)
+    }
+
+    pub fn name(&self, db: &impl HirDatabase) -> Option<Name> {
+        self.source(db).1.name().map(|name| name.as_name())
+    }
+
+    /// The aliased type, e.g. `u32` in `type Item = u32;`. Associated types
+    /// declared in traits usually don't have one.
+    pub(crate) fn type_ref(&self, db: &impl HirDatabase) -> Option<TypeRef> {
+        self.source(db).1.type_ref().map(TypeRef::from_ast)
+    }
+
     pub fn generic_params(&self, db: &impl HirDatabase) -> Arc<GenericParams> {
         db.generic_params((*self).into())
     }
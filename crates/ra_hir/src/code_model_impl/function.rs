@@ -2,20 +2,21 @@ mod scope;
 
 use std::sync::Arc;
 
-use ra_syntax::ast::{self, NameOwner};
+use ra_syntax::ast::{self, AstNode, NameOwner};
 
 use crate::{
-    HirDatabase, Name, AsName, Function, FnSignature,
+    HirDatabase, Name, AsName, Function, FnSignature, Trait,
     type_ref::{TypeRef, Mutability},
     expr::Body,
     impl_block::ImplBlock,
+    ids::LocationCtx,
 };
 
 pub use self::scope::{FnScopes, ScopesWithSyntaxMapping, ScopeEntryWithSyntax};
 
 impl Function {
     pub(crate) fn body(&self, db: &impl HirDatabase) -> Arc<Body> {
-        db.body_hir(*self)
+        db.body_hir((*self).into())
     }
 
     /// The containing impl block, if this is a method.
@@ -23,6 +24,17 @@ impl Function {
         let module_impls = db.impls_in_module(self.module(db));
         ImplBlock::containing(module_impls, (*self).into())
     }
+
+    /// The containing trait, if this is a method declared in a trait.
+    pub(crate) fn parent_trait(&self, db: &impl HirDatabase) -> Option<Trait> {
+        let (file_id, node) = self.source(db);
+        let item_list = node.syntax().parent()?;
+        let trait_def = ast::TraitDef::cast(item_list.parent()?)?;
+        let ctx = LocationCtx::new(db, self.module(db), file_id);
+        Some(Trait {
+            id: ctx.to_def(trait_def),
+        })
+    }
 }
 
 impl FnSignature {
@@ -69,6 +81,7 @@ impl FnSignature {
             params,
             ret_type,
             has_self_param,
+            is_unsafe: node.is_unsafe(),
         };
         Arc::new(sig)
     }
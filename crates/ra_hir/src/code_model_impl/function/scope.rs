@@ -4,12 +4,13 @@ use rustc_hash::{FxHashMap, FxHashSet};
 
 use ra_syntax::{
     AstNode, SyntaxNode, TextUnit, TextRange, SyntaxNodePtr,
+    SyntaxKind::{IDENT, PATH_EXPR},
     algo::generate,
     ast,
 };
 use ra_arena::{Arena, RawId, impl_arena_id};
 
-use crate::{Name, AsName, expr::{PatId, ExprId, Pat, Expr, Body, Statement, BodySyntaxMapping}};
+use crate::{Name, AsName, SyntaxContext, expr::{PatId, ExprId, Pat, Expr, Body, Statement, BodySyntaxMapping}};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ScopeId(RawId);
@@ -26,6 +27,7 @@ pub struct FnScopes {
 pub struct ScopeEntry {
     name: Name,
     pat: PatId,
+    hygiene: SyntaxContext,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -62,10 +64,14 @@ impl FnScopes {
         context_expr: ExprId,
         name: Name,
     ) -> Option<&'a ScopeEntry> {
+        // Bindings from other syntax contexts neither match nor shadow
+        // anything.
+        let hygiene = self.body.expr_hygiene(context_expr);
         let mut shadowed = FxHashSet::default();
         let ret = self
             .scope_chain_for(context_expr)
             .flat_map(|scope| self.entries(scope).iter())
+            .filter(|entry| entry.hygiene == hygiene)
             .filter(|entry| shadowed.insert(entry.name()))
             .find(|entry| entry.name() == &name);
         ret
@@ -93,6 +99,7 @@ impl FnScopes {
                 let entry = ScopeEntry {
                     name: name.clone(),
                     pat,
+                    hygiene: body.pat_hygiene(pat),
                 };
                 self.scopes[scope].entries.push(entry)
             }
@@ -198,6 +205,9 @@ impl ScopesWithSyntaxMapping {
         let ret = self
             .scope_chain(name_ref.syntax())
             .flat_map(|scope| self.scopes.entries(scope).iter())
+            // Name references written in the source file can only see bindings
+            // from the source file.
+            .filter(|entry| entry.hygiene() == SyntaxContext::ROOT)
             .filter(|entry| shadowed.insert(entry.name()))
             .filter(|entry| entry.name() == &name)
             .nth(0);
@@ -209,10 +219,26 @@ impl ScopesWithSyntaxMapping {
         })
     }
 
+    /// Resolves an identifier inside of a macro call, like `x` in
+    /// `println!("{}", x)`, if the macro expands it to a path expression.
+    pub fn resolve_local_name_in_macro(&self, ident: &SyntaxNode) -> Option<ScopeEntryWithSyntax> {
+        let ptr = SyntaxNodePtr::from_range(ident.range(), PATH_EXPR);
+        let expr = self.syntax_mapping.syntax_expr(ptr)?;
+        let name = match &self.syntax_mapping.body()[expr] {
+            Expr::Path(path) => path.as_ident()?.clone(),
+            _ => return None,
+        };
+        let entry = self.scopes.resolve_local_name(expr, name)?;
+        Some(ScopeEntryWithSyntax {
+            name: entry.name().clone(),
+            ptr: self.syntax_mapping.pat_syntax(entry.pat())?,
+        })
+    }
+
     pub fn find_all_refs(&self, pat: &ast::BindPat) -> Vec<ReferenceDescriptor> {
         let fn_def = pat.syntax().ancestors().find_map(ast::FnDef::cast).unwrap();
         let name_ptr = SyntaxNodePtr::new(pat.syntax());
-        fn_def
+        let refs = fn_def
             .syntax()
             .descendants()
             .filter_map(ast::NameRef::cast)
@@ -220,11 +246,27 @@ impl ScopesWithSyntaxMapping {
                 None => false,
                 Some(entry) => entry.ptr() == name_ptr,
             })
-            .map(|name_ref| ReferenceDescriptor {
-                name: name_ref.syntax().text().to_string(),
-                range: name_ref.syntax().range(),
+            .map(|name_ref| name_ref.syntax());
+        let macro_refs = fn_def
+            .syntax()
+            .descendants()
+            .filter_map(ast::MacroCall::cast)
+            .filter_map(|macro_call| macro_call.token_tree())
+            .flat_map(|tt| tt.syntax().descendants())
+            .filter(|node| node.kind() == IDENT)
+            .filter(|ident| match self.resolve_local_name_in_macro(ident) {
+                None => false,
+                Some(entry) => entry.ptr() == name_ptr,
+            });
+        let mut res = refs
+            .chain(macro_refs)
+            .map(|node| ReferenceDescriptor {
+                name: node.text().to_string(),
+                range: node.range(),
             })
-            .collect()
+            .collect::<Vec<_>>();
+        res.sort_by_key(|it| it.range.start());
+        res
     }
 
     fn scope_for(&self, node: &SyntaxNode) -> Option<ScopeId> {
@@ -243,6 +285,12 @@ impl ScopeEntry {
     pub fn pat(&self) -> PatId {
         self.pat
     }
+
+    /// The syntax context of the binding, `SyntaxContext::ROOT` unless it
+    /// comes from a macro definition.
+    pub fn hygiene(&self) -> SyntaxContext {
+        self.hygiene
+    }
 }
 
 fn compute_block_scopes(
@@ -278,13 +326,16 @@ fn compute_block_scopes(
 fn compute_expr_scopes(expr: ExprId, body: &Body, scopes: &mut FnScopes, scope: ScopeId) {
     scopes.set_scope(expr, scope);
     match &body[expr] {
-        Expr::Block { statements, tail } => {
+        Expr::Block {
+            statements, tail, ..
+        } => {
             compute_block_scopes(&statements, *tail, body, scopes, scope);
         }
         Expr::For {
             iterable,
             pat,
             body: body_expr,
+            ..
         } => {
             compute_expr_scopes(*iterable, body, scopes, scope);
             let scope = scopes.new_scope(scope);
@@ -307,6 +358,10 @@ fn compute_expr_scopes(expr: ExprId, body: &Body, scopes: &mut FnScopes, scope:
                 for pat in &arm.pats {
                     scopes.add_bindings(body, scope, *pat);
                 }
+                if let Some(guard) = arm.guard {
+                    scopes.set_scope(guard, scope);
+                    compute_expr_scopes(guard, body, scopes, scope);
+                }
                 scopes.set_scope(arm.expr, scope);
                 compute_expr_scopes(arm.expr, body, scopes, scope);
             }
@@ -323,6 +378,7 @@ pub struct ReferenceDescriptor {
 
 #[cfg(test)]
 mod tests {
+    use ra_db::CfgOptions;
     use ra_syntax::{SourceFile, algo::find_node_at_offset};
     use test_utils::{extract_offset, assert_eq_text};
 
@@ -343,7 +399,8 @@ mod tests {
         let file = SourceFile::parse(&code);
         let marker: &ast::PathExpr = find_node_at_offset(file.syntax(), off).unwrap();
         let fn_def: &ast::FnDef = find_node_at_offset(file.syntax(), off).unwrap();
-        let body_hir = expr::collect_fn_body_syntax(fn_def);
+        let body_hir =
+            expr::collect_fn_body_syntax(fn_def, &CfgOptions::default(), &|_| None, &|_| None);
         let scopes = FnScopes::new(Arc::clone(body_hir.body()));
         let scopes = ScopesWithSyntaxMapping {
             scopes: Arc::new(scopes),
@@ -443,7 +500,8 @@ mod tests {
         let fn_def: &ast::FnDef = find_node_at_offset(file.syntax(), off).unwrap();
         let name_ref: &ast::NameRef = find_node_at_offset(file.syntax(), off).unwrap();
 
-        let body_hir = expr::collect_fn_body_syntax(fn_def);
+        let body_hir =
+            expr::collect_fn_body_syntax(fn_def, &CfgOptions::default(), &|_| None, &|_| None);
         let scopes = FnScopes::new(Arc::clone(body_hir.body()));
         let scopes = ScopesWithSyntaxMapping {
             scopes: Arc::new(scopes),
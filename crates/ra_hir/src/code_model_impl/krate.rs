@@ -27,6 +27,7 @@ impl Crate {
         let module = Module {
             krate: self.crate_id,
             module_id,
+            block: None,
         };
         Some(module)
     }
@@ -1,11 +1,12 @@
 use ra_db::FileId;
-use ra_syntax::{ast, SyntaxNode, TreeArc};
+use ra_syntax::{ast::{self, AstNode, ModuleItemOwner}, SyntaxNode, TreeArc};
 
 use crate::{
     Module, ModuleSource, Problem,
-    Crate, Name,
+    Crate, Name, HirFileId,
     module_tree::ModuleId,
-    nameres::{lower::ImportId},
+    nameres::lower::{ImportId, ImportSource},
+    ids::{AstItemDef, BlockId, LocationCtx},
     db::HirDatabase,
 };
 
@@ -14,9 +15,56 @@ impl Module {
         Module {
             module_id,
             krate: self.krate,
+            block: None,
         }
     }
 
+    /// The anonymous module of `block`, which declares items, inside of this
+    /// module.
+    pub(crate) fn block_module(
+        &self,
+        db: &impl HirDatabase,
+        file_id: HirFileId,
+        block: &ast::Block,
+    ) -> Module {
+        let ctx = LocationCtx::new(db, *self, file_id);
+        let block: BlockId = ctx.to_def(block);
+        Module {
+            krate: self.krate,
+            module_id: self.module_id,
+            block: Some(block),
+        }
+    }
+
+    /// The module whose items are in scope at `node`: the anonymous module of
+    /// the innermost block around `node` which declares items, or this module
+    /// if there is none. `node` must be in this module's own source.
+    pub(crate) fn module_at(
+        &self,
+        db: &impl HirDatabase,
+        file_id: HirFileId,
+        node: &SyntaxNode,
+    ) -> Module {
+        let mut blocks = node
+            .ancestors()
+            .take_while(|it| ast::Module::cast(it).is_none())
+            .filter_map(ast::Block::cast)
+            .filter(|it| it.items().next().is_some())
+            .collect::<Vec<_>>();
+        blocks.reverse();
+        blocks
+            .into_iter()
+            .fold(self.normal_module(), |module, block| {
+                module.block_module(db, file_id, block)
+            })
+    }
+
+    /// For the anonymous module of a block, the named module containing the
+    /// block. Otherwise, the module itself.
+    pub(crate) fn normal_module(&self) -> Module {
+        self.with_module_id(self.module_id)
+    }
+
     pub(crate) fn name_impl(&self, db: &impl HirDatabase) -> Option<Name> {
         let module_tree = db.module_tree(self.krate);
         let link = self.module_id.parent_link(&module_tree)?;
@@ -50,7 +98,7 @@ impl Module {
         &self,
         db: &impl HirDatabase,
         import: ImportId,
-    ) -> TreeArc<ast::PathSegment> {
+    ) -> ImportSource {
         let source_map = db.lower_module_source_map(self.clone());
         let (_, source) = self.definition_source(db);
         source_map.get(&source, import)
@@ -85,6 +133,9 @@ impl Module {
     }
 
     pub(crate) fn parent_impl(&self, db: &impl HirDatabase) -> Option<Module> {
+        if let Some(block) = self.block {
+            return Some(block.module(db));
+        }
         let module_tree = db.module_tree(self.krate);
         let parent_id = self.module_id.parent(&module_tree)?;
         Some(self.with_module_id(parent_id))
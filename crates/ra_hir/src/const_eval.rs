@@ -0,0 +1,391 @@
+//! Evaluation of constant expressions, like the initializers of consts and the
+//! lengths of arrays. Only arithmetic on integers, `bool`s and `char`s,
+//! references to other consts and casts between these types are supported;
+//! everything else (calls, structs, ...) has no value.
+use std::{cmp::Ordering, fmt};
+
+use crate::{
+    Const, HirDatabase, Module, ModuleDef, KnownName,
+    expr::{Body, Expr, ExprId, Literal, UnaryOp, BinaryOp},
+    type_ref::TypeRef,
+    ty::primitive::{UncertainIntTy, IntTy, UintTy},
+};
+
+/// The value of a constant expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstValue {
+    Int(i128),
+    Bool(bool),
+    Char(char),
+}
+
+impl ConstValue {
+    /// The value as an array length, if it is a valid `usize`.
+    pub fn as_usize(self) -> Option<u64> {
+        match self {
+            ConstValue::Int(it) if fits(it, USIZE) => Some(it as u64),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstValue::Int(it) => write!(f, "{}", it),
+            ConstValue::Bool(it) => write!(f, "{}", it),
+            ConstValue::Char(it) => write!(f, "{:?}", it),
+        }
+    }
+}
+
+const USIZE: UncertainIntTy = UncertainIntTy::Unsigned(UintTy::Usize);
+
+/// The value of the initializer of `c`. The consts it refers to are evaluated
+/// directly rather than through this query, so that cycles between consts are
+/// detected by the evaluator instead of by salsa.
+pub(crate) fn const_value_query(db: &impl HirDatabase, c: Const) -> Option<ConstValue> {
+    ConstEvaluator::new(db).eval_const(c)
+}
+
+/// The value of `expr` as an array length, like `N * 2` in `[0; N * 2]`.
+pub(crate) fn eval_usize(
+    db: &impl HirDatabase,
+    module: Module,
+    body: &Body,
+    expr: ExprId,
+) -> Option<u64> {
+    let (value, _) = ConstEvaluator::new(db).eval(module, body, expr, USIZE)?;
+    value.as_usize()
+}
+
+/// The value of `expr` in a pattern, like `-1` in `-1..=1`, if the pattern
+/// matches values of the integer type `ty`, a `bool` or a `char`.
+pub(crate) fn eval_pat_bound(
+    db: &impl HirDatabase,
+    module: Module,
+    body: &Body,
+    expr: ExprId,
+    ty: UncertainIntTy,
+) -> Option<ConstValue> {
+    let (value, value_ty) = ConstEvaluator::new(db).eval(module, body, expr, ty)?;
+    match value {
+        ConstValue::Int(it) if !fits(it, ty) || value_ty != ty => None,
+        _ => Some(value),
+    }
+}
+
+/// The types constant values can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConstTy {
+    Int(UncertainIntTy),
+    Bool,
+    Char,
+}
+
+impl ConstTy {
+    /// Only primitive types are recognized, so that evaluating a const never
+    /// needs type inference (which may evaluate consts itself).
+    fn from_type_ref(type_ref: &TypeRef) -> Option<ConstTy> {
+        let name = match type_ref {
+            TypeRef::Path(path) => path.as_ident()?,
+            _ => return None,
+        };
+        if let Some(int_ty) = UncertainIntTy::from_name(name) {
+            return Some(ConstTy::Int(int_ty));
+        }
+        match name.as_known_name()? {
+            KnownName::Bool => Some(ConstTy::Bool),
+            KnownName::Char => Some(ConstTy::Char),
+            _ => None,
+        }
+    }
+
+    fn int_ty(self) -> UncertainIntTy {
+        match self {
+            ConstTy::Int(it) => it,
+            ConstTy::Bool | ConstTy::Char => UncertainIntTy::Unknown,
+        }
+    }
+}
+
+struct ConstEvaluator<'a, D: HirDatabase> {
+    db: &'a D,
+    /// The consts whose initializers are being evaluated, to stop at cycles
+    /// like `const A: u32 = B; const B: u32 = A;`.
+    in_progress: Vec<Const>,
+}
+
+impl<'a, D: HirDatabase> ConstEvaluator<'a, D> {
+    fn new(db: &'a D) -> Self {
+        ConstEvaluator {
+            db,
+            in_progress: Vec::new(),
+        }
+    }
+
+    fn eval_const(&mut self, c: Const) -> Option<ConstValue> {
+        if self.in_progress.contains(&c) {
+            return None;
+        }
+        let ty = ConstTy::from_type_ref(&c.type_ref(self.db))?;
+        let body = self.db.body_hir(c.into());
+        self.in_progress.push(c);
+        let res = self.eval(c.module(self.db), &body, body.body_expr(), ty.int_ty());
+        self.in_progress.pop();
+        let (value, _) = res?;
+        // the initializer must have the declared type
+        match (value, ty) {
+            (ConstValue::Int(_), ConstTy::Int(_))
+            | (ConstValue::Bool(_), ConstTy::Bool)
+            | (ConstValue::Char(_), ConstTy::Char) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Evaluates `expr`, returning its value together with its integer type
+    /// (if it is an integer, and the type is known). `expected` is the integer
+    /// type the context requires, which unsuffixed literals take on.
+    fn eval(
+        &mut self,
+        module: Module,
+        body: &Body,
+        expr: ExprId,
+        expected: UncertainIntTy,
+    ) -> Option<(ConstValue, UncertainIntTy)> {
+        let unknown = UncertainIntTy::Unknown;
+        match &body[expr] {
+            Expr::Literal(Literal::Int(value, ty)) => {
+                let ty = if *ty == unknown { expected } else { *ty };
+                if *value > i128::max_value() as u128 {
+                    return None;
+                }
+                int_value(*value as i128, ty)
+            }
+            Expr::Literal(Literal::Bool(it)) => Some((ConstValue::Bool(*it), unknown)),
+            Expr::Literal(Literal::Char(it)) => Some((ConstValue::Char(*it), unknown)),
+            Expr::Path(path) => match module.resolve_path(self.db, path).take_values()? {
+                ModuleDef::Const(c) => {
+                    let ty = ConstTy::from_type_ref(&c.type_ref(self.db))?;
+                    let value = if self.in_progress.is_empty() {
+                        self.db.const_value(c)?
+                    } else {
+                        self.eval_const(c)?
+                    };
+                    Some((value, ty.int_ty()))
+                }
+                _ => None,
+            },
+            Expr::Block {
+                statements,
+                tail: Some(tail),
+                label: None,
+                ..
+            } if statements.is_empty() => self.eval(module, body, *tail, expected),
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch: Some(else_branch),
+            } => match self.eval(module, body, *condition, unknown)?.0 {
+                ConstValue::Bool(true) => self.eval(module, body, *then_branch, expected),
+                ConstValue::Bool(false) => self.eval(module, body, *else_branch, expected),
+                _ => None,
+            },
+            Expr::UnaryOp { expr, op } => {
+                let (value, ty) = self.eval(module, body, *expr, expected)?;
+                match (op, value) {
+                    (UnaryOp::Not, ConstValue::Bool(it)) => Some((ConstValue::Bool(!it), ty)),
+                    (UnaryOp::Not, ConstValue::Int(it)) => int_value(truncate(!it, ty), ty),
+                    (UnaryOp::Neg, ConstValue::Int(it)) => match ty {
+                        UncertainIntTy::Unsigned(_) => None,
+                        _ => int_value(it.checked_neg()?, ty),
+                    },
+                    _ => None,
+                }
+            }
+            Expr::BinaryOp {
+                lhs,
+                rhs,
+                op: Some(op),
+            } => self.eval_binary_op(module, body, *lhs, *rhs, *op, expected),
+            Expr::Cast { expr, type_ref } => {
+                let target = ConstTy::from_type_ref(type_ref)?;
+                let (value, _) = self.eval(module, body, *expr, unknown)?;
+                let value = match (value, target) {
+                    (ConstValue::Int(it), ConstTy::Int(ty)) => ConstValue::Int(truncate(it, ty)),
+                    (ConstValue::Bool(it), ConstTy::Int(_)) => ConstValue::Int(it as i128),
+                    (ConstValue::Char(it), ConstTy::Int(ty)) => {
+                        ConstValue::Int(truncate(it as i128, ty))
+                    }
+                    // only `u8` can be cast to `char`
+                    (ConstValue::Int(it), ConstTy::Char) if (0..=0xff).contains(&it) => {
+                        ConstValue::Char(it as u8 as char)
+                    }
+                    (ConstValue::Bool(_), ConstTy::Bool) | (ConstValue::Char(_), ConstTy::Char) => {
+                        value
+                    }
+                    _ => return None,
+                };
+                Some((value, target.int_ty()))
+            }
+            _ => None,
+        }
+    }
+
+    fn eval_binary_op(
+        &mut self,
+        module: Module,
+        body: &Body,
+        lhs: ExprId,
+        rhs: ExprId,
+        op: BinaryOp,
+        expected: UncertainIntTy,
+    ) -> Option<(ConstValue, UncertainIntTy)> {
+        let unknown = UncertainIntTy::Unknown;
+        match op {
+            BinaryOp::BooleanOr | BinaryOp::BooleanAnd => {
+                let (lhs, _) = self.eval(module, body, lhs, unknown)?;
+                let (rhs, _) = self.eval(module, body, rhs, unknown)?;
+                let value = match (lhs, rhs, op) {
+                    (ConstValue::Bool(l), ConstValue::Bool(r), BinaryOp::BooleanOr) => l || r,
+                    (ConstValue::Bool(l), ConstValue::Bool(r), _) => l && r,
+                    _ => return None,
+                };
+                Some((ConstValue::Bool(value), unknown))
+            }
+            BinaryOp::EqualityTest
+            | BinaryOp::LesserEqualTest
+            | BinaryOp::GreaterEqualTest
+            | BinaryOp::LesserTest
+            | BinaryOp::GreaterTest => {
+                // the operands have the same type, which comes from either side
+                let (lhs, lhs_ty) = self.eval(module, body, lhs, unknown)?;
+                let (rhs, _) = self.eval(module, body, rhs, lhs_ty)?;
+                let ordering = match (lhs, rhs) {
+                    (ConstValue::Int(l), ConstValue::Int(r)) => l.cmp(&r),
+                    (ConstValue::Bool(l), ConstValue::Bool(r)) => l.cmp(&r),
+                    (ConstValue::Char(l), ConstValue::Char(r)) => l.cmp(&r),
+                    _ => return None,
+                };
+                let value = match op {
+                    BinaryOp::EqualityTest => ordering == Ordering::Equal,
+                    BinaryOp::LesserEqualTest => ordering != Ordering::Greater,
+                    BinaryOp::GreaterEqualTest => ordering != Ordering::Less,
+                    BinaryOp::LesserTest => ordering == Ordering::Less,
+                    _ => ordering == Ordering::Greater,
+                };
+                Some((ConstValue::Bool(value), unknown))
+            }
+            BinaryOp::LeftShift | BinaryOp::RightShift => {
+                // the shift amount may have any integer type
+                let (lhs, ty) = self.eval(module, body, lhs, expected)?;
+                let (rhs, _) = self.eval(module, body, rhs, unknown)?;
+                let (lhs, rhs) = match (lhs, rhs) {
+                    (ConstValue::Int(l), ConstValue::Int(r)) => (l, r),
+                    _ => return None,
+                };
+                if rhs < 0 || rhs >= i128::from(bits(ty).unwrap_or(128)) {
+                    return None;
+                }
+                let value = if op == BinaryOp::LeftShift {
+                    truncate(lhs << rhs, ty)
+                } else {
+                    lhs >> rhs
+                };
+                int_value(value, ty)
+            }
+            BinaryOp::Addition
+            | BinaryOp::Subtraction
+            | BinaryOp::Multiplication
+            | BinaryOp::Division
+            | BinaryOp::Remainder
+            | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr
+            | BinaryOp::BitwiseXor => {
+                let (lhs, lhs_ty) = self.eval(module, body, lhs, expected)?;
+                let rhs_expected = if lhs_ty == unknown { expected } else { lhs_ty };
+                let (rhs, rhs_ty) = self.eval(module, body, rhs, rhs_expected)?;
+                let ty = if lhs_ty == unknown { rhs_ty } else { lhs_ty };
+                match (lhs, rhs) {
+                    (ConstValue::Bool(l), ConstValue::Bool(r)) => {
+                        let value = match op {
+                            BinaryOp::BitwiseAnd => l & r,
+                            BinaryOp::BitwiseOr => l | r,
+                            BinaryOp::BitwiseXor => l ^ r,
+                            _ => return None,
+                        };
+                        Some((ConstValue::Bool(value), unknown))
+                    }
+                    (ConstValue::Int(l), ConstValue::Int(r)) => {
+                        let value = match op {
+                            BinaryOp::Addition => l.checked_add(r)?,
+                            BinaryOp::Subtraction => l.checked_sub(r)?,
+                            BinaryOp::Multiplication => l.checked_mul(r)?,
+                            BinaryOp::Division => l.checked_div(r)?,
+                            BinaryOp::Remainder => l.checked_rem(r)?,
+                            BinaryOp::BitwiseAnd => l & r,
+                            BinaryOp::BitwiseOr => l | r,
+                            _ => l ^ r,
+                        };
+                        int_value(value, ty)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An integer value of type `ty`; overflowing the type is an error.
+fn int_value(value: i128, ty: UncertainIntTy) -> Option<(ConstValue, UncertainIntTy)> {
+    if fits(value, ty) {
+        Some((ConstValue::Int(value), ty))
+    } else {
+        None
+    }
+}
+
+/// The size of the integer type in bits. `usize` and `isize` are assumed to
+/// be 64 bits wide.
+pub(crate) fn bits(ty: UncertainIntTy) -> Option<u32> {
+    let bits = match ty {
+        UncertainIntTy::Unknown => return None,
+        UncertainIntTy::Signed(IntTy::I8) | UncertainIntTy::Unsigned(UintTy::U8) => 8,
+        UncertainIntTy::Signed(IntTy::I16) | UncertainIntTy::Unsigned(UintTy::U16) => 16,
+        UncertainIntTy::Signed(IntTy::I32) | UncertainIntTy::Unsigned(UintTy::U32) => 32,
+        UncertainIntTy::Signed(IntTy::I64)
+        | UncertainIntTy::Signed(IntTy::Isize)
+        | UncertainIntTy::Unsigned(UintTy::U64)
+        | UncertainIntTy::Unsigned(UintTy::Usize) => 64,
+        UncertainIntTy::Signed(IntTy::I128) | UncertainIntTy::Unsigned(UintTy::U128) => 128,
+    };
+    Some(bits)
+}
+
+/// Whether `value` is in the range of `ty`. Values of unknown type are not
+/// checked.
+fn fits(value: i128, ty: UncertainIntTy) -> bool {
+    match (ty, bits(ty)) {
+        (UncertainIntTy::Signed(_), Some(bits)) if bits < 128 => {
+            let max = (1i128 << (bits - 1)) - 1;
+            -max - 1 <= value && value <= max
+        }
+        (UncertainIntTy::Unsigned(_), Some(bits)) => {
+            0 <= value && (bits == 128 || value < (1i128 << bits))
+        }
+        _ => true,
+    }
+}
+
+/// Wraps `value` around to the range of `ty`, like an `as` cast does.
+fn truncate(value: i128, ty: UncertainIntTy) -> i128 {
+    match (ty, bits(ty)) {
+        (UncertainIntTy::Signed(_), Some(bits)) if bits < 128 => {
+            let shift = 128 - bits;
+            (value << shift) >> shift
+        }
+        (UncertainIntTy::Unsigned(_), Some(bits)) if bits < 128 => value & ((1i128 << bits) - 1),
+        _ => value,
+    }
+}
@@ -1,21 +1,29 @@
 use std::sync::Arc;
 
 use ra_syntax::{SyntaxNode, TreeArc, SourceFile};
-use ra_db::{SyntaxDatabase, CrateId, salsa};
+use ra_db::{SyntaxDatabase, CrateId, FileId, salsa};
+use rustc_hash::FxHashMap;
 
 use crate::{
     MacroCallId, HirFileId,
-    SourceFileItems, SourceItemId, Crate, Module, HirInterner,
+    SourceFileItems, SourceItemId, Crate, Module, HirInterner, BlockId,
     query_definitions,
-    Function, FnSignature, FnScopes,
-    Struct, Enum, StructField,
+    Function, FnSignature, FnScopes, DefWithBody, Const,
+    Struct, Enum, StructField, Trait,
     macros::MacroExpansion,
     module_tree::ModuleTree,
-    nameres::{ItemMap, lower::{LoweredModule, ImportSourceMap}},
-    ty::{InferenceResult, Ty, method_resolution::CrateImplBlocks, TypableDef},
+    nameres::{ItemMap, ModuleScope, lower::{LoweredModule, ImportSourceMap}},
+    ty::{
+        InferenceResult, Ty, method_resolution::CrateImplBlocks, TypableDef,
+        traits::{TraitRef, Environment, Solution},
+    },
     adt::{StructData, EnumData},
+    traits::TraitData,
     impl_block::ModuleImplBlocks,
     generics::{GenericParams, GenericDef},
+    lang_item::LangItems,
+    unsafe_check::UnsafeExpr,
+    const_eval::ConstValue,
 };
 
 #[salsa::query_group(HirDatabaseStorage)]
@@ -27,7 +35,7 @@ pub trait HirDatabase: SyntaxDatabase + AsRef<HirInterner> {
     fn expand_macro_invocation(&self, invoc: MacroCallId) -> Option<Arc<MacroExpansion>>;
 
     #[salsa::invoke(query_definitions::fn_scopes)]
-    fn fn_scopes(&self, func: Function) -> Arc<FnScopes>;
+    fn fn_scopes(&self, def: DefWithBody) -> Arc<FnScopes>;
 
     #[salsa::invoke(crate::adt::StructData::struct_data_query)]
     fn struct_data(&self, s: Struct) -> Arc<StructData>;
@@ -35,8 +43,17 @@ pub trait HirDatabase: SyntaxDatabase + AsRef<HirInterner> {
     #[salsa::invoke(crate::adt::EnumData::enum_data_query)]
     fn enum_data(&self, e: Enum) -> Arc<EnumData>;
 
+    #[salsa::invoke(crate::traits::TraitData::trait_data_query)]
+    fn trait_data(&self, t: Trait) -> Arc<TraitData>;
+
     #[salsa::invoke(crate::ty::infer)]
-    fn infer(&self, func: Function) -> Arc<InferenceResult>;
+    fn infer(&self, def: DefWithBody) -> Arc<InferenceResult>;
+
+    #[salsa::invoke(crate::const_eval::const_value_query)]
+    fn const_value(&self, c: Const) -> Option<ConstValue>;
+
+    #[salsa::invoke(crate::unsafe_check::unsafe_exprs_query)]
+    fn unsafe_exprs(&self, def: DefWithBody) -> Arc<Vec<UnsafeExpr>>;
 
     #[salsa::invoke(crate::ty::type_for_def)]
     fn type_for_def(&self, def: TypableDef) -> Ty;
@@ -65,24 +82,39 @@ pub trait HirDatabase: SyntaxDatabase + AsRef<HirInterner> {
     #[salsa::invoke(query_definitions::item_map)]
     fn item_map(&self, crate_id: CrateId) -> Arc<ItemMap>;
 
+    #[salsa::invoke(crate::nameres::block_scope_query)]
+    fn block_scope(&self, block: BlockId) -> Arc<ModuleScope>;
+
     #[salsa::invoke(crate::module_tree::ModuleTree::module_tree_query)]
     fn module_tree(&self, crate_id: CrateId) -> Arc<ModuleTree>;
 
+    #[salsa::invoke(crate::module_tree::ModuleTree::external_modules_query)]
+    fn external_modules(&self) -> Arc<FxHashMap<FileId, CrateId>>;
+
     #[salsa::invoke(crate::impl_block::impls_in_module)]
     fn impls_in_module(&self, module: Module) -> Arc<ModuleImplBlocks>;
 
     #[salsa::invoke(crate::ty::method_resolution::CrateImplBlocks::impls_in_crate_query)]
     fn impls_in_crate(&self, krate: Crate) -> Arc<CrateImplBlocks>;
 
+    #[salsa::invoke(crate::ty::traits::implements)]
+    fn implements(&self, env: Arc<Environment>, trait_ref: TraitRef) -> Option<Solution>;
+
     #[salsa::invoke(crate::expr::body_hir)]
-    fn body_hir(&self, func: Function) -> Arc<crate::expr::Body>;
+    fn body_hir(&self, def: DefWithBody) -> Arc<crate::expr::Body>;
 
     #[salsa::invoke(crate::expr::body_syntax_mapping)]
-    fn body_syntax_mapping(&self, func: Function) -> Arc<crate::expr::BodySyntaxMapping>;
+    fn body_syntax_mapping(&self, def: DefWithBody) -> Arc<crate::expr::BodySyntaxMapping>;
 
     #[salsa::invoke(crate::generics::GenericParams::generic_params_query)]
     fn generic_params(&self, def: GenericDef) -> Arc<GenericParams>;
 
+    #[salsa::invoke(crate::ty::traits::generic_predicates_query)]
+    fn generic_predicates(&self, def: GenericDef) -> Arc<[TraitRef]>;
+
     #[salsa::invoke(crate::FnSignature::fn_signature_query)]
     fn fn_signature(&self, func: Function) -> Arc<FnSignature>;
+
+    #[salsa::invoke(crate::lang_item::LangItems::lang_items_query)]
+    fn lang_items(&self, krate: Crate) -> Arc<LangItems>;
 }
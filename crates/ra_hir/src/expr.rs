@@ -1,18 +1,23 @@
+use std::mem;
 use std::ops::Index;
 use std::sync::Arc;
 
 use rustc_hash::FxHashMap;
 
 use ra_arena::{Arena, RawId, impl_arena_id, map::ArenaMap};
+use ra_db::CfgOptions;
 use ra_syntax::{
-    SyntaxNodePtr, AstNode,
-    ast::{self, LoopBodyOwner, ArgListOwner, NameOwner, LiteralFlavor}
+    SyntaxNodePtr, AstNode, TextUnit,
+    SyntaxKind::MUT_KW,
+    ast::{self, LoopBodyOwner, ArgListOwner, NameOwner, LiteralFlavor, ModuleItemOwner}
 };
 
 use crate::{
-    Path, Name, HirDatabase, Function,
+    Path, Name, HirDatabase, DefWithBody, HirFileId, MacroExpansion, SyntaxContext, TokenOrigin,
+    Module,
     name::AsName,
     type_ref::{Mutability, TypeRef},
+    macros::{self, ReadFile},
 };
 use crate::ty::primitive::{UintTy, UncertainIntTy, UncertainFloatTy};
 
@@ -34,6 +39,25 @@ pub struct Body {
     params: Vec<PatId>,
     /// The `ExprId` of the actual body expression.
     body_expr: ExprId,
+    /// The syntax contexts of the expressions and patterns which come from
+    /// macro definitions rather than from the source file.
+    expr_hygiene: ArenaMap<ExprId, SyntaxContext>,
+    pat_hygiene: ArenaMap<PatId, SyntaxContext>,
+    /// The anonymous modules of the blocks which declare items.
+    block_modules: ArenaMap<ExprId, Module>,
+    /// The loop or labeled block each `break` and `continue` refers to.
+    break_targets: ArenaMap<ExprId, ExprId>,
+    problems: Vec<BodyProblem>,
+}
+
+/// A problem in a body which is found while lowering it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyProblem {
+    /// A `break` or `continue` with a label which none of the loops or blocks
+    /// around it has.
+    UnresolvedLabel { expr: ExprId, label: Name },
+    /// A `continue` with the label of a block rather than of a loop.
+    ContinueToBlock { expr: ExprId, label: Name },
 }
 
 /// An item body together with the mapping from syntax nodes to HIR expression
@@ -58,6 +82,47 @@ impl Body {
     pub fn body_expr(&self) -> ExprId {
         self.body_expr
     }
+
+    /// The syntax context of the names used by `expr`.
+    pub fn expr_hygiene(&self, expr: ExprId) -> SyntaxContext {
+        self.expr_hygiene
+            .get(expr)
+            .cloned()
+            .unwrap_or(SyntaxContext::ROOT)
+    }
+
+    /// The syntax context of the names bound by `pat`.
+    pub fn pat_hygiene(&self, pat: PatId) -> SyntaxContext {
+        self.pat_hygiene
+            .get(pat)
+            .cloned()
+            .unwrap_or(SyntaxContext::ROOT)
+    }
+
+    /// The module of the items declared in `block`, if it declares any.
+    pub fn block_module(&self, block: ExprId) -> Option<Module> {
+        self.block_modules.get(block).cloned()
+    }
+
+    /// The loop or labeled block which the `break` or `continue` expression
+    /// `expr` leaves. `None` for unresolved labels and for a `break` outside
+    /// of any loop.
+    pub fn break_target(&self, expr: ExprId) -> Option<ExprId> {
+        self.break_targets.get(expr).cloned()
+    }
+
+    /// The `break` and `continue` expressions which leave the loop or labeled
+    /// block `target`.
+    pub fn breaks_of(&self, target: ExprId) -> impl Iterator<Item = ExprId> + '_ {
+        self.break_targets
+            .iter()
+            .filter(move |&(_, &it)| it == target)
+            .map(|(expr, _)| expr)
+    }
+
+    pub fn problems(&self) -> &[BodyProblem] {
+        &self.problems
+    }
 }
 
 impl Index<ExprId> for Body {
@@ -116,7 +181,7 @@ pub enum Literal {
     ByteString(Vec<u8>),
     Char(char),
     Bool(bool),
-    Int(u64, UncertainIntTy),
+    Int(u128, UncertainIntTy),
     Float(u64, UncertainFloatTy), // FIXME: f64 is not Eq
 }
 
@@ -133,18 +198,23 @@ pub enum Expr {
     Block {
         statements: Vec<Statement>,
         tail: Option<ExprId>,
+        label: Option<Name>,
+        is_unsafe: bool,
     },
     Loop {
         body: ExprId,
+        label: Option<Name>,
     },
     While {
         condition: ExprId,
         body: ExprId,
+        label: Option<Name>,
     },
     For {
         iterable: ExprId,
         pat: PatId,
         body: ExprId,
+        label: Option<Name>,
     },
     Call {
         callee: ExprId,
@@ -159,9 +229,12 @@ pub enum Expr {
         expr: ExprId,
         arms: Vec<MatchArm>,
     },
-    Continue,
+    Continue {
+        label: Option<Name>,
+    },
     Break {
         expr: Option<ExprId>,
+        label: Option<Name>,
     },
     Return {
         expr: Option<ExprId>,
@@ -195,6 +268,16 @@ pub enum Expr {
         rhs: ExprId,
         op: Option<BinaryOp>,
     },
+    Index {
+        base: ExprId,
+        index: ExprId,
+    },
+    /// A range, like `a..b`, `a..`, `..=b` or `..`.
+    Range {
+        lhs: Option<ExprId>,
+        rhs: Option<ExprId>,
+        range_type: RangeOp,
+    },
     Lambda {
         args: Vec<PatId>,
         arg_types: Vec<Option<TypeRef>>,
@@ -206,16 +289,22 @@ pub enum Expr {
     Array {
         exprs: Vec<ExprId>,
     },
+    /// An array repeat expression like `[0; 4]`.
+    ArrayRepeat {
+        initializer: ExprId,
+        repeat: ExprId,
+    },
     Literal(Literal),
 }
 
 pub use ra_syntax::ast::PrefixOp as UnaryOp;
 pub use ra_syntax::ast::BinOp as BinaryOp;
+pub use ra_syntax::ast::RangeOp;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct MatchArm {
     pub pats: Vec<PatId>,
-    // guard: Option<ExprId>, // TODO
+    pub guard: Option<ExprId>,
     pub expr: ExprId,
 }
 
@@ -251,7 +340,9 @@ impl Expr {
                     f(*else_branch);
                 }
             }
-            Expr::Block { statements, tail } => {
+            Expr::Block {
+                statements, tail, ..
+            } => {
                 for stmt in statements {
                     match stmt {
                         Statement::Let { initializer, .. } => {
@@ -266,8 +357,10 @@ impl Expr {
                     f(*expr);
                 }
             }
-            Expr::Loop { body } => f(*body),
-            Expr::While { condition, body } => {
+            Expr::Loop { body, .. } => f(*body),
+            Expr::While {
+                condition, body, ..
+            } => {
                 f(*condition);
                 f(*body);
             }
@@ -290,11 +383,14 @@ impl Expr {
             Expr::Match { expr, arms } => {
                 f(*expr);
                 for arm in arms {
+                    if let Some(guard) = arm.guard {
+                        f(guard);
+                    }
                     f(arm.expr);
                 }
             }
-            Expr::Continue => {}
-            Expr::Break { expr } | Expr::Return { expr } => {
+            Expr::Continue { .. } => {}
+            Expr::Break { expr, .. } | Expr::Return { expr } => {
                 if let Some(expr) = expr {
                     f(*expr);
                 }
@@ -310,10 +406,19 @@ impl Expr {
             Expr::Lambda { body, .. } => {
                 f(*body);
             }
-            Expr::BinaryOp { lhs, rhs, .. } => {
+            Expr::BinaryOp { lhs, rhs, .. }
+            | Expr::Index {
+                base: lhs,
+                index: rhs,
+            } => {
                 f(*lhs);
                 f(*rhs);
             }
+            Expr::Range { lhs, rhs, .. } => {
+                for expr in lhs.iter().chain(rhs.iter()) {
+                    f(*expr);
+                }
+            }
             Expr::Field { expr, .. }
             | Expr::Try { expr }
             | Expr::Cast { expr, .. }
@@ -326,6 +431,13 @@ impl Expr {
                     f(*expr);
                 }
             }
+            Expr::ArrayRepeat {
+                initializer,
+                repeat,
+            } => {
+                f(*initializer);
+                f(*repeat);
+            }
             Expr::Literal(_) => {}
         }
     }
@@ -439,42 +551,153 @@ impl Pat {
 
 // Queries
 
-pub(crate) fn body_hir(db: &impl HirDatabase, func: Function) -> Arc<Body> {
-    Arc::clone(&body_syntax_mapping(db, func).body)
+pub(crate) fn body_hir(db: &impl HirDatabase, def: DefWithBody) -> Arc<Body> {
+    Arc::clone(&body_syntax_mapping(db, def).body)
 }
 
-struct ExprCollector {
+/// Gives the anonymous module of a block which declares items.
+pub(crate) type BlockModule<'a> = &'a dyn Fn(&ast::Block) -> Option<Module>;
+
+struct ExprCollector<'a> {
+    cfg_options: &'a CfgOptions,
+    read_file: ReadFile<'a>,
+    block_module: BlockModule<'a>,
+    /// Macro calls which are being collected at the moment, innermost last,
+    /// together with the offsets of their token trees and the syntax contexts
+    /// of their expansions.
+    expansions: Vec<(TextUnit, MacroExpansion, SyntaxContext)>,
+    /// The last syntax context handed out to a macro expansion.
+    last_ctx: SyntaxContext,
     exprs: Arena<ExprId, Expr>,
     pats: Arena<PatId, Pat>,
     expr_syntax_mapping: FxHashMap<SyntaxNodePtr, ExprId>,
     expr_syntax_mapping_back: ArenaMap<ExprId, SyntaxNodePtr>,
     pat_syntax_mapping: FxHashMap<SyntaxNodePtr, PatId>,
     pat_syntax_mapping_back: ArenaMap<PatId, SyntaxNodePtr>,
+    expr_hygiene: ArenaMap<ExprId, SyntaxContext>,
+    pat_hygiene: ArenaMap<PatId, SyntaxContext>,
+    block_modules: ArenaMap<ExprId, Module>,
+    /// The loops and labeled blocks around the expression being collected,
+    /// innermost last.
+    breakables: Vec<Breakable>,
+    break_targets: ArenaMap<ExprId, ExprId>,
+    problems: Vec<BodyProblem>,
 }
 
-impl ExprCollector {
-    fn new() -> Self {
+/// A loop or labeled block which is being collected.
+struct Breakable {
+    label: Option<Name>,
+    is_loop: bool,
+    /// The `break` and `continue` expressions referring to it so far.
+    breaks: Vec<ExprId>,
+}
+
+impl<'a> ExprCollector<'a> {
+    fn new(
+        cfg_options: &'a CfgOptions,
+        read_file: ReadFile<'a>,
+        block_module: BlockModule<'a>,
+    ) -> Self {
         ExprCollector {
+            cfg_options,
+            read_file,
+            block_module,
+            expansions: Vec::new(),
+            last_ctx: SyntaxContext::ROOT,
             exprs: Arena::default(),
             pats: Arena::default(),
             expr_syntax_mapping: FxHashMap::default(),
             expr_syntax_mapping_back: ArenaMap::default(),
             pat_syntax_mapping: FxHashMap::default(),
             pat_syntax_mapping_back: ArenaMap::default(),
+            expr_hygiene: ArenaMap::default(),
+            pat_hygiene: ArenaMap::default(),
+            block_modules: ArenaMap::default(),
+            breakables: Vec::new(),
+            break_targets: ArenaMap::default(),
+            problems: Vec::new(),
         }
     }
 
     fn alloc_expr(&mut self, expr: Expr, syntax_ptr: SyntaxNodePtr) -> ExprId {
         let id = self.exprs.alloc(expr);
-        self.expr_syntax_mapping.insert(syntax_ptr, id);
-        self.expr_syntax_mapping_back.insert(id, syntax_ptr);
+        let ctx = self.hygiene(syntax_ptr.range().start());
+        if ctx != SyntaxContext::ROOT {
+            self.expr_hygiene.insert(id, ctx);
+        }
+        if let Some(syntax_ptr) = self.map_ptr(syntax_ptr) {
+            self.expr_syntax_mapping.insert(syntax_ptr, id);
+            self.expr_syntax_mapping_back.insert(id, syntax_ptr);
+        }
         id
     }
 
     fn alloc_pat(&mut self, pat: Pat, syntax_ptr: SyntaxNodePtr) -> PatId {
         let id = self.pats.alloc(pat);
-        self.pat_syntax_mapping.insert(syntax_ptr, id);
-        self.pat_syntax_mapping_back.insert(id, syntax_ptr);
+        let ctx = self.hygiene(syntax_ptr.range().start());
+        if ctx != SyntaxContext::ROOT {
+            self.pat_hygiene.insert(id, ctx);
+        }
+        if let Some(syntax_ptr) = self.map_ptr(syntax_ptr) {
+            self.pat_syntax_mapping.insert(syntax_ptr, id);
+            self.pat_syntax_mapping_back.insert(id, syntax_ptr);
+        }
+        id
+    }
+
+    /// Maps a pointer into the current macro expansion back to the original
+    /// file. Nodes which are not written by the user, like the ones
+    /// synthesized by the macro itself, have no syntax.
+    fn map_ptr(&self, syntax_ptr: SyntaxNodePtr) -> Option<SyntaxNodePtr> {
+        let mut range = syntax_ptr.range();
+        for (off, exp, _) in self.expansions.iter().rev() {
+            range = exp.map_range_back(range)? + *off;
+        }
+        if self.expansions.is_empty() {
+            return Some(syntax_ptr);
+        }
+        Some(SyntaxNodePtr::from_range(range, syntax_ptr.kind()))
+    }
+
+    /// Finds the syntax context of the token at `offset` in the current macro
+    /// expansion: tokens copied from a macro call are traced back to the
+    /// outer expansion, until we reach a token written by some macro
+    /// definition or by the user.
+    fn hygiene(&self, mut offset: TextUnit) -> SyntaxContext {
+        for (off, exp, ctx) in self.expansions.iter().rev() {
+            match exp.token_origin(offset) {
+                Some(TokenOrigin::CallSite(range)) => offset = range.start() + *off,
+                _ => return *ctx,
+            }
+        }
+        SyntaxContext::ROOT
+    }
+
+    fn collect_macro_call(&mut self, e: &ast::MacroCall, syntax_ptr: SyntaxNodePtr) -> ExprId {
+        // Protects against macros which expand to themselves.
+        const MAX_EXPANSION_DEPTH: usize = 32;
+        if self.expansions.len() >= MAX_EXPANSION_DEPTH {
+            return self.alloc_expr(Expr::Missing, syntax_ptr);
+        }
+        let (off, exp) = match macros::expand_expr_macro(e, self.read_file) {
+            Some(it) => it,
+            None => return self.alloc_expr(Expr::Missing, syntax_ptr),
+        };
+        let expansion = exp.syntax();
+        let expr = match ast::Expr::cast(&expansion) {
+            Some(it) => it,
+            None => return self.alloc_expr(Expr::Missing, syntax_ptr),
+        };
+        self.last_ctx = SyntaxContext(self.last_ctx.0 + 1);
+        self.expansions.push((off, exp, self.last_ctx));
+        let id = self.collect_expr(expr);
+        self.expansions.pop();
+        if let Some(syntax_ptr) = self.map_ptr(syntax_ptr) {
+            self.expr_syntax_mapping.insert(syntax_ptr, id);
+            if self.expr_syntax_mapping_back.get(id).is_none() {
+                self.expr_syntax_mapping_back.insert(id, syntax_ptr);
+            }
+        }
         id
     }
 
@@ -482,10 +705,58 @@ impl ExprCollector {
         let block = Expr::Block {
             statements: Vec::new(),
             tail: None,
+            label: None,
+            is_unsafe: false,
         };
         self.exprs.alloc(block)
     }
 
+    /// Collects a loop or a labeled block, which `break` can leave. `collect`
+    /// collects the contents and allocates the expression.
+    fn collect_breakable(
+        &mut self,
+        label: Option<&ast::Label>,
+        is_loop: bool,
+        collect: impl FnOnce(&mut Self, Option<Name>) -> ExprId,
+    ) -> ExprId {
+        let label = label.and_then(|it| it.lifetime()).map(|it| it.as_name());
+        self.breakables.push(Breakable {
+            label: label.clone(),
+            is_loop,
+            breaks: Vec::new(),
+        });
+        let id = collect(self, label);
+        let breakable = self.breakables.pop().expect("breakable pushed above");
+        for expr in breakable.breaks {
+            self.break_targets.insert(expr, id);
+        }
+        id
+    }
+
+    /// Resolves the target of the `break` or `continue` expression `expr`:
+    /// the innermost loop, or the innermost loop or block with the label.
+    /// Only loops can be continued.
+    fn resolve_break(&mut self, expr: ExprId, label: Option<&Name>, is_continue: bool) {
+        let target = self.breakables.iter_mut().rev().find(|it| match label {
+            Some(label) => it.label.as_ref() == Some(label),
+            None => it.is_loop,
+        });
+        match (target, label) {
+            (Some(ref target), Some(label)) if is_continue && !target.is_loop => {
+                self.problems.push(BodyProblem::ContinueToBlock {
+                    expr,
+                    label: label.clone(),
+                })
+            }
+            (Some(target), _) => target.breaks.push(expr),
+            (None, Some(label)) => self.problems.push(BodyProblem::UnresolvedLabel {
+                expr,
+                label: label.clone(),
+            }),
+            (None, None) => (),
+        }
+    }
+
     fn collect_expr(&mut self, expr: &ast::Expr) -> ExprId {
         let syntax_ptr = SyntaxNodePtr::new(expr.syntax());
         match expr.kind() {
@@ -504,10 +775,12 @@ impl ExprCollector {
                     let arms = vec![
                         MatchArm {
                             pats: vec![pat],
+                            guard: None,
                             expr: then_branch,
                         },
                         MatchArm {
                             pats: vec![placeholder_pat],
+                            guard: None,
                             expr: else_branch,
                         },
                     ];
@@ -532,11 +805,19 @@ impl ExprCollector {
                     )
                 }
             }
-            ast::ExprKind::BlockExpr(e) => self.collect_block_opt(e.block()),
-            ast::ExprKind::LoopExpr(e) => {
-                let body = self.collect_block_opt(e.loop_body());
-                self.alloc_expr(Expr::Loop { body }, syntax_ptr)
-            }
+            ast::ExprKind::BlockExpr(e) => match (e.label(), e.block()) {
+                (Some(label), Some(block)) => {
+                    self.collect_breakable(Some(label), false, |this, label| {
+                        this.collect_block_with(block, label, e.is_unsafe())
+                    })
+                }
+                (None, Some(block)) => self.collect_block_with(block, None, e.is_unsafe()),
+                (_, None) => self.exprs.alloc(Expr::Missing),
+            },
+            ast::ExprKind::LoopExpr(e) => self.collect_breakable(e.label(), true, |this, label| {
+                let body = this.collect_block_opt(e.loop_body());
+                this.alloc_expr(Expr::Loop { body, label }, syntax_ptr)
+            }),
             ast::ExprKind::WhileExpr(e) => {
                 let condition = if let Some(condition) = e.condition() {
                     if condition.pat().is_none() {
@@ -548,21 +829,33 @@ impl ExprCollector {
                 } else {
                     self.exprs.alloc(Expr::Missing)
                 };
-                let body = self.collect_block_opt(e.loop_body());
-                self.alloc_expr(Expr::While { condition, body }, syntax_ptr)
+                self.collect_breakable(e.label(), true, |this, label| {
+                    let body = this.collect_block_opt(e.loop_body());
+                    this.alloc_expr(
+                        Expr::While {
+                            condition,
+                            body,
+                            label,
+                        },
+                        syntax_ptr,
+                    )
+                })
             }
             ast::ExprKind::ForExpr(e) => {
                 let iterable = self.collect_expr_opt(e.iterable());
                 let pat = self.collect_pat_opt(e.pat());
-                let body = self.collect_block_opt(e.loop_body());
-                self.alloc_expr(
-                    Expr::For {
-                        iterable,
-                        pat,
-                        body,
-                    },
-                    syntax_ptr,
-                )
+                self.collect_breakable(e.label(), true, |this, label| {
+                    let body = this.collect_block_opt(e.loop_body());
+                    this.alloc_expr(
+                        Expr::For {
+                            iterable,
+                            pat,
+                            body,
+                            label,
+                        },
+                        syntax_ptr,
+                    )
+                })
             }
             ast::ExprKind::CallExpr(e) => {
                 let callee = self.collect_expr_opt(e.expr());
@@ -595,11 +888,14 @@ impl ExprCollector {
             }
             ast::ExprKind::MatchExpr(e) => {
                 let expr = self.collect_expr_opt(e.expr());
+                let cfg_options = self.cfg_options;
                 let arms = if let Some(match_arm_list) = e.match_arm_list() {
                     match_arm_list
                         .arms()
+                        .filter(|arm| cfg_options.is_cfg_enabled(*arm))
                         .map(|arm| MatchArm {
                             pats: arm.pats().map(|p| self.collect_pat(p)).collect(),
+                            guard: arm.guard().map(|guard| self.collect_expr_opt(guard.expr())),
                             expr: self.collect_expr_opt(arm.expr()),
                         })
                         .collect()
@@ -616,13 +912,29 @@ impl ExprCollector {
                     .unwrap_or(Expr::Missing);
                 self.alloc_expr(path, syntax_ptr)
             }
-            ast::ExprKind::ContinueExpr(_e) => {
-                // TODO: labels
-                self.alloc_expr(Expr::Continue, syntax_ptr)
+            ast::ExprKind::ContinueExpr(e) => {
+                let label = e.lifetime().map(|it| it.as_name());
+                let id = self.alloc_expr(
+                    Expr::Continue {
+                        label: label.clone(),
+                    },
+                    syntax_ptr,
+                );
+                self.resolve_break(id, label.as_ref(), true);
+                id
             }
             ast::ExprKind::BreakExpr(e) => {
                 let expr = e.expr().map(|e| self.collect_expr(e));
-                self.alloc_expr(Expr::Break { expr }, syntax_ptr)
+                let label = e.lifetime().map(|it| it.as_name());
+                let id = self.alloc_expr(
+                    Expr::Break {
+                        expr,
+                        label: label.clone(),
+                    },
+                    syntax_ptr,
+                );
+                self.resolve_break(id, label.as_ref(), false);
+                id
             }
             ast::ExprKind::ParenExpr(e) => {
                 let inner = self.collect_expr_opt(e.expr());
@@ -712,7 +1024,10 @@ impl ExprCollector {
                         arg_types.push(type_ref);
                     }
                 }
+                // `break` and `continue` can't leave the closure
+                let breakables = mem::replace(&mut self.breakables, Vec::new());
                 let body = self.collect_expr_opt(e.body());
+                self.breakables = breakables;
                 self.alloc_expr(
                     Expr::Lambda {
                         args,
@@ -732,6 +1047,18 @@ impl ExprCollector {
                 let exprs = e.exprs().map(|expr| self.collect_expr(expr)).collect();
                 self.alloc_expr(Expr::Tuple { exprs }, syntax_ptr)
             }
+            ast::ExprKind::ArrayExpr(e) if e.is_repeat() => {
+                let mut exprs = e.exprs();
+                let initializer = self.collect_expr_opt(exprs.next());
+                let repeat = self.collect_expr_opt(exprs.next());
+                self.alloc_expr(
+                    Expr::ArrayRepeat {
+                        initializer,
+                        repeat,
+                    },
+                    syntax_ptr,
+                )
+            }
             ast::ExprKind::ArrayExpr(e) => {
                 let exprs = e.exprs().map(|expr| self.collect_expr(expr)).collect();
                 self.alloc_expr(Expr::Array { exprs }, syntax_ptr)
@@ -750,7 +1077,7 @@ impl ExprCollector {
                             .and_then(|name| UncertainIntTy::from_name(&name));
 
                         Literal::Int(
-                            Default::default(),
+                            child.int_value().unwrap_or_default(),
                             known_name.unwrap_or(UncertainIntTy::Unknown),
                         )
                     }
@@ -767,18 +1094,39 @@ impl ExprCollector {
                     LiteralFlavor::ByteString => Literal::ByteString(Default::default()),
                     LiteralFlavor::String => Literal::String(Default::default()),
                     LiteralFlavor::Byte => {
-                        Literal::Int(Default::default(), UncertainIntTy::Unsigned(UintTy::U8))
+                        let value = child.int_value().unwrap_or_default();
+                        Literal::Int(value, UncertainIntTy::Unsigned(UintTy::U8))
                     }
-                    LiteralFlavor::Bool => Literal::Bool(Default::default()),
-                    LiteralFlavor::Char => Literal::Char(Default::default()),
+                    LiteralFlavor::Bool => Literal::Bool(child.bool_value().unwrap_or_default()),
+                    LiteralFlavor::Char => Literal::Char(child.char_value().unwrap_or_default()),
                 };
                 self.alloc_expr(Expr::Literal(lit), syntax_ptr)
             }
 
+            ast::ExprKind::MacroCall(e) => self.collect_macro_call(e, syntax_ptr),
+
             // TODO implement HIR for these:
             ast::ExprKind::Label(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
-            ast::ExprKind::IndexExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
-            ast::ExprKind::RangeExpr(_e) => self.alloc_expr(Expr::Missing, syntax_ptr),
+            ast::ExprKind::IndexExpr(e) => {
+                let base = self.collect_expr_opt(e.base());
+                let index = self.collect_expr_opt(e.index());
+                self.alloc_expr(Expr::Index { base, index }, syntax_ptr)
+            }
+            ast::ExprKind::RangeExpr(e) => {
+                let lhs = e.start().map(|lhs| self.collect_expr(lhs));
+                let rhs = e.end().map(|rhs| self.collect_expr(rhs));
+                match e.op_kind() {
+                    Some(range_type) => self.alloc_expr(
+                        Expr::Range {
+                            lhs,
+                            rhs,
+                            range_type,
+                        },
+                        syntax_ptr,
+                    ),
+                    None => self.alloc_expr(Expr::Missing, syntax_ptr),
+                }
+            }
         }
     }
 
@@ -791,29 +1139,52 @@ impl ExprCollector {
     }
 
     fn collect_block(&mut self, block: &ast::Block) -> ExprId {
+        self.collect_block_with(block, None, false)
+    }
+
+    fn collect_block_with(
+        &mut self,
+        block: &ast::Block,
+        label: Option<Name>,
+        is_unsafe: bool,
+    ) -> ExprId {
         let statements = block
             .statements()
-            .map(|s| match s.kind() {
+            .filter_map(|s| match s.kind() {
+                ast::StmtKind::LetStmt(stmt) if !self.cfg_options.is_cfg_enabled(stmt) => None,
+                ast::StmtKind::ExprStmt(stmt) if !self.cfg_options.is_cfg_enabled(stmt) => None,
                 ast::StmtKind::LetStmt(stmt) => {
                     let pat = self.collect_pat_opt(stmt.pat());
                     let type_ref = stmt.type_ref().map(TypeRef::from_ast);
                     let initializer = stmt.initializer().map(|e| self.collect_expr(e));
-                    Statement::Let {
+                    Some(Statement::Let {
                         pat,
                         type_ref,
                         initializer,
-                    }
+                    })
                 }
                 ast::StmtKind::ExprStmt(stmt) => {
-                    Statement::Expr(self.collect_expr_opt(stmt.expr()))
+                    Some(Statement::Expr(self.collect_expr_opt(stmt.expr())))
                 }
             })
             .collect();
         let tail = block.expr().map(|e| self.collect_expr(e));
-        self.alloc_expr(
-            Expr::Block { statements, tail },
+        let id = self.alloc_expr(
+            Expr::Block {
+                statements,
+                tail,
+                label,
+                is_unsafe,
+            },
             SyntaxNodePtr::new(block.syntax()),
-        )
+        );
+        // Items in macro expansions don't have a place in the item tree.
+        if self.expansions.is_empty() && block.items().next().is_some() {
+            if let Some(module) = (self.block_module)(block) {
+                self.block_modules.insert(id, module);
+            }
+        }
+        id
     }
 
     fn collect_block_opt(&mut self, block: Option<&ast::Block>) -> ExprId {
@@ -886,6 +1257,20 @@ impl ExprCollector {
                 }
             }
 
+            ast::PatKind::LiteralPat(_) => match self.collect_pat_bound(pat) {
+                Some(expr) => Pat::Lit(expr),
+                None => Pat::Missing,
+            },
+            // exclusive range patterns are unstable, so they are not
+            // supported
+            ast::PatKind::RangePat(p) if p.is_inclusive() => {
+                let start = p.start().and_then(|it| self.collect_pat_bound(it));
+                let end = p.end().and_then(|it| self.collect_pat_bound(it));
+                match (start, end) {
+                    (Some(start), Some(end)) => Pat::Range { start, end },
+                    _ => Pat::Missing,
+                }
+            }
             // TODO: implement
             ast::PatKind::SlicePat(_) | ast::PatKind::RangePat(_) => Pat::Missing,
         };
@@ -893,6 +1278,36 @@ impl ExprCollector {
         self.alloc_pat(pattern, syntax_ptr)
     }
 
+    /// Collects the expression a literal pattern or a bound of a range
+    /// pattern compares with, like `-1` or `MAX`.
+    fn collect_pat_bound(&mut self, pat: &ast::Pat) -> Option<ExprId> {
+        match pat.kind() {
+            ast::PatKind::LiteralPat(p) => {
+                let literal = self.collect_expr(ast::Expr::cast(p.literal()?.syntax())?);
+                if p.is_negative() {
+                    let syntax_ptr = SyntaxNodePtr::new(p.syntax());
+                    let expr = Expr::UnaryOp {
+                        expr: literal,
+                        op: UnaryOp::Neg,
+                    };
+                    Some(self.alloc_expr(expr, syntax_ptr))
+                } else {
+                    Some(literal)
+                }
+            }
+            ast::PatKind::PathPat(p) => {
+                let path = p.path().and_then(Path::from_ast)?;
+                Some(self.alloc_expr(Expr::Path(path), SyntaxNodePtr::new(p.syntax())))
+            }
+            // `1..=LIMIT` parses its end as a binding, but it can only be a path
+            ast::PatKind::BindPat(bp) if !bp.is_mutable() && !bp.is_ref() && bp.pat().is_none() => {
+                let path = Path::from(bp.name()?.as_name());
+                Some(self.alloc_expr(Expr::Path(path), SyntaxNodePtr::new(bp.syntax())))
+            }
+            _ => None,
+        }
+    }
+
     fn collect_pat_opt(&mut self, pat: Option<&ast::Pat>) -> PatId {
         if let Some(pat) = pat {
             self.collect_pat(pat)
@@ -907,6 +1322,11 @@ impl ExprCollector {
             pats: self.pats,
             params,
             body_expr,
+            expr_hygiene: self.expr_hygiene,
+            pat_hygiene: self.pat_hygiene,
+            block_modules: self.block_modules,
+            break_targets: self.break_targets,
+            problems: self.problems,
         };
         BodySyntaxMapping {
             body: Arc::new(body),
@@ -918,13 +1338,21 @@ impl ExprCollector {
     }
 }
 
-pub(crate) fn collect_fn_body_syntax(node: &ast::FnDef) -> BodySyntaxMapping {
-    let mut collector = ExprCollector::new();
+pub(crate) fn collect_fn_body_syntax(
+    node: &ast::FnDef,
+    cfg_options: &CfgOptions,
+    read_file: ReadFile,
+    block_module: BlockModule,
+) -> BodySyntaxMapping {
+    let mut collector = ExprCollector::new(cfg_options, read_file, block_module);
 
     let params = if let Some(param_list) = node.param_list() {
         let mut params = Vec::new();
 
         if let Some(self_param) = param_list.self_param() {
+            // `mut self` is a mutable binding, `&mut self` is not.
+            let is_mutable = self_param.flavor() == ast::SelfParamFlavor::Owned
+                && self_param.syntax().children().any(|it| it.kind() == MUT_KW);
             let self_param = SyntaxNodePtr::new(
                 self_param
                     .self_kw()
@@ -934,7 +1362,7 @@ pub(crate) fn collect_fn_body_syntax(node: &ast::FnDef) -> BodySyntaxMapping {
             let param = collector.alloc_pat(
                 Pat::Bind {
                     name: Name::self_param(),
-                    mode: BindingAnnotation::Unannotated,
+                    mode: BindingAnnotation::new(is_mutable, false),
                     subpat: None,
                 },
                 self_param,
@@ -959,8 +1387,82 @@ pub(crate) fn collect_fn_body_syntax(node: &ast::FnDef) -> BodySyntaxMapping {
     collector.into_body_syntax_mapping(params, body)
 }
 
-pub(crate) fn body_syntax_mapping(db: &impl HirDatabase, func: Function) -> Arc<BodySyntaxMapping> {
-    let (_, fn_def) = func.source(db);
-    let body_syntax_mapping = collect_fn_body_syntax(&fn_def);
+/// Lowers a body which is a single expression without parameters: the
+/// initializer of a const or static, or an enum discriminant.
+pub(crate) fn collect_expr_body_syntax(
+    expr: Option<&ast::Expr>,
+    cfg_options: &CfgOptions,
+    read_file: ReadFile,
+    block_module: BlockModule,
+) -> BodySyntaxMapping {
+    let mut collector = ExprCollector::new(cfg_options, read_file, block_module);
+    let body = collector.collect_expr_opt(expr);
+    collector.into_body_syntax_mapping(Vec::new(), body)
+}
+
+/// Lowers an expression in a type, like the length `N * 2` in `[u8; N * 2]`.
+/// Types are lowered without queries, so `cfg` attributes and `include!`s in
+/// the expression are not taken into account, and items in its blocks are not
+/// visible.
+pub(crate) fn collect_type_expr(expr: &ast::Expr) -> Arc<Body> {
+    let cfg_options = CfgOptions::default();
+    let read_file = |_: &str| None;
+    let block_module = |_: &ast::Block| None;
+    let mapping = collect_expr_body_syntax(Some(expr), &cfg_options, &read_file, &block_module);
+    Arc::clone(&mapping.body)
+}
+
+pub(crate) fn body_syntax_mapping(
+    db: &impl HirDatabase,
+    def: DefWithBody,
+) -> Arc<BodySyntaxMapping> {
+    let crate_graph = db.crate_graph();
+    let module = def.module(db);
+    let cfg_options = crate_graph.cfg_options(module.krate);
+    let read_file_in = |file_id: HirFileId| {
+        let file_id = file_id.original_file(db);
+        move |path: &str| macros::read_included_file(db, file_id, path)
+    };
+    let block_module_in = |file_id: HirFileId| {
+        move |block: &ast::Block| Some(module.module_at(db, file_id, block.syntax()))
+    };
+    let body_syntax_mapping = match def {
+        DefWithBody::Function(func) => {
+            let (file_id, fn_def) = func.source(db);
+            collect_fn_body_syntax(
+                &fn_def,
+                cfg_options,
+                &read_file_in(file_id),
+                &block_module_in(file_id),
+            )
+        }
+        DefWithBody::Const(const_) => {
+            let (file_id, const_def) = const_.source(db);
+            collect_expr_body_syntax(
+                const_def.expr(),
+                cfg_options,
+                &read_file_in(file_id),
+                &block_module_in(file_id),
+            )
+        }
+        DefWithBody::Static(static_) => {
+            let (file_id, static_def) = static_.source(db);
+            collect_expr_body_syntax(
+                static_def.expr(),
+                cfg_options,
+                &read_file_in(file_id),
+                &block_module_in(file_id),
+            )
+        }
+        DefWithBody::EnumVariant(variant) => {
+            let (file_id, variant_def) = variant.source(db);
+            collect_expr_body_syntax(
+                variant_def.expr(),
+                cfg_options,
+                &read_file_in(file_id),
+                &block_module_in(file_id),
+            )
+        }
+    };
     Arc::new(body_syntax_mapping)
 }
@@ -5,21 +5,49 @@
 
 use std::sync::Arc;
 
-use ra_syntax::ast::{self, NameOwner, TypeParamsOwner};
+use ra_syntax::ast::{self, AstToken, NameOwner, TypeParamsOwner};
 
-use crate::{db::HirDatabase, Name, AsName, Function, Struct, Enum, Trait, Type};
+use crate::{
+    db::HirDatabase, Name, AsName, Function, Struct, Enum, Trait, Type, Path, PathKind,
+    type_ref::TypeRef,
+    path::{PathSegment, GenericArgs, GenericArg},
+};
 
 /// Data about a generic parameter (to a function, struct, impl, ...).
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct GenericParam {
     pub(crate) idx: u32,
     pub(crate) name: Name,
+    /// The default type, like `Self` in `trait Add<Rhs = Self>`.
+    pub(crate) default: Option<TypeRef>,
+}
+
+/// A lifetime parameter, like `'a` in `struct Foo<'a>`. We don't do anything
+/// with lifetimes yet, so we just record the name.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LifetimeParam {
+    pub(crate) name: Name,
 }
 
 /// Data about the generic parameters of a function, struct, impl, etc.
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
 pub struct GenericParams {
+    /// The generic parameters of the containing item, e.g. of the trait for a
+    /// method declared in a trait or an impl. They come first in the
+    /// numbering.
+    pub(crate) parent_params: Option<Arc<GenericParams>>,
     pub(crate) params: Vec<GenericParam>,
+    pub(crate) lifetimes: Vec<LifetimeParam>,
+    pub(crate) where_predicates: Vec<WherePredicate>,
+}
+
+/// A single predicate from a where clause, i.e. `where Type: Trait`. Bounds on
+/// type parameters, like `T: Trait` in `fn foo<T: Trait>()`, are desugared to
+/// these as well.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WherePredicate {
+    pub(crate) type_ref: TypeRef,
+    pub(crate) trait_ref: Path,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
@@ -39,37 +67,145 @@ impl GenericParams {
     ) -> Arc<GenericParams> {
         let mut generics = GenericParams::default();
         match def {
-            GenericDef::Function(it) => generics.fill(&*it.source(db).1),
+            GenericDef::Function(it) => {
+                generics.parent_params = match it.impl_block(db) {
+                    Some(impl_block) => Some(Arc::new(impl_block.generic_params().clone())),
+                    None => it.parent_trait(db).map(|tr| db.generic_params(tr.into())),
+                };
+                generics.fill(&*it.source(db).1)
+            }
             GenericDef::Struct(it) => generics.fill(&*it.source(db).1),
             GenericDef::Enum(it) => generics.fill(&*it.source(db).1),
-            GenericDef::Trait(it) => generics.fill(&*it.source(db).1),
+            GenericDef::Trait(it) => {
+                // Traits have an implicit `Self` parameter, which always comes
+                // first.
+                generics.params.push(GenericParam {
+                    idx: 0,
+                    name: Name::self_type(),
+                    default: None,
+                });
+                let (_, node) = it.source(db);
+                generics.fill(&*node);
+                // As in rustc, the predicates of a trait include `Self: Trait`.
+                if let Some(name) = node.name() {
+                    generics.add_self_predicate(name.as_name());
+                }
+            }
             GenericDef::Type(it) => generics.fill(&*it.source(db).1),
         }
 
         Arc::new(generics)
     }
 
+    pub(crate) fn from_ast(node: &impl TypeParamsOwner) -> GenericParams {
+        let mut generics = GenericParams::default();
+        generics.fill(node);
+        generics
+    }
+
     fn fill(&mut self, node: &impl TypeParamsOwner) {
         if let Some(params) = node.type_param_list() {
             self.fill_params(params)
         }
+        if let Some(where_clause) = node.where_clause() {
+            self.fill_where_predicates(where_clause);
+        }
     }
 
     fn fill_params(&mut self, params: &ast::TypeParamList) {
+        let start = self.count_params_including_parent();
         for (idx, type_param) in params.type_params().enumerate() {
             let name = type_param
                 .name()
                 .map(AsName::as_name)
                 .unwrap_or_else(Name::missing);
+            for bound in type_param.bounds() {
+                self.add_where_predicate(TypeRef::Path(name.clone().into()), bound);
+            }
             let param = GenericParam {
-                idx: idx as u32,
+                idx: (start + idx) as u32,
                 name,
+                default: type_param.default_type().map(TypeRef::from_ast),
             };
             self.params.push(param);
         }
+        for lifetime_param in params.lifetime_params() {
+            if let Some(lifetime) = lifetime_param.lifetime() {
+                self.lifetimes.push(LifetimeParam {
+                    name: Name::new(lifetime.text().clone()),
+                });
+            }
+        }
+    }
+
+    fn fill_where_predicates(&mut self, where_clause: &ast::WhereClause) {
+        for pred in where_clause.predicates() {
+            let type_ref = match pred.type_ref() {
+                Some(it) => TypeRef::from_ast(it),
+                None => continue,
+            };
+            for bound in pred.bounds() {
+                self.add_where_predicate(type_ref.clone(), bound);
+            }
+        }
+    }
+
+    fn add_where_predicate(&mut self, type_ref: TypeRef, bound: &ast::Path) {
+        if let Some(trait_ref) = Path::from_ast(bound) {
+            self.where_predicates.push(WherePredicate {
+                type_ref,
+                trait_ref,
+            });
+        }
+    }
+
+    fn add_self_predicate(&mut self, trait_name: Name) {
+        let args = self.params[1..]
+            .iter()
+            .map(|param| GenericArg::Type(TypeRef::Path(param.name.clone().into())))
+            .collect::<Vec<_>>();
+        let args_and_bindings = if args.is_empty() {
+            None
+        } else {
+            Some(Arc::new(GenericArgs {
+                args,
+                bindings: Vec::new(),
+            }))
+        };
+        self.where_predicates.push(WherePredicate {
+            type_ref: TypeRef::Path(Name::self_type().into()),
+            trait_ref: Path {
+                kind: PathKind::Plain,
+                segments: vec![PathSegment {
+                    name: trait_name,
+                    args_and_bindings,
+                }],
+            },
+        });
+    }
+
+    /// The where predicates of this item and of the items containing it.
+    pub(crate) fn where_predicates_including_parent(&self) -> Vec<&WherePredicate> {
+        let mut predicates = self
+            .parent_params
+            .as_ref()
+            .map_or_else(Vec::new, |p| p.where_predicates_including_parent());
+        predicates.extend(self.where_predicates.iter());
+        predicates
     }
 
     pub(crate) fn find_by_name(&self, name: &Name) -> Option<&GenericParam> {
-        self.params.iter().find(|p| &p.name == name)
+        self.params
+            .iter()
+            .find(|p| &p.name == name)
+            .or_else(|| self.parent_params.as_ref()?.find_by_name(name))
+    }
+
+    pub(crate) fn count_params_including_parent(&self) -> usize {
+        let parent_count = self
+            .parent_params
+            .as_ref()
+            .map_or(0, |p| p.count_params_including_parent());
+        parent_count + self.params.len()
     }
 }
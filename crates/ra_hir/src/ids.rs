@@ -4,7 +4,7 @@ use std::{
 };
 
 use ra_db::{LocationIntener, FileId};
-use ra_syntax::{TreeArc, SyntaxNode, SourceFile, AstNode, ast};
+use ra_syntax::{TreeArc, SyntaxNode, SourceFile, AstNode, ast::{self, ModuleItemOwner}};
 use ra_arena::{Arena, RawId, ArenaId, impl_arena_id};
 
 use crate::{
@@ -22,6 +22,7 @@ pub struct HirInterner {
     statics: LocationIntener<ItemLoc<ast::StaticDef>, StaticId>,
     traits: LocationIntener<ItemLoc<ast::TraitDef>, TraitId>,
     types: LocationIntener<ItemLoc<ast::TypeDef>, TypeId>,
+    blocks: LocationIntener<ItemLoc<ast::Block>, BlockId>,
 }
 
 impl HirInterner {
@@ -34,6 +35,7 @@ impl HirInterner {
             + self.statics.len()
             + self.traits.len()
             + self.types.len()
+            + self.blocks.len()
     }
 }
 
@@ -295,6 +297,18 @@ impl AstItemDef<ast::TypeDef> for TypeId {
     }
 }
 
+/// A block expression which declares items, like `{ fn helper() {} helper() }`.
+/// Such a block has its own anonymous module, which holds the items and
+/// imports of the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId(RawId);
+impl_arena_id!(BlockId);
+impl AstItemDef<ast::Block> for BlockId {
+    fn interner(interner: &HirInterner) -> &LocationIntener<ItemLoc<ast::Block>, Self> {
+        &interner.blocks
+    }
+}
+
 /// Identifier of item within a specific file. This is stable over reparses, so
 /// it's OK to use it as a salsa key/value.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -335,6 +349,10 @@ impl SourceFileItems {
                 self.alloc(module_item.syntax().to_owned());
             } else if let Some(macro_call) = ast::MacroCall::cast(it) {
                 self.alloc(macro_call.syntax().to_owned());
+            } else if let Some(block) = ast::Block::cast(it) {
+                if block.items().next().is_some() {
+                    self.alloc(block.syntax().to_owned());
+                }
             }
         })
     }
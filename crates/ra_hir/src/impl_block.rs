@@ -2,14 +2,20 @@ use std::sync::Arc;
 use rustc_hash::FxHashMap;
 
 use ra_arena::{Arena, RawId, impl_arena_id};
-use ra_syntax::ast::{self, AstNode};
+use ra_db::CfgOptions;
+use ra_syntax::{
+    SyntaxNode,
+    SyntaxKind::{IDENT, COMMA, MODULE},
+    ast::{self, AstNode, AttrsOwner, NameOwner, TypeParamsOwner, ModuleItemOwner},
+};
 
 use crate::{
-    Const, Type,
-    Function, HirFileId,
+    Const, Type, Trait, ModuleDef, Function, HirFileId, Name, AsName, Path, PathKind,
     db::HirDatabase,
     type_ref::TypeRef,
-    ids::LocationCtx,
+    ids::{LocationCtx, AstItemDef},
+    path::{PathSegment, GenericArgs, GenericArg},
+    generics::{GenericParams, WherePredicate},
 };
 
 use crate::code_model_api::{Module, ModuleSource};
@@ -43,10 +49,31 @@ impl ImplBlock {
         &self.module_impl_blocks.impls[self.impl_id]
     }
 
+    pub fn module(&self) -> Module {
+        self.module_impl_blocks.module
+    }
+
     pub fn target_trait(&self) -> Option<&TypeRef> {
         self.impl_data().target_trait()
     }
 
+    /// Resolves the trait this impl is for, if any.
+    pub fn target_trait_ref(&self, db: &impl HirDatabase) -> Option<Trait> {
+        let path = match self.target_trait()? {
+            TypeRef::Path(path) => path,
+            _ => return None,
+        };
+        match self.module().resolve_path(db, path).take_types()? {
+            ModuleDef::Trait(tr) => Some(tr),
+            _ => None,
+        }
+    }
+
+    /// Whether this is an `unsafe impl`.
+    pub fn is_unsafe(&self) -> bool {
+        self.impl_data().is_unsafe
+    }
+
     pub fn target_type(&self) -> &TypeRef {
         self.impl_data().target_type()
     }
@@ -54,6 +81,10 @@ impl ImplBlock {
     pub fn items(&self) -> &[ImplItem] {
         self.impl_data().items()
     }
+
+    pub fn generic_params(&self) -> &GenericParams {
+        self.impl_data().generic_params()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -61,6 +92,8 @@ pub struct ImplData {
     target_trait: Option<TypeRef>,
     target_type: TypeRef,
     items: Vec<ImplItem>,
+    generics: GenericParams,
+    is_unsafe: bool,
 }
 
 impl ImplData {
@@ -72,10 +105,15 @@ impl ImplData {
     ) -> Self {
         let target_trait = node.target_trait().map(TypeRef::from_ast);
         let target_type = TypeRef::from_ast_opt(node.target_type());
+        let generics = GenericParams::from_ast(node);
+        let is_unsafe = node.is_unsafe();
         let ctx = LocationCtx::new(db, module, file_id);
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(module.krate);
         let items = if let Some(item_list) = node.item_list() {
             item_list
                 .impl_items()
+                .filter(|item_node| cfg_options.is_cfg_enabled(*item_node))
                 .map(|item_node| match item_node.kind() {
                     ast::ImplItemKind::FnDef(it) => {
                         ImplItem::Method(Function { id: ctx.to_def(it) })
@@ -93,6 +131,31 @@ impl ImplData {
             target_trait,
             target_type,
             items,
+            generics,
+            is_unsafe,
+        }
+    }
+
+    /// Creates the impl of a built-in derive, like `impl Clone for Foo`. The
+    /// impl has no items: the methods are the ones declared in the trait. As
+    /// in rustc, every type parameter of the item is required to implement the
+    /// derived trait as well.
+    fn from_derive(trait_path: Path, target_type: TypeRef, mut generics: GenericParams) -> Self {
+        let bounds = generics
+            .params
+            .iter()
+            .map(|param| WherePredicate {
+                type_ref: TypeRef::Path(param.name.clone().into()),
+                trait_ref: trait_path.clone(),
+            })
+            .collect::<Vec<_>>();
+        generics.where_predicates.extend(bounds);
+        ImplData {
+            target_trait: Some(TypeRef::Path(trait_path)),
+            target_type,
+            items: Vec::new(),
+            generics,
+            is_unsafe: false,
         }
     }
 
@@ -107,6 +170,10 @@ impl ImplData {
     pub fn items(&self) -> &[ImplItem] {
         &self.items
     }
+
+    pub fn generic_params(&self) -> &GenericParams {
+        &self.generics
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -136,19 +203,30 @@ impl_arena_id!(ImplId);
 /// we don't need to do the second step again.
 #[derive(Debug, PartialEq, Eq)]
 pub struct ModuleImplBlocks {
+    module: Module,
     pub(crate) impls: Arena<ImplId, ImplData>,
     impls_by_def: FxHashMap<ImplItem, ImplId>,
+    /// The anonymous modules of the blocks in the module which may contain
+    /// impls, including the ones of nested blocks.
+    pub(crate) blocks: Vec<Module>,
 }
 
 impl ModuleImplBlocks {
-    fn new() -> Self {
+    fn new(module: Module) -> Self {
         ModuleImplBlocks {
+            module,
             impls: Arena::default(),
             impls_by_def: FxHashMap::default(),
+            blocks: Vec::new(),
         }
     }
 
     fn collect(&mut self, db: &impl HirDatabase, module: Module) {
+        if let Some(block) = module.block {
+            let (file_id, block) = block.source(db);
+            self.collect_items(db, module, file_id, block.syntax());
+            return;
+        }
         let (file_id, module_source) = module.definition_source(db);
         let file_id: HirFileId = file_id.into();
         let node = match &module_source {
@@ -158,19 +236,191 @@ impl ModuleImplBlocks {
                 .expect("inline module should have item list")
                 .syntax(),
         };
+        self.collect_items(db, module, file_id, node);
+        self.collect_blocks(db, module, file_id, node);
+    }
 
+    /// Collects the impls among the items of `node`, a module's item list or
+    /// a block.
+    fn collect_items(
+        &mut self,
+        db: &impl HirDatabase,
+        module: Module,
+        file_id: HirFileId,
+        node: &SyntaxNode,
+    ) {
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(module.krate);
         for impl_block_ast in node.children().filter_map(ast::ImplBlock::cast) {
+            if !cfg_options.is_cfg_enabled(impl_block_ast) {
+                continue;
+            }
             let impl_block = ImplData::from_ast(db, file_id, module, impl_block_ast);
             let id = self.impls.alloc(impl_block);
             for &impl_item in &self.impls[id].items {
                 self.impls_by_def.insert(impl_item, id);
             }
         }
+
+        let derive_crate = derive_crate(db, module);
+        for struct_def in node.children().filter_map(ast::StructDef::cast) {
+            self.collect_derives(cfg_options, &derive_crate, struct_def);
+        }
+        for enum_def in node.children().filter_map(ast::EnumDef::cast) {
+            self.collect_derives(cfg_options, &derive_crate, enum_def);
+        }
+    }
+
+    /// Finds the blocks inside of `node` which declare impls or types with
+    /// derives. Nested inline modules have impls of their own.
+    fn collect_blocks(
+        &mut self,
+        db: &impl HirDatabase,
+        module: Module,
+        file_id: HirFileId,
+        node: &SyntaxNode,
+    ) {
+        for child in node.children() {
+            if child.kind() == MODULE {
+                continue;
+            }
+            let block = match ast::Block::cast(child) {
+                Some(it) if it.items().next().is_some() => it,
+                _ => {
+                    self.collect_blocks(db, module, file_id, child);
+                    continue;
+                }
+            };
+            let block_module = module.block_module(db, file_id, block);
+            let has_impls = block.items().any(|item| match item.kind() {
+                ast::ModuleItemKind::ImplBlock(_)
+                | ast::ModuleItemKind::StructDef(_)
+                | ast::ModuleItemKind::EnumDef(_) => true,
+                _ => false,
+            });
+            if has_impls {
+                self.blocks.push(block_module);
+            }
+            self.collect_blocks(db, block_module, file_id, child);
+        }
     }
+
+    fn collect_derives<N>(&mut self, cfg_options: &CfgOptions, derive_crate: &Name, adt: &N)
+    where
+        N: AttrsOwner + NameOwner + TypeParamsOwner,
+    {
+        if !cfg_options.is_cfg_enabled(adt) {
+            return;
+        }
+        let name = match adt.name() {
+            Some(it) => it.as_name(),
+            None => return,
+        };
+        let target_type = derive_target_type(name, adt.type_param_list());
+        let generics = GenericParams::from_ast(adt);
+        for trait_path in builtin_derives(derive_crate, adt) {
+            self.impls.alloc(ImplData::from_derive(
+                trait_path,
+                target_type.clone(),
+                generics.clone(),
+            ));
+        }
+    }
+}
+
+/// The built-in derives, together with the paths of the traits they implement.
+const BUILTIN_DERIVES: &[(&str, &str)] = &[
+    ("Clone", "clone"),
+    ("Copy", "marker"),
+    ("Debug", "fmt"),
+    ("Default", "default"),
+    ("PartialEq", "cmp"),
+    ("Eq", "cmp"),
+    ("PartialOrd", "cmp"),
+    ("Ord", "cmp"),
+    ("Hash", "hash"),
+];
+
+/// The crate providing the traits of the built-in derives: `core` if it is in
+/// the extern prelude, like in `no_std` crates, and `std` otherwise.
+fn derive_crate(db: &impl HirDatabase, module: Module) -> Name {
+    let core = Name::new("core".into());
+    if db.item_map(module.krate).has_extern_crate(&core) {
+        core
+    } else {
+        Name::new("std".into())
+    }
+}
+
+/// Returns the paths of the traits implemented by the built-in derives of an
+/// item, like `::core::clone::Clone` for `#[derive(Clone)]`. The paths start
+/// at the extern prelude, so local items named `core` or `std` don't matter.
+/// Custom derives are ignored.
+fn builtin_derives(derive_crate: &Name, node: &impl AttrsOwner) -> Vec<Path> {
+    let mut res = Vec::new();
+    for attr in node.attrs() {
+        let tt = match attr.as_call() {
+            Some((name, tt)) if name == "derive" => tt,
+            _ => continue,
+        };
+        // Takes the last identifier of each comma-separated path, so that
+        // `std::fmt::Debug` works as well.
+        let mut last_ident = None;
+        for token in tt.syntax().children() {
+            match token.kind() {
+                IDENT => last_ident = token.leaf_text().cloned(),
+                COMMA => res.extend(last_ident.take()),
+                _ => (),
+            }
+        }
+        res.extend(last_ident);
+    }
+    res.into_iter()
+        .filter_map(|derive| {
+            let &(name, module) = BUILTIN_DERIVES.iter().find(|(name, _)| *name == derive)?;
+            let segments = Some(derive_crate.clone())
+                .into_iter()
+                .chain([module, name].iter().map(|&it| Name::new(it.into())))
+                .map(|name| PathSegment {
+                    name,
+                    args_and_bindings: None,
+                })
+                .collect();
+            Some(Path {
+                kind: PathKind::ExternPrelude,
+                segments,
+            })
+        })
+        .collect()
+}
+
+/// The self type of a derived impl: `Foo<T, U>` for `struct Foo<T, U>`.
+fn derive_target_type(name: Name, type_params: Option<&ast::TypeParamList>) -> TypeRef {
+    let args = type_params
+        .into_iter()
+        .flat_map(|it| it.type_params())
+        .filter_map(|it| it.name())
+        .map(|it| GenericArg::Type(TypeRef::Path(it.as_name().into())))
+        .collect::<Vec<_>>();
+    let args_and_bindings = if args.is_empty() {
+        None
+    } else {
+        Some(Arc::new(GenericArgs {
+            args,
+            bindings: Vec::new(),
+        }))
+    };
+    TypeRef::Path(Path {
+        kind: PathKind::Plain,
+        segments: vec![PathSegment {
+            name,
+            args_and_bindings,
+        }],
+    })
 }
 
 pub(crate) fn impls_in_module(db: &impl HirDatabase, module: Module) -> Arc<ModuleImplBlocks> {
-    let mut result = ModuleImplBlocks::new();
+    let mut result = ModuleImplBlocks::new(module);
     result.collect(db, module);
     Arc::new(result)
 }
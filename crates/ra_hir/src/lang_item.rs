@@ -0,0 +1,84 @@
+//! Lang items are the items the compiler knows about specially, like the `Add`
+//! trait which `+` desugars to. They are marked with `#[lang = "..."]`
+//! attributes, mostly in `core`.
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use ra_syntax::{SmolStr, ast::AttrsOwner};
+
+use crate::{Crate, Module, ModuleDef, Trait, HirDatabase};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LangItemTarget {
+    Trait(Trait),
+}
+
+impl LangItemTarget {
+    pub fn as_trait(self) -> Option<Trait> {
+        match self {
+            LangItemTarget::Trait(it) => Some(it),
+        }
+    }
+}
+
+/// The lang items declared in a crate.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LangItems {
+    items: FxHashMap<SmolStr, LangItemTarget>,
+}
+
+impl LangItems {
+    pub fn target(&self, item: &str) -> Option<LangItemTarget> {
+        self.items.get(&SmolStr::new(item)).cloned()
+    }
+
+    pub(crate) fn lang_items_query(db: &impl HirDatabase, krate: Crate) -> Arc<LangItems> {
+        let mut lang_items = LangItems::default();
+        if let Some(module) = krate.root_module(db) {
+            lang_items.collect_lang_items_recursive(db, module);
+        }
+        Arc::new(lang_items)
+    }
+
+    fn collect_lang_items_recursive(&mut self, db: &impl HirDatabase, module: Module) {
+        for (_, resolution) in module.scope(db).entries() {
+            // only the items declared here, not the imported ones
+            if resolution.import.is_some() {
+                continue;
+            }
+            if let Some(ModuleDef::Trait(trait_)) = resolution.def.types {
+                let (_, source) = trait_.source(db);
+                if let Some(name) = lang_item_name(&*source) {
+                    self.items
+                        .entry(name)
+                        .or_insert(LangItemTarget::Trait(trait_));
+                }
+            }
+        }
+        for child in module.children(db) {
+            self.collect_lang_items_recursive(db, child);
+        }
+    }
+}
+
+/// Looks up the lang item `item` in `start_crate` and its dependencies.
+pub(crate) fn lang_item_lookup(
+    db: &impl HirDatabase,
+    start_crate: Crate,
+    item: &str,
+) -> Option<LangItemTarget> {
+    if let Some(target) = db.lang_items(start_crate).target(item) {
+        return Some(target);
+    }
+    start_crate
+        .dependencies(db)
+        .into_iter()
+        .find_map(|dep| lang_item_lookup(db, dep.krate, item))
+}
+
+fn lang_item_name(node: &impl AttrsOwner) -> Option<SmolStr> {
+    node.attrs()
+        .filter_map(|attr| attr.as_key_value())
+        .find(|(key, _)| key == "lang")
+        .map(|(_, value)| value)
+}
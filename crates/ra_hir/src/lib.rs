@@ -33,9 +33,14 @@ mod adt;
 mod type_ref;
 mod ty;
 mod impl_block;
+mod traits;
 mod expr;
 mod generics;
 mod docs;
+mod lang_item;
+mod const_eval;
+mod unsafe_check;
+mod mutability_check;
 
 mod code_model_api;
 mod code_model_impl;
@@ -46,26 +51,32 @@ mod marks;
 use crate::{
     db::HirDatabase,
     name::{AsName, KnownName},
-    ids::{SourceItemId, SourceFileItems},
+    ids::{SourceItemId, SourceFileItems, BlockId},
 };
 
 pub use self::{
     path::{Path, PathKind},
     name::Name,
     ids::{HirFileId, MacroCallId, MacroCallLoc, HirInterner},
-    macros::{MacroDef, MacroInput, MacroExpansion},
-    nameres::{ItemMap, PerNs, Namespace, Resolution},
-    ty::Ty,
+    macros::{MacroDef, MacroInput, MacroExpansion, BuiltinMacro, TokenOrigin, SyntaxContext},
+    nameres::{ItemMap, PerNs, Namespace, Resolution, lower::ImportSource},
+    ty::{Ty, InferenceDiagnostic, traits::{TraitRef, TraitBound, ProjectionTy, Environment, Solution}},
     impl_block::{ImplBlock, ImplItem},
+    traits::TraitItem,
     code_model_impl::function::{FnScopes, ScopesWithSyntaxMapping},
     docs::{Docs, Documentation},
     adt::AdtDef,
+    lang_item::{LangItems, LangItemTarget},
+    const_eval::ConstValue,
+    unsafe_check::{UnsafeExpr, UnsafeOp},
+    mutability_check::{MutabilityDiagnostic, Mutation},
+    expr::BodyProblem,
 };
 
 pub use self::code_model_api::{
     Crate, CrateDependency,
     Def,
-    Module, ModuleDef, ModuleSource, Problem,
+    Module, ModuleDef, ModuleSource, Problem, DefWithBody,
     Struct, Enum, EnumVariant,
     Function, FnSignature, ScopeEntryWithSyntax,
     StructField, FieldSource,
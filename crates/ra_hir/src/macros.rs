@@ -5,39 +5,65 @@
 /// do we do that.
 ///
 /// When the file-management question is resolved, all that is left is a
-/// token-tree-to-token-tree transformation plus hygiene. We don't have either of
-/// those yet, so all macros are string based at the moment!
+/// token-tree-to-token-tree transformation plus hygiene. We don't have token
+/// trees yet, so all macros are string based at the moment! Hygiene is
+/// approximated by remembering, for each token of the expansion, whether it
+/// comes from the macro call or from the macro itself (see `TokenOrigin` and
+/// `SyntaxContext`).
+mod builtin;
+
 use std::sync::Arc;
 
+use relative_path::RelativePath;
+use ra_db::FileId;
 use ra_syntax::{
-    TextRange, TextUnit, SourceFile, AstNode, SyntaxNode, TreeArc, SyntaxNodePtr,
+    TextRange, TextUnit, SourceFile, AstNode, SyntaxNode, TreeArc, SyntaxNodePtr, SmolStr,
+    SyntaxKind::{COMMA, EXPR_STMT, IDENT}, tokenize,
     ast::{self, NameOwner},
 };
 
 use crate::{HirDatabase, MacroCallId};
 
+pub use self::builtin::BuiltinMacro;
+
 // Hard-coded defs for now :-(
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MacroDef {
     CTry,
     Vec,
     QueryGroup,
+    BuiltIn(BuiltinMacro),
+}
+
+/// Reads a file referred to by a macro, like `include_str!("foo.txt")`.
+pub(crate) type ReadFile<'a> = &'a dyn Fn(&str) -> Option<Arc<String>>;
+
+/// Where the macro is called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MacroPosition {
+    Item,
+    Expr,
+}
+
+pub(crate) struct MacroEnv<'a> {
+    read_file: ReadFile<'a>,
+    position: MacroPosition,
 }
 
 impl MacroDef {
     /// Expands macro call, returning the expansion and offset to be used to
     /// convert ranges between expansion and original source.
     pub fn ast_expand(macro_call: &ast::MacroCall) -> Option<(TextUnit, MacroExpansion)> {
-        let (def, input) = MacroDef::from_call(macro_call)?;
-        let exp = def.expand(input)?;
-        let off = macro_call.token_tree()?.syntax().range().start();
-        Some((off, exp))
+        expand_expr_macro(macro_call, &|_| None)
     }
 
     fn from_call(macro_call: &ast::MacroCall) -> Option<(MacroDef, MacroInput)> {
         let def = {
             let path = macro_call.path()?;
             let name_ref = path.segment()?.name_ref()?;
+            if is_shadowed_by_macro_rules(macro_call, name_ref.text()) {
+                return None;
+            }
             if name_ref.text() == "ctry" {
                 MacroDef::CTry
             } else if name_ref.text() == "vec" {
@@ -45,24 +71,47 @@ impl MacroDef {
             } else if name_ref.text() == "query_group" {
                 MacroDef::QueryGroup
             } else {
-                return None;
+                MacroDef::BuiltIn(BuiltinMacro::by_name(name_ref.text())?)
             }
         };
 
         let input = {
-            let arg = macro_call.token_tree()?.syntax();
+            let tt = macro_call.token_tree()?.syntax();
+            let start = tt.range().start();
+            let mut args = Vec::new();
+            let mut arg: Option<TextRange> = None;
+            let mut children = tt.children().collect::<Vec<_>>();
+            // Skip the delimiters
+            if children.len() >= 2 {
+                children.pop();
+                children.remove(0);
+            }
+            for child in children.into_iter().filter(|it| !it.kind().is_trivia()) {
+                if child.kind() == COMMA {
+                    args.extend(arg.take());
+                    continue;
+                }
+                let range = child.range() - start;
+                arg = Some(match arg {
+                    Some(arg) => TextRange::from_to(arg.start(), range.end()),
+                    None => range,
+                });
+            }
+            args.extend(arg);
             MacroInput {
-                text: arg.text().to_string(),
+                text: tt.text().to_string(),
+                args,
             }
         };
         Some((def, input))
     }
 
-    fn expand(self, input: MacroInput) -> Option<MacroExpansion> {
+    fn expand(self, input: MacroInput, env: &MacroEnv) -> Option<MacroExpansion> {
         match self {
             MacroDef::CTry => self.expand_ctry(input),
             MacroDef::Vec => self.expand_vec(input),
             MacroDef::QueryGroup => self.expand_query_group(input),
+            MacroDef::BuiltIn(it) => it.expand(&input, env),
         }
     }
     fn expand_ctry(self, input: MacroInput) -> Option<MacroExpansion> {
@@ -82,11 +131,7 @@ impl MacroDef {
         let ptr = SyntaxNodePtr::new(match_arg.syntax());
         let src_range = TextRange::offset_len(0.into(), TextUnit::of_str(&input.text));
         let ranges_map = vec![(src_range, match_arg.syntax().range())];
-        let res = MacroExpansion {
-            text,
-            ranges_map,
-            ptr,
-        };
+        let res = MacroExpansion::new(self, text, ranges_map, ptr);
         Some(res)
     }
     fn expand_vec(self, input: MacroInput) -> Option<MacroExpansion> {
@@ -96,11 +141,7 @@ impl MacroDef {
         let ptr = SyntaxNodePtr::new(array_expr.syntax());
         let src_range = TextRange::offset_len(0.into(), TextUnit::of_str(&input.text));
         let ranges_map = vec![(src_range, array_expr.syntax().range())];
-        let res = MacroExpansion {
-            text,
-            ranges_map,
-            ptr,
-        };
+        let res = MacroExpansion::new(self, text, ranges_map, ptr);
         Some(res)
     }
     fn expand_query_group(self, input: MacroInput) -> Option<MacroExpansion> {
@@ -120,11 +161,7 @@ impl MacroDef {
         let name = trait_def.name()?;
         let ptr = SyntaxNodePtr::new(trait_def.syntax());
         let ranges_map = vec![(src_range, name.syntax().range())];
-        let res = MacroExpansion {
-            text,
-            ranges_map,
-            ptr,
-        };
+        let res = MacroExpansion::new(self, text, ranges_map, ptr);
         Some(res)
     }
 }
@@ -133,6 +170,8 @@ impl MacroDef {
 pub struct MacroInput {
     // Should be token trees
     pub text: String,
+    /// Comma-separated arguments, relative to the start of the `text`.
+    pub args: Vec<TextRange>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -142,13 +181,66 @@ pub struct MacroExpansion {
     /// Correspondence between ranges in the original source code and ranges in
     /// the macro.
     ranges_map: Vec<(TextRange, TextRange)>,
+    /// The origins of all non-trivia tokens of the expansion, sorted by range.
+    token_map: Vec<(TextRange, TokenOrigin)>,
     /// Implementation detail: internally, a macro is expanded to the whole file,
     /// even if it is an expression. This `ptr` selects the actual expansion from
     /// the expanded file.
     ptr: SyntaxNodePtr,
 }
 
+/// Where a token of a macro expansion comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenOrigin {
+    /// The token is copied from the arguments of the macro call. The range is
+    /// relative to the start of the call's token tree.
+    CallSite(TextRange),
+    /// The token is produced by the given macro definition itself, like the
+    /// `left_val` binding of `assert_eq!`.
+    DefSite(MacroDef),
+}
+
+/// Identifies the macro expansion an identifier comes from. Local variables
+/// are hygienic: a name only resolves to bindings from the same context, so
+/// that bindings introduced by a macro are invisible to the arguments of the
+/// macro call, and vice versa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SyntaxContext(pub(crate) u32);
+
+impl SyntaxContext {
+    /// The context of the code written directly in the source file.
+    pub const ROOT: SyntaxContext = SyntaxContext(0);
+}
+
 impl MacroExpansion {
+    fn new(
+        def: MacroDef,
+        text: String,
+        ranges_map: Vec<(TextRange, TextRange)>,
+        ptr: SyntaxNodePtr,
+    ) -> MacroExpansion {
+        let mut token_map = Vec::new();
+        let mut offset = TextUnit::from(0);
+        for token in tokenize(&text) {
+            let range = TextRange::offset_len(offset, token.len);
+            offset += token.len;
+            if token.kind.is_trivia() {
+                continue;
+            }
+            let origin = match map_range(&ranges_map, range, |(s, t)| (t, s)) {
+                Some(src_range) => TokenOrigin::CallSite(src_range),
+                None => TokenOrigin::DefSite(def),
+            };
+            token_map.push((range, origin));
+        }
+        MacroExpansion {
+            text,
+            ranges_map,
+            token_map,
+            ptr,
+        }
+    }
+
     // FIXME: does not really make sense, macro expansion is not neccessary a
     // whole file. See `MacroExpansion::ptr` as well.
     pub(crate) fn file(&self) -> TreeArc<SourceFile> {
@@ -160,28 +252,69 @@ impl MacroExpansion {
     }
     /// Maps range in the source code to the range in the expanded code.
     pub fn map_range_forward(&self, src_range: TextRange) -> Option<TextRange> {
-        for (s_range, t_range) in self.ranges_map.iter() {
-            if src_range.is_subrange(&s_range) {
-                let src_at_zero_range = src_range - src_range.start();
-                let src_range_offset = src_range.start() - s_range.start();
-                let src_range = src_at_zero_range + src_range_offset + t_range.start();
-                return Some(src_range);
-            }
-        }
-        None
+        map_range(&self.ranges_map, src_range, |(s, t)| (s, t))
     }
     /// Maps range in the expanded code to the range in the source code.
     pub fn map_range_back(&self, tgt_range: TextRange) -> Option<TextRange> {
-        for (s_range, t_range) in self.ranges_map.iter() {
-            if tgt_range.is_subrange(&t_range) {
-                let tgt_at_zero_range = tgt_range - tgt_range.start();
-                let tgt_range_offset = tgt_range.start() - t_range.start();
-                let src_range = tgt_at_zero_range + tgt_range_offset + s_range.start();
-                return Some(src_range);
-            }
+        map_range(&self.ranges_map, tgt_range, |(s, t)| (t, s))
+    }
+    /// Returns the origin of the token which starts at `offset` in the
+    /// expanded code.
+    pub fn token_origin(&self, offset: TextUnit) -> Option<TokenOrigin> {
+        let idx = self
+            .token_map
+            .binary_search_by_key(&offset, |(range, _)| range.start())
+            .ok()?;
+        Some(self.token_map[idx].1)
+    }
+}
+
+/// Maps `range` through `ranges_map`, where `dir` selects the pair of
+/// (from, to) ranges of an entry.
+fn map_range(
+    ranges_map: &[(TextRange, TextRange)],
+    range: TextRange,
+    dir: impl Fn((TextRange, TextRange)) -> (TextRange, TextRange),
+) -> Option<TextRange> {
+    for &entry in ranges_map.iter() {
+        let (from, to) = dir(entry);
+        if range.is_subrange(&from) {
+            let at_zero_range = range - range.start();
+            let range_offset = range.start() - from.start();
+            return Some(at_zero_range + range_offset + to.start());
         }
-        None
     }
+    None
+}
+
+/// Whether a `macro_rules! name` defined before `macro_call` in one of its
+/// enclosing scopes shadows the hard-coded macros. Like in rustc, such macros
+/// are scoped textually.
+fn is_shadowed_by_macro_rules(macro_call: &ast::MacroCall, name: &str) -> bool {
+    let start = macro_call.syntax().range().start();
+    macro_call.syntax().ancestors().skip(1).any(|scope| {
+        scope
+            .children()
+            .take_while(|it| it.range().end() <= start)
+            .filter_map(|it| match it.kind() {
+                EXPR_STMT => it.children().find_map(ast::MacroCall::cast),
+                _ => ast::MacroCall::cast(it),
+            })
+            .any(|it| macro_rules_name(it).map_or(false, |it| it == name))
+    })
+}
+
+/// The name of the macro defined by `macro_rules! name { ... }`.
+fn macro_rules_name(macro_call: &ast::MacroCall) -> Option<&SmolStr> {
+    let path = macro_call.path()?;
+    if path.qualifier().is_some() || path.segment()?.name_ref()?.text() != "macro_rules" {
+        return None;
+    }
+    macro_call
+        .syntax()
+        .children()
+        .find(|it| it.kind() == IDENT)?
+        .leaf_text()
 }
 
 pub(crate) fn expand_macro_invocation(
@@ -193,5 +326,70 @@ pub(crate) fn expand_macro_invocation(
     let macro_call = ast::MacroCall::cast(&syntax).unwrap();
 
     let (def, input) = MacroDef::from_call(macro_call)?;
-    def.expand(input).map(Arc::new)
+    let file_id = loc.source_item_id.file_id.original_file(db);
+    let env = MacroEnv {
+        read_file: &|path| read_included_file(db, file_id, path),
+        position: MacroPosition::Item,
+    };
+    def.expand(input, &env).map(Arc::new)
+}
+
+/// Expands a macro call in expression position. Returns the offset of the
+/// token tree, like `MacroDef::ast_expand`.
+pub(crate) fn expand_expr_macro(
+    macro_call: &ast::MacroCall,
+    read_file: ReadFile,
+) -> Option<(TextUnit, MacroExpansion)> {
+    let (def, input) = MacroDef::from_call(macro_call)?;
+    let env = MacroEnv {
+        read_file,
+        position: MacroPosition::Expr,
+    };
+    let exp = def.expand(input, &env)?;
+    let off = macro_call.token_tree()?.syntax().range().start();
+    Some((off, exp))
+}
+
+/// Reads a file by a path relative to the directory of `file_id`. This records
+/// a dependency on the contents of the included file.
+pub(crate) fn read_included_file(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    path: &str,
+) -> Option<Arc<String>> {
+    let file_path = db.file_relative_path(file_id);
+    let dir = file_path.parent().unwrap_or_else(|| RelativePath::new(""));
+    let path = dir.join(path).normalize();
+    let included = crate::module_tree::resolve_file(db, db.file_source_root(file_id), &path)?;
+    Some(db.file_text(included))
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::{SourceFile, AstNode, ast};
+
+    use super::*;
+
+    #[test]
+    fn token_origins_of_expansion() {
+        let file = SourceFile::parse("fn f() { format!(\"{}\", foo) }");
+        let call = file
+            .syntax()
+            .descendants()
+            .find_map(ast::MacroCall::cast)
+            .unwrap();
+        let (_, exp) = MacroDef::ast_expand(call).unwrap();
+        let format = MacroDef::BuiltIn(BuiltinMacro::Format);
+        let format_fn = TextUnit::from_usize(exp.text.find("::std::fmt::format").unwrap());
+        assert_eq!(
+            exp.token_origin(format_fn),
+            Some(TokenOrigin::DefSite(format))
+        );
+        let foo = TextUnit::from_usize(exp.text.find("foo").unwrap());
+        let foo_in_call = TextRange::offset_len(7.into(), 3.into());
+        assert_eq!(
+            exp.token_origin(foo),
+            Some(TokenOrigin::CallSite(foo_in_call))
+        );
+    }
 }
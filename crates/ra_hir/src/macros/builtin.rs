@@ -0,0 +1,272 @@
+//! Macros which are built into the compiler or defined in `std`, like
+//! `format_args!`, `concat!` or `include!`.
+//!
+//! The expansions are not precise: they only have to produce code which is
+//! well-typed, and which contains the arguments of the macro call as real
+//! expressions, so that we can infer their types and map them back.
+use ra_syntax::{
+    TextRange, TextUnit, SourceFile, AstNode, SyntaxNodePtr,
+    ast::{self, ModuleItemOwner},
+};
+
+use super::{MacroDef, MacroInput, MacroExpansion, MacroEnv, MacroPosition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinMacro {
+    FormatArgs,
+    Format,
+    Print,
+    Eprint,
+    Write,
+    Panic,
+    Assert,
+    AssertEq,
+    AssertNe,
+    Concat,
+    Stringify,
+    Include,
+    IncludeStr,
+    Env,
+    OptionEnv,
+    File,
+    Line,
+    Column,
+    ModulePath,
+}
+
+impl BuiltinMacro {
+    pub(super) fn by_name(name: &str) -> Option<BuiltinMacro> {
+        let res = match name {
+            "format_args" => BuiltinMacro::FormatArgs,
+            "format" => BuiltinMacro::Format,
+            "print" | "println" => BuiltinMacro::Print,
+            "eprint" | "eprintln" => BuiltinMacro::Eprint,
+            "write" | "writeln" => BuiltinMacro::Write,
+            "panic" | "unreachable" | "unimplemented" => BuiltinMacro::Panic,
+            "assert" | "debug_assert" => BuiltinMacro::Assert,
+            "assert_eq" | "debug_assert_eq" => BuiltinMacro::AssertEq,
+            "assert_ne" | "debug_assert_ne" => BuiltinMacro::AssertNe,
+            "concat" => BuiltinMacro::Concat,
+            "stringify" => BuiltinMacro::Stringify,
+            "include" => BuiltinMacro::Include,
+            "include_str" => BuiltinMacro::IncludeStr,
+            "env" => BuiltinMacro::Env,
+            "option_env" => BuiltinMacro::OptionEnv,
+            "file" => BuiltinMacro::File,
+            "line" => BuiltinMacro::Line,
+            "column" => BuiltinMacro::Column,
+            "module_path" => BuiltinMacro::ModulePath,
+            _ => return None,
+        };
+        Some(res)
+    }
+
+    pub(super) fn expand(self, input: &MacroInput, env: &MacroEnv) -> Option<MacroExpansion> {
+        // Only `include!` can expand to items, the rest are expressions.
+        if env.position == MacroPosition::Item && self != BuiltinMacro::Include {
+            return None;
+        }
+        let args = &input.args;
+        let mut b = ExpansionBuilder::new(self, input);
+        match self {
+            BuiltinMacro::FormatArgs => b.push_format_args(args),
+            BuiltinMacro::Format => {
+                b.push("::std::fmt::format(");
+                b.push_format_args(args);
+                b.push(")");
+            }
+            BuiltinMacro::Print | BuiltinMacro::Eprint => {
+                b.push(if self == BuiltinMacro::Print {
+                    "{ ::std::io::_print("
+                } else {
+                    "{ ::std::io::_eprint("
+                });
+                b.push_format_args(args);
+                b.push("); }");
+            }
+            BuiltinMacro::Write => {
+                let (dst, args) = args.split_first()?;
+                b.push("(");
+                b.push_arg(*dst);
+                b.push(").write_fmt(");
+                b.push_format_args(args);
+                b.push(")");
+            }
+            BuiltinMacro::Panic => b.push_panic(args),
+            BuiltinMacro::Assert => {
+                let (cond, args) = args.split_first()?;
+                b.push("{ if !(");
+                b.push_arg(*cond);
+                b.push(") { ");
+                b.push_panic(args);
+                b.push(" } }");
+            }
+            BuiltinMacro::AssertEq | BuiltinMacro::AssertNe => {
+                if args.len() < 2 {
+                    return None;
+                }
+                b.push("{ match (&(");
+                b.push_arg(args[0]);
+                b.push("), &(");
+                b.push_arg(args[1]);
+                b.push(")) { (left_val, right_val) => { if ");
+                b.push(if self == BuiltinMacro::AssertEq {
+                    "!(*left_val == *right_val)"
+                } else {
+                    "*left_val == *right_val"
+                });
+                b.push(" { ");
+                b.push_panic(&args[2..]);
+                b.push(" } } } }");
+            }
+            BuiltinMacro::Concat => {
+                let mut res = String::new();
+                for &arg in args {
+                    let text = &input.text[arg];
+                    let is_quoted =
+                        (text.starts_with('"') || text.starts_with('\'')) && text.len() >= 2;
+                    res.push_str(if is_quoted {
+                        &text[1..text.len() - 1]
+                    } else {
+                        text
+                    });
+                }
+                b.push(&format!("\"{}\"", res));
+            }
+            BuiltinMacro::Stringify => {
+                let text = input.text.get(1..input.text.len().saturating_sub(1))?;
+                b.push(&format!("{:?}", text.trim()));
+            }
+            BuiltinMacro::Include => {
+                let text = (env.read_file)(&string_arg(input)?)?;
+                if env.position == MacroPosition::Item {
+                    return expand_items(self, &text);
+                }
+                b.push(&text);
+            }
+            BuiltinMacro::IncludeStr => {
+                let text = (env.read_file)(&string_arg(input)?).unwrap_or_default();
+                b.push(&format!("{:?}", text));
+            }
+            BuiltinMacro::Env | BuiltinMacro::File | BuiltinMacro::ModulePath => b.push("\"\""),
+            BuiltinMacro::OptionEnv => b.push("::std::option::Option::None::<&'static str>"),
+            BuiltinMacro::Line | BuiltinMacro::Column => b.push("0u32"),
+        }
+        b.finish()
+    }
+}
+
+/// The value of the only argument, which should be a string literal.
+fn string_arg(input: &MacroInput) -> Option<String> {
+    match input.args.as_slice() {
+        [arg] => {
+            let text = &input.text[*arg];
+            if text.starts_with('"') && text.ends_with('"') && text.len() >= 2 {
+                Some(text[1..text.len() - 1].to_string())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn expand_items(mac: BuiltinMacro, text: &str) -> Option<MacroExpansion> {
+    let file = SourceFile::parse(text);
+    let ptr = SyntaxNodePtr::new(file.syntax());
+    // Make sure that there's something to look at.
+    file.items().next()?;
+    Some(MacroExpansion::new(
+        MacroDef::BuiltIn(mac),
+        text.to_string(),
+        Vec::new(),
+        ptr,
+    ))
+}
+
+/// Builds the text of an expression expansion, remembering where the
+/// arguments of the macro call end up.
+struct ExpansionBuilder<'a> {
+    mac: BuiltinMacro,
+    input: &'a MacroInput,
+    text: String,
+    ranges_map: Vec<(TextRange, TextRange)>,
+}
+
+impl<'a> ExpansionBuilder<'a> {
+    fn new(mac: BuiltinMacro, input: &'a MacroInput) -> ExpansionBuilder<'a> {
+        ExpansionBuilder {
+            mac,
+            input,
+            text: "fn dummy() { ".to_string(),
+            ranges_map: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn push_arg(&mut self, arg: TextRange) {
+        let arg_text = &self.input.text[arg];
+        let range = TextRange::offset_len(TextUnit::of_str(&self.text), arg.len());
+        self.text.push_str(arg_text);
+        self.ranges_map.push((arg, range));
+    }
+
+    /// Pushes the expansion of `format_args!`. The first argument is the
+    /// format string, the rest are (possibly named) arguments.
+    fn push_format_args(&mut self, args: &[TextRange]) {
+        self.push("::std::fmt::Arguments::new_v1(&[");
+        if let Some(&fmt) = args.first() {
+            self.push_arg(fmt);
+        }
+        self.push("], &[");
+        for &arg in args.iter().skip(1) {
+            let arg = self.named_arg_value(arg);
+            self.push("::std::fmt::ArgumentV1::new(&(");
+            self.push_arg(arg);
+            self.push("), ::std::fmt::Display::fmt), ");
+        }
+        self.push("])");
+    }
+
+    fn push_panic(&mut self, args: &[TextRange]) {
+        self.push("::std::rt::begin_panic_fmt(&");
+        self.push_format_args(args);
+        self.push(")");
+    }
+
+    /// For a `name = value` argument, returns the range of the `value`.
+    fn named_arg_value(&self, arg: TextRange) -> TextRange {
+        let text = &self.input.text[arg];
+        let eq = match text.find('=') {
+            Some(it) => it,
+            None => return arg,
+        };
+        let name = text[..eq].trim();
+        let is_ident = !name.is_empty()
+            && name.chars().all(|c| c == '_' || c.is_alphanumeric())
+            && !name.starts_with(|c: char| c.is_numeric());
+        if !is_ident || text[eq + 1..].starts_with('=') {
+            return arg;
+        }
+        let value = &text[eq + 1..];
+        let value_start = eq + 1 + (value.len() - value.trim_start().len());
+        TextRange::from_to(arg.start() + TextUnit::from_usize(value_start), arg.end())
+    }
+
+    fn finish(mut self) -> Option<MacroExpansion> {
+        self.push(" }");
+        let file = SourceFile::parse(&self.text);
+        let fn_def = file.syntax().descendants().find_map(ast::FnDef::cast)?;
+        let expr = fn_def.body()?.expr()?;
+        let ptr = SyntaxNodePtr::new(expr.syntax());
+        Some(MacroExpansion::new(
+            MacroDef::BuiltIn(self.mac),
+            self.text,
+            self.ranges_map,
+            ptr,
+        ))
+    }
+}
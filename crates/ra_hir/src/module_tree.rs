@@ -1,14 +1,17 @@
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf, Component},
+    sync::Arc,
+};
 
-use arrayvec::ArrayVec;
-use relative_path::RelativePathBuf;
-use ra_db::{FileId, SourceRoot, CrateId};
+use relative_path::{RelativePath, RelativePathBuf};
+use ra_db::{FileId, SourceRootId, CrateId, CfgExpr, CfgOptions};
 use ra_syntax::{
-    SyntaxNode, TreeArc,
+    SmolStr, SyntaxNode, TreeArc,
     algo::generate,
-    ast::{self, AstNode, NameOwner},
+    ast::{self, AstNode, NameOwner, AttrsOwner},
 };
 use ra_arena::{Arena, RawId, impl_arena_id};
+use rustc_hash::FxHashMap;
 use test_utils::tested_by;
 
 use crate::{Name, AsName, HirDatabase, SourceItemId, HirFileId, Problem, SourceFileItems, ModuleSource};
@@ -35,6 +38,9 @@ pub struct Submodule {
     name: Name,
     is_declaration: bool,
     source: SourceItemId,
+    cfg: Option<CfgExpr>,
+    /// The value of the `#[path = "..."]` attribute, if any.
+    path: Option<SmolStr>,
 }
 
 impl Submodule {
@@ -79,6 +85,12 @@ impl Submodule {
                             file_id,
                             item_id: Some(file_items.id_of(file_id, module.syntax())),
                         },
+                        cfg: CfgExpr::from_attrs(module),
+                        path: module
+                            .attrs()
+                            .filter_map(|it| it.as_key_value())
+                            .find(|(key, _)| key == "path")
+                            .map(|(_, value)| value),
                     };
                     Some(sub)
                 })
@@ -106,6 +118,9 @@ impl_arena_id!(LinkId);
 pub struct ModuleTree {
     mods: Arena<ModuleId, ModuleData>,
     links: Arena<LinkId, LinkData>,
+    /// Files of modules which are outside the source root of the crate root,
+    /// reached through `#[path]` attributes.
+    external_files: Vec<FileId>,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -132,6 +147,21 @@ impl ModuleTree {
         Arc::new(res)
     }
 
+    /// Maps the files which are included into crates from other source roots
+    /// to those crates. Only the crates of local roots are considered, as
+    /// libraries don't point into other source roots.
+    pub(crate) fn external_modules_query(db: &impl HirDatabase) -> Arc<FxHashMap<FileId, CrateId>> {
+        let mut res = FxHashMap::default();
+        for &source_root_id in db.local_roots().iter() {
+            for &krate in db.source_root_crates(source_root_id).iter() {
+                for &file_id in db.module_tree(krate).external_files.iter() {
+                    res.entry(file_id).or_insert(krate);
+                }
+            }
+        }
+        Arc::new(res)
+    }
+
     pub(crate) fn modules<'a>(&'a self) -> impl Iterator<Item = ModuleId> + 'a {
         self.mods.iter().map(|(id, _)| id)
     }
@@ -144,20 +174,27 @@ impl ModuleTree {
     fn init_crate(&mut self, db: &impl HirDatabase, crate_id: CrateId) {
         let crate_graph = db.crate_graph();
         let file_id = crate_graph.crate_root(crate_id);
-        let source_root_id = db.file_source_root(file_id);
-
-        let source_root = db.source_root(source_root_id);
+        let dir = ModuleDir::for_file(db, file_id, true);
         let source = SourceItemId {
             file_id: file_id.into(),
             item_id: None,
         };
-        self.init_subtree(db, &source_root, None, source);
+        let cfg_options = crate_graph.cfg_options(crate_id);
+        self.init_subtree(db, &dir, cfg_options, None, source);
+        self.external_files = self
+            .mods
+            .iter()
+            .filter(|(_, m)| m.source.item_id.is_none())
+            .map(|(_, m)| m.source.file_id.as_original_file())
+            .filter(|&it| db.file_source_root(it) != dir.source_root)
+            .collect();
     }
 
     fn init_subtree(
         &mut self,
         db: &impl HirDatabase,
-        source_root: &SourceRoot,
+        dir: &ModuleDir,
+        cfg_options: &CfgOptions,
         parent: Option<LinkId>,
         source: SourceItemId,
     ) -> ModuleId {
@@ -167,6 +204,11 @@ impl ModuleTree {
             children: Vec::new(),
         });
         for sub in db.submodules(source).iter() {
+            if let Some(cfg) = &sub.cfg {
+                if cfg_options.check(cfg) == Some(false) {
+                    continue;
+                }
+            }
             let link = self.alloc_link(LinkData {
                 source: sub.source,
                 name: sub.name.clone(),
@@ -176,13 +218,14 @@ impl ModuleTree {
             });
 
             let (points_to, problem) = if sub.is_declaration {
-                let (points_to, problem) = resolve_submodule(db, source.file_id, &sub.name);
+                let (points_to, problem) = resolve_submodule(db, dir, sub);
                 let points_to = points_to
                     .into_iter()
-                    .map(|file_id| {
+                    .map(|(file_id, child_dir)| {
                         self.init_subtree(
                             db,
-                            source_root,
+                            &child_dir,
+                            cfg_options,
                             Some(link),
                             SourceItemId {
                                 file_id: file_id.into(),
@@ -193,7 +236,9 @@ impl ModuleTree {
                     .collect::<Vec<_>>();
                 (points_to, problem)
             } else {
-                let points_to = self.init_subtree(db, source_root, Some(link), sub.source);
+                let child_dir = dir.inline_child(sub);
+                let points_to =
+                    self.init_subtree(db, &child_dir, cfg_options, Some(link), sub.source);
                 (vec![points_to], None)
             };
 
@@ -280,42 +325,125 @@ impl LinkId {
     }
 }
 
+/// Directories in which files of `mod foo;` declarations are looked up.
+///
+/// This follows rustc: for "mod-rs" files (crate roots, `mod.rs` and files
+/// loaded via `#[path]`), the children live next to the file. For other files
+/// like `foo.rs`, the children live in `foo/`. Inline modules add their name to
+/// the directory. `#[path]` attributes are relative to the directory of the
+/// current file, or to the directory of the enclosing inline module.
+#[derive(Debug, Clone)]
+struct ModuleDir {
+    source_root: SourceRootId,
+    /// Directory with the files of child modules.
+    dir: RelativePathBuf,
+    /// Directory against which `#[path]` attributes are resolved.
+    attr_dir: RelativePathBuf,
+}
+
+impl ModuleDir {
+    fn for_file(db: &impl HirDatabase, file_id: FileId, is_mod_rs: bool) -> ModuleDir {
+        let path = db.file_relative_path(file_id);
+        let file_dir = path.parent().map(|it| it.to_owned()).unwrap_or_default();
+        let dir = if is_mod_rs {
+            file_dir.clone()
+        } else {
+            file_dir.join(path.file_stem().unwrap_or("unknown"))
+        };
+        ModuleDir {
+            source_root: db.file_source_root(file_id),
+            dir,
+            attr_dir: file_dir,
+        }
+    }
+
+    fn inline_child(&self, sub: &Submodule) -> ModuleDir {
+        let dir = match &sub.path {
+            Some(path) => self.attr_dir.join(path.as_str()),
+            None => self.dir.join(sub.name.to_string()),
+        };
+        ModuleDir {
+            source_root: self.source_root,
+            dir: dir.clone(),
+            attr_dir: dir,
+        }
+    }
+}
+
 fn resolve_submodule(
     db: &impl HirDatabase,
-    file_id: HirFileId,
-    name: &Name,
-) -> (Vec<FileId>, Option<Problem>) {
-    // FIXME: handle submodules of inline modules properly
-    let file_id = file_id.original_file(db);
-    let source_root_id = db.file_source_root(file_id);
-    let path = db.file_relative_path(file_id);
-    let root = RelativePathBuf::default();
-    let dir_path = path.parent().unwrap_or(&root);
-    let mod_name = path.file_stem().unwrap_or("unknown");
-    let is_dir_owner = mod_name == "mod" || mod_name == "lib" || mod_name == "main";
+    dir: &ModuleDir,
+    sub: &Submodule,
+) -> (Vec<(FileId, ModuleDir)>, Option<Problem>) {
+    if let Some(path) = &sub.path {
+        let candidate = dir.attr_dir.join(path.as_str()).normalize();
+        return match resolve_file(db, dir.source_root, &candidate) {
+            Some(file_id) => (
+                vec![(file_id, ModuleDir::for_file(db, file_id, true))],
+                None,
+            ),
+            None => (Vec::new(), Some(Problem::UnresolvedModule { candidate })),
+        };
+    }
 
-    let file_mod = dir_path.join(format!("{}.rs", name));
-    let dir_mod = dir_path.join(format!("{}/mod.rs", name));
-    let file_dir_mod = dir_path.join(format!("{}/{}.rs", mod_name, name));
-    let mut candidates = ArrayVec::<[_; 2]>::new();
-    if is_dir_owner {
-        candidates.push(file_mod.clone());
-        candidates.push(dir_mod);
-    } else {
-        candidates.push(file_dir_mod.clone());
-    };
-    let sr = db.source_root(source_root_id);
-    let points_to = candidates
-        .into_iter()
-        .filter_map(|path| sr.files.get(&path))
-        .map(|&it| it)
+    let file_mod = dir.dir.join(format!("{}.rs", sub.name)).normalize();
+    let dir_mod = dir.dir.join(format!("{}/mod.rs", sub.name)).normalize();
+    let points_to = [(&file_mod, false), (&dir_mod, true)]
+        .iter()
+        .filter_map(|&(path, is_mod_rs)| {
+            let file_id = resolve_file(db, dir.source_root, path)?;
+            Some((file_id, ModuleDir::for_file(db, file_id, is_mod_rs)))
+        })
         .collect::<Vec<_>>();
     let problem = if points_to.is_empty() {
         Some(Problem::UnresolvedModule {
-            candidate: if is_dir_owner { file_mod } else { file_dir_mod },
+            candidate: file_mod,
         })
     } else {
         None
     };
     (points_to, problem)
 }
+
+/// Finds a file by a normalized path relative to the `source_root`. If the
+/// path leads outside of the source root, the file is looked up in other
+/// source roots, by comparing absolute paths.
+pub(crate) fn resolve_file(
+    db: &impl HirDatabase,
+    source_root_id: SourceRootId,
+    path: &RelativePath,
+) -> Option<FileId> {
+    let source_root = db.source_root(source_root_id);
+    if let Some(&file_id) = source_root.files.get(path) {
+        return Some(file_id);
+    }
+    if !path.as_str().starts_with("../") {
+        return None;
+    }
+    let path = normalize_path(&path.to_path(source_root.path.as_ref()?));
+    db.local_roots()
+        .iter()
+        .chain(db.library_roots().iter())
+        .filter(|&&it| it != source_root_id)
+        .find_map(|&it| {
+            let source_root = db.source_root(it);
+            let relative = path.strip_prefix(source_root.path.as_ref()?).ok()?;
+            let relative = RelativePathBuf::from_path(relative).ok()?;
+            source_root.files.get(&relative).cloned()
+        })
+}
+
+/// Lexically resolves `.` and `..` components of an absolute path.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                res.pop();
+            }
+            it => res.push(it),
+        }
+    }
+    res
+}
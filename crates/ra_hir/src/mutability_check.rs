@@ -0,0 +1,322 @@
+//! Checks the mutability of bindings: a binding which is assigned to or
+//! borrowed mutably has to be declared `mut`, and a `mut` binding should be
+//! mutated somewhere.
+use rustc_hash::FxHashSet;
+
+use crate::{
+    HirDatabase, DefWithBody, FnScopes, Name, SyntaxContext,
+    expr::{Body, Expr, ExprId, Pat, PatId, BindingAnnotation, Statement, UnaryOp},
+    type_ref::{TypeRef, Mutability},
+    ty::{Ty, InferenceResult},
+};
+
+/// How a place is mutated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    /// An assignment, like `x = 1` or `x.a += 1`.
+    Assign,
+    /// A mutable borrow, like `&mut x`, or the implicit one of `x.push(1)` or
+    /// of a `ref mut` pattern.
+    BorrowMut,
+}
+
+/// A problem with the mutability of a binding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MutabilityDiagnostic {
+    /// `expr` mutates `place`, which is the binding `binding` or a part of
+    /// it, but the binding isn't declared `mut`.
+    MutatedImmutable {
+        expr: ExprId,
+        place: ExprId,
+        binding: PatId,
+        mutation: Mutation,
+    },
+    /// The binding is declared `mut`, but is never mutated.
+    UnusedMut { binding: PatId },
+}
+
+pub(crate) fn mutability_diagnostics(
+    db: &impl HirDatabase,
+    def: DefWithBody,
+) -> Vec<MutabilityDiagnostic> {
+    let body = def.body(db);
+    let infer = def.infer(db);
+    let scopes = db.fn_scopes(def);
+    let mut checker = MutabilityChecker {
+        db,
+        body: &body,
+        infer: &infer,
+        scopes: &scopes,
+        uninit: FxHashSet::default(),
+        mutated: FxHashSet::default(),
+        has_missing: false,
+        diagnostics: Vec::new(),
+    };
+    checker.check_expr(body.body_expr());
+    checker.check_unused_mut();
+    checker.diagnostics
+}
+
+struct MutabilityChecker<'a, D: HirDatabase> {
+    db: &'a D,
+    body: &'a Body,
+    infer: &'a InferenceResult,
+    scopes: &'a FnScopes,
+    /// The bindings declared without an initializer, like `let x;`. The first
+    /// assignment to them initializes them.
+    uninit: FxHashSet<PatId>,
+    /// The `mut` bindings which are, or might be, mutated.
+    mutated: FxHashSet<PatId>,
+    /// Whether some expressions couldn't be lowered, like macro calls which
+    /// failed to expand. They might mutate any binding.
+    has_missing: bool,
+    diagnostics: Vec<MutabilityDiagnostic>,
+}
+
+impl<'a, D: HirDatabase> MutabilityChecker<'a, D> {
+    fn check_expr(&mut self, expr: ExprId) {
+        let body = self.body;
+        match &body[expr] {
+            Expr::Missing => self.has_missing = true,
+            Expr::Block { statements, .. } => {
+                for stmt in statements {
+                    match stmt {
+                        Statement::Let {
+                            pat,
+                            initializer: None,
+                            ..
+                        } => self.collect_uninit(*pat),
+                        Statement::Let {
+                            pat,
+                            initializer: Some(initializer),
+                            ..
+                        } => self.check_ref_mut_pats(*initializer, &[*pat]),
+                        Statement::Expr(_) => {}
+                    }
+                }
+            }
+            Expr::Match {
+                expr: scrutinee,
+                arms,
+            } => {
+                let pats = arms
+                    .iter()
+                    .flat_map(|arm| arm.pats.iter().cloned())
+                    .collect::<Vec<_>>();
+                self.check_ref_mut_pats(*scrutinee, &pats);
+            }
+            Expr::BinaryOp {
+                lhs, op: Some(op), ..
+            } if op.is_assignment() => self.mutate(expr, *lhs, Mutation::Assign),
+            Expr::Ref {
+                expr: inner,
+                mutability: Mutability::Mut,
+            } => self.mutate(expr, *inner, Mutation::BorrowMut),
+            Expr::MethodCall { receiver, .. } => match self.infer.method_resolution(expr) {
+                Some(func) => {
+                    let sig = func.signature(self.db);
+                    let takes_mut_ref = match sig.params().first() {
+                        Some(TypeRef::Reference(_, Mutability::Mut)) => sig.has_self_param(),
+                        _ => false,
+                    };
+                    // A receiver which is a reference already is reborrowed,
+                    // rather than borrowed.
+                    if takes_mut_ref && !is_pointer(&self.infer[*receiver]) {
+                        self.mutate(expr, *receiver, Mutation::BorrowMut);
+                    }
+                }
+                None => self.maybe_mutate(*receiver),
+            },
+            // Calling a closure might need a mutable borrow of it.
+            Expr::Call { callee, .. } => match self.infer[*callee] {
+                Ty::FnPtr(_) => {}
+                _ => self.maybe_mutate(*callee),
+            },
+            _ => {}
+        }
+        body[expr].walk_child_exprs(|child| self.check_expr(child));
+    }
+
+    fn collect_uninit(&mut self, pat: PatId) {
+        if let Pat::Bind { .. } = &self.body[pat] {
+            self.uninit.insert(pat);
+        }
+        self.body[pat].walk_child_pats(|child| self.collect_uninit(child));
+    }
+
+    /// A `ref mut` binding in one of the `pats` matched against `place`
+    /// borrows a part of it mutably.
+    fn check_ref_mut_pats(&mut self, place: ExprId, pats: &[PatId]) {
+        if pats.iter().any(|&pat| self.has_ref_mut(pat)) {
+            self.mutate(place, place, Mutation::BorrowMut);
+        }
+    }
+
+    fn has_ref_mut(&self, pat: PatId) -> bool {
+        let mut res = match &self.body[pat] {
+            Pat::Bind { mode, .. } => *mode == BindingAnnotation::RefMut,
+            _ => false,
+        };
+        self.body[pat].walk_child_pats(|child| res = res || self.has_ref_mut(child));
+        res
+    }
+
+    fn mutate(&mut self, expr: ExprId, place: ExprId, mutation: Mutation) {
+        let (binding, certain) = match self.place_binding(place) {
+            Some(it) => it,
+            None => return,
+        };
+        match &self.body[binding] {
+            Pat::Bind {
+                mode: BindingAnnotation::Mutable,
+                ..
+            } => {
+                self.mutated.insert(binding);
+            }
+            // Only the place a `ref` binding points to can be mutated
+            // through it, which needs a `ref mut` binding.
+            Pat::Bind {
+                mode: BindingAnnotation::Ref,
+                ..
+            } if certain && !place_is_binding(&self.body[place]) => {
+                self.diagnostics
+                    .push(MutabilityDiagnostic::MutatedImmutable {
+                        expr,
+                        place,
+                        binding,
+                        mutation,
+                    });
+            }
+            Pat::Bind {
+                mode: BindingAnnotation::Unannotated,
+                ..
+            } if certain => {
+                let initializes = mutation == Mutation::Assign
+                    && self.uninit.contains(&binding)
+                    && place_is_binding(&self.body[place]);
+                if !initializes {
+                    self.diagnostics
+                        .push(MutabilityDiagnostic::MutatedImmutable {
+                            expr,
+                            place,
+                            binding,
+                            mutation,
+                        });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Records that `place` might be mutated, without reporting anything.
+    fn maybe_mutate(&mut self, place: ExprId) {
+        if let Some((binding, _)) = self.place_binding(place) {
+            self.mutated.insert(binding);
+        }
+    }
+
+    /// Finds the local binding which `place` is a part of, if mutating the
+    /// place mutates the binding. Places behind a reference or raw pointer
+    /// are not part of the binding. The flag is false if the types in
+    /// between are not known, so that it is only a guess.
+    fn place_binding(&self, place: ExprId) -> Option<(PatId, bool)> {
+        match &self.body[place] {
+            Expr::Path(path) => {
+                let name = if path.is_self() {
+                    Name::self_param()
+                } else {
+                    path.as_ident()?.clone()
+                };
+                let entry = self.scopes.resolve_local_name(place, name)?;
+                Some((entry.pat(), true))
+            }
+            Expr::Field { expr: inner, .. }
+            | Expr::Index { base: inner, .. }
+            | Expr::UnaryOp {
+                expr: inner,
+                op: UnaryOp::Deref,
+            } => match &self.infer[*inner] {
+                Ty::Ref(..) if self.is_ref_binding(*inner) => self.place_binding(*inner),
+                ty if is_pointer(ty) => None,
+                Ty::Unknown => self
+                    .place_binding(*inner)
+                    .map(|(binding, _)| (binding, false)),
+                _ => self.place_binding(*inner),
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether `expr` is a `ref` binding, whose reference is part of the
+    /// binding rather than of the matched value.
+    fn is_ref_binding(&self, expr: ExprId) -> bool {
+        match self.place_binding(expr) {
+            Some((binding, _)) if place_is_binding(&self.body[expr]) => match &self.body[binding] {
+                Pat::Bind { mode, .. } => *mode == BindingAnnotation::Ref,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn check_unused_mut(&mut self) {
+        if self.has_missing {
+            return;
+        }
+        let mut pats = self.body.params().to_vec();
+        collect_pats(self.body, self.body.body_expr(), &mut pats);
+        while let Some(pat) = pats.pop() {
+            let body = self.body;
+            body[pat].walk_child_pats(|child| pats.push(child));
+            let is_unused_mut = match &body[pat] {
+                Pat::Bind {
+                    mode: BindingAnnotation::Mutable,
+                    ..
+                } => !self.mutated.contains(&pat),
+                _ => false,
+            };
+            // Bindings written by macros are not the user's to fix.
+            if is_unused_mut && body.pat_hygiene(pat) == SyntaxContext::ROOT {
+                self.diagnostics
+                    .push(MutabilityDiagnostic::UnusedMut { binding: pat });
+            }
+        }
+    }
+}
+
+/// Collects the patterns of `let` statements, match arms, `for` loops and
+/// closure parameters in `expr`.
+fn collect_pats(body: &Body, expr: ExprId, acc: &mut Vec<PatId>) {
+    match &body[expr] {
+        Expr::Block { statements, .. } => {
+            for stmt in statements {
+                if let Statement::Let { pat, .. } = stmt {
+                    acc.push(*pat);
+                }
+            }
+        }
+        Expr::Match { arms, .. } => {
+            for arm in arms {
+                acc.extend(arm.pats.iter().cloned());
+            }
+        }
+        Expr::For { pat, .. } => acc.push(*pat),
+        Expr::Lambda { args, .. } => acc.extend(args.iter().cloned()),
+        _ => {}
+    }
+    body[expr].walk_child_exprs(|child| collect_pats(body, child, acc));
+}
+
+fn place_is_binding(place: &Expr) -> bool {
+    match place {
+        Expr::Path(_) => true,
+        _ => false,
+    }
+}
+
+fn is_pointer(ty: &Ty) -> bool {
+    match ty {
+        Ty::Ref(..) | Ty::RawPtr(..) => true,
+        _ => false,
+    }
+}
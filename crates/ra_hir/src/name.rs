@@ -1,6 +1,6 @@
 use std::fmt;
 
-use ra_syntax::{ast, SmolStr};
+use ra_syntax::{ast::{self, AstToken}, SmolStr};
 
 /// `Name` is a wrapper around string, which is used in hir for both references
 /// and declarations. In theory, names should also carry hygiene info, but we are
@@ -39,6 +39,11 @@ impl Name {
         Name::new("Self".into())
     }
 
+    /// The `Output` associated type of the `Fn` traits.
+    pub(crate) fn output() -> Name {
+        Name::new("Output".into())
+    }
+
     pub(crate) fn tuple_field_name(idx: usize) -> Name {
         Name::new(idx.to_string().into())
     }
@@ -86,6 +91,12 @@ impl AsName for ast::Name {
     }
 }
 
+impl AsName for ast::Lifetime {
+    fn as_name(&self) -> Name {
+        Name::new(self.text().clone())
+    }
+}
+
 impl AsName for ra_db::Dependency {
     fn as_name(&self) -> Name {
         Name::new(self.name.clone())
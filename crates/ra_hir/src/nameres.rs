@@ -18,7 +18,7 @@ pub(crate) mod lower;
 
 use std::sync::Arc;
 
-use ra_db::CrateId;
+use ra_db::{CrateId, Edition};
 use ra_arena::map::ArenaMap;
 use test_utils::tested_by;
 use rustc_hash::{FxHashMap, FxHashSet};
@@ -30,12 +30,18 @@ use crate::{
     Name,
     module_tree::{ModuleId, ModuleTree},
     nameres::lower::{ImportId, LoweredModule, ImportData},
+    ids::{AstItemDef, BlockId},
 };
 
 /// `ItemMap` is the result of name resolution. It contains, for each
 /// module, the set of visible items.
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct ItemMap {
+    /// Crates which are visible in every module: the dependencies, and crates
+    /// named by `extern crate` items in the crate root.
+    extern_prelude: FxHashMap<Name, ModuleDef>,
+    /// Crates imported with `#[macro_use] extern crate`.
+    macro_use_crates: Vec<Crate>,
     per_module: ArenaMap<ModuleId, ModuleScope>,
 }
 
@@ -186,6 +192,7 @@ where
     }
 
     pub(crate) fn resolve(mut self) -> ItemMap {
+        self.populate_extern_prelude();
         for (&module_id, items) in self.input.iter() {
             self.populate_module(module_id, Arc::clone(items));
         }
@@ -209,40 +216,75 @@ where
         self.result
     }
 
+    fn populate_extern_prelude(&mut self) {
+        let krate = Crate::new(self.krate);
+        for dep in krate.dependencies(self.db) {
+            if let Some(module) = dep.krate.root_module(self.db) {
+                self.result
+                    .extern_prelude
+                    .insert(dep.name.clone(), module.into());
+            }
+        }
+        // `extern crate` items in the crate root add (possibly renamed) crates
+        // to the prelude as well.
+        let root_id = match krate.root_module(self.db) {
+            Some(it) => it.module_id,
+            None => return,
+        };
+        let root_input = match self.input.get(&root_id) {
+            Some(it) => Arc::clone(it),
+            None => return,
+        };
+        for (_, import) in root_input.imports.iter() {
+            if !import.is_extern_crate {
+                continue;
+            }
+            let module = match self.resolve_extern_crate(&import.path) {
+                Some(it) => it,
+                None => continue,
+            };
+            if import.is_macro_use {
+                self.result.macro_use_crates.push(Crate::new(module.krate));
+            }
+            if let Some(name) = import.name() {
+                self.result
+                    .extern_prelude
+                    .insert(name.clone(), module.into());
+            }
+        }
+    }
+
+    /// Resolves the crate named by `extern crate foo;` or `extern crate self`.
+    fn resolve_extern_crate(&self, path: &Path) -> Option<Module> {
+        let krate = Crate::new(self.krate);
+        if path.is_self() {
+            return krate.root_module(self.db);
+        }
+        let name = path.as_ident()?;
+        krate
+            .dependencies(self.db)
+            .into_iter()
+            .find(|dep| &dep.name == name)?
+            .krate
+            .root_module(self.db)
+    }
+
     fn populate_module(&mut self, module_id: ModuleId, input: Arc<LoweredModule>) {
         let mut module_items = ModuleScope::default();
 
         // Populate extern crates prelude
-        {
-            let root_id = module_id.crate_root(&self.module_tree);
-            let file_id = root_id.source(&self.module_tree).file_id;
-            let crate_graph = self.db.crate_graph();
-            if let Some(crate_id) = crate_graph.crate_id_for_crate_root(file_id.as_original_file())
-            {
-                let krate = Crate::new(crate_id);
-                for dep in krate.dependencies(self.db) {
-                    if let Some(module) = dep.krate.root_module(self.db) {
-                        let def = module.into();
-                        self.add_module_item(
-                            &mut module_items,
-                            dep.name.clone(),
-                            PerNs::types(def),
-                        );
-                    }
-                }
-            };
+        for (name, &def) in self.result.extern_prelude.iter() {
+            self.add_module_item(&mut module_items, name.clone(), PerNs::types(def));
         }
         for (import_id, import_data) in input.imports.iter() {
-            if let Some(segment) = import_data.path.segments.iter().last() {
-                if !import_data.is_glob {
-                    module_items.items.insert(
-                        segment.name.clone(),
-                        Resolution {
-                            def: PerNs::none(),
-                            import: Some(import_id),
-                        },
-                    );
-                }
+            if let Some(name) = import_data.name() {
+                module_items.items.insert(
+                    name.clone(),
+                    Resolution {
+                        def: PerNs::none(),
+                        import: Some(import_id),
+                    },
+                );
             }
         }
         // Populate explicitly declared items, except modules
@@ -256,6 +298,7 @@ where
             let module = Module {
                 module_id,
                 krate: self.krate,
+                block: None,
             };
             self.add_module_item(&mut module_items, name, PerNs::types(module.into()));
         }
@@ -291,26 +334,37 @@ where
         if import.is_glob {
             return ReachedFixedPoint::Yes;
         };
-        let original_module = Module {
-            krate: self.krate,
-            module_id,
+        let name = match import.name() {
+            Some(it) => it.clone(),
+            None => return ReachedFixedPoint::Yes,
         };
-        let (def, reached_fixedpoint) =
+        let (def, reached_fixedpoint) = if import.is_extern_crate {
+            let def = match self.resolve_extern_crate(&import.path) {
+                Some(module) => PerNs::types(module.into()),
+                None => PerNs::none(),
+            };
+            (def, ReachedFixedPoint::Yes)
+        } else {
+            let original_module = Module {
+                krate: self.krate,
+                module_id,
+                block: None,
+            };
             self.result
-                .resolve_path_fp(self.db, original_module, &import.path);
+                .resolve_path_fp(self.db, original_module, &import.path, ResolveMode::Import)
+        };
 
         if reached_fixedpoint == ReachedFixedPoint::Yes {
-            let last_segment = import.path.segments.last().unwrap();
             self.update(module_id, |items| {
                 let res = Resolution {
                     def,
                     import: Some(import_id),
                 };
-                items.items.insert(last_segment.name.clone(), res);
+                items.items.insert(name.clone(), res);
             });
             log::debug!(
                 "resolved import {:?} ({:?}) cross-source root to {:?}",
-                last_segment.name,
+                name,
                 import,
                 def,
             );
@@ -330,14 +384,33 @@ enum ReachedFixedPoint {
     No,
 }
 
+/// Paths in `use` items are resolved differently from other paths in the
+/// 2015 edition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResolveMode {
+    Import,
+    Other,
+}
+
 impl ItemMap {
+    /// Crates imported with `#[macro_use] extern crate`.
+    pub fn macro_use_crates<'a>(&'a self) -> impl Iterator<Item = Crate> + 'a {
+        self.macro_use_crates.iter().cloned()
+    }
+
+    /// Whether a crate is visible as `name` in every module.
+    pub(crate) fn has_extern_crate(&self, name: &Name) -> bool {
+        self.extern_prelude.contains_key(name)
+    }
+
     pub(crate) fn resolve_path(
         &self,
         db: &impl HirDatabase,
         original_module: Module,
         path: &Path,
     ) -> PerNs<ModuleDef> {
-        self.resolve_path_fp(db, original_module, path).0
+        self.resolve_path_fp(db, original_module, path, ResolveMode::Other)
+            .0
     }
 
     // Returns Yes if we are sure that additions to `ItemMap` wouldn't change
@@ -347,9 +420,24 @@ impl ItemMap {
         db: &impl HirDatabase,
         original_module: Module,
         path: &Path,
+        mode: ResolveMode,
     ) -> (PerNs<ModuleDef>, ReachedFixedPoint) {
-        let mut curr_per_ns: PerNs<ModuleDef> = PerNs::types(match path.kind {
+        let edition = db.crate_graph().edition(original_module.krate);
+        if mode == ResolveMode::Other || edition == Edition::Edition2018 {
+            if let Some(res) = self.resolve_path_in_blocks(db, original_module, path) {
+                return res;
+            }
+        }
+        // `self`, `super` and the rest of the paths refer to the named module
+        // containing the blocks.
+        let original_module = original_module.normal_module();
+        let mut segments = path.segments.iter().enumerate();
+        let curr_per_ns: PerNs<ModuleDef> = PerNs::types(match path.kind {
             PathKind::Crate => original_module.crate_root(db).into(),
+            // In 2015, `use` paths are relative to the crate root.
+            PathKind::Plain if mode == ResolveMode::Import && edition == Edition::Edition2015 => {
+                original_module.crate_root(db).into()
+            }
             PathKind::Self_ | PathKind::Plain => original_module.into(),
             PathKind::Super => {
                 if let Some(p) = original_module.parent(db) {
@@ -359,13 +447,65 @@ impl ItemMap {
                     return (PerNs::none(), ReachedFixedPoint::Yes);
                 }
             }
-            PathKind::Abs => {
-                // TODO: absolute use is not supported
-                return (PerNs::none(), ReachedFixedPoint::Yes);
+            PathKind::Abs if edition == Edition::Edition2015 => {
+                original_module.crate_root(db).into()
             }
+            // In 2018, `::foo` refers to the extern crate `foo`.
+            PathKind::Abs | PathKind::ExternPrelude => {
+                let crate_name = match segments.next() {
+                    Some((_, segment)) => &segment.name,
+                    None => return (PerNs::none(), ReachedFixedPoint::Yes),
+                };
+                match self.extern_prelude.get(crate_name) {
+                    Some(&def) => def,
+                    None => {
+                        log::debug!("extern crate {:?} not found", crate_name);
+                        return (PerNs::none(), ReachedFixedPoint::Yes);
+                    }
+                }
+            }
+            // Qualified paths refer to associated items, which are resolved
+            // during type inference.
+            PathKind::Type { .. } => return (PerNs::none(), ReachedFixedPoint::Yes),
         });
+        let start = segments.next().map_or(path.segments.len(), |(i, _)| i);
+        self.resolve_segments(db, original_module, curr_per_ns, path, start)
+    }
+
+    /// Resolves a plain path whose first segment names an item declared or
+    /// imported in one of the blocks around `module`. Items of inner blocks
+    /// shadow the ones of outer blocks and of the module.
+    fn resolve_path_in_blocks(
+        &self,
+        db: &impl HirDatabase,
+        module: Module,
+        path: &Path,
+    ) -> Option<(PerNs<ModuleDef>, ReachedFixedPoint)> {
+        let name = path_first_name(path)?;
+        let mut module = module;
+        while let Some(block) = module.block {
+            let scope = db.block_scope(block);
+            match scope.get(name) {
+                Some(res) if !res.def.is_none() => {
+                    let res = self.resolve_segments(db, module.normal_module(), res.def, path, 1);
+                    return Some(res);
+                }
+                _ => module = block.module(db),
+            }
+        }
+        None
+    }
 
-        for (i, segment) in path.segments.iter().enumerate() {
+    /// Resolves `path.segments[start..]`, starting from `curr_per_ns`.
+    fn resolve_segments(
+        &self,
+        db: &impl HirDatabase,
+        original_module: Module,
+        mut curr_per_ns: PerNs<ModuleDef>,
+        path: &Path,
+        start: usize,
+    ) -> (PerNs<ModuleDef>, ReachedFixedPoint) {
+        for (i, segment) in path.segments.iter().enumerate().skip(start) {
             let curr = match curr_per_ns.as_ref().take_types() {
                 Some(r) => r,
                 None => {
@@ -403,7 +543,7 @@ impl ItemMap {
                     // enum variant
                     tested_by!(item_map_enum_importing);
                     match e.variant(db, &segment.name) {
-                        Some(variant) => PerNs::both(variant.into(), (*e).into()),
+                        Some(variant) => PerNs::both(variant.into(), variant.into()),
                         None => PerNs::none(),
                     }
                 }
@@ -424,5 +564,90 @@ impl ItemMap {
     }
 }
 
+/// Computes the scope of the anonymous module of `block`: the items declared
+/// in the block, and the ones imported by its `use` items. Names imported by
+/// glob imports are shadowed by the declared and explicitly imported ones.
+pub(crate) fn block_scope_query(db: &impl HirDatabase, block: BlockId) -> Arc<ModuleScope> {
+    let parent = block.module(db);
+    let module = Module {
+        krate: parent.krate,
+        module_id: parent.module_id,
+        block: Some(block),
+    };
+    let edition = db.crate_graph().edition(module.krate);
+    let item_map = db.item_map(module.krate);
+    let input = db.lower_module_module(module);
+    let mut scope = ModuleScope::default();
+    for (name, &def) in input.declarations.iter() {
+        scope
+            .items
+            .insert(name.clone(), Resolution { def, import: None });
+    }
+    let mut globs = Vec::new();
+    for (import_id, import) in input.imports.iter() {
+        if import.is_extern_crate {
+            continue;
+        }
+        // In 2018, imports can refer to the other items of the block.
+        let local = match (path_first_name(&import.path), edition) {
+            (Some(first), Edition::Edition2018) => input.declarations.get(first),
+            _ => None,
+        };
+        let (def, _) = match local {
+            Some(&def) => {
+                item_map.resolve_segments(db, parent.normal_module(), def, &import.path, 1)
+            }
+            None => item_map.resolve_path_fp(db, parent, &import.path, ResolveMode::Import),
+        };
+        match import.name() {
+            Some(name) => {
+                scope.items.entry(name.clone()).or_insert(Resolution {
+                    def,
+                    import: Some(import_id),
+                });
+            }
+            None if import.is_glob => globs.push((import_id, def)),
+            None => (),
+        }
+    }
+    for (import_id, def) in globs {
+        for (name, def) in glob_items(db, def) {
+            scope.items.entry(name).or_insert(Resolution {
+                def,
+                import: Some(import_id),
+            });
+        }
+    }
+    Arc::new(scope)
+}
+
+/// The items imported by a glob import of `def`: the items of a module, or
+/// the variants of an enum.
+fn glob_items(db: &impl HirDatabase, def: PerNs<ModuleDef>) -> Vec<(Name, PerNs<ModuleDef>)> {
+    match def.take_types() {
+        Some(ModuleDef::Module(module)) => db.item_map(module.krate)[module.module_id]
+            .entries()
+            .map(|(name, res)| (name.clone(), res.def))
+            .collect(),
+        Some(ModuleDef::Enum(e)) => e
+            .variants(db)
+            .into_iter()
+            .filter_map(|variant| {
+                let name = variant.name(db)?;
+                Some((name, PerNs::both(variant.into(), variant.into())))
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The first segment of a plain path like `foo::bar`.
+fn path_first_name(path: &Path) -> Option<&Name> {
+    match path.kind {
+        PathKind::Plain => path.segments.first().map(|it| &it.name),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests;
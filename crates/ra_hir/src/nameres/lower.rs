@@ -1,17 +1,18 @@
 use std::sync::Arc;
 
 use ra_syntax::{
-    AstNode, SourceFile, TreeArc, AstPtr,
-    ast::{self, ModuleItemOwner, NameOwner},
+    AstNode, SourceFile, TreeArc, AstPtr, SyntaxNode,
+    ast::{self, ModuleItemOwner, NameOwner, AttrsOwner},
 };
 use ra_arena::{Arena, RawId, impl_arena_id, map::ArenaMap};
+use ra_db::CfgOptions;
 use rustc_hash::FxHashMap;
 
 use crate::{
-    SourceItemId, Path, ModuleSource, HirDatabase, Name,
+    SourceItemId, Path, PathKind, ModuleSource, HirDatabase, Name,
     HirFileId, MacroCallLoc, AsName, PerNs, Function,
     ModuleDef, Module, Struct, Enum, Const, Static, Trait, Type,
-    ids::LocationCtx,
+    ids::{LocationCtx, AstItemDef},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -21,7 +22,23 @@ impl_arena_id!(ImportId);
 #[derive(Debug, PartialEq, Eq)]
 pub(super) struct ImportData {
     pub(super) path: Path,
+    pub(super) alias: Option<Name>,
     pub(super) is_glob: bool,
+    /// `extern crate foo;`: the path is the name of the crate, or `self`.
+    pub(super) is_extern_crate: bool,
+    pub(super) is_macro_use: bool,
+}
+
+impl ImportData {
+    /// The name under which the import is visible in the module.
+    pub(super) fn name(&self) -> Option<&Name> {
+        if self.is_glob {
+            return None;
+        }
+        self.alias
+            .as_ref()
+            .or_else(|| self.path.segments.last().map(|it| &it.name))
+    }
 }
 
 /// A set of items and imports declared inside a module, without relation to
@@ -36,23 +53,51 @@ pub struct LoweredModule {
     pub(super) imports: Arena<ImportId, ImportData>,
 }
 
+/// The syntax of an import: the last segment of a path in a `use` item, or an
+/// `extern crate` item.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImportSource {
+    UseTree(TreeArc<ast::PathSegment>),
+    ExternCrate(TreeArc<ast::ExternCrateItem>),
+}
+
+impl ImportSource {
+    pub fn syntax(&self) -> &SyntaxNode {
+        match self {
+            ImportSource::UseTree(it) => it.syntax(),
+            ImportSource::ExternCrate(it) => it.syntax(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ImportSourcePtr {
+    UseTree(AstPtr<ast::PathSegment>),
+    ExternCrate(AstPtr<ast::ExternCrateItem>),
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct ImportSourceMap {
-    map: ArenaMap<ImportId, AstPtr<ast::PathSegment>>,
+    map: ArenaMap<ImportId, ImportSourcePtr>,
 }
 
 impl ImportSourceMap {
-    fn insert(&mut self, import: ImportId, segment: &ast::PathSegment) {
-        self.map.insert(import, AstPtr::new(segment))
+    fn insert(&mut self, import: ImportId, ptr: ImportSourcePtr) {
+        self.map.insert(import, ptr)
     }
 
-    pub fn get(&self, source: &ModuleSource, import: ImportId) -> TreeArc<ast::PathSegment> {
+    pub fn get(&self, source: &ModuleSource, import: ImportId) -> ImportSource {
         let file = match source {
             ModuleSource::SourceFile(file) => &*file,
             ModuleSource::Module(m) => m.syntax().ancestors().find_map(SourceFile::cast).unwrap(),
         };
 
-        self.map[import].to_node(file).to_owned()
+        match &self.map[import] {
+            ImportSourcePtr::UseTree(ptr) => ImportSource::UseTree(ptr.to_node(file).to_owned()),
+            ImportSourcePtr::ExternCrate(ptr) => {
+                ImportSource::ExternCrate(ptr.to_node(file).to_owned())
+            }
+        }
     }
 }
 
@@ -75,14 +120,29 @@ impl LoweredModule {
         db: &impl HirDatabase,
         module: Module,
     ) -> (Arc<LoweredModule>, Arc<ImportSourceMap>) {
-        let (file_id, source) = module.definition_source(db);
-        let file_id: HirFileId = file_id.into();
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(module.krate);
         let mut source_map = ImportSourceMap::default();
         let mut res = LoweredModule::default();
+        if let Some(block) = module.block {
+            let (file_id, block) = block.source(db);
+            res.fill(
+                &mut source_map,
+                db,
+                cfg_options,
+                module,
+                file_id,
+                &mut block.items_with_macros(),
+            );
+            return (Arc::new(res), Arc::new(source_map));
+        }
+        let (file_id, source) = module.definition_source(db);
+        let file_id: HirFileId = file_id.into();
         match source {
             ModuleSource::SourceFile(it) => res.fill(
                 &mut source_map,
                 db,
+                cfg_options,
                 module,
                 file_id,
                 &mut it.items_with_macros(),
@@ -92,6 +152,7 @@ impl LoweredModule {
                     res.fill(
                         &mut source_map,
                         db,
+                        cfg_options,
                         module,
                         file_id,
                         &mut item_list.items_with_macros(),
@@ -106,6 +167,7 @@ impl LoweredModule {
         &mut self,
         source_map: &mut ImportSourceMap,
         db: &impl HirDatabase,
+        cfg_options: &CfgOptions,
         module: Module,
         file_id: HirFileId,
         items: &mut Iterator<Item = ast::ItemOrMacro>,
@@ -115,7 +177,9 @@ impl LoweredModule {
         for item in items {
             match item {
                 ast::ItemOrMacro::Item(it) => {
-                    self.add_def_id(source_map, db, module, file_id, it);
+                    if cfg_options.is_cfg_enabled(it) {
+                        self.add_def_id(source_map, db, module, file_id, it);
+                    }
                 }
                 ast::ItemOrMacro::Macro(macro_call) => {
                     let item_id = file_items.id_of_unchecked(macro_call.syntax());
@@ -130,7 +194,9 @@ impl LoweredModule {
                     let file_id = HirFileId::from(id);
                     //FIXME: expand recursively
                     for item in db.hir_source_file(file_id).items() {
-                        self.add_def_id(source_map, db, module, file_id, item);
+                        if cfg_options.is_cfg_enabled(item) {
+                            self.add_def_id(source_map, db, module, file_id, item);
+                        }
                     }
                 }
             }
@@ -188,8 +254,8 @@ impl LoweredModule {
             ast::ModuleItemKind::UseItem(it) => {
                 self.add_use_item(source_map, it);
             }
-            ast::ModuleItemKind::ExternCrateItem(_) => {
-                // TODO
+            ast::ModuleItemKind::ExternCrateItem(it) => {
+                self.add_extern_crate_item(source_map, it);
             }
             ast::ModuleItemKind::ConstDef(it) => {
                 if let Some(name) = it.name() {
@@ -212,14 +278,49 @@ impl LoweredModule {
     }
 
     fn add_use_item(&mut self, source_map: &mut ImportSourceMap, item: &ast::UseItem) {
-        Path::expand_use_item(item, |path, segment| {
+        Path::expand_use_item(item, |path, segment, alias| {
             let import = self.imports.alloc(ImportData {
                 path,
+                alias,
                 is_glob: segment.is_none(),
+                is_extern_crate: false,
+                is_macro_use: false,
             });
             if let Some(segment) = segment {
-                source_map.insert(import, segment)
+                source_map.insert(import, ImportSourcePtr::UseTree(AstPtr::new(segment)))
             }
         })
     }
+
+    fn add_extern_crate_item(
+        &mut self,
+        source_map: &mut ImportSourceMap,
+        item: &ast::ExternCrateItem,
+    ) {
+        let name_ref = match item.name_ref() {
+            Some(it) => it,
+            None => return,
+        };
+        let path = if name_ref.text() == "self" {
+            Path {
+                kind: PathKind::Self_,
+                segments: Vec::new(),
+            }
+        } else {
+            Path::from_name_ref(name_ref)
+        };
+        let alias = item.alias().and_then(|it| it.name()).map(|it| it.as_name());
+        let is_macro_use = item
+            .attrs()
+            .filter_map(|it| it.as_atom())
+            .any(|it| it == "macro_use");
+        let import = self.imports.alloc(ImportData {
+            path,
+            alias,
+            is_glob: false,
+            is_extern_crate: true,
+            is_macro_use,
+        });
+        source_map.insert(import, ImportSourcePtr::ExternCrate(AstPtr::new(item)));
+    }
 }
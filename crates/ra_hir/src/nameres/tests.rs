@@ -1,13 +1,13 @@
 use std::sync::Arc;
 
-use ra_db::{CrateGraph, SourceRootId, FilesDatabase};
+use ra_db::{CrateGraph, CrateId, SourceRootId, FilesDatabase, CfgOptions, Edition};
 use relative_path::RelativePath;
 use test_utils::{assert_eq_text, covers};
 
 use crate::{
     ItemMap, Resolution,
     db::HirDatabase,
-    mock::MockDatabase,
+    mock::{MockDatabase, WORKSPACE},
     module_tree::ModuleId,
 };
 
@@ -162,6 +162,123 @@ fn name_res_works_for_broken_modules() {
     );
 }
 
+#[test]
+fn module_resolution_works_for_path_attr() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /main.rs
+        #[path = \"bar/baz/foo.rs\"]
+        mod foo;
+
+        use self::foo::inner::Baz;
+        <|>
+
+        //- /bar/baz/foo.rs
+        pub mod inner;
+
+        //- /bar/baz/inner.rs
+        pub struct Baz;
+
+        //- /bar/baz/foo/inner.rs
+        pub struct Qux;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn module_resolution_works_for_path_attr_on_inline_module() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /main.rs
+        #[path = \"models\"]
+        mod foo {
+            mod bar;
+        }
+
+        use self::foo::bar::Baz;
+        <|>
+
+        //- /models/bar.rs
+        pub struct Baz;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn module_resolution_works_for_inline_modules_in_non_mod_rs_files() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /main.rs
+        mod foo;
+
+        use self::foo::bar::baz::Baz;
+        <|>
+
+        //- /foo.rs
+        pub mod bar {
+            pub mod baz;
+        }
+
+        //- /foo/bar/baz.rs
+        pub struct Baz;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn module_resolution_works_for_path_attr_in_inline_module() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /main.rs
+        mod foo;
+
+        use self::foo::bar::baz::Baz;
+        <|>
+
+        //- /foo/mod.rs
+        pub mod bar {
+            #[path = \"qux.rs\"]
+            pub mod baz;
+        }
+
+        //- /foo/bar/qux.rs
+        pub struct Baz;
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            foo: t
+        ",
+    );
+}
+
 #[test]
 fn item_map_contains_items_from_expansions() {
     let (item_map, module_id) = item_map(
@@ -215,6 +332,29 @@ fn item_map_using_self() {
     );
 }
 
+#[test]
+fn item_map_use_alias() {
+    let (item_map, module_id) = item_map(
+        "
+            //- /lib.rs
+            mod foo;
+            use crate::foo::{Baz as Quux, self as bar};
+            <|>
+            //- /foo.rs
+            pub struct Baz;
+        ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Quux: t v
+            bar: t
+            foo: t
+        ",
+    );
+}
+
 #[test]
 fn item_map_enum_importing() {
     covers!(item_map_enum_importing);
@@ -325,6 +465,84 @@ fn import_across_source_roots() {
     );
 }
 
+#[test]
+fn module_resolution_works_for_path_attr_across_source_roots() {
+    let (mut db, sr) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        #[path = \"../shared/foo.rs\"]
+        mod foo;
+
+        use self::foo::Baz;
+    ",
+    );
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let mut sr = (*db.source_root(WORKSPACE)).clone();
+    sr.path = Some("/project/main".into());
+    db.set_source_root(WORKSPACE, Arc::new(sr));
+
+    let shared_root = SourceRootId(1);
+    let (mut sr2, _) = db.add_fixture(
+        shared_root,
+        "
+        //- /foo.rs
+        pub struct Baz;
+    ",
+    );
+    sr2.path = Some("/project/shared".into());
+    db.set_source_root(shared_root, Arc::new(sr2));
+    db.set_local_roots(Arc::new(vec![WORKSPACE, shared_root]));
+
+    let module = crate::source_binder::module_from_file_id(&db, main_id).unwrap();
+    let krate = module.krate(&db).unwrap();
+    let item_map = db.item_map(krate.crate_id);
+
+    check_module_item_map(
+        &item_map,
+        module.module_id,
+        "
+            Baz: t v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn module_from_file_in_other_source_root() {
+    let (mut db, _) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        #[path = \"../shared/foo.rs\"]
+        mod foo;
+    ",
+    );
+    let mut sr = (*db.source_root(WORKSPACE)).clone();
+    sr.path = Some("/project/main".into());
+    db.set_source_root(WORKSPACE, Arc::new(sr));
+
+    let shared_root = SourceRootId(1);
+    let (mut sr2, _) = db.add_fixture(
+        shared_root,
+        "
+        //- /foo.rs
+        pub struct Baz;
+
+        //- /orphan.rs
+        pub struct Orphan;
+    ",
+    );
+    sr2.path = Some("/project/shared".into());
+    db.set_source_root(shared_root, Arc::new(sr2.clone()));
+    db.set_local_roots(Arc::new(vec![WORKSPACE, shared_root]));
+
+    let foo_id = sr2.files[RelativePath::new("/foo.rs")];
+    let module = crate::source_binder::module_from_file_id(&db, foo_id).unwrap();
+    assert_eq!(module.name(&db).unwrap().to_string(), "foo");
+
+    let orphan_id = sr2.files[RelativePath::new("/orphan.rs")];
+    assert!(crate::source_binder::module_from_file_id(&db, orphan_id).is_none());
+}
+
 #[test]
 fn reexport_across_crates() {
     let (mut db, sr) = MockDatabase::with_files(
@@ -367,6 +585,264 @@ fn reexport_across_crates() {
     );
 }
 
+/// Sets up the crate `/main.rs` of the given edition, which depends on the
+/// crate `/lib.rs` under the name `test_crate`.
+fn item_map_with_dep(fixture: &str, edition: Edition) -> (Arc<ItemMap>, ModuleId) {
+    let (mut db, pos) = MockDatabase::with_position(fixture);
+    let sr = db.source_root(WORKSPACE);
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+    let lib_id = sr.files[RelativePath::new("/lib.rs")];
+
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id);
+    crate_graph.set_edition(main_crate, edition);
+    let lib_crate = crate_graph.add_crate_root(lib_id);
+    crate_graph
+        .add_dep(main_crate, "test_crate".into(), lib_crate)
+        .unwrap();
+    db.set_crate_graph(Arc::new(crate_graph));
+
+    let module = crate::source_binder::module_from_position(&db, pos).unwrap();
+    (db.item_map(main_crate), module.module_id)
+}
+
+#[test]
+fn extern_crate_rename() {
+    let (item_map, module_id) = item_map_with_dep(
+        "
+        //- /main.rs
+        extern crate test_crate as other_name;
+
+        mod foo;
+
+        //- /foo.rs
+        use other_name::Baz;
+        <|>
+
+        //- /lib.rs
+        pub struct Baz;
+    ",
+        Edition::Edition2018,
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            other_name: t
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn extern_crate_self_as() {
+    let (item_map, module_id) = item_map_with_dep(
+        "
+        //- /main.rs
+        extern crate self as bar;
+
+        mod foo;
+
+        pub struct Baz;
+
+        //- /foo.rs
+        use bar::Baz;
+        <|>
+
+        //- /lib.rs
+    ",
+        Edition::Edition2018,
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            bar: t
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn macro_use_extern_crate() {
+    let (item_map, _) = item_map_with_dep(
+        "
+        //- /main.rs
+        #[macro_use]
+        extern crate test_crate;
+        <|>
+
+        //- /lib.rs
+    ",
+        Edition::Edition2015,
+    );
+    // the dependency, not the importing crate
+    let crates = item_map
+        .macro_use_crates()
+        .map(|krate| krate.crate_id)
+        .collect::<Vec<_>>();
+    assert_eq!(crates, vec![CrateId(1)]);
+}
+
+#[test]
+fn use_paths_are_relative_to_crate_root_in_2015() {
+    let fixture = "
+        //- /main.rs
+        mod foo;
+        mod bar;
+
+        //- /foo.rs
+        pub struct Foo;
+
+        //- /bar.rs
+        mod foo {
+            pub struct Bar;
+        }
+
+        use foo::Foo;
+        use foo::Bar;
+        <|>
+
+        //- /lib.rs
+    ";
+    let (item_map, module_id) = item_map_with_dep(fixture, Edition::Edition2015);
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: _
+            Foo: t v
+            foo: t
+            test_crate: t
+        ",
+    );
+    let (item_map, module_id) = item_map_with_dep(fixture, Edition::Edition2018);
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Bar: t v
+            Foo: _
+            foo: t
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn absolute_paths_depend_on_edition() {
+    let fixture = "
+        //- /main.rs
+        mod test_crate {
+            pub struct Local;
+        }
+        mod foo;
+
+        //- /foo.rs
+        use ::test_crate::Local;
+        use ::test_crate::Baz;
+        <|>
+
+        //- /lib.rs
+        pub struct Baz;
+    ";
+    let (item_map, module_id) = item_map_with_dep(fixture, Edition::Edition2015);
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: _
+            Local: t v
+            test_crate: t
+        ",
+    );
+    let (item_map, module_id) = item_map_with_dep(fixture, Edition::Edition2018);
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Baz: t v
+            Local: _
+            test_crate: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_respects_cfg() {
+    let (mut db, sr) = MockDatabase::with_files(
+        "
+        //- /main.rs
+        mod foo;
+        #[cfg(test)]
+        mod tests;
+
+        #[cfg(unix)]
+        pub struct Unix;
+        #[cfg(windows)]
+        pub struct Windows;
+
+        #[cfg(all(unix, feature = \"std\"))]
+        pub fn f() {}
+        #[cfg(not(all(unix, feature = \"std\")))]
+        pub struct f;
+
+        //- /foo.rs
+        pub struct Foo;
+
+        //- /tests.rs
+        pub struct Tests;
+    ",
+    );
+    let main_id = sr.files[RelativePath::new("/main.rs")];
+
+    let mut cfg_options = CfgOptions::default();
+    cfg_options.insert_atom("unix".into());
+    cfg_options.insert_key_value("feature".into(), "std".into());
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id);
+    crate_graph.set_cfg_options(main_crate, cfg_options);
+    db.set_crate_graph(Arc::new(crate_graph));
+
+    let module = crate::source_binder::module_from_file_id(&db, main_id).unwrap();
+    let item_map = db.item_map(main_crate);
+
+    check_module_item_map(
+        &item_map,
+        module.module_id,
+        "
+            Unix: t v
+            f: v
+            foo: t
+        ",
+    );
+}
+
+#[test]
+fn item_map_include_macro() {
+    let (item_map, module_id) = item_map(
+        "
+        //- /lib.rs
+        include!(\"gen/items.rs\");
+        <|>
+
+        //- /gen/items.rs
+        pub struct Generated;
+        pub fn generated() {}
+    ",
+    );
+    check_module_item_map(
+        &item_map,
+        module_id,
+        "
+            Generated: t v
+            generated: v
+        ",
+    );
+}
+
 fn check_item_map_is_not_recomputed(initial: &str, file_change: &str) {
     let (mut db, pos) = MockDatabase::with_position(initial);
     let module = crate::source_binder::module_from_file_id(&db, pos.file_id).unwrap();
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use ra_syntax::{ast, AstNode};
+use ra_syntax::{ast::{self, NameOwner}, AstNode};
 
 use crate::{Name, AsName, type_ref::TypeRef};
 
@@ -17,12 +17,13 @@ pub struct PathSegment {
 }
 
 /// Generic arguments to a path segment (e.g. the `i32` in `Option<i32>`). This
-/// can (in the future) also include bindings of associated types, like in
-/// `Iterator<Item = Foo>`.
+/// also includes bindings of associated types, like in `Iterator<Item = Foo>`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct GenericArgs {
     pub args: Vec<GenericArg>,
-    // someday also bindings
+    /// The associated type bindings, like the `Item = Foo` in
+    /// `Iterator<Item = Foo>`.
+    pub bindings: Vec<(Name, TypeRef)>,
 }
 
 /// A single generic argument.
@@ -32,21 +33,33 @@ pub enum GenericArg {
     // or lifetime...
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PathKind {
     Plain,
     Self_,
     Super,
     Crate,
-    // Absolute path
+    // Absolute path, `::foo`. Depending on the edition, it starts either at the
+    // crate root (2015) or at the extern prelude (2018).
     Abs,
+    /// A path starting at a crate of the extern prelude, whatever the edition
+    /// and the items in scope, like the `::core::clone::Clone` of a
+    /// `#[derive(Clone)]`.
+    ExternPrelude,
+    /// A path starting with a qualified self type, like `<T>::Item` or
+    /// `<T as Trait>::Item`.
+    Type {
+        self_type: Box<TypeRef>,
+        trait_ref: Option<Box<Path>>,
+    },
 }
 
 impl Path {
-    /// Calls `cb` with all paths, represented by this use item.
+    /// Calls `cb` with all paths, represented by this use item, together with
+    /// the `as` alias, if any.
     pub fn expand_use_item<'a>(
         item: &'a ast::UseItem,
-        mut cb: impl FnMut(Path, Option<&'a ast::PathSegment>),
+        mut cb: impl FnMut(Path, Option<&'a ast::PathSegment>, Option<Name>),
     ) {
         if let Some(tree) = item.use_tree() {
             expand_use_tree(None, tree, &mut cb);
@@ -69,6 +82,12 @@ impl Path {
                     let args = segment
                         .type_arg_list()
                         .and_then(GenericArgs::from_ast)
+                        .or_else(|| {
+                            GenericArgs::from_fn_like_path_ast(
+                                segment.param_list(),
+                                segment.ret_type(),
+                            )
+                        })
                         .map(Arc::new);
                     let segment = PathSegment {
                         name: name.as_name(),
@@ -76,6 +95,20 @@ impl Path {
                     };
                     segments.push(segment);
                 }
+                ast::PathSegmentKind::Type {
+                    type_ref,
+                    trait_ref,
+                } => {
+                    let trait_ref = match trait_ref {
+                        Some(it) => Some(Box::new(Path::from_ast(it.path()?)?)),
+                        None => None,
+                    };
+                    kind = PathKind::Type {
+                        self_type: Box::new(TypeRef::from_ast_opt(type_ref)),
+                        trait_ref,
+                    };
+                    break;
+                }
                 ast::PathSegmentKind::CrateKw => {
                     kind = PathKind::Crate;
                     break;
@@ -140,13 +173,42 @@ impl GenericArgs {
             let type_ref = TypeRef::from_ast_opt(type_arg.type_ref());
             args.push(GenericArg::Type(type_ref));
         }
-        // lifetimes and assoc type args ignored for now
-        if args.len() > 0 {
-            Some(GenericArgs { args })
+        let mut bindings = Vec::new();
+        for assoc_type_arg in node.assoc_type_args() {
+            if let Some(name_ref) = assoc_type_arg.name_ref() {
+                let type_ref = TypeRef::from_ast_opt(assoc_type_arg.type_ref());
+                bindings.push((name_ref.as_name(), type_ref));
+            }
+        }
+        // lifetimes ignored for now
+        if !args.is_empty() || !bindings.is_empty() {
+            Some(GenericArgs { args, bindings })
         } else {
             None
         }
     }
+
+    /// Collects the arguments of the `Fn` traits' sugar, like `Fn(u32) -> u8`:
+    /// the parameter types become a single tuple argument, and the return type
+    /// a binding of `Output`.
+    fn from_fn_like_path_ast(
+        params: Option<&ast::ParamList>,
+        ret_type: Option<&ast::RetType>,
+    ) -> Option<GenericArgs> {
+        let params = params?;
+        let param_types = params
+            .params()
+            .map(|param| TypeRef::from_ast_opt(param.type_ref()))
+            .collect();
+        let ret_type = ret_type
+            .and_then(|it| it.type_ref())
+            .map(TypeRef::from_ast)
+            .unwrap_or_else(|| TypeRef::Tuple(Vec::new()));
+        Some(GenericArgs {
+            args: vec![GenericArg::Type(TypeRef::Tuple(param_types))],
+            bindings: vec![(Name::output(), ret_type)],
+        })
+    }
 }
 
 impl From<Name> for Path {
@@ -164,7 +226,7 @@ impl From<Name> for Path {
 fn expand_use_tree<'a>(
     prefix: Option<Path>,
     tree: &'a ast::UseTree,
-    cb: &mut impl FnMut(Path, Option<&'a ast::PathSegment>),
+    cb: &mut impl FnMut(Path, Option<&'a ast::PathSegment>, Option<Name>),
 ) {
     if let Some(use_tree_list) = tree.use_tree_list() {
         let prefix = match tree.path() {
@@ -181,6 +243,7 @@ fn expand_use_tree<'a>(
             expand_use_tree(prefix.clone(), child_tree, cb);
         }
     } else {
+        let alias = tree.alias().and_then(|it| it.name()).map(|it| it.as_name());
         if let Some(ast_path) = tree.path() {
             // Handle self in a path.
             // E.g. `use something::{self, <...>}`
@@ -188,7 +251,7 @@ fn expand_use_tree<'a>(
                 if let Some(segment) = ast_path.segment() {
                     if segment.kind() == Some(ast::PathSegmentKind::SelfKw) {
                         if let Some(prefix) = prefix {
-                            cb(prefix, Some(segment));
+                            cb(prefix, Some(segment), alias);
                             return;
                         }
                     }
@@ -196,9 +259,9 @@ fn expand_use_tree<'a>(
             }
             if let Some(path) = convert_path(prefix, ast_path) {
                 if tree.has_star() {
-                    cb(path, None)
+                    cb(path, None, None)
                 } else if let Some(segment) = ast_path.segment() {
-                    cb(path, Some(segment))
+                    cb(path, Some(segment), alias)
                 };
             }
             // TODO: report errors somewhere
@@ -217,7 +280,11 @@ fn convert_path(prefix: Option<Path>, path: &ast::Path) -> Option<Path> {
     let res = match segment.kind()? {
         ast::PathSegmentKind::Name(name) => {
             let mut res = prefix.unwrap_or_else(|| Path {
-                kind: PathKind::Plain,
+                kind: if segment.has_colon_colon() {
+                    PathKind::Abs
+                } else {
+                    PathKind::Plain
+                },
                 segments: Vec::with_capacity(1),
             });
             res.segments.push(PathSegment {
@@ -226,6 +293,7 @@ fn convert_path(prefix: Option<Path>, path: &ast::Path) -> Option<Path> {
             });
             res
         }
+        ast::PathSegmentKind::Type { .. } => return None,
         ast::PathSegmentKind::CrateKw => {
             if prefix.is_some() {
                 return None;
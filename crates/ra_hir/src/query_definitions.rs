@@ -11,13 +11,13 @@ use ra_db::{CrateId};
 
 use crate::{
     SourceFileItems, SourceItemId, HirFileId,
-    Function, FnScopes, Module,
+    DefWithBody, FnScopes, Module,
     db::HirDatabase,
     nameres::{ItemMap, Resolver},
 };
 
-pub(super) fn fn_scopes(db: &impl HirDatabase, func: Function) -> Arc<FnScopes> {
-    let body = db.body_hir(func);
+pub(super) fn fn_scopes(db: &impl HirDatabase, def: DefWithBody) -> Arc<FnScopes> {
+    let body = db.body_hir(def);
     let res = FnScopes::new(body);
     Arc::new(res)
 }
@@ -52,6 +52,7 @@ pub(super) fn item_map(db: &impl HirDatabase, crate_id: CrateId) -> Arc<ItemMap>
                 db.lower_module_module(Module {
                     krate: crate_id,
                     module_id,
+                    block: None,
                 }),
             )
         })
@@ -14,7 +14,7 @@ use ra_syntax::{
 
 use crate::{
     HirDatabase, Function, SourceItemId, ModuleDef,
-    AsName, Module,
+    AsName, Module, Const, Static, Enum, DefWithBody,
     ids::LocationCtx,
 };
 
@@ -66,13 +66,14 @@ fn module_from_inline(
     module_from_source(db, source)
 }
 
-/// Locates the module by child syntax element within the module
+/// Locates the module by child syntax element within the module. Inside of a
+/// block which declares items, this is the anonymous module of the block.
 pub fn module_from_child_node(
     db: &impl HirDatabase,
     file_id: FileId,
     child: &SyntaxNode,
 ) -> Option<Module> {
-    if let Some(m) = child
+    let module = if let Some(m) = child
         .ancestors()
         .filter_map(ast::Module::cast)
         .find(|it| !it.has_semi())
@@ -80,18 +81,28 @@ pub fn module_from_child_node(
         module_from_inline(db, file_id.into(), m)
     } else {
         module_from_file_id(db, file_id.into())
-    }
+    }?;
+    Some(module.module_at(db, file_id.into(), child))
 }
 
 fn module_from_source(db: &impl HirDatabase, source: SourceItemId) -> Option<Module> {
-    let source_root_id = db.file_source_root(source.file_id.as_original_file());
+    let find_in_crate = |krate| {
+        let module_tree = db.module_tree(krate);
+        let module_id = module_tree.find_module_by_source(source)?;
+        Some(Module {
+            krate,
+            module_id,
+            block: None,
+        })
+    };
+    let file_id = source.file_id.as_original_file();
+    let source_root_id = db.file_source_root(file_id);
     db.source_root_crates(source_root_id)
         .iter()
-        .find_map(|&krate| {
-            let module_tree = db.module_tree(krate);
-            let module_id = module_tree.find_module_by_source(source)?;
-            Some(Module { krate, module_id })
-        })
+        .find_map(|&krate| find_in_crate(krate))
+        // With `#[path]` attributes, a module might belong to a crate from
+        // another source root.
+        .or_else(|| find_in_crate(*db.external_modules().get(&file_id)?))
 }
 
 pub fn function_from_position(db: &impl HirDatabase, position: FilePosition) -> Option<Function> {
@@ -117,6 +128,7 @@ pub fn function_from_module(
 ) -> Function {
     let (file_id, _) = module.definition_source(db);
     let file_id = file_id.into();
+    let module = module.module_at(db, file_id, fn_def.syntax());
     let ctx = LocationCtx::new(db, module, file_id);
     Function {
         id: ctx.to_def(fn_def),
@@ -132,6 +144,42 @@ pub fn function_from_child_node(
     function_from_source(db, file_id, fn_def)
 }
 
+/// Locates the function, const, static or enum variant whose body (or
+/// initializer, or discriminant) contains `node`.
+pub fn def_with_body_from_child_node(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    node: &SyntaxNode,
+) -> Option<DefWithBody> {
+    let module = module_from_child_node(db, file_id, node)?;
+    let (file_id, _) = module.definition_source(db);
+    let file_id = file_id.into();
+    // Items nested in blocks belong to the anonymous modules of the blocks.
+    let ctx =
+        |item: &SyntaxNode| LocationCtx::new(db, module.module_at(db, file_id, item), file_id);
+    node.ancestors().find_map(|node| {
+        if let Some(fn_def) = ast::FnDef::cast(node) {
+            let id = ctx(node).to_def(fn_def);
+            return Some(Function { id }.into());
+        }
+        if let Some(const_def) = ast::ConstDef::cast(node) {
+            let id = ctx(node).to_def(const_def);
+            return Some(Const { id }.into());
+        }
+        if let Some(static_def) = ast::StaticDef::cast(node) {
+            let id = ctx(node).to_def(static_def);
+            return Some(Static { id }.into());
+        }
+        let variant = ast::EnumVariant::cast(node)?;
+        let enum_def = variant.syntax().ancestors().find_map(ast::EnumDef::cast)?;
+        let id = ctx(enum_def.syntax()).to_def(enum_def);
+        let enum_ = Enum { id };
+        enum_
+            .variant(db, &variant.name()?.as_name())
+            .map(DefWithBody::from)
+    })
+}
+
 pub fn macro_symbols(db: &impl HirDatabase, file_id: FileId) -> Vec<(SmolStr, TextRange)> {
     let module = match module_from_file_id(db, file_id) {
         Some(it) => it,
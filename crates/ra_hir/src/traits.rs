@@ -0,0 +1,55 @@
+//! HIR for trait definitions.
+
+use std::sync::Arc;
+
+use ra_syntax::ast;
+
+use crate::{Trait, Function, Const, Type, db::HirDatabase, ids::LocationCtx};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraitData {
+    items: Vec<TraitItem>,
+    is_unsafe: bool,
+}
+
+impl TraitData {
+    pub(crate) fn trait_data_query(db: &impl HirDatabase, tr: Trait) -> Arc<TraitData> {
+        let (file_id, node) = tr.source(db);
+        let module = tr.module(db);
+        let ctx = LocationCtx::new(db, module, file_id);
+        let crate_graph = db.crate_graph();
+        let cfg_options = crate_graph.cfg_options(module.krate);
+        let items = if let Some(item_list) = node.item_list() {
+            item_list
+                .impl_items()
+                .filter(|item_node| cfg_options.is_cfg_enabled(*item_node))
+                .map(|item_node| match item_node.kind() {
+                    ast::ImplItemKind::FnDef(it) => Function { id: ctx.to_def(it) }.into(),
+                    ast::ImplItemKind::ConstDef(it) => Const { id: ctx.to_def(it) }.into(),
+                    ast::ImplItemKind::TypeDef(it) => Type { id: ctx.to_def(it) }.into(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let is_unsafe = node.is_unsafe();
+        Arc::new(TraitData { items, is_unsafe })
+    }
+
+    pub(crate) fn items(&self) -> &[TraitItem] {
+        &self.items
+    }
+
+    pub(crate) fn is_unsafe(&self) -> bool {
+        self.is_unsafe
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraitItem {
+    Function(Function),
+    Const(Const),
+    Type(Type),
+    // Existential
+}
+impl_froms!(TraitItem: Function, Const, Type);
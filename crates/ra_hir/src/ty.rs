@@ -14,10 +14,12 @@
 //! rustc.
 
 mod autoderef;
+mod match_check;
 pub(crate) mod primitive;
 #[cfg(test)]
 mod tests;
 pub(crate) mod method_resolution;
+pub(crate) mod traits;
 
 use std::borrow::Cow;
 use std::ops::Index;
@@ -27,18 +29,21 @@ use std::{fmt, mem};
 use ena::unify::{InPlaceUnificationTable, UnifyKey, UnifyValue, NoError};
 use ra_arena::map::ArenaMap;
 use join_to_string::join;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    Module, Function, Struct, StructField, Enum, EnumVariant, Path, Name, ImplBlock,
-    FnSignature, FnScopes, ModuleDef, AdtDef,
+    Module, Function, Struct, StructField, Enum, EnumVariant, Path, PathKind, Name, ImplBlock,
+    FnSignature, FnScopes, ModuleDef, AdtDef, Trait, Type, Const, Static, DefWithBody,
     db::HirDatabase,
-    type_ref::{TypeRef, Mutability},
+    type_ref::{TypeRef, Mutability, ArrayLen},
     name::KnownName,
-    expr::{Body, Expr, BindingAnnotation, Literal, ExprId, Pat, PatId, UnaryOp, BinaryOp, Statement, FieldPat},
+    expr::{Body, Expr, BindingAnnotation, Literal, ExprId, Pat, PatId, UnaryOp, BinaryOp, RangeOp, Statement, FieldPat},
     generics::GenericParams,
     path::GenericArg,
     adt::VariantDef,
+    lang_item::lang_item_lookup,
+    const_eval,
+    ty::traits::{TraitRef, TraitBound, ProjectionTy, Environment},
 };
 
 /// The ID of a type variable.
@@ -154,7 +159,7 @@ impl Expectation {
 }
 
 /// A list of substitutions for generic parameters.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct Substs(Arc<[Ty]>);
 
 impl Substs {
@@ -166,7 +171,7 @@ impl Substs {
 /// A type. This is based on the `TyKind` enum in rustc (librustc/ty/sty.rs).
 ///
 /// This should be cheap to clone.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub enum Ty {
     /// The primitive boolean type. Written as `bool`.
     Bool,
@@ -197,8 +202,8 @@ pub enum Ty {
     /// The pointee of an array slice.  Written as `[T]`.
     Slice(Arc<Ty>),
 
-    // An array with the given length. Written as `[T; n]`.
-    Array(Arc<Ty>),
+    /// An array with the given length, if it is known. Written as `[T; n]`.
+    Array(Arc<Ty>, Option<u64>),
 
     /// A raw pointer. Written as `*mut T` or `*const T`
     RawPtr(Arc<Ty>, Mutability),
@@ -220,13 +225,20 @@ pub enum Ty {
     // rustc has a separate type for each function, which just coerces to the
     // above function pointer type. Once we implement generics, we will probably
     // need this as well.
-
-    // A trait, defined with `dyn Trait`.
-    // Dynamic(),
-
-    // The anonymous type of a closure. Used to represent the type of
-    // `|a| a`.
-    // Closure(DefId, ClosureSubsts<'tcx>),
+    /// A trait object, written as `dyn Trait`. It implements the traits of its
+    /// bounds.
+    Dyn(Arc<[TraitBound]>),
+
+    /// The anonymous type of a closure. Used to represent the type of
+    /// `|a| a`.
+    Closure {
+        /// The function, const etc. containing the closure.
+        def: DefWithBody,
+        /// The closure expression.
+        expr: ExprId,
+        /// The parameter and return types of the closure.
+        sig: Arc<FnSig>,
+    },
 
     // The anonymous type of a generator. Used to represent the type of
     // `|a| yield a`.
@@ -241,12 +253,14 @@ pub enum Ty {
     /// A tuple type.  For example, `(i32, bool)`.
     Tuple(Arc<[Ty]>),
 
-    // The projection of an associated type.  For example,
-    // `<T as Trait<..>>::N`.pub
-    // Projection(ProjectionTy),
+    /// The projection of an associated type.  For example,
+    /// `<T as Trait<..>>::N`.
+    Projection(Arc<ProjectionTy>),
+
+    /// An opaque type, written as `impl Trait` in argument or return position.
+    /// All we know about it are its bounds.
+    Opaque(Arc<[TraitBound]>),
 
-    // Opaque (`impl Trait`) type found in a return type.
-    // Opaque(DefId, Substs),
     /// A type parameter; for example, `T` in `fn f<T>(x: T) {}
     Param {
         /// The index of the parameter (starting with parameters from the
@@ -269,12 +283,25 @@ pub enum Ty {
 }
 
 /// A function signature.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct FnSig {
     input: Vec<Ty>,
     output: Ty,
 }
 
+impl FnSig {
+    fn subst(&self, substs: &Substs) -> FnSig {
+        FnSig {
+            input: self
+                .input
+                .iter()
+                .map(|ty| ty.clone().subst(substs))
+                .collect(),
+            output: self.output.clone().subst(substs),
+        }
+    }
+}
+
 impl Ty {
     pub(crate) fn from_hir(
         db: &impl HirDatabase,
@@ -300,9 +327,9 @@ impl Ty {
                 let inner_ty = Ty::from_hir(db, module, impl_block, generics, inner);
                 Ty::RawPtr(Arc::new(inner_ty), *mutability)
             }
-            TypeRef::Array(inner) => {
+            TypeRef::Array(inner, len) => {
                 let inner_ty = Ty::from_hir(db, module, impl_block, generics, inner);
-                Ty::Array(Arc::new(inner_ty))
+                Ty::Array(Arc::new(inner_ty), Ty::array_len_from_hir(db, module, len))
             }
             TypeRef::Slice(inner) => {
                 let inner_ty = Ty::from_hir(db, module, impl_block, generics, inner);
@@ -327,6 +354,14 @@ impl Ty {
                 };
                 Ty::FnPtr(Arc::new(sig))
             }
+            TypeRef::ImplTrait(paths) => {
+                Ty::bounds_from_hir(db, module, impl_block, generics, paths)
+                    .map_or(Ty::Unknown, Ty::Opaque)
+            }
+            TypeRef::DynTrait(paths) => {
+                Ty::bounds_from_hir(db, module, impl_block, generics, paths)
+                    .map_or(Ty::Unknown, Ty::Dyn)
+            }
             TypeRef::Error => Ty::Unknown,
         }
     }
@@ -343,6 +378,26 @@ impl Ty {
         })
     }
 
+    /// Lowers the bounds of a `dyn Trait` or `impl Trait` type; `None` if none
+    /// of them could be resolved.
+    fn bounds_from_hir(
+        db: &impl HirDatabase,
+        module: &Module,
+        impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
+        paths: &[Path],
+    ) -> Option<Arc<[TraitBound]>> {
+        let bounds = paths
+            .iter()
+            .filter_map(|path| TraitBound::from_path(db, module, impl_block, generics, path))
+            .collect::<Vec<_>>();
+        if bounds.is_empty() {
+            None
+        } else {
+            Some(bounds.into())
+        }
+    }
+
     pub(crate) fn from_hir_path(
         db: &impl HirDatabase,
         module: &Module,
@@ -356,15 +411,25 @@ impl Ty {
             } else if let Some(float_ty) = primitive::UncertainFloatTy::from_name(name) {
                 return Ty::Float(float_ty);
             } else if name.as_known_name() == Some(KnownName::SelfType) {
-                // TODO pass the impl block's generics?
-                let generics = &GenericParams::default();
-                return Ty::from_hir_opt(
-                    db,
-                    module,
-                    None,
-                    generics,
-                    impl_block.map(|i| i.target_type()),
-                );
+                if impl_block.is_none() {
+                    // In a trait, `Self` is a type parameter.
+                    if let Some(generic_param) = generics.find_by_name(name) {
+                        return Ty::Param {
+                            idx: generic_param.idx,
+                            name: generic_param.name.clone(),
+                        };
+                    }
+                }
+                return match impl_block {
+                    Some(impl_block) => Ty::from_hir(
+                        db,
+                        module,
+                        None,
+                        impl_block.generic_params(),
+                        impl_block.target_type(),
+                    ),
+                    None => Ty::Unknown,
+                };
             } else if let Some(known) = name.as_known_name() {
                 match known {
                     KnownName::Bool => return Ty::Bool,
@@ -380,6 +445,10 @@ impl Ty {
             }
         }
 
+        if let Some(ty) = Ty::from_hir_assoc_type_path(db, module, impl_block, generics, path) {
+            return ty;
+        }
+
         // Resolve in module (in type namespace)
         let typable: TypableDef = match module
             .resolve_path(db, path)
@@ -391,7 +460,105 @@ impl Ty {
         };
         let ty = db.type_for_def(typable);
         let substs = Ty::substs_from_path(db, module, impl_block, generics, path, typable);
-        ty.apply_substs(substs)
+        match typable {
+            // aliases are expanded, so their parameters are substituted into
+            // the aliased type
+            TypableDef::Type(_) => ty.subst(&substs),
+            _ => ty.apply_substs(substs),
+        }
+    }
+
+    /// Evaluates the length of an array type, like `N * 2` in `[u8; N * 2]`.
+    fn array_len_from_hir(db: &impl HirDatabase, module: &Module, len: &ArrayLen) -> Option<u64> {
+        match len {
+            ArrayLen::Expr(body) => const_eval::eval_usize(db, *module, body, body.body_expr()),
+            ArrayLen::Unknown => None,
+        }
+    }
+
+    /// Lowers paths referring to associated types: `<T as Trait>::Item`,
+    /// `<T>::Item`, and `T::Item` or `Self::Item` for type parameters and for
+    /// `Self` in impls. Returns `None` if the path is not of that form.
+    fn from_hir_assoc_type_path(
+        db: &impl HirDatabase,
+        module: &Module,
+        impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
+        path: &Path,
+    ) -> Option<Self> {
+        let (self_ty, trait_path, name) = match &path.kind {
+            PathKind::Type {
+                self_type,
+                trait_ref,
+            } => {
+                if path.segments.len() != 1 {
+                    return Some(Ty::Unknown);
+                }
+                let self_ty = Ty::from_hir(db, module, impl_block, generics, self_type);
+                (self_ty, trait_ref.as_ref(), &path.segments[0].name)
+            }
+            PathKind::Plain if path.segments.len() == 2 => {
+                let self_name = &path.segments[0].name;
+                if self_name.as_known_name() != Some(KnownName::SelfType)
+                    && generics.find_by_name(self_name).is_none()
+                {
+                    return None;
+                }
+                let self_path = self_name.clone().into();
+                let self_ty = Ty::from_hir_path(db, module, impl_block, generics, &self_path);
+                (self_ty, None, &path.segments[1].name)
+            }
+            _ => return None,
+        };
+        let trait_ref = match trait_path {
+            Some(trait_path) => {
+                TraitRef::from_path(db, module, impl_block, generics, trait_path, self_ty)
+            }
+            None => match self_ty {
+                Ty::Param { .. } => Ty::trait_for_assoc_type(db, module, generics, self_ty, name),
+                // `Self::Item` in an impl
+                _ => impl_block.and_then(|impl_block| TraitRef::for_impl(db, impl_block)),
+            },
+        };
+        let ty = match trait_ref {
+            Some(trait_ref) => {
+                Ty::Projection(Arc::new(ProjectionTy::new(db, trait_ref, name.clone())))
+            }
+            None => Ty::Unknown,
+        };
+        Some(ty)
+    }
+
+    /// Finds the trait an associated type like `T::Item` belongs to, by
+    /// looking for a bound on `T` whose trait declares `Item`.
+    fn trait_for_assoc_type(
+        db: &impl HirDatabase,
+        module: &Module,
+        generics: &GenericParams,
+        param_ty: Ty,
+        name: &Name,
+    ) -> Option<TraitRef> {
+        let param_name = match &param_ty {
+            Ty::Param { name, .. } => name,
+            _ => return None,
+        };
+        generics
+            .where_predicates_including_parent()
+            .into_iter()
+            .filter(|pred| match &pred.type_ref {
+                TypeRef::Path(path) => path.as_ident() == Some(param_name),
+                _ => false,
+            })
+            .find_map(
+                |pred| match module.resolve_path(db, &pred.trait_ref).take_types()? {
+                    ModuleDef::Trait(trait_) if traits::has_assoc_type(db, trait_, name) => {
+                        // The bound's own arguments might refer to this very
+                        // projection (`T: Foo<T::Item>`), so we don't lower them.
+                        Some(TraitRef::with_unknown_substs(db, trait_, param_ty.clone()))
+                    }
+                    _ => None,
+                },
+            )
     }
 
     /// Collect generic arguments from a path into a `Substs`. See also
@@ -410,10 +577,12 @@ impl Ty {
             .segments
             .last()
             .expect("path should have at least one segment");
-        let (def_generics, segment) = match resolved {
-            TypableDef::Function(func) => (func.generic_params(db), last),
-            TypableDef::Struct(s) => (s.generic_params(db), last),
-            TypableDef::Enum(e) => (e.generic_params(db), last),
+        let (def_generics, segment, def_module) = match resolved {
+            TypableDef::Function(func) => (func.generic_params(db), last, func.module(db)),
+            TypableDef::Struct(s) => (s.generic_params(db), last, s.module(db)),
+            TypableDef::Enum(e) => (e.generic_params(db), last, e.module(db)),
+            TypableDef::Type(t) => (t.generic_params(db), last, t.module(db)),
+            TypableDef::Const(_) | TypableDef::Static(_) => return Substs::empty(),
             TypableDef::EnumVariant(var) => {
                 // the generic args for an enum variant may be either specified
                 // on the segment referring to the enum, or on the segment
@@ -428,7 +597,12 @@ impl Ty {
                     // Option::None::<T>
                     last
                 };
-                (var.parent_enum(db).generic_params(db), segment)
+                let parent_enum = var.parent_enum(db);
+                (
+                    parent_enum.generic_params(db),
+                    segment,
+                    parent_enum.module(db),
+                )
             }
         };
         // substs_from_path
@@ -444,15 +618,17 @@ impl Ty {
                 }
             }
         }
-        // add placeholders for args that were not provided
-        // TODO: handle defaults
-        for _ in segment
-            .args_and_bindings
-            .as_ref()
-            .map(|ga| ga.args.len())
-            .unwrap_or(0)..def_generics.params.len()
-        {
-            substs.push(Ty::Unknown);
+        // add defaults or placeholders for args that were not provided; the
+        // defaults may refer to the preceding parameters
+        for param in def_generics.params.iter().skip(substs.len()) {
+            let ty = match &param.default {
+                Some(default) => {
+                    let default_ty = Ty::from_hir(db, &def_module, None, &def_generics, default);
+                    default_ty.subst(&Substs(substs.clone().into()))
+                }
+                None => Ty::Unknown,
+            };
+            substs.push(ty);
         }
         assert_eq!(substs.len(), def_generics.params.len());
         Substs(substs.into())
@@ -462,10 +638,31 @@ impl Ty {
         Ty::Tuple(Arc::new([]))
     }
 
+    /// Whether the type is known well enough to report errors about it: it
+    /// has no unknown parts, and none which the inference doesn't handle
+    /// completely yet, like trait objects, function pointers and closures
+    /// (which coerce to each other) or associated types. Type parameters
+    /// count as known only with `allow_params`.
+    fn is_fully_known(&self, allow_params: bool) -> bool {
+        let mut known = true;
+        self.clone().walk_mut(&mut |ty| match ty {
+            Ty::Unknown
+            | Ty::Infer(_)
+            | Ty::Projection(_)
+            | Ty::Dyn(_)
+            | Ty::Opaque(_)
+            | Ty::FnPtr(_)
+            | Ty::Closure { .. } => known = false,
+            Ty::Param { .. } if !allow_params => known = false,
+            _ => {}
+        });
+        known
+    }
+
     fn walk_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
         f(self);
         match self {
-            Ty::Slice(t) | Ty::Array(t) => Arc::make_mut(t).walk_mut(f),
+            Ty::Slice(t) | Ty::Array(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::RawPtr(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::Ref(t, _) => Arc::make_mut(t).walk_mut(f),
             Ty::Tuple(ts) => {
@@ -476,7 +673,7 @@ impl Ty {
                 }
                 *ts = v.into();
             }
-            Ty::FnPtr(sig) => {
+            Ty::FnPtr(sig) | Ty::Closure { sig, .. } => {
                 let sig_mut = Arc::make_mut(sig);
                 for input in &mut sig_mut.input {
                     input.walk_mut(f);
@@ -491,6 +688,29 @@ impl Ty {
                 }
                 substs.0 = v.into();
             }
+            Ty::Projection(projection) => {
+                let trait_ref = &mut Arc::make_mut(projection).trait_ref;
+                trait_ref.self_ty.walk_mut(f);
+                let mut v: Vec<_> = trait_ref.substs.0.iter().cloned().collect();
+                for t in &mut v {
+                    t.walk_mut(f);
+                }
+                trait_ref.substs.0 = v.into();
+            }
+            Ty::Dyn(bounds) | Ty::Opaque(bounds) => {
+                let mut v: Vec<_> = bounds.iter().cloned().collect();
+                for bound in &mut v {
+                    let mut substs: Vec<_> = bound.substs.0.iter().cloned().collect();
+                    for t in &mut substs {
+                        t.walk_mut(f);
+                    }
+                    bound.substs.0 = substs.into();
+                    for (_, t) in &mut bound.bindings {
+                        t.walk_mut(f);
+                    }
+                }
+                *bounds = v.into();
+            }
             _ => {}
         }
     }
@@ -503,6 +723,15 @@ impl Ty {
         self
     }
 
+    /// The bounds of a `dyn Trait` or `impl Trait` type, which are all we know
+    /// about such a type. Empty for other types.
+    pub(crate) fn trait_bounds(&self) -> &[TraitBound] {
+        match self {
+            Ty::Dyn(bounds) | Ty::Opaque(bounds) => bounds,
+            _ => &[],
+        }
+    }
+
     fn builtin_deref(&self) -> Option<Ty> {
         match self {
             Ty::Ref(t, _) => Some(Ty::clone(t)),
@@ -563,7 +792,9 @@ impl fmt::Display for Ty {
             Ty::Int(t) => write!(f, "{}", t.ty_to_string()),
             Ty::Float(t) => write!(f, "{}", t.ty_to_string()),
             Ty::Str => write!(f, "str"),
-            Ty::Slice(t) | Ty::Array(t) => write!(f, "[{}]", t),
+            Ty::Slice(t) => write!(f, "[{}]", t),
+            Ty::Array(t, Some(len)) => write!(f, "[{}; {}]", t, len),
+            Ty::Array(t, None) => write!(f, "[{}; _]", t),
             Ty::RawPtr(t, m) => write!(f, "*{}{}", m.as_keyword_for_ptr(), t),
             Ty::Ref(t, m) => write!(f, "&{}{}", m.as_keyword_for_ref(), t),
             Ty::Never => write!(f, "!"),
@@ -584,6 +815,13 @@ impl fmt::Display for Ty {
                     .to_fmt(f)?;
                 write!(f, " -> {}", sig.output)
             }
+            Ty::Closure { sig, .. } => {
+                join(sig.input.iter())
+                    .surround_with("|", "|")
+                    .separator(", ")
+                    .to_fmt(f)?;
+                write!(f, " -> {}", sig.output)
+            }
             Ty::Adt { name, substs, .. } => {
                 write!(f, "{}", name)?;
                 if substs.0.len() > 0 {
@@ -594,6 +832,25 @@ impl fmt::Display for Ty {
                 }
                 Ok(())
             }
+            Ty::Projection(projection) => {
+                let trait_ref = &projection.trait_ref;
+                write!(f, "<{} as {}", trait_ref.self_ty, projection.trait_name)?;
+                if !trait_ref.substs.0.is_empty() {
+                    join(trait_ref.substs.0.iter())
+                        .surround_with("<", ">")
+                        .separator(", ")
+                        .to_fmt(f)?;
+                }
+                write!(f, ">::{}", projection.name)
+            }
+            Ty::Dyn(bounds) => join(bounds.iter())
+                .surround_with("dyn ", "")
+                .separator(" + ")
+                .to_fmt(f),
+            Ty::Opaque(bounds) => join(bounds.iter())
+                .surround_with("impl ", "")
+                .separator(" + ")
+                .to_fmt(f),
             Ty::Param { name, .. } => write!(f, "{}", name),
             Ty::Unknown => write!(f, "[unknown]"),
             Ty::Infer(..) => write!(f, "_"),
@@ -667,8 +924,11 @@ pub enum TypableDef {
     Struct(Struct),
     Enum(Enum),
     EnumVariant(EnumVariant),
+    Type(Type),
+    Const(Const),
+    Static(Static),
 }
-impl_froms!(TypableDef: Function, Struct, Enum, EnumVariant);
+impl_froms!(TypableDef: Function, Struct, Enum, EnumVariant, Type, Const, Static);
 
 impl From<ModuleDef> for Option<TypableDef> {
     fn from(def: ModuleDef) -> Option<TypableDef> {
@@ -677,11 +937,10 @@ impl From<ModuleDef> for Option<TypableDef> {
             ModuleDef::Struct(s) => s.into(),
             ModuleDef::Enum(e) => e.into(),
             ModuleDef::EnumVariant(v) => v.into(),
-            ModuleDef::Const(_)
-            | ModuleDef::Static(_)
-            | ModuleDef::Module(_)
-            | ModuleDef::Trait(_)
-            | ModuleDef::Type(_) => return None,
+            ModuleDef::Type(t) => t.into(),
+            ModuleDef::Const(c) => c.into(),
+            ModuleDef::Static(s) => s.into(),
+            ModuleDef::Module(_) | ModuleDef::Trait(_) => return None,
         };
         Some(res)
     }
@@ -693,7 +952,64 @@ pub(super) fn type_for_def(db: &impl HirDatabase, def: TypableDef) -> Ty {
         TypableDef::Struct(s) => type_for_struct(db, s),
         TypableDef::Enum(e) => type_for_enum(db, e),
         TypableDef::EnumVariant(v) => type_for_enum_variant(db, v),
+        TypableDef::Type(t) => type_for_type_alias(db, t),
+        TypableDef::Const(c) => type_for_const(db, c),
+        TypableDef::Static(s) => type_for_static(db, s),
+    }
+}
+
+/// The declared type of a const. Associated consts may refer to `Self`.
+fn type_for_const(db: &impl HirDatabase, c: Const) -> Ty {
+    let module = c.module(db);
+    let impl_block = c.impl_block(db);
+    let generics = GenericParams::default();
+    Ty::from_hir(db, &module, impl_block.as_ref(), &generics, &c.type_ref(db))
+}
+
+fn type_for_static(db: &impl HirDatabase, s: Static) -> Ty {
+    let module = s.module(db);
+    let generics = GenericParams::default();
+    Ty::from_hir(db, &module, None, &generics, &s.type_ref(db))
+}
+
+/// The type a type alias stands for, in terms of the alias's type parameters.
+/// Cyclic aliases like `type A = Vec<A>;` are an error, and become unknown.
+fn type_for_type_alias(db: &impl HirDatabase, t: Type) -> Ty {
+    if is_cyclic_alias(db, t) {
+        return Ty::Unknown;
+    }
+    let generics = t.generic_params(db);
+    let module = t.module(db);
+    Ty::from_hir_opt(db, &module, None, &generics, t.type_ref(db).as_ref())
+}
+
+/// Whether expanding `alias` leads back to `alias` itself. This has to be
+/// checked on the type refs before lowering them, since the `type_for_def`
+/// query can't handle cycles.
+fn is_cyclic_alias(db: &impl HirDatabase, alias: Type) -> bool {
+    let mut visited = FxHashSet::default();
+    let mut stack = vec![alias];
+    while let Some(t) = stack.pop() {
+        let type_ref = match t.type_ref(db) {
+            Some(it) => it,
+            None => continue,
+        };
+        let module = t.module(db);
+        let mut cyclic = false;
+        type_ref.walk_paths(&mut |path| {
+            if let Some(ModuleDef::Type(target)) = module.resolve_path(db, path).take_types() {
+                if target == alias {
+                    cyclic = true;
+                } else if visited.insert(target) {
+                    stack.push(target);
+                }
+            }
+        });
+        if cyclic {
+            return true;
+        }
     }
+    false
 }
 
 pub(super) fn type_for_field(db: &impl HirDatabase, field: StructField) -> Ty {
@@ -714,8 +1030,12 @@ pub struct InferenceResult {
     method_resolutions: FxHashMap<ExprId, Function>,
     /// For each field access expr, records the field it resolves to.
     field_resolutions: FxHashMap<ExprId, StructField>,
+    /// For each path expr which doesn't refer to a local variable, records the
+    /// item it resolves to.
+    path_resolutions: FxHashMap<ExprId, ModuleDef>,
     type_of_expr: ArenaMap<ExprId, Ty>,
     type_of_pat: ArenaMap<PatId, Ty>,
+    diagnostics: Vec<InferenceDiagnostic>,
 }
 
 impl InferenceResult {
@@ -725,6 +1045,69 @@ impl InferenceResult {
     pub fn field_resolution(&self, expr: ExprId) -> Option<StructField> {
         self.field_resolutions.get(&expr).map(|it| *it)
     }
+    pub fn path_resolution(&self, expr: ExprId) -> Option<ModuleDef> {
+        self.path_resolutions.get(&expr).map(|it| *it)
+    }
+    pub fn diagnostics(&self) -> &[InferenceDiagnostic] {
+        &self.diagnostics
+    }
+}
+
+/// A type error found during type inference. Errors involving types which
+/// could not be inferred completely are not reported, since they are more
+/// likely to come from gaps in the inference than from the code.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum InferenceDiagnostic {
+    /// The type of `expr` is not the one it is expected to have.
+    MismatchedType {
+        expr: ExprId,
+        expected: Ty,
+        actual: Ty,
+    },
+    /// The call or method call `expr` has `actual` arguments, but the callee
+    /// takes `expected`.
+    WrongArgCount {
+        expr: ExprId,
+        expected: usize,
+        actual: usize,
+    },
+    /// The field access or struct literal `expr` uses the field `field`,
+    /// which `ty` doesn't have.
+    NoSuchField { expr: ExprId, field: Name, ty: Ty },
+    /// The struct pattern `pat` uses the field `field`, which `ty` doesn't
+    /// have.
+    NoSuchPatField { pat: PatId, field: Name, ty: Ty },
+    /// The method call `expr` calls `method`, which `receiver` doesn't have.
+    NoSuchMethod {
+        expr: ExprId,
+        method: Name,
+        receiver: Ty,
+    },
+    /// The callee `callee` of a call has the type `ty`, which isn't callable.
+    NotCallable { callee: ExprId, ty: Ty },
+    /// The match `expr` doesn't cover the values matched by the patterns in
+    /// `missing`.
+    MissingMatchArms { expr: ExprId, missing: Vec<String> },
+    /// The pattern `pat` of a match arm only matches values which earlier
+    /// arms already match.
+    UnreachablePattern { pat: PatId },
+}
+
+impl InferenceDiagnostic {
+    fn tys_mut(&mut self) -> Vec<&mut Ty> {
+        match self {
+            InferenceDiagnostic::MismatchedType {
+                expected, actual, ..
+            } => vec![expected, actual],
+            InferenceDiagnostic::WrongArgCount { .. }
+            | InferenceDiagnostic::MissingMatchArms { .. }
+            | InferenceDiagnostic::UnreachablePattern { .. } => Vec::new(),
+            InferenceDiagnostic::NoSuchField { ty, .. }
+            | InferenceDiagnostic::NoSuchPatField { ty, .. }
+            | InferenceDiagnostic::NotCallable { ty, .. } => vec![ty],
+            InferenceDiagnostic::NoSuchMethod { receiver, .. } => vec![receiver],
+        }
+    }
 }
 
 impl Index<ExprId> for InferenceResult {
@@ -743,21 +1126,88 @@ impl Index<PatId> for InferenceResult {
     }
 }
 
+/// The maximum number of derefs tried when coercing a reference.
+const AUTODEREF_LIMIT: usize = 10;
+
+/// What a path pattern, or an identifier pattern which doesn't bind a
+/// variable, refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PatResolution {
+    Variant(VariantDef),
+    Const(Const),
+}
+
+impl PatResolution {
+    fn is_unit(self, db: &impl HirDatabase) -> bool {
+        match self {
+            PatResolution::Variant(it) => it.variant_data(db).fields().is_none(),
+            PatResolution::Const(_) => true,
+        }
+    }
+}
+
+impl From<PatResolution> for TypableDef {
+    fn from(resolution: PatResolution) -> TypableDef {
+        match resolution {
+            PatResolution::Variant(VariantDef::Struct(it)) => it.into(),
+            PatResolution::Variant(VariantDef::EnumVariant(it)) => it.into(),
+            PatResolution::Const(it) => it.into(),
+        }
+    }
+}
+
+/// A loop or labeled block which `break` expressions in it refer to.
+#[derive(Clone, Debug)]
+struct BreakableContext {
+    /// The loop or block expression.
+    expr: ExprId,
+    /// Whether the loop is left by a `break` somewhere.
+    may_break: bool,
+    /// The type of the values given to `break`, which is the type of the loop.
+    break_ty: Ty,
+}
+
 /// The inference context contains all information needed during type inference.
 #[derive(Clone, Debug)]
 struct InferenceContext<'a, D: HirDatabase> {
     db: &'a D,
+    def: DefWithBody,
     body: Arc<Body>,
     scopes: Arc<FnScopes>,
     module: Module,
     impl_block: Option<ImplBlock>,
+    /// The generic parameters of the function being inferred; consts, statics
+    /// and enum discriminants have none.
+    generics: Arc<GenericParams>,
+    /// The where clauses of the function being inferred.
+    env: Arc<Environment>,
+    /// The signatures required by `Fn` bounds on type parameters, both of the
+    /// function being inferred and of the functions it calls (instantiated
+    /// with the type variables of the call). Closures passed for these types
+    /// get their parameter and return types from here.
+    fn_bounds: Vec<(Ty, FnSig)>,
     var_unification_table: InPlaceUnificationTable<TypeVarId>,
     method_resolutions: FxHashMap<ExprId, Function>,
     field_resolutions: FxHashMap<ExprId, StructField>,
+    path_resolutions: FxHashMap<ExprId, ModuleDef>,
     type_of_expr: ArenaMap<ExprId, Ty>,
     type_of_pat: ArenaMap<PatId, Ty>,
-    /// The return type of the function being inferred.
+    diagnostics: Vec<InferenceDiagnostic>,
+    /// What the path patterns (and identifier patterns which don't bind a
+    /// variable) refer to.
+    pat_resolutions: FxHashMap<PatId, PatResolution>,
+    /// The match expressions, with the modules their patterns are resolved
+    /// in, to check their arms once all types are known.
+    matches: Vec<(ExprId, Module)>,
+    /// The type the body must have: the return type of a function, or the
+    /// declared type of a const or static.
     return_ty: Ty,
+    /// Whether the code inferred so far always diverges, i.e. never reaches
+    /// the current expression because of a `return`, `break`, endless loop or
+    /// the like before it.
+    diverges: bool,
+    /// The loops enclosing the current expression, innermost last.
+    breakables: Vec<BreakableContext>,
 }
 
 fn binary_op_return_ty(op: BinaryOp, rhs_ty: Ty) -> Ty {
@@ -800,6 +1250,61 @@ fn binary_op_return_ty(op: BinaryOp, rhs_ty: Ty) -> Ty {
     }
 }
 
+/// Whether an arithmetic or bitwise operation on `lhs_ty` and `rhs_ty` is
+/// built into the language, i.e. both operands are numbers, rather than
+/// overloaded through the operator traits.
+fn is_builtin_binop(lhs_ty: &Ty, rhs_ty: &Ty) -> bool {
+    is_number(lhs_ty) && is_number(rhs_ty)
+}
+
+fn is_number(ty: &Ty) -> bool {
+    match ty {
+        Ty::Int(..)
+        | Ty::Float(..)
+        | Ty::Infer(InferTy::IntVar(..))
+        | Ty::Infer(InferTy::FloatVar(..)) => true,
+        _ => false,
+    }
+}
+
+fn is_shift(op: BinaryOp) -> bool {
+    match op {
+        BinaryOp::LeftShift | BinaryOp::RightShift | BinaryOp::ShlAssign | BinaryOp::ShrAssign => {
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The lang item of the trait which overloads the arithmetic or bitwise
+/// operator `op`, or the compound assignment `op`.
+fn binary_op_lang_item(op: BinaryOp) -> Option<&'static str> {
+    let lang_item = match op {
+        BinaryOp::Addition => "add",
+        BinaryOp::Subtraction => "sub",
+        BinaryOp::Multiplication => "mul",
+        BinaryOp::Division => "div",
+        BinaryOp::Remainder => "rem",
+        BinaryOp::LeftShift => "shl",
+        BinaryOp::RightShift => "shr",
+        BinaryOp::BitwiseAnd => "bitand",
+        BinaryOp::BitwiseOr => "bitor",
+        BinaryOp::BitwiseXor => "bitxor",
+        BinaryOp::AddAssign => "add_assign",
+        BinaryOp::SubAssign => "sub_assign",
+        BinaryOp::MulAssign => "mul_assign",
+        BinaryOp::DivAssign => "div_assign",
+        BinaryOp::RemAssign => "rem_assign",
+        BinaryOp::ShlAssign => "shl_assign",
+        BinaryOp::ShrAssign => "shr_assign",
+        BinaryOp::BitAndAssign => "bitand_assign",
+        BinaryOp::BitOrAssign => "bitor_assign",
+        BinaryOp::BitXorAssign => "bitxor_assign",
+        _ => return None,
+    };
+    Some(lang_item)
+}
+
 fn binary_op_rhs_expectation(op: BinaryOp, lhs_ty: Ty) -> Ty {
     match op {
         BinaryOp::BooleanAnd | BinaryOp::BooleanOr => Ty::Bool,
@@ -810,73 +1315,105 @@ fn binary_op_rhs_expectation(op: BinaryOp, lhs_ty: Ty) -> Ty {
         BinaryOp::LesserEqualTest
         | BinaryOp::GreaterEqualTest
         | BinaryOp::LesserTest
-        | BinaryOp::GreaterTest
-        | BinaryOp::AddAssign
-        | BinaryOp::SubAssign
-        | BinaryOp::DivAssign
-        | BinaryOp::MulAssign
-        | BinaryOp::RemAssign
-        | BinaryOp::ShrAssign
-        | BinaryOp::ShlAssign
-        | BinaryOp::BitAndAssign
-        | BinaryOp::BitOrAssign
-        | BinaryOp::BitXorAssign
-        | BinaryOp::Addition
-        | BinaryOp::Subtraction
-        | BinaryOp::Multiplication
-        | BinaryOp::Division
-        | BinaryOp::Remainder
-        | BinaryOp::LeftShift
-        | BinaryOp::RightShift
-        | BinaryOp::BitwiseAnd
-        | BinaryOp::BitwiseOr
-        | BinaryOp::BitwiseXor => match lhs_ty {
+        | BinaryOp::GreaterTest => match lhs_ty {
             Ty::Int(..) | Ty::Float(..) => lhs_ty,
             _ => Ty::Unknown,
         },
+        // arithmetic and bitwise operators may be overloaded for a primitive
+        // left-hand side and any right-hand side, so the operands are only
+        // unified once both are known to be numbers
         _ => Ty::Unknown,
     }
 }
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
-    fn new(
-        db: &'a D,
-        body: Arc<Body>,
-        scopes: Arc<FnScopes>,
-        module: Module,
-        impl_block: Option<ImplBlock>,
-    ) -> Self {
+    fn new(db: &'a D, def: DefWithBody) -> Self {
+        let (impl_block, generics, env, fn_bounds) = match def {
+            DefWithBody::Function(func) => (
+                func.impl_block(db),
+                func.generic_params(db),
+                Environment::for_def(db, func.into()),
+                traits::fn_bounds(db, func.into()),
+            ),
+            DefWithBody::Const(c) => (
+                c.impl_block(db),
+                Arc::default(),
+                Environment::empty(db, def.module(db)),
+                Vec::new(),
+            ),
+            DefWithBody::Static(_) | DefWithBody::EnumVariant(_) => (
+                None,
+                Arc::default(),
+                Environment::empty(db, def.module(db)),
+                Vec::new(),
+            ),
+        };
         InferenceContext {
             method_resolutions: FxHashMap::default(),
             field_resolutions: FxHashMap::default(),
+            path_resolutions: FxHashMap::default(),
             type_of_expr: ArenaMap::default(),
             type_of_pat: ArenaMap::default(),
+            diagnostics: Vec::new(),
+            pat_resolutions: FxHashMap::default(),
+            matches: Vec::new(),
             var_unification_table: InPlaceUnificationTable::new(),
-            return_ty: Ty::Unknown, // set in collect_fn_signature
+            return_ty: Ty::Unknown, // set in collect_fn_signature or collect_const_type
+            diverges: false,
+            breakables: Vec::new(),
             db,
-            body,
-            scopes,
-            module,
+            def,
+            body: def.body(db),
+            scopes: db.fn_scopes(def),
+            module: def.module(db),
             impl_block,
+            generics,
+            env,
+            fn_bounds,
         }
     }
 
     fn resolve_all(mut self) -> InferenceResult {
         let mut expr_types = mem::replace(&mut self.type_of_expr, ArenaMap::default());
         for ty in expr_types.values_mut() {
-            let resolved = self.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
-            *ty = resolved;
+            let normalized = self.normalize_associated_types(mem::replace(ty, Ty::Unknown));
+            *ty = self.resolve_ty_completely(normalized);
         }
         let mut pat_types = mem::replace(&mut self.type_of_pat, ArenaMap::default());
         for ty in pat_types.values_mut() {
-            let resolved = self.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
-            *ty = resolved;
+            let normalized = self.normalize_associated_types(mem::replace(ty, Ty::Unknown));
+            *ty = self.resolve_ty_completely(normalized);
+        }
+        let mut diagnostics = mem::replace(&mut self.diagnostics, Vec::new());
+        for diagnostic in &mut diagnostics {
+            for ty in diagnostic.tys_mut() {
+                let normalized = self.normalize_associated_types(mem::replace(ty, Ty::Unknown));
+                *ty = self.resolve_ty_completely(normalized);
+            }
+        }
+        diagnostics.retain(|it| match it {
+            InferenceDiagnostic::MismatchedType {
+                expected, actual, ..
+            } => expected.is_fully_known(true) && actual.is_fully_known(true),
+            InferenceDiagnostic::WrongArgCount { .. } => true,
+            InferenceDiagnostic::NoSuchField { ty, .. }
+            | InferenceDiagnostic::NoSuchPatField { ty, .. }
+            | InferenceDiagnostic::NotCallable { ty, .. } => ty.is_fully_known(false),
+            InferenceDiagnostic::NoSuchMethod { receiver, .. } => receiver.is_fully_known(false),
+            InferenceDiagnostic::MissingMatchArms { .. }
+            | InferenceDiagnostic::UnreachablePattern { .. } => true,
+        });
+        for &(expr, module) in &self.matches {
+            match_check::MatchChecker::new(self.db, module, &self.body, &self.pat_resolutions)
+                .check_match(expr, &expr_types, &mut diagnostics);
         }
         InferenceResult {
             method_resolutions: mem::replace(&mut self.method_resolutions, Default::default()),
             field_resolutions: mem::replace(&mut self.field_resolutions, Default::default()),
+            path_resolutions: mem::replace(&mut self.path_resolutions, Default::default()),
             type_of_expr: expr_types,
             type_of_pat: pat_types,
+            diagnostics,
         }
     }
 
@@ -892,22 +1429,24 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         self.field_resolutions.insert(expr, field);
     }
 
+    fn write_path_resolution(&mut self, expr: ExprId, def: ModuleDef) {
+        self.path_resolutions.insert(expr, def);
+    }
+
     fn write_pat_ty(&mut self, pat: PatId, ty: Ty) {
         self.type_of_pat.insert(pat, ty);
     }
 
     fn make_ty(&mut self, type_ref: &TypeRef) -> Ty {
-        // TODO provide generics of function
-        let generics = GenericParams::default();
         let ty = Ty::from_hir(
             self.db,
             &self.module,
             self.impl_block.as_ref(),
-            &generics,
+            &self.generics,
             type_ref,
         );
         let ty = self.insert_type_vars(ty);
-        ty
+        self.normalize_associated_types(ty)
     }
 
     fn unify_substs(&mut self, substs1: &Substs, substs2: &Substs) -> bool {
@@ -935,7 +1474,10 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 | (_, primitive::UncertainFloatTy::Unknown) => true,
                 _ => t1 == t2,
             },
-            (Ty::Bool, _) | (Ty::Str, _) | (Ty::Never, _) | (Ty::Char, _) => ty1 == ty2,
+            (Ty::Bool, Ty::Bool) | (Ty::Str, Ty::Str) | (Ty::Char, Ty::Char) => true,
+            // A diverging expression doesn't tell us anything about the type
+            // of e.g. the `if` it is in, so `!` doesn't unify with variables.
+            (Ty::Never, _) | (_, Ty::Never) => ty1 == ty2,
             (
                 Ty::Adt {
                     def_id: def_id1,
@@ -949,13 +1491,51 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 },
             ) if def_id1 == def_id2 => self.unify_substs(substs1, substs2),
             (Ty::Slice(t1), Ty::Slice(t2)) => self.unify(t1, t2),
+            // arrays of unknown length can have any length
+            (Ty::Array(t1, len1), Ty::Array(t2, len2))
+                if len1 == len2 || len1.is_none() || len2.is_none() =>
+            {
+                self.unify(t1, t2)
+            }
             (Ty::RawPtr(t1, m1), Ty::RawPtr(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::Ref(t1, m1), Ty::Ref(t2, m2)) if m1 == m2 => self.unify(t1, t2),
             (Ty::FnPtr(sig1), Ty::FnPtr(sig2)) if sig1 == sig2 => true,
+            (
+                Ty::Closure {
+                    def: def1,
+                    expr: expr1,
+                    sig: sig1,
+                },
+                Ty::Closure {
+                    def: def2,
+                    expr: expr2,
+                    sig: sig2,
+                },
+            ) if def1 == def2 && expr1 == expr2 => {
+                sig1.input
+                    .iter()
+                    .zip(sig2.input.iter())
+                    .all(|(t1, t2)| self.unify(t1, t2))
+                    && self.unify(&sig1.output, &sig2.output)
+            }
             (Ty::Tuple(ts1), Ty::Tuple(ts2)) if ts1.len() == ts2.len() => ts1
                 .iter()
                 .zip(ts2.iter())
                 .all(|(t1, t2)| self.unify(t1, t2)),
+            (Ty::Dyn(bounds1), Ty::Dyn(bounds2)) | (Ty::Opaque(bounds1), Ty::Opaque(bounds2))
+                if bounds1.len() == bounds2.len() =>
+            {
+                bounds1.iter().zip(bounds2.iter()).all(|(b1, b2)| {
+                    b1.trait_ == b2.trait_
+                        && self.unify_substs(&b1.substs, &b2.substs)
+                        && b1.bindings.len() == b2.bindings.len()
+                        && b1
+                            .bindings
+                            .iter()
+                            .zip(b2.bindings.iter())
+                            .all(|((n1, t1), (n2, t2))| n1 == n2 && self.unify(t1, t2))
+                })
+            }
             (Ty::Infer(InferTy::TypeVar(tv1)), Ty::Infer(InferTy::TypeVar(tv2)))
             | (Ty::Infer(InferTy::IntVar(tv1)), Ty::Infer(InferTy::IntVar(tv2)))
             | (Ty::Infer(InferTy::FloatVar(tv1)), Ty::Infer(InferTy::FloatVar(tv2))) => {
@@ -963,6 +1543,8 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 self.var_unification_table.union(*tv1, *tv2);
                 true
             }
+            (Ty::Projection(..), Ty::Projection(..)) => ty1 == ty2,
+            (Ty::Param { idx: idx1, .. }, Ty::Param { idx: idx2, .. }) => idx1 == idx2,
             (Ty::Infer(InferTy::TypeVar(tv)), other)
             | (other, Ty::Infer(InferTy::TypeVar(tv)))
             | (Ty::Infer(InferTy::IntVar(tv)), other)
@@ -1029,22 +1611,231 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         })
     }
 
+    /// Replaces projections like `<Vec<u32> as IntoIterator>::Item` by the
+    /// types they are defined to be, as far as we can tell by now.
+    fn normalize_associated_types(&mut self, ty: Ty) -> Ty {
+        let ty = self.resolve_ty_as_possible(ty);
+        ty.fold(&mut |ty| match ty {
+            Ty::Projection(..) => {
+                let normalized = traits::normalize(self.db, &self.env, ty);
+                self.insert_type_vars(normalized)
+            }
+            _ => ty,
+        })
+    }
+
+    /// The associated type `name` of the standard library trait at
+    /// `trait_path` (e.g. `["iter", "IntoIterator"]`) for `ty`.
+    fn std_assoc_type(&mut self, ty: Ty, trait_path: &[&str], name: &str) -> Ty {
+        let trait_ = match traits::std_trait(self.db, self.module, trait_path) {
+            Some(it) => it,
+            None => return Ty::Unknown,
+        };
+        let trait_ref = TraitRef::with_unknown_substs(self.db, trait_, ty);
+        let projection = ProjectionTy::new(self.db, trait_ref, Name::new(name.into()));
+        self.normalize_associated_types(Ty::Projection(Arc::new(projection)))
+    }
+
+    /// Unifies `from_ty` with `to_ty`, allowing the coercions which may happen
+    /// at coercion sites: `!` to any type, `&mut T` to `&T`, and references to
+    /// references to the dereferenced or unsized (array to slice) type.
+    fn coerce(&mut self, from_ty: &Ty, to_ty: &Ty) -> bool {
+        let from_ty = self.resolve_ty_shallow(from_ty).into_owned();
+        let to_ty = self.resolve_ty_shallow(to_ty).into_owned();
+        match (&from_ty, &to_ty) {
+            // If the target is not known yet, it's just the type of the
+            // expression, even if that's `!`.
+            (Ty::Never, Ty::Infer(InferTy::TypeVar(tv))) => {
+                self.var_unification_table
+                    .union_value(*tv, TypeVarValue::Known(Ty::Never));
+                true
+            }
+            (Ty::Never, _) => true,
+            (Ty::Ref(from_inner, from_mut), Ty::Ref(to_inner, to_mut))
+                if *from_mut == Mutability::Mut || from_mut == to_mut =>
+            {
+                self.coerce_pointee(from_inner, to_inner)
+            }
+            _ => self.unify(&from_ty, &to_ty),
+        }
+    }
+
+    /// Coerces the pointee of a reference: tries the type itself and then each
+    /// of its derefs, unsizing arrays to slices on the way.
+    fn coerce_pointee(&mut self, from_ty: &Ty, to_ty: &Ty) -> bool {
+        let mut derefed_ty = from_ty.clone();
+        for _ in 0..AUTODEREF_LIMIT {
+            let snapshot = self.var_unification_table.snapshot();
+            let coerced = match (&*self.resolve_ty_shallow(&derefed_ty), to_ty) {
+                (Ty::Array(from_elem, _), Ty::Slice(to_elem)) => {
+                    let (from_elem, to_elem) = (Ty::clone(from_elem), Ty::clone(to_elem));
+                    self.unify(&from_elem, &to_elem)
+                }
+                _ => self.unify(&derefed_ty, to_ty),
+            };
+            if coerced {
+                self.var_unification_table.commit(snapshot);
+                return true;
+            }
+            self.var_unification_table.rollback_to(snapshot);
+            derefed_ty = match self.deref_ty(&derefed_ty) {
+                Some(it) => it,
+                None => break,
+            };
+        }
+        // no coercion applies; unify anyway to get as much information as
+        // possible
+        self.unify(from_ty, to_ty)
+    }
+
+    /// The type `*ty` has: the pointee for references and raw pointers, and
+    /// the `Deref::Target` for other types.
+    fn deref_ty(&mut self, ty: &Ty) -> Option<Ty> {
+        let ty = self.resolve_ty_as_possible(ty.clone());
+        if let Some(derefed_ty) = ty.builtin_deref() {
+            return Some(derefed_ty);
+        }
+        match ty {
+            Ty::Unknown | Ty::Infer(..) => return None,
+            _ => {}
+        }
+        match self.lang_item_assoc_ty("deref", ty, Vec::new(), Name::new("Target".into()))? {
+            Ty::Projection(..) => None,
+            derefed_ty => Some(derefed_ty),
+        }
+    }
+
+    /// The trait which is the lang item `item`, e.g. `Add` for `"add"`.
+    fn lang_trait(&self, item: &str) -> Option<Trait> {
+        let krate = self.module.krate(self.db)?;
+        lang_item_lookup(self.db, krate, item)?.as_trait()
+    }
+
+    /// The result type of an overloaded operator: the `Output` of the trait
+    /// which is the lang item `item`, implemented for `self_ty` with the trait
+    /// parameters `params` (e.g. the type of the right-hand side for `Add`).
+    fn op_output_ty(&mut self, item: &str, self_ty: Ty, params: Vec<Ty>) -> Ty {
+        self.lang_item_assoc_ty(item, self_ty, params, Name::output())
+            .unwrap_or(Ty::Unknown)
+    }
+
+    /// The associated type `name` of the trait which is the lang item `item`,
+    /// implemented for `self_ty` with the trait parameters `params`. Returns
+    /// `None` if there's no such implementation.
+    fn lang_item_assoc_ty(
+        &mut self,
+        item: &str,
+        self_ty: Ty,
+        params: Vec<Ty>,
+        name: Name,
+    ) -> Option<Ty> {
+        let trait_ = self.lang_trait(item)?;
+        let mut trait_ref = TraitRef::with_unknown_substs(self.db, trait_, self_ty);
+        let substs = trait_ref
+            .substs
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| params.get(i).unwrap_or(ty).clone())
+            .collect::<Vec<_>>();
+        trait_ref.substs = Substs(substs.into());
+        let projection = ProjectionTy::new(self.db, trait_ref, name);
+        let projection = self.resolve_ty_as_possible(Ty::Projection(Arc::new(projection)));
+        match traits::normalize(self.db, &self.env, projection) {
+            Ty::Unknown => None,
+            ty => Some(self.insert_type_vars(ty)),
+        }
+    }
+
+    /// The type of `base[index]`: the element type for slices and arrays
+    /// indexed by integers, and otherwise the `Index::Output` of the base or
+    /// of one of its derefs.
+    fn index_output_ty(&mut self, base_ty: Ty, index_ty: Ty) -> Ty {
+        let mut derefed_ty = base_ty;
+        for _ in 0..AUTODEREF_LIMIT {
+            let base_ty = self.resolve_ty_as_possible(derefed_ty);
+            let index_ty = self.resolve_ty_as_possible(index_ty.clone());
+            match (&base_ty, &index_ty) {
+                (Ty::Slice(elem_ty), Ty::Int(..))
+                | (Ty::Array(elem_ty, _), Ty::Int(..))
+                | (Ty::Slice(elem_ty), Ty::Infer(InferTy::IntVar(..)))
+                | (Ty::Array(elem_ty, _), Ty::Infer(InferTy::IntVar(..))) => {
+                    let usize_ty = Ty::Int(primitive::UncertainIntTy::Unsigned(
+                        primitive::UintTy::Usize,
+                    ));
+                    self.unify(&index_ty, &usize_ty);
+                    return Ty::clone(elem_ty);
+                }
+                _ => {}
+            }
+            let output =
+                self.lang_item_assoc_ty("index", base_ty.clone(), vec![index_ty], Name::output());
+            if let Some(output) = output {
+                return output;
+            }
+            derefed_ty = match self.deref_ty(&base_ty) {
+                Some(it) => it,
+                None => break,
+            };
+        }
+        Ty::Unknown
+    }
+
+    /// Remembers the signatures required by the `Fn` bounds of `func`, with
+    /// its type parameters substituted by `substs`, for closures passed to it.
+    fn register_fn_bounds(&mut self, func: Function, substs: &Substs) {
+        for (ty, sig) in traits::fn_bounds(self.db, func.into()) {
+            self.fn_bounds.push((ty.subst(substs), sig.subst(substs)));
+        }
+    }
+
+    /// The signature with which a value of type `ty` can be called: the
+    /// signature of a function pointer or closure, or the one required by an
+    /// `Fn` bound on `ty`.
+    fn callable_sig(&mut self, ty: &Ty) -> Option<FnSig> {
+        let ty = self.resolve_ty_shallow(ty).into_owned();
+        let table = &mut self.var_unification_table;
+        let bound = match &ty {
+            Ty::FnPtr(sig) | Ty::Closure { sig, .. } => return Some(FnSig::clone(sig)),
+            Ty::Infer(InferTy::TypeVar(tv)) => {
+                self.fn_bounds.iter().find(|(bound_ty, _)| match bound_ty {
+                    Ty::Infer(InferTy::TypeVar(bound_tv)) => table.unioned(*tv, *bound_tv),
+                    _ => false,
+                })
+            }
+            Ty::Param { .. } => self.fn_bounds.iter().find(|(bound_ty, _)| *bound_ty == ty),
+            Ty::Dyn(bounds) | Ty::Opaque(bounds) => {
+                let fn_traits = traits::fn_traits(self.db, self.module);
+                return bounds.iter().find_map(|bound| bound.fn_sig(&fn_traits));
+            }
+            // references to callables are callable as well
+            Ty::Ref(inner, _) => return self.callable_sig(inner),
+            _ => None,
+        };
+        bound.map(|(_, sig)| sig.clone())
+    }
+
     /// If `ty` is a type variable with known type, returns that type;
     /// otherwise, return ty.
     fn resolve_ty_shallow<'b>(&mut self, ty: &'b Ty) -> Cow<'b, Ty> {
-        match ty {
-            Ty::Infer(tv) => {
-                let inner = tv.to_inner();
-                match self.var_unification_table.probe_value(inner).known() {
-                    Some(known_ty) => {
-                        // The known_ty can't be a type var itself
-                        Cow::Owned(known_ty.clone())
+        let mut ty = Cow::Borrowed(ty);
+        // A type variable can be known to be an integer or float variable, so
+        // we may need to resolve more than once; there are no longer chains,
+        // since each kind of variable is only resolved once.
+        for _ in 0..3 {
+            let known_ty = match &*ty {
+                Ty::Infer(tv) => {
+                    let inner = tv.to_inner();
+                    match self.var_unification_table.probe_value(inner).known() {
+                        Some(known_ty) => known_ty.clone(),
+                        None => return ty,
                     }
-                    _ => Cow::Borrowed(ty),
                 }
-            }
-            _ => Cow::Borrowed(ty),
+                _ => return ty,
+            };
+            ty = Cow::Owned(known_ty);
         }
+        ty
     }
 
     /// Resolves the type completely; type variables without known type are
@@ -1076,13 +1867,36 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         };
 
         // resolve in module
-        let typable: Option<TypableDef> = self
-            .module
-            .resolve_path(self.db, &path)
-            .take_values()?
-            .into();
+        let def = self.module.resolve_path(self.db, &path).take_values()?;
+        self.write_path_resolution(expr, def);
+        let typable: Option<TypableDef> = def.into();
         let typable = typable?;
         let ty = self.db.type_for_def(typable);
+        let ty = match typable {
+            // The type parameters of a generic function are given by the path
+            // (`id::<u32>`), or inferred from the call.
+            TypableDef::Function(func) => {
+                let substs = Ty::substs_from_path(
+                    self.db,
+                    &self.module,
+                    self.impl_block.as_ref(),
+                    &self.generics,
+                    path,
+                    typable,
+                );
+                // Each parameter gets a single type variable, so that e.g. the
+                // argument and return types of `fn id<T>(t: T) -> T` are linked.
+                let substs = substs
+                    .0
+                    .iter()
+                    .map(|ty| self.insert_type_vars(ty.clone()))
+                    .collect::<Vec<_>>();
+                let substs = Substs(substs.into());
+                self.register_fn_bounds(func, &substs);
+                ty.subst(&substs)
+            }
+            _ => ty,
+        };
         let ty = self.insert_type_vars(ty);
         Some(ty)
     }
@@ -1102,13 +1916,11 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             Some(it) => it,
         };
         // TODO remove the duplication between here and `Ty::from_path`?
-        // TODO provide generics of function
-        let generics = GenericParams::default();
         let substs = Ty::substs_from_path(
             self.db,
             &self.module,
             self.impl_block.as_ref(),
-            &generics,
+            &self.generics,
             path,
             def,
         );
@@ -1123,17 +1935,50 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 let ty = self.insert_type_vars(ty.apply_substs(substs));
                 (ty, Some(var.into()))
             }
-            TypableDef::Enum(_) | TypableDef::Function(_) => (Ty::Unknown, None),
+            TypableDef::Enum(_)
+            | TypableDef::Function(_)
+            | TypableDef::Type(_)
+            | TypableDef::Const(_)
+            | TypableDef::Static(_) => (Ty::Unknown, None),
+        }
+    }
+
+    /// Resolves the path of a path pattern to the struct, enum variant or
+    /// const it refers to.
+    fn resolve_pat_path(&self, path: &Path) -> Option<PatResolution> {
+        let resolution = match self.module.resolve_path(self.db, path).take_values()? {
+            ModuleDef::Struct(it) => PatResolution::Variant(it.into()),
+            ModuleDef::EnumVariant(it) => PatResolution::Variant(it.into()),
+            ModuleDef::Const(it) => PatResolution::Const(it),
+            _ => return None,
+        };
+        Some(resolution)
+    }
+
+    /// Resolves the name of an identifier pattern to the unit struct, unit
+    /// variant or const it refers to, in which case the pattern doesn't bind
+    /// a variable but compares with it, like `None` does.
+    fn resolve_unit_pat(&self, name: &Name) -> Option<PatResolution> {
+        let resolution = self.resolve_pat_path(&Path::from(name.clone()))?;
+        if resolution.is_unit(self.db) {
+            Some(resolution)
+        } else {
+            None
         }
     }
 
     fn infer_tuple_struct_pat(
         &mut self,
+        pat: PatId,
         path: Option<&Path>,
         subpats: &[PatId],
         expected: &Ty,
     ) -> Ty {
         let (ty, def) = self.resolve_variant(path);
+        if let Some(def) = def {
+            self.pat_resolutions
+                .insert(pat, PatResolution::Variant(def));
+        }
 
         self.unify(&ty, expected);
 
@@ -1150,8 +1995,18 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         ty
     }
 
-    fn infer_struct_pat(&mut self, path: Option<&Path>, subpats: &[FieldPat], expected: &Ty) -> Ty {
+    fn infer_struct_pat(
+        &mut self,
+        pat: PatId,
+        path: Option<&Path>,
+        subpats: &[FieldPat],
+        expected: &Ty,
+    ) -> Ty {
         let (ty, def) = self.resolve_variant(path);
+        if let Some(def) = def {
+            self.pat_resolutions
+                .insert(pat, PatResolution::Variant(def));
+        }
 
         self.unify(&ty, expected);
 
@@ -1159,6 +2014,13 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
 
         for subpat in subpats {
             let matching_field = def.and_then(|it| it.field(self.db, &subpat.name));
+            if def.is_some() && matching_field.is_none() {
+                self.diagnostics.push(InferenceDiagnostic::NoSuchPatField {
+                    pat,
+                    field: subpat.name.clone(),
+                    ty: ty.clone(),
+                });
+            }
             let expected_ty = matching_field
                 .map_or(Ty::Unknown, |field| field.ty(self.db))
                 .subst(&substs);
@@ -1206,38 +2068,55 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             Pat::TupleStruct {
                 path: ref p,
                 args: ref subpats,
-            } => self.infer_tuple_struct_pat(p.as_ref(), subpats, expected),
+            } => self.infer_tuple_struct_pat(pat, p.as_ref(), subpats, expected),
             Pat::Struct {
                 path: ref p,
                 args: ref fields,
-            } => self.infer_struct_pat(p.as_ref(), fields, expected),
-            Pat::Path(path) => self
-                .module
-                .resolve_path(self.db, &path)
-                .take_values()
-                .and_then(|module_def| module_def.into())
-                .map_or(Ty::Unknown, |resolved| self.db.type_for_def(resolved)),
-            Pat::Bind {
-                mode,
-                name: _name,
-                subpat,
-            } => {
-                let subty = if let Some(subpat) = subpat {
-                    self.infer_pat(*subpat, expected)
-                } else {
-                    expected.clone()
+            } => self.infer_struct_pat(pat, p.as_ref(), fields, expected),
+            Pat::Path(path) => {
+                if let Some(resolution) = self.resolve_pat_path(path) {
+                    self.pat_resolutions.insert(pat, resolution);
+                }
+                self.module
+                    .resolve_path(self.db, &path)
+                    .take_values()
+                    .and_then(|module_def| module_def.into())
+                    .map_or(Ty::Unknown, |resolved| self.db.type_for_def(resolved))
+            }
+            Pat::Lit(expr) => self.infer_expr(*expr, &Expectation::none()),
+            Pat::Range { start, end } => {
+                let start_ty = self.infer_expr(*start, &Expectation::none());
+                let end_ty = self.infer_expr(*end, &Expectation::none());
+                self.unify(&start_ty, &end_ty);
+                start_ty
+            }
+            Pat::Bind { mode, name, subpat } => {
+                let resolution = match (mode, subpat) {
+                    (BindingAnnotation::Unannotated, None) => self.resolve_unit_pat(name),
+                    _ => None,
                 };
+                if let Some(resolution) = resolution {
+                    self.pat_resolutions.insert(pat, resolution);
+                    self.db.type_for_def(resolution.into())
+                } else {
+                    let subty = if let Some(subpat) = subpat {
+                        self.infer_pat(*subpat, expected)
+                    } else {
+                        expected.clone()
+                    };
 
-                match mode {
-                    BindingAnnotation::Ref => Ty::Ref(subty.into(), Mutability::Shared),
-                    BindingAnnotation::RefMut => Ty::Ref(subty.into(), Mutability::Mut),
-                    BindingAnnotation::Mutable | BindingAnnotation::Unannotated => subty,
+                    match mode {
+                        BindingAnnotation::Ref => Ty::Ref(subty.into(), Mutability::Shared),
+                        BindingAnnotation::RefMut => Ty::Ref(subty.into(), Mutability::Mut),
+                        BindingAnnotation::Mutable | BindingAnnotation::Unannotated => subty,
+                    }
                 }
             }
             _ => Ty::Unknown,
         };
         // use a new type variable if we got Ty::Unknown here
         let ty = self.insert_type_vars_shallow(ty);
+        let ty = self.normalize_associated_types(ty);
         self.unify(&ty, expected);
         let ty = self.resolve_ty_as_possible(ty);
         self.write_pat_ty(pat, ty.clone());
@@ -1245,6 +2124,67 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     }
 
     fn infer_expr(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Ty {
+        let first_new_diagnostic = self.diagnostics.len();
+        let ty = self.infer_expr_inner(tgt_expr, expected);
+        if !self.unify(&ty, &expected.ty) {
+            self.report_mismatch(tgt_expr, &expected.ty, &ty, first_new_diagnostic);
+        }
+        self.resolve_ty_as_possible(ty)
+    }
+
+    /// Infers the type of the expression at a coercion site, like a function
+    /// argument or the initializer of a `let` with a type annotation, where
+    /// its type only needs to be coercible to the expected type.
+    fn infer_expr_coerce(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Ty {
+        let first_new_diagnostic = self.diagnostics.len();
+        let ty = self.infer_expr_inner(tgt_expr, expected);
+        if !self.coerce(&ty, &expected.ty) {
+            self.report_mismatch(tgt_expr, &expected.ty, &ty, first_new_diagnostic);
+        }
+        self.resolve_ty_as_possible(ty)
+    }
+
+    /// Reports that `expr` has the type `actual` instead of `expected`, unless
+    /// the mismatch was already reported for one of its subexpressions, like
+    /// the tail of a block, since `first_new_diagnostic`.
+    fn report_mismatch(
+        &mut self,
+        expr: ExprId,
+        expected: &Ty,
+        actual: &Ty,
+        first_new_diagnostic: usize,
+    ) {
+        // `!` is only not coerced when the type of the expression isn't
+        // known yet, which is not an error
+        if *actual == Ty::Never {
+            return;
+        }
+        let reported = self.diagnostics[first_new_diagnostic..]
+            .iter()
+            .any(|it| match it {
+                InferenceDiagnostic::MismatchedType { expected: ty, .. } => ty == expected,
+                _ => false,
+            });
+        if !reported {
+            self.diagnostics.push(InferenceDiagnostic::MismatchedType {
+                expr,
+                expected: expected.clone(),
+                actual: actual.clone(),
+            });
+        }
+    }
+
+    /// Whether `ty` or one of its builtin derefs implements `Deref`. Method and
+    /// field lookup don't look at the targets of such derefs yet, so they may
+    /// fail wrongly for these types.
+    fn has_overloaded_deref(&mut self, ty: &Ty) -> bool {
+        let derefs = ty.clone().autoderef(self.db).collect::<Vec<_>>();
+        derefs
+            .iter()
+            .any(|ty| ty.builtin_deref().is_none() && self.deref_ty(ty).is_some())
+    }
+
+    fn infer_expr_inner(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Ty {
         let body = Arc::clone(&self.body); // avoid borrow checker problem
         let ty = match &body[tgt_expr] {
             Expr::Missing => Ty::Unknown,
@@ -1255,38 +2195,71 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             } => {
                 // if let is desugared to match, so this is always simple if
                 self.infer_expr(*condition, &Expectation::has_type(Ty::Bool));
-                let then_ty = self.infer_expr(*then_branch, expected);
-                match else_branch {
-                    Some(else_branch) => {
-                        self.infer_expr(*else_branch, expected);
-                    }
-                    None => {
-                        // no else branch -> unit
-                        self.unify(&then_ty, &Ty::unit()); // actually coerce
-                    }
+                let condition_diverges = mem::replace(&mut self.diverges, false);
+                let result_ty = match else_branch {
+                    Some(_) => self.expected_or_new_var(expected),
+                    // no else branch -> unit
+                    None => Ty::unit(),
                 };
-                then_ty
+                let then_ty = self.infer_branch(*then_branch, &result_ty);
+                let then_diverges = mem::replace(&mut self.diverges, false);
+                let else_ty = match else_branch {
+                    Some(else_branch) => self.infer_branch(*else_branch, &result_ty),
+                    None => Ty::unit(),
+                };
+                self.diverges = condition_diverges || (then_diverges && self.diverges);
+                if then_ty == Ty::Never && else_ty == Ty::Never {
+                    Ty::Never
+                } else {
+                    result_ty
+                }
             }
-            Expr::Block { statements, tail } => self.infer_block(statements, *tail, expected),
-            Expr::Loop { body } => {
-                self.infer_expr(*body, &Expectation::has_type(Ty::unit()));
-                // TODO handle break with value
-                Ty::Never
+            Expr::Block {
+                statements,
+                tail,
+                label,
+                ..
+            } => {
+                let module = self
+                    .body
+                    .block_module(tgt_expr)
+                    .map(|block_module| mem::replace(&mut self.module, block_module));
+                let ty = match label {
+                    Some(_) => self.infer_labeled_block(tgt_expr, statements, *tail, expected),
+                    None => self.infer_block(statements, *tail, expected),
+                };
+                if let Some(module) = module {
+                    self.module = module;
+                }
+                ty
+            }
+            Expr::Loop { body, .. } => {
+                let break_ty = self.new_type_var();
+                let ctx = self.infer_loop_body(tgt_expr, *body, break_ty);
+                if ctx.may_break {
+                    ctx.break_ty
+                } else {
+                    Ty::Never
+                }
             }
-            Expr::While { condition, body } => {
+            Expr::While {
+                condition, body, ..
+            } => {
                 // while let is desugared to a match loop, so this is always simple while
                 self.infer_expr(*condition, &Expectation::has_type(Ty::Bool));
-                self.infer_expr(*body, &Expectation::has_type(Ty::unit()));
+                self.infer_loop_body(tgt_expr, *body, Ty::unit());
                 Ty::unit()
             }
             Expr::For {
                 iterable,
                 body,
                 pat,
+                ..
             } => {
-                let _iterable_ty = self.infer_expr(*iterable, &Expectation::none());
-                self.infer_pat(*pat, &Ty::Unknown);
-                self.infer_expr(*body, &Expectation::has_type(Ty::unit()));
+                let iterable_ty = self.infer_expr(*iterable, &Expectation::none());
+                let pat_ty = self.std_assoc_type(iterable_ty, &["iter", "IntoIterator"], "Item");
+                self.infer_pat(*pat, &pat_ty);
+                self.infer_loop_body(tgt_expr, *body, Ty::unit());
                 Ty::unit()
             }
             Expr::Lambda {
@@ -1296,32 +2269,80 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             } => {
                 assert_eq!(args.len(), arg_types.len());
 
-                for (arg_pat, arg_type) in args.iter().zip(arg_types.iter()) {
-                    let expected = if let Some(type_ref) = arg_type {
+                // The parameter and return types come from the annotations,
+                // or from the signature the closure is expected to have.
+                let expected_sig = self.callable_sig(&expected.ty);
+                let mut input = Vec::with_capacity(args.len());
+                for (i, (arg_pat, arg_type)) in args.iter().zip(arg_types.iter()).enumerate() {
+                    let expected_arg_ty = expected_sig
+                        .as_ref()
+                        .and_then(|sig| sig.input.get(i).cloned())
+                        .unwrap_or(Ty::Unknown);
+                    let expected_arg_ty = self.insert_type_vars(expected_arg_ty);
+                    let expected_arg_ty = self.normalize_associated_types(expected_arg_ty);
+                    let arg_ty = if let Some(type_ref) = arg_type {
                         let ty = self.make_ty(type_ref);
+                        self.unify(&ty, &expected_arg_ty);
                         ty
                     } else {
-                        Ty::Unknown
+                        expected_arg_ty
                     };
-                    self.infer_pat(*arg_pat, &expected);
+                    input.push(self.infer_pat(*arg_pat, &arg_ty));
                 }
 
-                // TODO: infer lambda type etc.
-                let _body_ty = self.infer_expr(*body, &Expectation::none());
-                Ty::Unknown
+                let output = expected_sig.map_or(Ty::Unknown, |sig| sig.output);
+                let output = self.insert_type_vars(output);
+                let output = self.normalize_associated_types(output);
+                // `return` in the body returns from the closure, and neither
+                // divergence nor loops carry over into or out of it
+                let fn_return_ty = mem::replace(&mut self.return_ty, output.clone());
+                let diverges = mem::replace(&mut self.diverges, false);
+                let breakables = mem::replace(&mut self.breakables, Vec::new());
+                self.infer_expr_coerce(*body, &Expectation::has_type(output.clone()));
+                self.return_ty = fn_return_ty;
+                self.diverges = diverges;
+                self.breakables = breakables;
+
+                Ty::Closure {
+                    def: self.def,
+                    expr: tgt_expr,
+                    sig: Arc::new(FnSig { input, output }),
+                }
             }
             Expr::Call { callee, args } => {
                 let callee_ty = self.infer_expr(*callee, &Expectation::none());
-                let (param_tys, ret_ty) = match &callee_ty {
-                    Ty::FnPtr(sig) => (&sig.input[..], sig.output.clone()),
-                    _ => {
-                        // not callable
-                        // TODO report an error?
-                        (&[][..], Ty::Unknown)
+                let (param_tys, ret_ty) = match self.callable_sig(&callee_ty) {
+                    Some(sig) => {
+                        if sig.input.len() != args.len() {
+                            self.diagnostics.push(InferenceDiagnostic::WrongArgCount {
+                                expr: tgt_expr,
+                                expected: sig.input.len(),
+                                actual: args.len(),
+                            });
+                        }
+                        let ret_ty = self.insert_type_vars(sig.output);
+                        (sig.input, self.normalize_associated_types(ret_ty))
+                    }
+                    None => {
+                        // Types which deref to a callable type are callable
+                        // as well, which `callable_sig` doesn't know about.
+                        // Tuple struct and variant constructors have the type
+                        // of the struct or enum for now.
+                        let is_adt = match callee_ty {
+                            Ty::Adt { .. } => true,
+                            _ => false,
+                        };
+                        if !is_adt && self.deref_ty(&callee_ty).is_none() {
+                            self.diagnostics.push(InferenceDiagnostic::NotCallable {
+                                callee: *callee,
+                                ty: callee_ty.clone(),
+                            });
+                        }
+                        (Vec::new(), Ty::Unknown)
                     }
                 };
                 for (i, arg) in args.iter().enumerate() {
-                    self.infer_expr(
+                    self.infer_expr_coerce(
                         *arg,
                         &Expectation::has_type(param_tys.get(i).cloned().unwrap_or(Ty::Unknown)),
                     );
@@ -1334,15 +2355,66 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 method_name,
             } => {
                 let receiver_ty = self.infer_expr(*receiver, &Expectation::none());
-                let resolved = receiver_ty.clone().lookup_method(self.db, method_name);
+                let receiver_ty = self.resolve_ty_as_possible(receiver_ty);
+                let resolved =
+                    receiver_ty
+                        .clone()
+                        .lookup_method(self.db, self.module, &self.env, method_name);
+                let is_resolved = resolved.is_some();
                 let method_ty = match resolved {
-                    Some(func) => {
+                    Some((found_ty, func)) => {
                         self.write_method_resolution(tgt_expr, func);
-                        self.db.type_for_def(func.into())
+                        let method_ty = self.db.type_for_def(func.into());
+                        let generics = func.generic_params(self.db);
+                        let mut substs =
+                            vec![Ty::Unknown; generics.count_params_including_parent()];
+                        if func.parent_trait(self.db).is_some() {
+                            // The first parameter of a trait method is `Self`.
+                            substs[0] = found_ty;
+                        } else if let Some(impl_block) = func.impl_block(self.db) {
+                            // The parameters of the impl come first; they are
+                            // determined by the receiver type.
+                            let impl_substs =
+                                traits::impl_substs_for_self_ty(self.db, &impl_block, &found_ty);
+                            for (subst, ty) in substs.iter_mut().zip(impl_substs.0.iter()) {
+                                *subst = ty.clone();
+                            }
+                        }
+                        // Each parameter gets a single type variable, so that
+                        // e.g. the return type of a closure argument is linked
+                        // to the return type of the method.
+                        let substs = substs
+                            .into_iter()
+                            .map(|ty| self.insert_type_vars(ty))
+                            .collect::<Vec<_>>();
+                        let substs = Substs(substs.into());
+                        self.register_fn_bounds(func, &substs);
+                        method_ty.subst(&substs)
+                    }
+                    None => {
+                        // The methods of primitive types come from impls in
+                        // `core` and `std` which aren't fully modelled, so
+                        // only a missing method of an ADT is reported.
+                        let mut derefed_ty = &receiver_ty;
+                        while let Ty::Ref(inner, _) = derefed_ty {
+                            derefed_ty = inner;
+                        }
+                        let is_adt = match derefed_ty {
+                            Ty::Adt { .. } => true,
+                            _ => false,
+                        };
+                        if is_adt && !self.has_overloaded_deref(&receiver_ty) {
+                            self.diagnostics.push(InferenceDiagnostic::NoSuchMethod {
+                                expr: tgt_expr,
+                                method: method_name.clone(),
+                                receiver: receiver_ty.clone(),
+                            });
+                        }
+                        Ty::Unknown
                     }
-                    None => Ty::Unknown,
                 };
                 let method_ty = self.insert_type_vars(method_ty);
+                let method_ty = self.normalize_associated_types(method_ty);
                 let (expected_receiver_ty, param_tys, ret_ty) = match &method_ty {
                     Ty::FnPtr(sig) => {
                         if sig.input.len() > 0 {
@@ -1353,11 +2425,18 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                     }
                     _ => (&Ty::Unknown, &[][..], Ty::Unknown),
                 };
+                if is_resolved && param_tys.len() != args.len() {
+                    self.diagnostics.push(InferenceDiagnostic::WrongArgCount {
+                        expr: tgt_expr,
+                        expected: param_tys.len(),
+                        actual: args.len(),
+                    });
+                }
                 // TODO we would have to apply the autoderef/autoref steps here
                 // to get the correct receiver type to unify...
                 self.unify(expected_receiver_ty, &receiver_ty);
                 for (i, arg) in args.iter().enumerate() {
-                    self.infer_expr(
+                    self.infer_expr_coerce(
                         *arg,
                         &Expectation::has_type(param_tys.get(i).cloned().unwrap_or(Ty::Unknown)),
                     );
@@ -1365,35 +2444,62 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 ret_ty
             }
             Expr::Match { expr, arms } => {
-                let expected = if expected.ty == Ty::Unknown {
-                    Expectation::has_type(self.new_type_var())
-                } else {
-                    expected.clone()
-                };
+                let result_ty = self.expected_or_new_var(expected);
                 let input_ty = self.infer_expr(*expr, &Expectation::none());
+                let input_diverges = self.diverges;
 
+                let mut all_arms_diverge = true;
+                let mut all_arm_tys_never = true;
                 for arm in arms {
+                    self.diverges = false;
                     for &pat in &arm.pats {
                         let _pat_ty = self.infer_pat(pat, &input_ty);
                     }
-                    // TODO type the guard
-                    self.infer_expr(arm.expr, &expected);
+                    if let Some(guard) = arm.guard {
+                        self.infer_expr(guard, &Expectation::has_type(Ty::Bool));
+                    }
+                    let arm_ty = self.infer_branch(arm.expr, &result_ty);
+                    all_arms_diverge &= self.diverges;
+                    all_arm_tys_never &= arm_ty == Ty::Never;
                 }
+                self.diverges = input_diverges || all_arms_diverge;
+                self.matches.push((tgt_expr, self.module));
 
-                expected.ty
+                if all_arm_tys_never {
+                    Ty::Never
+                } else {
+                    result_ty
+                }
             }
             Expr::Path(p) => self.infer_path_expr(tgt_expr, p).unwrap_or(Ty::Unknown),
-            Expr::Continue => Ty::Never,
-            Expr::Break { expr } => {
-                if let Some(expr) = expr {
-                    // TODO handle break with value
-                    self.infer_expr(*expr, &Expectation::none());
+            Expr::Continue { .. } => Ty::Never,
+            Expr::Break { expr, .. } => {
+                let target = self.body.break_target(tgt_expr);
+                let breakable = self
+                    .breakables
+                    .iter_mut()
+                    .rev()
+                    .find(|ctx| Some(ctx.expr) == target);
+                let break_ty = match breakable {
+                    Some(ctx) => {
+                        ctx.may_break = true;
+                        ctx.break_ty.clone()
+                    }
+                    None => Ty::Unknown,
+                };
+                match expr {
+                    Some(expr) => {
+                        self.infer_expr_coerce(*expr, &Expectation::has_type(break_ty));
+                    }
+                    None => {
+                        self.coerce(&Ty::unit(), &break_ty);
+                    }
                 }
                 Ty::Never
             }
             Expr::Return { expr } => {
                 if let Some(expr) = expr {
-                    self.infer_expr(*expr, &Expectation::has_type(self.return_ty.clone()));
+                    self.infer_expr_coerce(*expr, &Expectation::has_type(self.return_ty.clone()));
                 }
                 Ty::Never
             }
@@ -1405,11 +2511,18 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 let (ty, def_id) = self.resolve_variant(path.as_ref());
                 let substs = ty.substs().unwrap_or_else(Substs::empty);
                 for field in fields {
-                    let field_ty = def_id
-                        .and_then(|it| it.field(self.db, &field.name))
+                    let matching_field = def_id.and_then(|it| it.field(self.db, &field.name));
+                    if def_id.is_some() && matching_field.is_none() {
+                        self.diagnostics.push(InferenceDiagnostic::NoSuchField {
+                            expr: tgt_expr,
+                            field: field.name.clone(),
+                            ty: ty.clone(),
+                        });
+                    }
+                    let field_ty = matching_field
                         .map_or(Ty::Unknown, |field| field.ty(self.db))
                         .subst(&substs);
-                    self.infer_expr(field.expr, &Expectation::has_type(field_ty));
+                    self.infer_expr_coerce(field.expr, &Expectation::has_type(field_ty));
                 }
                 if let Some(expr) = spread {
                     self.infer_expr(*expr, &Expectation::has_type(ty.clone()));
@@ -1419,6 +2532,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             Expr::Field { expr, name } => {
                 let receiver_ty = self.infer_expr(*expr, &Expectation::none());
                 let ty = receiver_ty
+                    .clone()
                     .autoderef(self.db)
                     .find_map(|derefed_ty| match derefed_ty {
                         Ty::Tuple(fields) => {
@@ -1434,13 +2548,36 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                             field.ty(self.db).subst(substs)
                         }),
                         _ => None,
-                    })
-                    .unwrap_or(Ty::Unknown);
+                    });
+                let ty = match ty {
+                    Some(ty) => ty,
+                    None => {
+                        // The methods of primitive types come from impls in
+                        // `core` and `std` which aren't fully modelled, so
+                        // only a missing method of an ADT is reported.
+                        let mut derefed_ty = &receiver_ty;
+                        while let Ty::Ref(inner, _) = derefed_ty {
+                            derefed_ty = inner;
+                        }
+                        let is_adt = match derefed_ty {
+                            Ty::Adt { .. } => true,
+                            _ => false,
+                        };
+                        if is_adt && !self.has_overloaded_deref(&receiver_ty) {
+                            self.diagnostics.push(InferenceDiagnostic::NoSuchField {
+                                expr: tgt_expr,
+                                field: name.clone(),
+                                ty: receiver_ty.clone(),
+                            });
+                        }
+                        Ty::Unknown
+                    }
+                };
                 self.insert_type_vars(ty)
             }
             Expr::Try { expr } => {
-                let _inner_ty = self.infer_expr(*expr, &Expectation::none());
-                Ty::Unknown
+                let inner_ty = self.infer_expr(*expr, &Expectation::none());
+                self.std_assoc_type(inner_ty, &["ops", "Try"], "Ok")
             }
             Expr::Cast { expr, type_ref } => {
                 let _inner_ty = self.infer_expr(*expr, &Expectation::none());
@@ -1449,36 +2586,32 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 cast_ty
             }
             Expr::Ref { expr, mutability } => {
-                // TODO pass the expectation down
-                let inner_ty = self.infer_expr(*expr, &Expectation::none());
-                // TODO reference coercions etc.
+                let expectation = match &expected.ty {
+                    Ty::Ref(inner, _) => Expectation::has_type(Ty::clone(inner)),
+                    _ => Expectation::none(),
+                };
+                // The expected type is just a hint here: the reference may be
+                // coerced to it, e.g. `&String` to `&str`.
+                let inner_ty = self.infer_expr_inner(*expr, &expectation);
+                let inner_ty = self.resolve_ty_as_possible(inner_ty);
                 Ty::Ref(Arc::new(inner_ty), *mutability)
             }
             Expr::UnaryOp { expr, op } => {
                 let inner_ty = self.infer_expr(*expr, &Expectation::none());
                 match op {
-                    UnaryOp::Deref => {
-                        if let Some(derefed_ty) = inner_ty.builtin_deref() {
-                            derefed_ty
-                        } else {
-                            // TODO Deref::deref
-                            Ty::Unknown
-                        }
-                    }
-                    UnaryOp::Neg => {
-                        match inner_ty {
-                            Ty::Int(primitive::UncertainIntTy::Unknown)
-                            | Ty::Int(primitive::UncertainIntTy::Signed(..))
-                            | Ty::Infer(InferTy::IntVar(..))
-                            | Ty::Infer(InferTy::FloatVar(..))
-                            | Ty::Float(..) => inner_ty,
-                            // TODO: resolve ops::Neg trait
-                            _ => Ty::Unknown,
-                        }
-                    }
-                    UnaryOp::Not if inner_ty == Ty::Bool => Ty::Bool,
-                    // TODO: resolve ops::Not trait for inner_ty
-                    UnaryOp::Not => Ty::Unknown,
+                    UnaryOp::Deref => self.deref_ty(&inner_ty).unwrap_or(Ty::Unknown),
+                    UnaryOp::Neg => match inner_ty {
+                        Ty::Int(primitive::UncertainIntTy::Unknown)
+                        | Ty::Int(primitive::UncertainIntTy::Signed(..))
+                        | Ty::Infer(InferTy::IntVar(..))
+                        | Ty::Infer(InferTy::FloatVar(..))
+                        | Ty::Float(..) => inner_ty,
+                        _ => self.op_output_ty("neg", inner_ty, Vec::new()),
+                    },
+                    UnaryOp::Not => match inner_ty {
+                        Ty::Bool | Ty::Int(..) | Ty::Infer(InferTy::IntVar(..)) => inner_ty,
+                        _ => self.op_output_ty("not", inner_ty, Vec::new()),
+                    },
                 }
             }
             Expr::BinaryOp { lhs, rhs, op } => match op {
@@ -1490,16 +2623,74 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                         _ => Expectation::none(),
                     };
                     let lhs_ty = self.infer_expr(*lhs, &lhs_expectation);
-                    // TODO: find implementation of trait corresponding to operation
-                    // symbol and resolve associated `Output` type
-                    let rhs_expectation = binary_op_rhs_expectation(*op, lhs_ty);
-                    let rhs_ty = self.infer_expr(*rhs, &Expectation::has_type(rhs_expectation));
+                    let rhs_expectation = binary_op_rhs_expectation(*op, lhs_ty.clone());
+                    let mut rhs_ty = self.infer_expr(*rhs, &Expectation::has_type(rhs_expectation));
+                    // a number only has built-in operations with numbers of
+                    // the same type, so unless the right-hand side is known
+                    // to be something else, it has the type of a left-hand
+                    // side of known type
+                    let rhs_is_number = match (&lhs_ty, &rhs_ty) {
+                        (Ty::Int(..), Ty::Infer(InferTy::TypeVar(..)))
+                        | (Ty::Float(..), Ty::Infer(InferTy::TypeVar(..))) => true,
+                        _ => is_number(&rhs_ty),
+                    };
+                    if binary_op_lang_item(*op).is_some()
+                        && !is_shift(*op)
+                        && is_number(&lhs_ty)
+                        && rhs_is_number
+                    {
+                        self.unify(&lhs_ty, &rhs_ty);
+                        rhs_ty = self.resolve_ty_as_possible(rhs_ty);
+                    }
 
-                    // TODO: similar as above, return ty is often associated trait type
-                    binary_op_return_ty(*op, rhs_ty)
+                    match binary_op_lang_item(*op) {
+                        // not a built-in operation, so it's the `Output` of the
+                        // operator trait; compound assignments always evaluate
+                        // to `()`
+                        Some(lang_item)
+                            if !op.is_assignment() && !is_builtin_binop(&lhs_ty, &rhs_ty) =>
+                        {
+                            self.op_output_ty(lang_item, lhs_ty, vec![rhs_ty])
+                        }
+                        _ => binary_op_return_ty(*op, rhs_ty),
+                    }
                 }
                 _ => Ty::Unknown,
             },
+            Expr::Index { base, index } => {
+                let base_ty = self.infer_expr(*base, &Expectation::none());
+                let index_ty = self.infer_expr(*index, &Expectation::none());
+                self.index_output_ty(base_ty, index_ty)
+            }
+            Expr::Range {
+                lhs,
+                rhs,
+                range_type,
+            } => {
+                // both ends of the range have the same type
+                let idx_ty = self.new_type_var();
+                for expr in lhs.iter().chain(rhs.iter()) {
+                    self.infer_expr(*expr, &Expectation::has_type(idx_ty.clone()));
+                }
+                let (name, substs) = match (lhs, rhs, range_type) {
+                    (None, None, _) => ("RangeFull", Substs::empty()),
+                    (Some(_), None, _) => ("RangeFrom", Substs(Arc::new([idx_ty]))),
+                    (None, Some(_), RangeOp::Exclusive) => ("RangeTo", Substs(Arc::new([idx_ty]))),
+                    (None, Some(_), RangeOp::Inclusive) => {
+                        ("RangeToInclusive", Substs(Arc::new([idx_ty])))
+                    }
+                    (Some(_), Some(_), RangeOp::Exclusive) => ("Range", Substs(Arc::new([idx_ty]))),
+                    (Some(_), Some(_), RangeOp::Inclusive) => {
+                        ("RangeInclusive", Substs(Arc::new([idx_ty])))
+                    }
+                };
+                match traits::std_item(self.db, self.module, &["ops", name]) {
+                    Some(ModuleDef::Struct(s)) => {
+                        self.db.type_for_def(s.into()).apply_substs(substs)
+                    }
+                    _ => Ty::Unknown,
+                }
+            }
             Expr::Tuple { exprs } => {
                 let mut ty_vec = Vec::with_capacity(exprs.len());
                 for arg in exprs.iter() {
@@ -1510,15 +2701,34 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             }
             Expr::Array { exprs } => {
                 let elem_ty = match &expected.ty {
-                    Ty::Slice(inner) | Ty::Array(inner) => Ty::clone(&inner),
+                    Ty::Slice(inner) | Ty::Array(inner, _) => Ty::clone(inner),
                     _ => self.new_type_var(),
                 };
 
                 for expr in exprs.iter() {
-                    self.infer_expr(*expr, &Expectation::has_type(elem_ty.clone()));
+                    self.infer_expr_coerce(*expr, &Expectation::has_type(elem_ty.clone()));
                 }
 
-                Ty::Array(Arc::new(elem_ty))
+                Ty::Array(Arc::new(elem_ty), Some(exprs.len() as u64))
+            }
+            Expr::ArrayRepeat {
+                initializer,
+                repeat,
+            } => {
+                let elem_ty = match &expected.ty {
+                    Ty::Slice(inner) | Ty::Array(inner, _) => Ty::clone(inner),
+                    _ => self.new_type_var(),
+                };
+                self.infer_expr_coerce(*initializer, &Expectation::has_type(elem_ty.clone()));
+                self.infer_expr(
+                    *repeat,
+                    &Expectation::has_type(Ty::Int(primitive::UncertainIntTy::Unsigned(
+                        primitive::UintTy::Usize,
+                    ))),
+                );
+                let len = const_eval::eval_usize(self.db, self.module, &self.body, *repeat);
+
+                Ty::Array(Arc::new(elem_ty), len)
             }
             Expr::Literal(lit) => match lit {
                 Literal::Bool(..) => Ty::Bool,
@@ -1537,12 +2747,89 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         };
         // use a new type variable if we got Ty::Unknown here
         let ty = self.insert_type_vars_shallow(ty);
-        self.unify(&ty, &expected.ty);
-        let ty = self.resolve_ty_as_possible(ty);
+        let ty = self.normalize_associated_types(ty);
+        if *self.resolve_ty_shallow(&ty) == Ty::Never {
+            self.diverges = true;
+        }
         self.write_expr_ty(tgt_expr, ty.clone());
         ty
     }
 
+    /// The type of an `if` or `match` if it's known from the context, or a
+    /// new type variable for it.
+    fn expected_or_new_var(&mut self, expected: &Expectation) -> Ty {
+        if expected.ty == Ty::Unknown {
+            self.new_type_var()
+        } else {
+            expected.ty.clone()
+        }
+    }
+
+    /// Infers a branch of an `if` or `match` and coerces it to the type of the
+    /// whole expression, `result_ty`. Branches of type `!` are left out, so
+    /// that the other branches determine the type.
+    fn infer_branch(&mut self, branch: ExprId, result_ty: &Ty) -> Ty {
+        let ty = self.infer_expr_inner(branch, &Expectation::has_type(result_ty.clone()));
+        let ty = self.resolve_ty_as_possible(ty);
+        if ty != Ty::Never {
+            self.coerce(&ty, result_ty);
+        }
+        ty
+    }
+
+    /// Infers the body of a loop whose `break` values have to be coercible to
+    /// `break_ty`. The loop body may never run (or be left by `continue`
+    /// early), so it doesn't make the code after the loop diverge.
+    fn infer_loop_body(
+        &mut self,
+        loop_expr: ExprId,
+        body: ExprId,
+        break_ty: Ty,
+    ) -> BreakableContext {
+        let diverges = self.diverges;
+        self.breakables.push(BreakableContext {
+            expr: loop_expr,
+            may_break: false,
+            break_ty,
+        });
+        self.infer_expr(body, &Expectation::has_type(Ty::unit()));
+        self.diverges = diverges;
+        self.breakables
+            .pop()
+            .expect("breakable context pushed above")
+    }
+
+    /// Infers a labeled block, which `break` can leave with a value for the
+    /// whole block.
+    fn infer_labeled_block(
+        &mut self,
+        block: ExprId,
+        statements: &[Statement],
+        tail: Option<ExprId>,
+        expected: &Expectation,
+    ) -> Ty {
+        let break_ty = self.expected_or_new_var(expected);
+        let diverges = self.diverges;
+        self.breakables.push(BreakableContext {
+            expr: block,
+            may_break: false,
+            break_ty: break_ty.clone(),
+        });
+        let ty = self.infer_block(statements, tail, &Expectation::has_type(break_ty));
+        let ctx = self
+            .breakables
+            .pop()
+            .expect("breakable context pushed above");
+        if ctx.may_break {
+            // the code after the block is reachable through the `break`s
+            self.coerce(&ty, &ctx.break_ty);
+            self.diverges = diverges;
+            ctx.break_ty
+        } else {
+            ty
+        }
+    }
+
     fn infer_block(
         &mut self,
         statements: &[Statement],
@@ -1561,14 +2848,11 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                         .map(|tr| self.make_ty(tr))
                         .unwrap_or(Ty::Unknown);
                     let decl_ty = self.insert_type_vars(decl_ty);
-                    let ty = if let Some(expr) = initializer {
-                        let expr_ty = self.infer_expr(*expr, &Expectation::has_type(decl_ty));
-                        expr_ty
-                    } else {
-                        decl_ty
-                    };
+                    if let Some(expr) = initializer {
+                        self.infer_expr_coerce(*expr, &Expectation::has_type(decl_ty.clone()));
+                    }
 
-                    self.infer_pat(*pat, &ty);
+                    self.infer_pat(*pat, &decl_ty);
                 }
                 Statement::Expr(expr) => {
                     self.infer_expr(*expr, &Expectation::none());
@@ -1576,7 +2860,20 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             }
         }
         let ty = if let Some(expr) = tail {
-            self.infer_expr(expr, expected)
+            // the tail is a coercion site, but like for the branches of an
+            // `if`, a diverging tail doesn't decide the type of the block
+            let ty = self.infer_expr_inner(expr, expected);
+            let ty = self.resolve_ty_as_possible(ty);
+            if ty != Ty::Never {
+                let first_new_diagnostic = self.diagnostics.len();
+                if !self.coerce(&ty, &expected.ty) {
+                    self.report_mismatch(expr, &expected.ty, &ty, first_new_diagnostic);
+                }
+            }
+            ty
+        } else if self.diverges {
+            // a block ending in a diverging statement can have any type
+            Ty::Never
         } else {
             Ty::unit()
         };
@@ -1596,24 +2893,31 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         };
     }
 
+    fn collect_const_type(&mut self, type_ref: &TypeRef) {
+        self.return_ty = self.make_ty(type_ref);
+    }
+
     fn infer_body(&mut self) {
-        self.infer_expr(
+        self.infer_expr_coerce(
             self.body.body_expr(),
             &Expectation::has_type(self.return_ty.clone()),
         );
     }
 }
 
-pub fn infer(db: &impl HirDatabase, func: Function) -> Arc<InferenceResult> {
+pub fn infer(db: &impl HirDatabase, def: DefWithBody) -> Arc<InferenceResult> {
     db.check_canceled();
-    let body = func.body(db);
-    let scopes = db.fn_scopes(func);
-    let module = func.module(db);
-    let impl_block = func.impl_block(db);
-    let mut ctx = InferenceContext::new(db, body, scopes, module, impl_block);
-
-    let signature = func.signature(db);
-    ctx.collect_fn_signature(&signature);
+    let mut ctx = InferenceContext::new(db, def);
+
+    match def {
+        DefWithBody::Function(func) => ctx.collect_fn_signature(&func.signature(db)),
+        DefWithBody::Const(c) => ctx.collect_const_type(&c.type_ref(db)),
+        DefWithBody::Static(s) => ctx.collect_const_type(&s.type_ref(db)),
+        // without a `#[repr]`, discriminants are `isize`
+        DefWithBody::EnumVariant(_) => {
+            ctx.return_ty = Ty::Int(primitive::UncertainIntTy::Signed(primitive::IntTy::Isize))
+        }
+    }
 
     ctx.infer_body();
 
@@ -0,0 +1,635 @@
+//! Checking of match expressions: whether the arms cover all values of the
+//! matched type, and whether every arm matches some value the arms before it
+//! don't. This is the usefulness algorithm from "Warnings for pattern
+//! matching" by Luc Maranget, which rustc uses as well.
+//!
+//! The patterns are lowered to a simpler form first, where each pattern is a
+//! wildcard or a constructor (like an enum variant, `true` or a range of
+//! integers) applied to the patterns of its fields. Matches with patterns
+//! which can't be lowered (like slice patterns, or consts whose values are
+//! unknown) or whose type isn't completely known are not checked at all, so
+//! that only errors which are certain are reported.
+use ra_arena::map::ArenaMap;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    EnumVariant, Module, StructField,
+    adt::{AdtDef, VariantDef},
+    const_eval::{self, ConstValue},
+    db::HirDatabase,
+    expr::{Body, Expr, ExprId, Literal, Pat, PatId},
+    ty::primitive::UncertainIntTy,
+};
+use super::{InferenceDiagnostic, PatResolution, Substs, Ty};
+
+/// A set of values matched by a pattern, not looking at the fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Constructor {
+    /// The only constructor of tuples, structs and references.
+    Single,
+    Variant(EnumVariant),
+    Bool(bool),
+    /// The inclusive range of integers or `char`s between the two values.
+    /// Integers are encoded as unsigned numbers which keep the order of
+    /// signed integers.
+    IntRange(u128, u128),
+    /// A value of a type whose values aren't enumerated, like a string
+    /// literal. Other patterns are never covered by it.
+    Opaque,
+}
+
+impl Constructor {
+    /// Whether all values of `other` are values of `self`.
+    fn covers(&self, other: &Constructor) -> bool {
+        match (self, other) {
+            (Constructor::IntRange(lo, hi), Constructor::IntRange(other_lo, other_hi)) => {
+                lo <= other_lo && other_hi <= hi
+            }
+            (Constructor::Opaque, _) | (_, Constructor::Opaque) => false,
+            _ => self == other,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum CheckPat {
+    Wild,
+    Ctor(Constructor, Vec<CheckPat>),
+}
+
+/// Rows of patterns for the values of a row of types.
+type Matrix = Vec<Vec<CheckPat>>;
+
+pub(super) struct MatchChecker<'a, D: HirDatabase> {
+    db: &'a D,
+    module: Module,
+    body: &'a Body,
+    pat_resolutions: &'a FxHashMap<PatId, PatResolution>,
+}
+
+impl<'a, D: HirDatabase> MatchChecker<'a, D> {
+    pub(super) fn new(
+        db: &'a D,
+        module: Module,
+        body: &'a Body,
+        pat_resolutions: &'a FxHashMap<PatId, PatResolution>,
+    ) -> Self {
+        MatchChecker {
+            db,
+            module,
+            body,
+            pat_resolutions,
+        }
+    }
+
+    /// Checks the arms of `match_expr`, reporting unreachable patterns and
+    /// the values no arm matches.
+    pub(super) fn check_match(
+        &self,
+        match_expr: ExprId,
+        expr_types: &ArenaMap<ExprId, Ty>,
+        diagnostics: &mut Vec<InferenceDiagnostic>,
+    ) {
+        let (scrutinee, arms) = match &self.body[match_expr] {
+            Expr::Match { expr, arms } => (*expr, arms),
+            _ => return,
+        };
+        let ty = match expr_types.get(scrutinee) {
+            Some(ty) => ty,
+            None => return,
+        };
+        // matches on uninhabited types don't need any arms
+        if !ty.is_fully_known(true) || *ty == Ty::Never || self.is_empty_enum(ty) {
+            return;
+        }
+
+        let mut rows = Vec::new();
+        for arm in arms {
+            for &pat in &arm.pats {
+                let check_pat = match self.lower_pat(pat, ty) {
+                    Some(it) => it,
+                    None => return,
+                };
+                rows.push((pat, check_pat, arm.guard.is_some()));
+            }
+        }
+
+        let tys = [ty.clone()];
+        let mut matrix = Matrix::new();
+        for (pat, check_pat, has_guard) in rows {
+            let row = vec![check_pat];
+            if self.witnesses(&matrix, &row, &tys).is_empty() {
+                diagnostics.push(InferenceDiagnostic::UnreachablePattern { pat });
+            }
+            // the guard may be false, so the arm doesn't cover anything
+            if !has_guard {
+                matrix.push(row);
+            }
+        }
+
+        let witnesses = self.witnesses(&matrix, &[CheckPat::Wild], &tys);
+        if !witnesses.is_empty() {
+            let missing = witnesses
+                .iter()
+                .map(|witness| self.display(&witness[0], ty))
+                .collect();
+            diagnostics.push(InferenceDiagnostic::MissingMatchArms {
+                expr: match_expr,
+                missing,
+            });
+        }
+    }
+
+    /// Rows of patterns for the values of `tys` which `row` matches, but no
+    /// row of `matrix` does. If there are none, `row` is useless after the
+    /// rows of `matrix`.
+    fn witnesses(&self, matrix: &[Vec<CheckPat>], row: &[CheckPat], tys: &[Ty]) -> Matrix {
+        let (head, ty) = match (row.first(), tys.first()) {
+            (Some(head), Some(ty)) => (head, ty),
+            _ if matrix.is_empty() => return vec![Vec::new()],
+            _ => return Vec::new(),
+        };
+        if let CheckPat::Ctor(ctor, _) = head {
+            return self
+                .split(ctor, matrix)
+                .iter()
+                .flat_map(|ctor| self.specialized_witnesses(matrix, row, tys, ctor))
+                .collect();
+        }
+
+        let used_ctors: Vec<&Constructor> = matrix
+            .iter()
+            .filter_map(|row| match &row[0] {
+                CheckPat::Ctor(ctor, _) => Some(ctor),
+                CheckPat::Wild => None,
+            })
+            .collect();
+        let missing_ctors = match self.constructors(ty) {
+            Some(ctors) => {
+                let all_ctors: Vec<Constructor> = ctors
+                    .iter()
+                    .flat_map(|ctor| self.split(ctor, matrix))
+                    .collect();
+                let missing_ctors: Vec<Constructor> = all_ctors
+                    .iter()
+                    .filter(|ctor| !used_ctors.iter().any(|used| used.covers(ctor)))
+                    .cloned()
+                    .collect();
+                if missing_ctors.is_empty() {
+                    return all_ctors
+                        .iter()
+                        .flat_map(|ctor| self.specialized_witnesses(matrix, row, tys, ctor))
+                        .collect();
+                }
+                Some(missing_ctors)
+            }
+            None => None,
+        };
+
+        // Some values are only matched by the rows starting with a wildcard,
+        // so only these rows matter for the remaining columns.
+        let default_matrix: Matrix = matrix
+            .iter()
+            .filter(|row| match row[0] {
+                CheckPat::Wild => true,
+                CheckPat::Ctor(..) => false,
+            })
+            .map(|row| row[1..].to_vec())
+            .collect();
+        let witnesses = self.witnesses(&default_matrix, &row[1..], &tys[1..]);
+        let heads = match missing_ctors {
+            Some(missing_ctors) if !used_ctors.is_empty() => missing_ctors
+                .into_iter()
+                .map(|ctor| {
+                    let arity = self.field_tys(&ctor, ty).len();
+                    CheckPat::Ctor(ctor, vec![CheckPat::Wild; arity])
+                })
+                .collect(),
+            _ => vec![CheckPat::Wild],
+        };
+        let mut res = Vec::new();
+        for witness in witnesses {
+            for head in &heads {
+                let mut witness_row = vec![head.clone()];
+                witness_row.extend(witness.iter().cloned());
+                res.push(witness_row);
+            }
+        }
+        res
+    }
+
+    /// The witnesses among the values constructed by `ctor`, which the head
+    /// of `row` covers.
+    fn specialized_witnesses(
+        &self,
+        matrix: &[Vec<CheckPat>],
+        row: &[CheckPat],
+        tys: &[Ty],
+        ctor: &Constructor,
+    ) -> Matrix {
+        let mut field_tys = self.field_tys(ctor, &tys[0]);
+        let arity = field_tys.len();
+        let specialize = |row: &[CheckPat]| {
+            let mut res = match &row[0] {
+                CheckPat::Wild => vec![CheckPat::Wild; arity],
+                CheckPat::Ctor(row_ctor, fields) if row_ctor.covers(ctor) => fields.clone(),
+                CheckPat::Ctor(..) => return None,
+            };
+            res.extend(row[1..].iter().cloned());
+            Some(res)
+        };
+        let matrix: Matrix = matrix.iter().filter_map(|row| specialize(row)).collect();
+        // the head of `row` covers `ctor` even if it is opaque
+        let mut row_fields = match &row[0] {
+            CheckPat::Wild => vec![CheckPat::Wild; arity],
+            CheckPat::Ctor(_, fields) => fields.clone(),
+        };
+        row_fields.extend(row[1..].iter().cloned());
+        field_tys.extend(tys[1..].iter().cloned());
+
+        self.witnesses(&matrix, &row_fields, &field_tys)
+            .into_iter()
+            .map(|witness| {
+                let mut res = vec![CheckPat::Ctor(ctor.clone(), witness[..arity].to_vec())];
+                res.extend(witness[arity..].iter().cloned());
+                res
+            })
+            .collect()
+    }
+
+    /// Splits an integer range into the ranges between the bounds of the
+    /// ranges at the head of `matrix`, so that each of them is either covered
+    /// completely or not at all by each row.
+    fn split(&self, ctor: &Constructor, matrix: &[Vec<CheckPat>]) -> Vec<Constructor> {
+        let (lo, hi) = match ctor {
+            Constructor::IntRange(lo, hi) => (*lo, *hi),
+            _ => return vec![ctor.clone()],
+        };
+        let mut starts = vec![lo];
+        for row in matrix {
+            if let CheckPat::Ctor(Constructor::IntRange(row_lo, row_hi), _) = row[0] {
+                if lo < row_lo && row_lo <= hi {
+                    starts.push(row_lo);
+                }
+                if lo <= row_hi && row_hi < hi {
+                    starts.push(row_hi + 1);
+                }
+            }
+        }
+        starts.sort();
+        starts.dedup();
+        let ends = starts.iter().skip(1).map(|start| start - 1).chain(Some(hi));
+        starts
+            .iter()
+            .zip(ends)
+            .map(|(&start, end)| Constructor::IntRange(start, end))
+            .collect()
+    }
+
+    /// All constructors of `ty`, if its values can be enumerated.
+    fn constructors(&self, ty: &Ty) -> Option<Vec<Constructor>> {
+        let ctors = match ty {
+            Ty::Bool => vec![Constructor::Bool(false), Constructor::Bool(true)],
+            Ty::Int(int_ty) => vec![Constructor::IntRange(0, int_max(*int_ty)?)],
+            // all Unicode scalar values, which exclude the surrogates
+            Ty::Char => vec![
+                Constructor::IntRange(0, 0xd7ff),
+                Constructor::IntRange(0xe000, 0x0010_ffff),
+            ],
+            // string literals are opaque
+            Ty::Ref(inner, _) if **inner == Ty::Str => return None,
+            Ty::Tuple(_) | Ty::Ref(..) => vec![Constructor::Single],
+            Ty::Adt {
+                def_id: AdtDef::Struct(_),
+                ..
+            } => vec![Constructor::Single],
+            // As in rustc, nested empty enums are treated as inhabited, so a
+            // wildcard is needed for them.
+            Ty::Adt {
+                def_id: AdtDef::Enum(e),
+                ..
+            } if !self.is_empty_enum(ty) => e
+                .variants(self.db)
+                .into_iter()
+                .map(Constructor::Variant)
+                .collect(),
+            _ => return None,
+        };
+        Some(ctors)
+    }
+
+    fn is_empty_enum(&self, ty: &Ty) -> bool {
+        match ty {
+            Ty::Adt {
+                def_id: AdtDef::Enum(e),
+                ..
+            } => e.variants(self.db).is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The types of the fields of the values of `ty` constructed by `ctor`.
+    fn field_tys(&self, ctor: &Constructor, ty: &Ty) -> Vec<Ty> {
+        match (ctor, ty) {
+            (Constructor::Single, Ty::Tuple(tys)) => tys.to_vec(),
+            (Constructor::Single, Ty::Ref(inner, _)) => vec![Ty::clone(inner)],
+            (
+                Constructor::Single,
+                Ty::Adt {
+                    def_id: AdtDef::Struct(s),
+                    substs,
+                    ..
+                },
+            ) => self.variant_field_tys((*s).into(), substs),
+            (Constructor::Variant(v), Ty::Adt { substs, .. }) => {
+                self.variant_field_tys((*v).into(), substs)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn variant_field_tys(&self, variant: VariantDef, substs: &Substs) -> Vec<Ty> {
+        variant_fields(self.db, variant)
+            .into_iter()
+            .map(|field| field.ty(self.db).subst(substs))
+            .collect()
+    }
+
+    /// Lowers the pattern `pat` for values of `ty`, if it can be checked.
+    fn lower_pat(&self, pat: PatId, ty: &Ty) -> Option<CheckPat> {
+        let resolution = self.pat_resolutions.get(&pat).cloned();
+        let is_binding = match (&self.body[pat], resolution) {
+            (Pat::Bind { .. }, None) => true,
+            _ => false,
+        };
+        match (&self.body[pat], ty) {
+            (Pat::Wild, _) => return Some(CheckPat::Wild),
+            (Pat::Bind { subpat, .. }, _) if is_binding => {
+                return match subpat {
+                    Some(subpat) => self.lower_pat(*subpat, ty),
+                    None => Some(CheckPat::Wild),
+                };
+            }
+            // With the default binding modes, patterns which aren't reference
+            // patterns match the values behind references.
+            (Pat::Ref { .. }, _) => {}
+            (_, Ty::Ref(inner, _)) if **inner != Ty::Str => {
+                let inner = self.lower_pat(pat, inner)?;
+                return Some(CheckPat::Ctor(Constructor::Single, vec![inner]));
+            }
+            _ => {}
+        }
+
+        let (ctor, fields) = match &self.body[pat] {
+            Pat::Bind { .. } | Pat::Path(_) => (self.resolution_ctor(resolution?, ty)?, Vec::new()),
+            Pat::Ref { pat, .. } => match ty {
+                Ty::Ref(inner, _) => (Constructor::Single, vec![self.lower_pat(*pat, inner)?]),
+                _ => return None,
+            },
+            Pat::Tuple(args) => match ty {
+                Ty::Tuple(tys) if tys.len() == args.len() => {
+                    let fields = args
+                        .iter()
+                        .zip(tys.iter())
+                        .map(|(&arg, ty)| self.lower_pat(arg, ty))
+                        .collect::<Option<_>>()?;
+                    (Constructor::Single, fields)
+                }
+                _ => return None,
+            },
+            Pat::TupleStruct { args, .. } => {
+                let ctor = self.resolution_ctor(resolution?, ty)?;
+                let field_tys = self.field_tys(&ctor, ty);
+                // `..` is not lowered, so the positions of the fields are
+                // only known if all of them are there
+                if field_tys.len() != args.len() {
+                    return None;
+                }
+                let fields = args
+                    .iter()
+                    .zip(field_tys.iter())
+                    .map(|(&arg, ty)| self.lower_pat(arg, ty))
+                    .collect::<Option<_>>()?;
+                (ctor, fields)
+            }
+            Pat::Struct { args, .. } => {
+                let variant = match resolution? {
+                    PatResolution::Variant(it) => it,
+                    PatResolution::Const(_) => return None,
+                };
+                let ctor = self.resolution_ctor(resolution?, ty)?;
+                let field_tys = self.field_tys(&ctor, ty);
+                let field_names: Vec<_> = variant_fields(self.db, variant)
+                    .into_iter()
+                    .map(|field| field.name(self.db))
+                    .collect();
+                if args.iter().any(|arg| !field_names.contains(&arg.name)) {
+                    return None;
+                }
+                let fields = field_names
+                    .iter()
+                    .zip(field_tys.iter())
+                    .map(
+                        |(name, ty)| match args.iter().find(|arg| arg.name == *name) {
+                            Some(arg) => self.lower_pat(arg.pat, ty),
+                            None => Some(CheckPat::Wild),
+                        },
+                    )
+                    .collect::<Option<_>>()?;
+                (ctor, fields)
+            }
+            Pat::Lit(expr) => {
+                let ctor = match self.body[*expr] {
+                    Expr::Literal(Literal::String(_)) | Expr::Literal(Literal::Float(..)) => {
+                        Constructor::Opaque
+                    }
+                    _ => self.eval_ctor(*expr, ty)?,
+                };
+                (ctor, Vec::new())
+            }
+            Pat::Range { start, end } => {
+                match (self.eval_ctor(*start, ty)?, self.eval_ctor(*end, ty)?) {
+                    (Constructor::IntRange(lo, _), Constructor::IntRange(hi, _)) if lo <= hi => {
+                        (Constructor::IntRange(lo, hi), Vec::new())
+                    }
+                    _ => return None,
+                }
+            }
+            Pat::Wild | Pat::Missing | Pat::Slice { .. } => return None,
+        };
+
+        // the pattern must fit the type, and opaque values can only be
+        // checked for types whose values are not enumerated
+        let is_opaque = ctor == Constructor::Opaque;
+        if self.constructors(ty).is_some() == is_opaque
+            || self.field_tys(&ctor, ty).len() != fields.len()
+        {
+            return None;
+        }
+        Some(CheckPat::Ctor(ctor, fields))
+    }
+
+    /// The constructor of the values of `ty` matched by a pattern which
+    /// refers to `resolution`.
+    fn resolution_ctor(&self, resolution: PatResolution, ty: &Ty) -> Option<Constructor> {
+        match (resolution, ty) {
+            (
+                PatResolution::Variant(VariantDef::Struct(s)),
+                Ty::Adt {
+                    def_id: AdtDef::Struct(ty_struct),
+                    ..
+                },
+            ) if s == *ty_struct => Some(Constructor::Single),
+            (
+                PatResolution::Variant(VariantDef::EnumVariant(v)),
+                Ty::Adt {
+                    def_id: AdtDef::Enum(ty_enum),
+                    ..
+                },
+            ) if v.parent_enum(self.db) == *ty_enum => Some(Constructor::Variant(v)),
+            (PatResolution::Const(c), _) => Some(
+                self.db
+                    .const_value(c)
+                    .and_then(|value| value_ctor(value, ty))
+                    .unwrap_or(Constructor::Opaque),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The constructor of the value of the constant expression `expr`, if it
+    /// is a value of `ty`.
+    fn eval_ctor(&self, expr: ExprId, ty: &Ty) -> Option<Constructor> {
+        let int_ty = match ty {
+            Ty::Int(int_ty) => *int_ty,
+            _ => UncertainIntTy::Unknown,
+        };
+        let value = const_eval::eval_pat_bound(self.db, self.module, self.body, expr, int_ty)?;
+        value_ctor(value, ty)
+    }
+
+    /// Displays the witness `pat` for a value of `ty` as a pattern.
+    fn display(&self, pat: &CheckPat, ty: &Ty) -> String {
+        let (ctor, fields) = match pat {
+            CheckPat::Wild => return "_".to_string(),
+            CheckPat::Ctor(ctor, fields) => (ctor, fields),
+        };
+        let fields: Vec<String> = fields
+            .iter()
+            .zip(self.field_tys(ctor, ty).iter())
+            .map(|(field, ty)| self.display(field, ty))
+            .collect();
+        match (ctor, ty) {
+            (Constructor::Single, Ty::Tuple(_)) if fields.len() == 1 => {
+                format!("({},)", fields[0])
+            }
+            (Constructor::Single, Ty::Tuple(_)) => format!("({})", fields.join(", ")),
+            (Constructor::Single, Ty::Ref(..)) => format!("&{}", fields[0]),
+            (
+                Constructor::Single,
+                Ty::Adt {
+                    def_id: AdtDef::Struct(s),
+                    name,
+                    ..
+                },
+            ) => self.display_variant(name.to_string(), (*s).into(), fields),
+            (Constructor::Variant(v), Ty::Adt { name, .. }) => {
+                let variant_name = v.name(self.db).map_or("_".to_string(), |it| it.to_string());
+                let path = format!("{}::{}", name, variant_name);
+                self.display_variant(path, (*v).into(), fields)
+            }
+            (Constructor::Bool(it), _) => it.to_string(),
+            (Constructor::IntRange(lo, hi), _) if lo == hi => display_int(*lo, ty),
+            (Constructor::IntRange(lo, hi), _) => {
+                format!("{}..={}", display_int(*lo, ty), display_int(*hi, ty))
+            }
+            _ => "_".to_string(),
+        }
+    }
+
+    fn display_variant(&self, path: String, variant: VariantDef, fields: Vec<String>) -> String {
+        let variant_data = variant.variant_data(self.db);
+        if variant_data.fields().is_none() {
+            path
+        } else if !variant_data.is_struct() {
+            format!("{}({})", path, fields.join(", "))
+        } else {
+            let mut field_pats: Vec<String> = variant_fields(self.db, variant)
+                .into_iter()
+                .zip(fields.iter())
+                .filter(|(_, pat)| *pat != "_")
+                .map(|(field, pat)| format!("{}: {}", field.name(self.db), pat))
+                .collect();
+            if field_pats.len() < fields.len() {
+                field_pats.push("..".to_string());
+            }
+            format!("{} {{ {} }}", path, field_pats.join(", "))
+        }
+    }
+}
+
+fn variant_fields(db: &impl HirDatabase, variant: VariantDef) -> Vec<StructField> {
+    match variant {
+        VariantDef::Struct(it) => it.fields(db),
+        VariantDef::EnumVariant(it) => it.fields(db),
+    }
+}
+
+/// The constructor of `value`, if it is a value of `ty`.
+fn value_ctor(value: ConstValue, ty: &Ty) -> Option<Constructor> {
+    let ctor = match (value, ty) {
+        (ConstValue::Bool(it), Ty::Bool) => Constructor::Bool(it),
+        (ConstValue::Int(it), Ty::Int(int_ty)) => {
+            let encoded = encode_int(it, *int_ty)?;
+            Constructor::IntRange(encoded, encoded)
+        }
+        (ConstValue::Char(it), Ty::Char) => Constructor::IntRange(it as u128, it as u128),
+        _ => return None,
+    };
+    Some(ctor)
+}
+
+/// Encodes `value` as an unsigned number, shifting signed integers such that
+/// the minimum value of the type becomes zero.
+fn encode_int(value: i128, ty: UncertainIntTy) -> Option<u128> {
+    let bits = const_eval::bits(ty)?;
+    match ty {
+        UncertainIntTy::Signed(_) => Some((value as u128).wrapping_add(1 << (bits - 1))),
+        _ => Some(value as u128),
+    }
+}
+
+/// The encoded maximum value of the integer type `ty`.
+fn int_max(ty: UncertainIntTy) -> Option<u128> {
+    let bits = const_eval::bits(ty)?;
+    if bits == 128 {
+        Some(u128::max_value())
+    } else {
+        Some((1 << bits) - 1)
+    }
+}
+
+fn display_int(encoded: u128, ty: &Ty) -> String {
+    let int_ty = match ty {
+        Ty::Char => {
+            let c = std::char::from_u32(encoded as u32).unwrap_or_default();
+            return format!("{:?}", c);
+        }
+        Ty::Int(int_ty) => *int_ty,
+        _ => return encoded.to_string(),
+    };
+    let signed = match int_ty {
+        UncertainIntTy::Signed(_) => true,
+        _ => false,
+    };
+    if Some(encoded) == int_max(int_ty) {
+        format!("{}::MAX", int_ty.ty_to_string())
+    } else if signed && encoded == 0 {
+        format!("{}::MIN", int_ty.ty_to_string())
+    } else if signed {
+        let bits = const_eval::bits(int_ty).unwrap_or(128);
+        (encoded.wrapping_sub(1 << (bits - 1)) as i128).to_string()
+    } else {
+        encoded.to_string()
+    }
+}
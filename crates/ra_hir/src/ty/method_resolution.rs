@@ -4,13 +4,13 @@
 //! and the corresponding code mostly in librustc_typeck/check/method/probe.rs.
 use std::sync::Arc;
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    HirDatabase, module_tree::ModuleId, Module, Crate, Name, Function,
-    impl_block::{ImplId, ImplBlock, ImplItem},
-    generics::GenericParams,
-    ty::{AdtDef, Ty}
+    HirDatabase, Module, ModuleDef, Crate, Name, Function, Trait, TraitItem,
+    type_ref::Mutability,
+    impl_block::{ImplId, ImplBlock, ImplItem, ModuleImplBlocks},
+    ty::{AdtDef, Ty, traits::{self, TraitRef, Environment, Solution}},
 };
 
 /// This is used as a key for indexing impls.
@@ -34,62 +34,87 @@ impl TyFingerprint {
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct CrateImplBlocks {
-    /// To make sense of the ModuleIds, we need the source root.
-    krate: Crate,
-    impls: FxHashMap<TyFingerprint, Vec<(ModuleId, ImplId)>>,
+    impls: FxHashMap<TyFingerprint, Vec<(Module, ImplId)>>,
+    /// All trait impls, including blanket impls like `impl<T> Foo for T`, by
+    /// the trait they implement.
+    impls_by_trait: FxHashMap<Trait, Vec<(Module, ImplId)>>,
 }
 
 impl CrateImplBlocks {
+    /// Looks up the inherent impls of `ty`.
     pub fn lookup_impl_blocks<'a>(
         &'a self,
         db: &'a impl HirDatabase,
         ty: &Ty,
     ) -> impl Iterator<Item = ImplBlock> + 'a {
         let fingerprint = TyFingerprint::for_impl(ty);
-        fingerprint
-            .and_then(|f| self.impls.get(&f))
-            .into_iter()
+        self.impl_blocks(db, fingerprint.and_then(|f| self.impls.get(&f)))
+    }
+
+    /// Looks up all impls of `tr`.
+    pub fn lookup_impl_blocks_for_trait<'a>(
+        &'a self,
+        db: &'a impl HirDatabase,
+        tr: Trait,
+    ) -> impl Iterator<Item = ImplBlock> + 'a {
+        self.impl_blocks(db, self.impls_by_trait.get(&tr))
+    }
+
+    fn impl_blocks<'a>(
+        &'a self,
+        db: &'a impl HirDatabase,
+        ids: Option<&'a Vec<(Module, ImplId)>>,
+    ) -> impl Iterator<Item = ImplBlock> + 'a {
+        ids.into_iter()
             .flat_map(|i| i.iter())
-            .map(move |(module_id, impl_id)| {
-                let module = Module {
-                    krate: self.krate.crate_id,
-                    module_id: *module_id,
-                };
-                let module_impl_blocks = db.impls_in_module(module);
+            .map(move |(module, impl_id)| {
+                let module_impl_blocks = db.impls_in_module(*module);
                 ImplBlock::from_id(module_impl_blocks, *impl_id)
             })
     }
 
     fn collect_recursive(&mut self, db: &impl HirDatabase, module: &Module) {
+        let module_impl_blocks = self.collect(db, module);
+        for block in module_impl_blocks.blocks.iter() {
+            self.collect(db, block);
+        }
+
+        for child in module.children(db) {
+            self.collect_recursive(db, &child);
+        }
+    }
+
+    /// Collects the impls of a module, or of the anonymous module of a block.
+    fn collect(&mut self, db: &impl HirDatabase, module: &Module) -> Arc<ModuleImplBlocks> {
         let module_impl_blocks = db.impls_in_module(module.clone());
 
         for (impl_id, impl_data) in module_impl_blocks.impls.iter() {
             let impl_block = ImplBlock::from_id(Arc::clone(&module_impl_blocks), impl_id);
 
-            if let Some(_target_trait) = impl_data.target_trait() {
-                // ignore for now
-            } else {
-                // TODO provide generics of impl
-                let generics = GenericParams::default();
-                let target_ty = Ty::from_hir(
-                    db,
-                    &module,
-                    Some(&impl_block),
-                    &generics,
-                    impl_data.target_type(),
-                );
-                if let Some(target_ty_fp) = TyFingerprint::for_impl(&target_ty) {
-                    self.impls
-                        .entry(target_ty_fp)
-                        .or_insert_with(Vec::new)
-                        .push((module.module_id, impl_id));
+            if impl_data.target_trait().is_some() {
+                if let Some(tr) = impl_block.target_trait_ref(db) {
+                    self.impls_by_trait
+                        .entry(tr)
+                        .or_default()
+                        .push((*module, impl_id));
                 }
+                continue;
+            }
+            let target_ty = Ty::from_hir(
+                db,
+                module,
+                Some(&impl_block),
+                impl_data.generic_params(),
+                impl_data.target_type(),
+            );
+            if let Some(target_ty_fp) = TyFingerprint::for_impl(&target_ty) {
+                self.impls
+                    .entry(target_ty_fp)
+                    .or_insert_with(Vec::new)
+                    .push((*module, impl_id));
             }
         }
-
-        for child in module.children(db) {
-            self.collect_recursive(db, &child);
-        }
+        module_impl_blocks
     }
 
     pub(crate) fn impls_in_crate_query(
@@ -97,8 +122,8 @@ impl CrateImplBlocks {
         krate: Crate,
     ) -> Arc<CrateImplBlocks> {
         let mut crate_impl_blocks = CrateImplBlocks {
-            krate: krate.clone(),
             impls: FxHashMap::default(),
+            impls_by_trait: FxHashMap::default(),
         };
         if let Some(module) = krate.root_module(db) {
             crate_impl_blocks.collect_recursive(db, &module);
@@ -107,7 +132,7 @@ impl CrateImplBlocks {
     }
 }
 
-fn def_crate(db: &impl HirDatabase, ty: &Ty) -> Option<Crate> {
+pub(super) fn def_crate(db: &impl HirDatabase, ty: &Ty) -> Option<Crate> {
     match ty {
         Ty::Adt { def_id, .. } => def_id.krate(db),
         _ => None,
@@ -118,11 +143,20 @@ impl Ty {
     // TODO: cache this as a query?
     // - if so, what signature? (TyFingerprint, Name)?
     // - or maybe cache all names and def_ids of methods per fingerprint?
-    pub fn lookup_method(self, db: &impl HirDatabase, name: &Name) -> Option<Function> {
-        self.iterate_methods(db, |f| {
+    /// Looks up the method `name` for calls in `module`, where the where
+    /// clauses in `env` hold. Returns the method together with the type it was
+    /// found for, i.e. the receiver type after autoderef and autoref.
+    pub fn lookup_method(
+        self,
+        db: &impl HirDatabase,
+        module: Module,
+        env: &Arc<Environment>,
+        name: &Name,
+    ) -> Option<(Ty, Function)> {
+        self.iterate_methods(db, module, env, |ty, f| {
             let sig = f.signature(db);
             if sig.name() == name && sig.has_self_param() {
-                Some(f)
+                Some((ty.clone(), f))
             } else {
                 None
             }
@@ -134,37 +168,167 @@ impl Ty {
     pub fn iterate_methods<T>(
         self,
         db: &impl HirDatabase,
-        mut callback: impl FnMut(Function) -> Option<T>,
+        module: Module,
+        env: &Arc<Environment>,
+        mut callback: impl FnMut(&Ty, Function) -> Option<T>,
     ) -> Option<T> {
         // For method calls, rust first does any number of autoderef, and then one
-        // autoref (i.e. when the method takes &self or &mut self). We just ignore
-        // the autoref currently -- when we find a method matching the given name,
-        // we assume it fits.
+        // autoref (i.e. when the method takes &self or &mut self). For inherent
+        // methods, we just ignore the autoref -- when we find a method matching
+        // the given name, we assume it fits. Trait impls can be for reference
+        // types though, so for traits we try `T`, `&T` and `&mut T`.
 
         // Also note that when we've got a receiver like &S, even if the method we
         // find in the end takes &self, we still do the autoderef step (just as
         // rustc does an autoderef and then autoref again).
 
+        let traits_in_scope = traits_in_scope(db, module);
         for derefed_ty in self.autoderef(db) {
-            let krate = match def_crate(db, &derefed_ty) {
-                Some(krate) => krate,
-                None => continue,
-            };
-            let impls = db.impls_in_crate(krate);
-
-            for impl_block in impls.lookup_impl_blocks(db, &derefed_ty) {
-                for item in impl_block.items() {
-                    match item {
-                        ImplItem::Method(f) => {
-                            if let Some(result) = callback(f.clone()) {
-                                return Some(result);
-                            }
-                        }
-                        _ => {}
-                    }
+            if let Some(result) = iterate_inherent_methods(db, &derefed_ty, &mut callback) {
+                return Some(result);
+            }
+            // Inherent methods take precedence over trait methods.
+            let autorefs = [
+                derefed_ty.clone(),
+                Ty::Ref(Arc::new(derefed_ty.clone()), Mutability::Shared),
+                Ty::Ref(Arc::new(derefed_ty), Mutability::Mut),
+            ];
+            for ty in autorefs.iter() {
+                if let Some(result) =
+                    iterate_trait_methods(db, env, ty, &traits_in_scope, &mut callback)
+                {
+                    return Some(result);
                 }
             }
         }
         None
     }
 }
+
+fn iterate_inherent_methods<T>(
+    db: &impl HirDatabase,
+    ty: &Ty,
+    callback: &mut impl FnMut(&Ty, Function) -> Option<T>,
+) -> Option<T> {
+    let krate = def_crate(db, ty)?;
+    let impls = db.impls_in_crate(krate);
+    for impl_block in impls.lookup_impl_blocks(db, ty) {
+        for item in impl_block.items() {
+            if let ImplItem::Method(f) = item {
+                if let Some(result) = callback(ty, *f) {
+                    return Some(result);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Iterates the methods of the traits `ty` implements, if the trait is in
+/// scope, is a bound on `ty` in `env`, or is a bound of the `dyn Trait` or
+/// `impl Trait` type `ty`.
+fn iterate_trait_methods<T>(
+    db: &impl HirDatabase,
+    env: &Arc<Environment>,
+    ty: &Ty,
+    traits_in_scope: &[Trait],
+    callback: &mut impl FnMut(&Ty, Function) -> Option<T>,
+) -> Option<T> {
+    let mut traits = traits_in_scope.to_vec();
+    traits.extend(
+        env.predicates
+            .iter()
+            .filter(|pred| pred.self_ty == *ty)
+            .map(|pred| pred.trait_),
+    );
+    traits.extend(ty.trait_bounds().iter().map(|bound| bound.trait_));
+    let mut seen = FxHashSet::default();
+    for tr in traits {
+        if !seen.insert(tr) {
+            continue;
+        }
+        let trait_ref = TraitRef::with_unknown_substs(db, tr, ty.clone());
+        let methods = match db.implements(Arc::clone(env), trait_ref) {
+            Some(Solution::Impl(impl_block, _)) => trait_impl_methods(db, &impl_block),
+            Some(Solution::Assumption) => trait_methods(db, tr),
+            None => continue,
+        };
+        for f in methods {
+            if let Some(result) = callback(ty, f) {
+                return Some(result);
+            }
+        }
+    }
+    None
+}
+
+/// The traits whose methods can be called in `module` without naming the
+/// trait, i.e. the ones defined in or imported into the module, or into the
+/// blocks around the call, and the ones of the standard library prelude.
+fn traits_in_scope(db: &impl HirDatabase, module: Module) -> Vec<Trait> {
+    let mut res = Vec::new();
+    let mut scope = Some(module);
+    while let Some(m) = scope {
+        res.extend(module_traits(db, m));
+        scope = if m.block.is_some() {
+            m.parent(db)
+        } else {
+            None
+        };
+    }
+    // The prelude is glob-imported into every module, as `std::prelude::v1`
+    // or, in `no_std` crates, `core::prelude::v1`.
+    if let Some(ModuleDef::Module(prelude)) = traits::std_item(db, module, &["prelude", "v1"]) {
+        res.extend(module_traits(db, prelude));
+    }
+    res
+}
+
+fn module_traits(db: &impl HirDatabase, module: Module) -> Vec<Trait> {
+    module
+        .scope(db)
+        .entries()
+        .filter_map(|(_name, res)| match res.def.types? {
+            ModuleDef::Trait(tr) => Some(tr),
+            _ => None,
+        })
+        .collect()
+}
+
+fn trait_methods(db: &impl HirDatabase, tr: Trait) -> Vec<Function> {
+    tr.items(db)
+        .into_iter()
+        .filter_map(|item| match item {
+            TraitItem::Function(f) => Some(f),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The methods available through a trait impl: the ones defined in the impl,
+/// and the remaining ones from the trait itself (e.g. provided methods, or all
+/// methods of a derived impl).
+fn trait_impl_methods(db: &impl HirDatabase, impl_block: &ImplBlock) -> Vec<Function> {
+    let mut res = impl_block
+        .items()
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Method(f) => Some(*f),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let tr = match impl_block.target_trait_ref(db) {
+        Some(it) => it,
+        None => return res,
+    };
+    let defined = res
+        .iter()
+        .map(|f| f.signature(db).name().clone())
+        .collect::<Vec<_>>();
+    for f in trait_methods(db, tr) {
+        if !defined.contains(f.signature(db).name()) {
+            res.push(f);
+        }
+    }
+    res
+}
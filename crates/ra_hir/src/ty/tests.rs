@@ -1,8 +1,12 @@
 use std::sync::Arc;
 use std::fmt::Write;
 
-use ra_db::{SyntaxDatabase, salsa::Database};
-use ra_syntax::ast::{self, AstNode};
+use ra_db::{SyntaxDatabase, FilesDatabase, FileId, CrateGraph, salsa::Database};
+use relative_path::RelativePath;
+use ra_syntax::{
+    AstNode,
+    SyntaxKind::{FN_DEF, CONST_DEF, STATIC_DEF, ENUM_VARIANT},
+};
 
 use crate::{
     source_binder,
@@ -413,6 +417,38 @@ fn test() {
     );
 }
 
+#[test]
+fn infer_literal_pattern() {
+    check_inference(
+        "infer_literal_pattern",
+        r#"
+const LIMIT: u8 = 10;
+
+fn test(x: u8, y: i64, c: char, b: bool) {
+    match x {
+        0 => 1,
+        1..=LIMIT => 2,
+        n if n > 100 => 3,
+        _ => 4,
+    };
+    match y {
+        -1 => (),
+        i64::MIN..=-2 => (),
+        _ => (),
+    }
+    match c {
+        'a'..='z' => (),
+        _ => (),
+    }
+    match b {
+        true => (),
+        false => (),
+    }
+}
+"#,
+    );
+}
+
 #[test]
 fn infer_struct_generics() {
     check_inference(
@@ -545,18 +581,953 @@ fn quux() {
     );
 }
 
+#[test]
+fn infer_cfg_disabled_code() {
+    check_inference(
+        "infer_cfg_disabled_code",
+        r#"
+struct S {
+    #[cfg(test)]
+    a: u32,
+    b: i64,
+}
+
+fn test(s: S) {
+    #[cfg(test)]
+    let x = 1u32;
+    #[cfg(not(test))]
+    let x = 1i64;
+    x;
+    s.a;
+    s.b;
+    match x {
+        #[cfg(test)]
+        _ => 1u32,
+        _ => 1i64,
+    };
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_builtin_macros() {
+    check_inference(
+        "infer_builtin_macros",
+        r#"
+fn test(a: u32, b: &str) {
+    let s = format!("{} {}", a, name = b);
+    println!("{}", a + 1);
+    assert_eq!(a, 92u32, "{}", b);
+    assert!(a > 1);
+    let c = concat!("a", 1, "b");
+    let n = line!();
+    s;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_builtin_macros_shadowed_by_macro_rules() {
+    check_inference(
+        "infer_builtin_macros_shadowed_by_macro_rules",
+        r#"
+macro_rules! concat {
+    ($($e:expr),*) => { 0 }
+}
+
+fn test(a: u32) {
+    let c = concat!("a", "b");
+    let before = line!();
+    macro_rules! line {
+        () => { 0 }
+    }
+    let after = line!();
+    let col = column!();
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_macro_hygiene() {
+    check_inference(
+        "infer_macro_hygiene",
+        r#"
+fn test() {
+    let left_val = 1u64;
+    assert_eq!(1u32, 2u32, "{}", left_val);
+    let right_val = "";
+    assert_ne!(left_val, 2u64, "{}", right_val);
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_trait_impl_selection() {
+    check_inference(
+        "infer_trait_impl_selection",
+        r#"
+trait Foo {}
+trait Bar { fn bar(&self) -> u32; }
+trait Baz { fn baz(&self) -> i64; }
+
+struct S<T>;
+struct A;
+struct B;
+
+impl Foo for A {}
+impl<T: Foo> Bar for S<T> { fn bar(&self) -> u32 { 0 } }
+impl<T> Baz for S<T> where T: Foo { fn baz(&self) -> i64 { 0 } }
+
+fn test(a: S<A>, b: S<B>) {
+    a.bar();
+    b.bar();
+    a.baz();
+    b.baz();
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_trait_methods() {
+    check_inference(
+        "infer_trait_methods",
+        r#"
+mod foo {
+    pub trait Foo {
+        fn foo(&self) -> u32;
+        fn default_foo(&self) -> Self { loop {} }
+    }
+    impl Foo for u64 { fn foo(&self) -> u32 { 0 } }
+}
+trait Bar { fn bar(self) -> i8; }
+impl<T> Bar for T { fn bar(self) -> i8 { 0 } }
+trait Baz { fn foo(&self) -> i128; }
+
+struct S;
+impl S { fn foo(&self) -> i64 { 0 } }
+impl Baz for S { fn foo(&self) -> i128 { 0 } }
+
+use foo::Foo;
+
+fn test(x: u64, s: S) {
+    x.foo();
+    x.default_foo();
+    (&x).default_foo();
+    x.bar();
+    s.bar();
+    s.foo();
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_associated_types() {
+    check_inference(
+        "infer_associated_types",
+        r#"
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+trait Named {
+    type Name = u8;
+}
+
+enum Option<T> { Some(T), None }
+struct Counter;
+struct Wrapper<T>;
+struct S;
+
+impl Iterator for Counter {
+    type Item = u32;
+    fn next(&mut self) -> Option<Self::Item> { Option::None }
+}
+impl<T> Iterator for Wrapper<T> {
+    type Item = (T, <Counter as Iterator>::Item);
+    fn next(&mut self) -> Option<(T, u32)> { Option::None }
+}
+impl Named for S {}
+
+fn first(c: Counter) -> <Counter as Iterator>::Item { 0 }
+fn name(s: S) -> <S as Named>::Name { 0 }
+
+fn test(c: Counter, w: Wrapper<i64>, s: S) {
+    let a = first(c);
+    let b: <Wrapper<i64> as Iterator>::Item;
+    let c2 = c.next();
+    let d = name(s);
+    let e: <S as Iterator>::Item;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_for_loop_item() {
+    check_inference(
+        "infer_for_loop_item",
+        r#"
+mod std {
+    pub mod iter {
+        pub trait Iterator {
+            type Item;
+            fn next(&mut self) -> Option<Self::Item>;
+        }
+        pub trait IntoIterator {
+            type Item;
+        }
+        impl<I: Iterator> IntoIterator for I {
+            type Item = I::Item;
+        }
+    }
+}
+
+use std::iter::Iterator;
+
+enum Option<T> { Some(T), None }
+struct Vec<T>;
+struct IntoIter<T>;
+struct Unknown;
+
+impl<T> std::iter::IntoIterator for Vec<T> {
+    type Item = T;
+}
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> { Option::None }
+}
+
+fn test(v: Vec<u64>, it: IntoIter<&str>, u: Unknown) {
+    for x in v {}
+    for y in it {}
+    for z in u {}
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_generic_bounds() {
+    check_inference(
+        "infer_generic_bounds",
+        r#"
+mod bar {
+    pub trait Bar { fn bar(&self) -> i8; }
+}
+trait Clone { fn clone(&self) -> Self; }
+trait Foo { fn foo(&self) -> u32; }
+trait Baz { fn baz(&self) -> u64 { self.foo() } fn foo(&self) -> u64; }
+
+struct S<T, U = u64> { t: T, u: U }
+
+impl<T: Clone> S<T> {
+    fn get(&self) -> T { self.t.clone() }
+}
+
+fn test<'a, T: Clone, U>(t: &'a T, u: U, s: S<i32>) where U: bar::Bar + Foo {
+    t.clone();
+    u.bar();
+    u.foo();
+    s.u;
+    s.get();
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_closures() {
+    check_inference(
+        "infer_closures",
+        r#"
+mod std {
+    pub mod ops {
+        pub trait FnOnce<Args> {}
+        pub trait FnMut<Args>: FnOnce<Args> {}
+        pub trait Fn<Args>: FnMut<Args> {}
+    }
+    pub mod iter {
+        pub trait Iterator {
+            type Item;
+            fn map<B, F: super::ops::FnMut(Self::Item) -> B>(self, f: F) -> Map<F, B> { loop {} }
+        }
+        pub struct Map<F, B> { f: F, b: B }
+    }
+}
+
+use std::iter::Iterator;
+
+struct S { field: u32 }
+struct IntoIter;
+impl Iterator for IntoIter {
+    type Item = S;
+}
+
+fn apply<T, R, F>(t: T, f: F) -> R where F: std::ops::FnOnce(T) -> R { f(t) }
+
+fn test(it: IntoIter) {
+    let add = |x: u32, y: u32| x + y;
+    add(1, 2);
+    let unit = || {};
+    unit();
+    let early = |b: bool| { if b { return 1; } 2u8 };
+    apply(1u64, |x| x);
+    apply(S { field: 1 }, |s| s.field);
+    it.map(|x| x.field);
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_coercions() {
+    check_inference(
+        "infer_coercions",
+        r#"
+mod std {
+    pub mod ops {
+        #[lang = "deref"]
+        pub trait Deref {
+            type Target;
+            fn deref(&self) -> &Self::Target;
+        }
+    }
+}
+
+struct String;
+struct Vec<T>;
+impl std::ops::Deref for String {
+    type Target = str;
+}
+impl<T> std::ops::Deref for Vec<T> {
+    type Target = [T];
+}
+
+struct S<'a> { s: &'a str }
+
+fn takes_str(s: &str) {}
+fn takes_slice<T>(s: &[T]) -> T {}
+
+fn test(string: &String, v: &Vec<u8>, m: &mut String, arr: &[i32; 3]) -> &str {
+    takes_str(string);
+    takes_str(m);
+    let x = takes_slice(v);
+    let y = takes_slice(arr);
+    let z = takes_slice(&[1u64, 2]);
+    let s: &str = &*m;
+    let t: u32 = return;
+    S { s: string };
+    let a: [&str; 2] = [string, "b"];
+    let b = if true { return string } else { 1 };
+    string
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_overloaded_ops() {
+    check_inference(
+        "infer_overloaded_ops",
+        r#"
+mod std {
+    pub mod ops {
+        #[lang = "add"]
+        pub trait Add<Rhs = Self> { type Output; }
+        #[lang = "mul"]
+        pub trait Mul<Rhs = Self> { type Output; }
+        #[lang = "neg"]
+        pub trait Neg { type Output; }
+        #[lang = "not"]
+        pub trait Not { type Output; }
+        #[lang = "deref"]
+        pub trait Deref { type Target; }
+    }
+}
+
+use std::ops::{Add, Mul, Neg, Not, Deref};
+
+struct V2;
+struct Scalar;
+struct Mask;
+struct Boxed;
+
+impl Add for V2 { type Output = V2; }
+impl Mul<Scalar> for V2 { type Output = V2; }
+impl Mul<V2> for V2 { type Output = Scalar; }
+impl Neg for V2 { type Output = V2; }
+impl Not for Mask { type Output = bool; }
+impl Deref for Boxed { type Target = V2; }
+
+fn test(a: V2, b: V2, s: Scalar, m: Mask, x: Boxed) {
+    a + b;
+    a * s;
+    a * b;
+    -a;
+    !m;
+    *x;
+    -(a + *x);
+    !1u8;
+    s + s;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_overloaded_ops_with_primitive_operand() {
+    check_inference(
+        "infer_overloaded_ops_with_primitive_operand",
+        r#"
+mod std {
+    pub mod ops {
+        #[lang = "mul"]
+        pub trait Mul<Rhs = Self> { type Output; }
+        #[lang = "mul_assign"]
+        pub trait MulAssign<Rhs = Self> {}
+    }
+}
+
+use std::ops::{Mul, MulAssign};
+
+struct V2;
+
+impl Mul<f32> for V2 { type Output = V2; }
+impl Mul<V2> for f32 { type Output = V2; }
+impl MulAssign<f32> for V2 {}
+
+fn test(mut a: V2) {
+    a * 2.0;
+    2.0f32 * a;
+    a *= 2.0;
+    2.0 * 3.0;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_index_and_range() {
+    check_inference(
+        "infer_index_and_range",
+        r#"
+mod std {
+    pub mod ops {
+        pub struct RangeFull;
+        pub struct Range<Idx> { pub start: Idx, pub end: Idx }
+        pub struct RangeFrom<Idx> { pub start: Idx }
+        pub struct RangeTo<Idx> { pub end: Idx }
+        pub struct RangeInclusive<Idx> { start: Idx, end: Idx }
+        pub struct RangeToInclusive<Idx> { pub end: Idx }
+        #[lang = "index"]
+        pub trait Index<Idx> { type Output; }
+        #[lang = "deref"]
+        pub trait Deref { type Target; }
+    }
+}
+
+use std::ops::{Index, Deref, Range};
+
+struct Vec<T>;
+struct Slice;
+
+impl<T> Index<usize> for Vec<T> { type Output = T; }
+impl<T> Index<Range<usize>> for Vec<T> { type Output = Slice; }
+impl<T> Deref for Vec<T> { type Target = [T]; }
+
+fn test(v: Vec<u32>, a: [f64; 2], s: &[i8]) {
+    ..;
+    1..;
+    ..2u8;
+    ..=2;
+    1..2;
+    1u16..=2;
+    v[0];
+    v[1..2];
+    a[1];
+    s[0];
+    (&v)[0];
+    v.x[0];
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_divergence() {
+    check_inference(
+        "infer_divergence",
+        r#"
+enum E { A, B }
+
+fn abort() -> ! { loop {} }
+
+fn test(x: bool, e: E) -> u32 {
+    let a = if x { return 1; } else { 2 };
+    let b = match e {
+        E::A => abort(),
+        E::B => 3u8,
+    };
+    let c = loop {
+        if x { break 4u64; }
+        break;
+    };
+    let d = loop {
+        if x { break; }
+    };
+    let e = loop {};
+    let f = if x { return 5 } else { abort() };
+    let g = while x { break; };
+    let h = |y: u32| -> u32 { return y; };
+    abort();
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_impl_and_dyn_trait() {
+    check_inference(
+        "infer_impl_and_dyn_trait",
+        r#"
+mod std {
+    pub mod ops {
+        pub trait Fn<Args> { type Output; }
+        pub trait FnMut<Args> { type Output; }
+        pub trait FnOnce<Args> { type Output; }
+    }
+    pub mod iter {
+        pub trait Iterator {
+            type Item;
+            fn next(&mut self) -> Self::Item;
+        }
+    }
+}
+
+use std::{iter::Iterator, ops::Fn};
+
+trait Shape { fn area(&self) -> f64; }
+
+fn numbers() -> impl Iterator<Item = u32> {}
+fn shape() -> Box<dyn Shape> {}
+struct Box<T>;
+
+fn test(f: &dyn Fn(i32) -> u8, s: &dyn Shape, g: impl Fn(u32)) {
+    let mut it = numbers();
+    it;
+    it.next();
+    f;
+    f(1);
+    s.area();
+    g(2);
+    shape();
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_type_alias() {
+    check_inference(
+        "infer_type_alias",
+        r#"
+mod std {
+    pub mod result {
+        pub enum Result<T, E> { Ok(T), Err(E) }
+        impl<T, E> Result<T, E> {
+            pub fn is_ok(&self) -> bool {}
+            pub fn unwrap_err(self) -> E {}
+        }
+    }
+    pub mod ops {
+        pub trait Try { type Ok; }
+        impl<T, E> Try for super::result::Result<T, E> { type Ok = T; }
+    }
+}
+
+struct Error;
+struct S<T>;
+
+type Result<T> = std::result::Result<T, Error>;
+type Pair<T, U = u8> = (T, U);
+type Id = u32;
+type A = Vec<B>;
+type B = (A, u32);
+type C = C;
+
+fn read() -> Result<Id> {}
+
+fn test(p: Pair<i64>, q: Pair<i64, S<Id>>, a: A, c: C) -> Result<()> {
+    let r = read();
+    r.is_ok();
+    r.unwrap_err();
+    read()?;
+    p;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_derived_trait_methods() {
+    check_inference_with_deps(
+        "infer_derived_trait_methods",
+        r#"
+//- /main.rs
+#[derive(PartialEq)]
+struct S;
+
+#[derive(std::cmp::PartialEq)]
+enum E { A }
+
+struct NotDerived;
+
+fn test(s: S, e: E, n: NotDerived) {
+    s.eq(&s);
+    s.ne(&s);
+    e.eq(&e);
+    n.eq(&n);
+}
+
+//- /std.rs
+pub mod prelude {
+    pub mod v1 {
+        pub use crate::cmp::PartialEq;
+    }
+}
+pub mod cmp {
+    pub trait PartialEq {
+        fn eq(&self, other: &Self) -> bool;
+        fn ne(&self, other: &Self) -> bool { !self.eq(other) }
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_local_impls_of_foreign_traits() {
+    check_inference_with_deps(
+        "infer_local_impls_of_foreign_traits",
+        r#"
+//- /main.rs
+use std::ops::Mul;
+use std::time::Duration;
+
+struct V2;
+
+impl Mul<V2> for f32 { type Output = V2; }
+impl<'a> Mul<V2> for &'a Duration { type Output = u32; }
+
+fn test() {
+    let a: <f32 as Mul<V2>>::Output;
+    let b: <&Duration as Mul<V2>>::Output;
+    let c: <u8 as Mul<V2>>::Output;
+}
+
+//- /std.rs
+pub mod ops {
+    pub trait Mul<Rhs = Self> { type Output; }
+}
+pub mod time {
+    pub struct Duration;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_derives_from_core_ignoring_local_std() {
+    check_inference_with_deps(
+        "infer_derives_from_core_ignoring_local_std",
+        r#"
+//- /main.rs
+#![no_std]
+
+mod std {
+    pub mod clone {
+        pub trait Clone {
+            fn clone(&self) -> u32;
+        }
+    }
+}
+
+#[derive(Clone)]
+struct S;
+
+fn test(s: S) {
+    s.clone();
+}
+
+//- /core.rs
+pub mod prelude {
+    pub mod v1 {
+        pub use crate::clone::Clone;
+    }
+}
+pub mod clone {
+    pub trait Clone {
+        fn clone(&self) -> Self;
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_method_call_of_prelude_trait() {
+    check_inference_with_deps(
+        "infer_method_call_of_prelude_trait",
+        r#"
+//- /main.rs
+struct S;
+
+impl std::convert::From<S> for u32 {
+    fn from(s: S) -> u32 { 0 }
+}
+
+fn test(s: S) {
+    let a: u32 = s.into();
+    s.ext();
+}
+
+//- /std.rs
+pub mod prelude {
+    pub mod v1 {
+        pub use crate::convert::{From, Into};
+    }
+}
+pub mod convert {
+    pub trait From<T> {
+        fn from(t: T) -> Self;
+    }
+    pub trait Into<T> {
+        fn into(self) -> T;
+    }
+    impl<T, U: From<T>> Into<U> for T {
+        fn into(self) -> U { U::from(self) }
+    }
+}
+pub mod ext {
+    pub trait Ext {
+        fn ext(&self) -> u8;
+    }
+    impl<T> Ext for T {
+        fn ext(&self) -> u8 { 0 }
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_const_static_and_discriminant() {
+    check_inference(
+        "infer_const_static_and_discriminant",
+        r#"
+struct S;
+
+impl S {
+    const ZERO: Self = S;
+}
+
+const N: usize = 4 * 2;
+static TABLE: [u8; 4] = [0; N];
+
+enum E {
+    A = 1,
+    B = N as isize + 1,
+}
+
+fn test() {
+    let a = [1u8; N];
+    let b = [TABLE[0]; 3];
+    let c = &TABLE;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_array_length_from_consts() {
+    check_inference(
+        "infer_array_length_from_consts",
+        r#"
+const WORD: usize = 4;
+const BYTES: usize = WORD * 2 + (1 << 3) - 0x8;
+const OVERFLOW: u8 = 1 << 9;
+const LOOP: usize = LOOP + 1;
+
+fn test(a: [u8; BYTES], b: [u8; OVERFLOW], c: [u8; LOOP]) {
+    let d = [0u8; WORD * 4];
+    let e = [0u8; 'a' as usize];
+    let f = [0u8; if WORD > 2 { 1 } else { 2 }];
+    let g = [0u8; (-1i8) as u8 as usize];
+    let h: [u16; BYTES] = [1; 8];
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_array_length_exprs_in_types() {
+    check_inference(
+        "infer_array_length_exprs_in_types",
+        r#"
+const WORD: usize = 4;
+
+struct Buf {
+    data: [u8; WORD * 2],
+}
+
+fn test(a: [u8; WORD * 2], b: [u8; { WORD + 1 }], buf: Buf) {
+    let c: [u16; WORD << 1] = [0; 8];
+    let d = buf.data;
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_items_in_blocks() {
+    check_inference(
+        "infer_items_in_blocks",
+        r#"
+mod foo {
+    pub fn bar() -> u32 { 0 }
+}
+struct S;
+fn helper() -> S { S }
+
+fn test() {
+    fn helper() -> u8 { 0 }
+    struct Local { x: i64 }
+    impl Local {
+        fn get(&self) -> i64 { self.x }
+    }
+    use foo::bar as baz;
+
+    let a = helper();
+    let b = Local { x: 1 }.get();
+    let c = baz();
+    {
+        fn helper() -> bool { true }
+        let d = helper();
+        let e: Local = Local { x: 2 };
+    }
+    let f = { 1; helper() };
+}
+
+fn other() {
+    let g = helper();
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_glob_imports_in_blocks() {
+    check_inference(
+        "infer_glob_imports_in_blocks",
+        r#"
+mod foo {
+    pub fn bar() -> u32 { 0 }
+    pub fn helper() -> u64 { 0 }
+}
+enum E { A, B }
+
+fn test() {
+    use E::*;
+    use foo::*;
+    fn helper() -> bool { true }
+
+    let a = A;
+    let b = B;
+    let c = bar();
+    let d = helper();
+}
+
+mod m {
+    fn test() {
+        use super::*;
+        let e = foo::bar();
+        let f = E::A;
+    }
+}
+"#,
+    );
+}
+
+#[test]
+fn infer_labeled_breaks() {
+    check_inference(
+        "infer_labeled_breaks",
+        r#"
+fn test(x: bool) {
+    let a = 'outer: loop {
+        loop {
+            if x { break 'outer 1u8; }
+            break;
+        }
+    };
+    let b = 'blk: {
+        if x { break 'blk "a"; }
+        "b"
+    };
+    let c = 'l: loop {
+        let f = || loop { break 2i64; };
+        while x { continue 'l; }
+        break 'l f();
+    };
+    let d = loop { break 'missing 1u32; };
+}
+"#,
+    );
+}
+
 fn infer(content: &str) -> String {
     let (db, _, file_id) = MockDatabase::with_single_file(content);
+    infer_file(&db, file_id)
+}
+
+/// Infers the types in `/main.rs` of the fixture, a crate depending on the
+/// crates `/std.rs` and `/core.rs`, if they are present.
+fn infer_with_deps(fixture: &str) -> String {
+    let (mut db, source_root) = MockDatabase::with_files(fixture);
+    let main_id = source_root.files[RelativePath::new("/main.rs")];
+    let mut crate_graph = CrateGraph::default();
+    let main_crate = crate_graph.add_crate_root(main_id);
+    for name in ["std", "core"].iter() {
+        let path = format!("/{}.rs", name);
+        if let Some(&file_id) = source_root.files.get(RelativePath::new(&path)) {
+            let krate = crate_graph.add_crate_root(file_id);
+            crate_graph
+                .add_dep(main_crate, (*name).into(), krate)
+                .unwrap();
+        }
+    }
+    db.set_crate_graph(Arc::new(crate_graph));
+    infer_file(&db, main_id)
+}
+
+fn infer_file(db: &MockDatabase, file_id: FileId) -> String {
     let source_file = db.source_file(file_id);
     let mut acc = String::new();
-    for fn_def in source_file
+    let def_kinds = [FN_DEF, CONST_DEF, STATIC_DEF, ENUM_VARIANT];
+    for node in source_file
         .syntax()
         .descendants()
-        .filter_map(ast::FnDef::cast)
+        .filter(|it| def_kinds.contains(&it.kind()))
     {
-        let func = source_binder::function_from_source(&db, file_id, fn_def).unwrap();
-        let inference_result = func.infer(&db);
-        let body_syntax_mapping = func.body_syntax_mapping(&db);
+        let def = source_binder::def_with_body_from_child_node(db, file_id, node).unwrap();
+        let inference_result = def.infer(db);
+        let body_syntax_mapping = def.body_syntax_mapping(db);
         let mut types = Vec::new();
         for (pat, ty) in inference_result.type_of_pat.iter() {
             let syntax_ptr = match body_syntax_mapping.pat_syntax(pat) {
@@ -575,12 +1546,14 @@ fn infer(content: &str) -> String {
         // sort ranges for consistency
         types.sort_by_key(|(ptr, _)| (ptr.range().start(), ptr.range().end()));
         for (syntax_ptr, ty) in &types {
-            let node = syntax_ptr.to_node(&source_file);
+            // Nodes from macro expansions don't exist in the file, so we can't
+            // use `to_node` here.
+            let text = source_file.syntax().text().slice(syntax_ptr.range());
             write!(
                 acc,
                 "{} '{}': {}\n",
                 syntax_ptr.range(),
-                ellipsize(node.text().to_string().replace("\n", " "), 15),
+                ellipsize(text.to_string().replace("\n", " "), 15),
                 ty
             )
             .unwrap();
@@ -595,6 +1568,12 @@ fn check_inference(name: &str, content: &str) {
     insta::assert_snapshot_matches!(&name, &result);
 }
 
+fn check_inference_with_deps(name: &str, fixture: &str) {
+    let result = infer_with_deps(fixture);
+
+    insta::assert_snapshot_matches!(&name, &result);
+}
+
 fn ellipsize(mut text: String, max_len: usize) -> String {
     if text.len() <= max_len {
         return text;
@@ -0,0 +1,740 @@
+//! Trait solving: finds out whether a type implements a trait, and if so,
+//! which impl (or which where clause) provides the implementation. For details
+//! about how this works in rustc, see the trait resolution page in the [rustc
+//! guide](https://rust-lang.github.io/rustc-guide/traits/resolution.html).
+//!
+//! This is a simple solver: we look for an impl whose header matches the goal,
+//! and then recursively check the where clauses of the impl. There are no
+//! inference variables in goals; unknown types match anything.
+//!
+//! Associated types are normalized using the same machinery: to find out what
+//! `<T as Trait>::Item` is, we select the impl of `Trait` for `T` and look at
+//! its `type Item = ...;`.
+use std::{fmt, sync::Arc};
+
+use join_to_string::join;
+
+use crate::{
+    HirDatabase, Module, ModuleDef, Crate, Trait, Path, PathKind, Name, TraitItem,
+    impl_block::{ImplBlock, ImplItem},
+    generics::{GenericParams, GenericDef, WherePredicate},
+    type_ref::TypeRef,
+    path::{GenericArg, PathSegment},
+    ty::{Ty, Substs, FnSig, method_resolution::def_crate},
+};
+
+/// A trait together with the types it is applied to, e.g. `Vec<u32>: Into<T>`.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct TraitRef {
+    pub trait_: Trait,
+    /// The type which should implement the trait.
+    pub self_ty: Ty,
+    /// The substitutions for the type parameters of the trait.
+    pub substs: Substs,
+}
+
+/// A trait bound of a `dyn Trait` or `impl Trait` type, like
+/// `Iterator<Item = u32>` in `impl Iterator<Item = u32>`. The self type is the
+/// bounded type itself, so it's not part of the bound.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct TraitBound {
+    pub trait_: Trait,
+    /// The name of the trait, for displaying.
+    pub trait_name: Name,
+    /// The substitutions for the type parameters of the trait.
+    pub substs: Substs,
+    /// The types given for associated types, like `Item = u32`.
+    pub bindings: Vec<(Name, Ty)>,
+}
+
+/// The where clauses which may be assumed to hold while solving, e.g. the
+/// bounds on the type parameters of the function we are checking.
+#[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
+pub struct Environment {
+    pub predicates: Vec<TraitRef>,
+    /// The crate we are solving in. Its impls are candidates as well, like a
+    /// local `impl Mul<V2> for f32`.
+    pub krate: Option<Crate>,
+}
+
+/// A proof that a `TraitRef` holds.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Solution {
+    /// The trait is implemented by an impl; the substs are the values of the
+    /// impl's type parameters.
+    Impl(ImplBlock, Substs),
+    /// The trait is implemented according to a where clause of the
+    /// environment.
+    Assumption,
+}
+
+/// The projection of an associated type out of a trait ref, e.g.
+/// `<Vec<u32> as IntoIterator>::Item`.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ProjectionTy {
+    pub trait_ref: TraitRef,
+    /// The name of the trait, for displaying.
+    pub trait_name: Name,
+    /// The name of the associated type.
+    pub name: Name,
+}
+
+/// Impls can be recursive (`impl<T: Foo> Foo for T`), so we need a limit.
+const MAX_DEPTH: usize = 16;
+
+impl TraitRef {
+    /// Lowers a trait bound like `Into<T>` of `self_ty`.
+    pub(crate) fn from_path(
+        db: &impl HirDatabase,
+        module: &Module,
+        impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
+        path: &Path,
+        self_ty: Ty,
+    ) -> Option<TraitRef> {
+        let bound = TraitBound::from_path(db, module, impl_block, generics, path)?;
+        Some(bound.trait_ref(self_ty))
+    }
+
+    /// `self_ty: trait_`, with unknown types for the trait's parameters.
+    pub(crate) fn with_unknown_substs(
+        db: &impl HirDatabase,
+        trait_: Trait,
+        self_ty: Ty,
+    ) -> TraitRef {
+        let substs = vec![Ty::Unknown; trait_param_count(db, trait_)];
+        TraitRef {
+            trait_,
+            self_ty,
+            substs: Substs(substs.into()),
+        }
+    }
+
+    pub(crate) fn from_where_predicate(
+        db: &impl HirDatabase,
+        module: &Module,
+        impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
+        pred: &WherePredicate,
+    ) -> Option<TraitRef> {
+        let self_ty = Ty::from_hir(db, module, impl_block, generics, &pred.type_ref);
+        TraitRef::from_path(db, module, impl_block, generics, &pred.trait_ref, self_ty)
+    }
+
+    /// The trait an impl block implements, in terms of the impl's type
+    /// parameters.
+    pub(crate) fn for_impl(db: &impl HirDatabase, impl_block: &ImplBlock) -> Option<TraitRef> {
+        let path = match impl_block.target_trait()? {
+            TypeRef::Path(path) => path,
+            _ => return None,
+        };
+        let module = impl_block.module();
+        let generics = impl_block.generic_params();
+        let self_ty = Ty::from_hir(
+            db,
+            &module,
+            Some(impl_block),
+            generics,
+            impl_block.target_type(),
+        );
+        TraitRef::from_path(db, &module, Some(impl_block), generics, path, self_ty)
+    }
+
+    pub fn subst(self, substs: &Substs) -> TraitRef {
+        self.fold(&mut |ty| ty.subst(substs))
+    }
+
+    /// Applies `f` to the self type and all parameters.
+    pub(crate) fn fold(self, f: &mut impl FnMut(Ty) -> Ty) -> TraitRef {
+        TraitRef {
+            trait_: self.trait_,
+            self_ty: f(self.self_ty),
+            substs: Substs(
+                self.substs
+                    .0
+                    .iter()
+                    .map(|ty| f(ty.clone()))
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        }
+    }
+}
+
+impl TraitBound {
+    /// Lowers a trait bound like `Iterator<Item = u32>`.
+    pub(crate) fn from_path(
+        db: &impl HirDatabase,
+        module: &Module,
+        impl_block: Option<&ImplBlock>,
+        generics: &GenericParams,
+        path: &Path,
+    ) -> Option<TraitBound> {
+        let trait_ = match module.resolve_path(db, path).take_types()? {
+            ModuleDef::Trait(it) => it,
+            _ => return None,
+        };
+        let param_count = trait_param_count(db, trait_);
+        let args = path
+            .segments
+            .last()
+            .and_then(|segment| segment.args_and_bindings.as_ref());
+        let mut substs = args
+            .map(|args| {
+                args.args
+                    .iter()
+                    .take(param_count)
+                    .map(|arg| match arg {
+                        GenericArg::Type(type_ref) => {
+                            Ty::from_hir(db, module, impl_block, generics, type_ref)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        substs.resize(param_count, Ty::Unknown);
+        let bindings = args
+            .map(|args| {
+                args.bindings
+                    .iter()
+                    .map(|(name, type_ref)| {
+                        let ty = Ty::from_hir(db, module, impl_block, generics, type_ref);
+                        (name.clone(), ty)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        Some(TraitBound {
+            trait_,
+            trait_name: trait_.name(db).unwrap_or_else(Name::missing),
+            substs: Substs(substs.into()),
+            bindings,
+        })
+    }
+
+    /// The trait ref saying that `self_ty` fulfills this bound.
+    pub fn trait_ref(&self, self_ty: Ty) -> TraitRef {
+        TraitRef {
+            trait_: self.trait_,
+            self_ty,
+            substs: self.substs.clone(),
+        }
+    }
+
+    /// The type given for the associated type `name` in this bound, if any.
+    pub fn binding(&self, name: &Name) -> Option<&Ty> {
+        self.bindings
+            .iter()
+            .find(|(binding_name, _)| binding_name == name)
+            .map(|(_, ty)| ty)
+    }
+
+    /// The signature a bound by one of the `Fn` traits requires, like
+    /// `fn(u32) -> u8` for `FnMut(u32) -> u8`. `fn_traits` are the `Fn`
+    /// traits, as found by `fn_traits`.
+    pub(crate) fn fn_sig(&self, fn_traits: &[Trait]) -> Option<FnSig> {
+        if !fn_traits.contains(&self.trait_) {
+            return None;
+        }
+        let input = match self.substs.0.first() {
+            Some(Ty::Tuple(params)) => params.to_vec(),
+            _ => return None,
+        };
+        let output = self
+            .binding(&Name::output())
+            .cloned()
+            .unwrap_or(Ty::Unknown);
+        Some(FnSig { input, output })
+    }
+}
+
+impl fmt::Display for TraitBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.trait_name)?;
+        // like the `Fn` traits' sugar is written, e.g. `Fn(u32) -> u8`
+        if let (Some(Ty::Tuple(params)), [(name, output)]) =
+            (self.substs.0.first(), &self.bindings[..])
+        {
+            if *name == Name::output() {
+                join(params.iter())
+                    .surround_with("(", ")")
+                    .separator(", ")
+                    .to_fmt(f)?;
+                if *output != Ty::unit() {
+                    write!(f, " -> {}", output)?;
+                }
+                return Ok(());
+            }
+        }
+        let substs = self.substs.0.iter().map(|ty| ty.to_string());
+        let bindings = self
+            .bindings
+            .iter()
+            .map(|(name, ty)| format!("{} = {}", name, ty));
+        let args = substs.chain(bindings).collect::<Vec<_>>();
+        if !args.is_empty() {
+            join(args.iter())
+                .surround_with("<", ">")
+                .separator(", ")
+                .to_fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl ProjectionTy {
+    pub(crate) fn new(db: &impl HirDatabase, trait_ref: TraitRef, name: Name) -> ProjectionTy {
+        ProjectionTy {
+            trait_name: trait_ref.trait_.name(db).unwrap_or_else(Name::missing),
+            trait_ref,
+            name,
+        }
+    }
+}
+
+impl Environment {
+    /// The environment in the body or signature of `def`, i.e. its where
+    /// clauses and those of its parents.
+    pub(crate) fn for_def(db: &impl HirDatabase, def: GenericDef) -> Arc<Environment> {
+        let (module, _) = module_and_impl_block(db, def);
+        Arc::new(Environment {
+            predicates: db.generic_predicates(def).to_vec(),
+            krate: module.krate(db),
+        })
+    }
+
+    /// The environment without any where clauses, for bodies like the ones
+    /// of consts and statics in `module`.
+    pub(crate) fn empty(db: &impl HirDatabase, module: Module) -> Arc<Environment> {
+        Arc::new(Environment {
+            predicates: Vec::new(),
+            krate: module.krate(db),
+        })
+    }
+}
+
+/// Lowers the bounds and where clauses of `def` and of its parents (the impl
+/// or trait of a method).
+pub(crate) fn generic_predicates_query(db: &impl HirDatabase, def: GenericDef) -> Arc<[TraitRef]> {
+    let (module, impl_block) = module_and_impl_block(db, def);
+    let generics = db.generic_params(def);
+    generics
+        .where_predicates_including_parent()
+        .into_iter()
+        .filter_map(|pred| {
+            TraitRef::from_where_predicate(db, &module, impl_block.as_ref(), &generics, pred)
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// The bounds of `def` by one of the `Fn`, `FnMut` and `FnOnce` traits, as the
+/// bounded type together with the signature the bound requires. So for
+/// `F: FnMut(u32) -> u8`, this contains `F` with the signature `fn(u32) -> u8`.
+pub(crate) fn fn_bounds(db: &impl HirDatabase, def: GenericDef) -> Vec<(Ty, FnSig)> {
+    let (module, impl_block) = module_and_impl_block(db, def);
+    let fn_traits = fn_traits(db, module);
+    if fn_traits.is_empty() {
+        return Vec::new();
+    }
+    let generics = db.generic_params(def);
+    generics
+        .where_predicates_including_parent()
+        .into_iter()
+        .filter_map(|pred| {
+            let bound = TraitBound::from_path(
+                db,
+                &module,
+                impl_block.as_ref(),
+                &generics,
+                &pred.trait_ref,
+            )?;
+            let sig = bound.fn_sig(&fn_traits)?;
+            let self_ty = Ty::from_hir(db, &module, impl_block.as_ref(), &generics, &pred.type_ref);
+            Some((self_ty, sig))
+        })
+        .collect()
+}
+
+/// The `Fn`, `FnMut` and `FnOnce` traits, as far as they can be found.
+pub(crate) fn fn_traits(db: &impl HirDatabase, module: Module) -> Vec<Trait> {
+    ["Fn", "FnMut", "FnOnce"]
+        .iter()
+        .filter_map(|name| std_trait(db, module, &["ops", name]))
+        .collect()
+}
+
+fn module_and_impl_block(db: &impl HirDatabase, def: GenericDef) -> (Module, Option<ImplBlock>) {
+    match def {
+        GenericDef::Function(it) => (it.module(db), it.impl_block(db)),
+        GenericDef::Struct(it) => (it.module(db), None),
+        GenericDef::Enum(it) => (it.module(db), None),
+        GenericDef::Trait(it) => (it.module(db), None),
+        GenericDef::Type(it) => (it.module(db), None),
+    }
+}
+
+/// The number of type parameters of the trait, not counting `Self`.
+fn trait_param_count(db: &impl HirDatabase, trait_: Trait) -> usize {
+    trait_.generic_params(db).params.len() - 1
+}
+
+/// Checks whether `trait_ref` holds in `env`, and selects the impl which
+/// implements it. Returns `None` if there is no such impl, and also if the
+/// self type is unknown, since then we can't tell.
+pub(crate) fn implements(
+    db: &impl HirDatabase,
+    env: Arc<Environment>,
+    trait_ref: TraitRef,
+) -> Option<Solution> {
+    match trait_ref.self_ty {
+        Ty::Unknown | Ty::Infer(_) => return None,
+        _ => {}
+    }
+    Solver { db, env: &env }.solve(&trait_ref, 0)
+}
+
+/// Replaces all projections in `ty` by the types they are defined to be in
+/// the selected impls. Projections which can't be normalized stay as they are
+/// if their self type is a type parameter (so e.g. `T::Item` in a generic
+/// function is kept) or a `dyn Trait` or `impl Trait` type, and become unknown
+/// otherwise.
+pub(crate) fn normalize(db: &impl HirDatabase, env: &Arc<Environment>, ty: Ty) -> Ty {
+    normalize_with_depth(db, env, ty, 0)
+}
+
+fn normalize_with_depth(db: &impl HirDatabase, env: &Arc<Environment>, ty: Ty, depth: usize) -> Ty {
+    ty.fold(&mut |ty| match ty {
+        Ty::Projection(projection) => {
+            if depth > MAX_DEPTH {
+                return Ty::Unknown;
+            }
+            // Normalize nested projections first, so that the trait ref can
+            // be matched against the impls.
+            let mut projection = ProjectionTy::clone(&projection);
+            projection.trait_ref = projection
+                .trait_ref
+                .fold(&mut |ty| normalize_with_depth(db, env, ty, depth + 1));
+            match normalize_projection(db, env, &projection) {
+                Some(normalized) => normalize_with_depth(db, env, normalized, depth + 1),
+                None => match projection.trait_ref.self_ty {
+                    Ty::Param { .. } | Ty::Dyn(_) | Ty::Opaque(_) => {
+                        Ty::Projection(Arc::new(projection))
+                    }
+                    _ => Ty::Unknown,
+                },
+            }
+        }
+        ty => ty,
+    })
+}
+
+/// Normalizes a single projection one step, without looking at projections
+/// in the result.
+fn normalize_projection(
+    db: &impl HirDatabase,
+    env: &Arc<Environment>,
+    projection: &ProjectionTy,
+) -> Option<Ty> {
+    // the bounds of `dyn Trait` and `impl Trait` types can give the type, as
+    // in `impl Iterator<Item = u32>`
+    let trait_ref = &projection.trait_ref;
+    let from_bounds = trait_ref
+        .self_ty
+        .trait_bounds()
+        .iter()
+        .filter(|bound| bound.trait_ == trait_ref.trait_)
+        .find_map(|bound| bound.binding(&projection.name));
+    if let Some(ty) = from_bounds {
+        return Some(ty.clone());
+    }
+    match db.implements(Arc::clone(env), projection.trait_ref.clone())? {
+        Solution::Impl(impl_block, substs) => {
+            let ty = match assoc_type_in_impl(db, &impl_block, &projection.name) {
+                Some(ty) => ty.subst(&substs),
+                None => assoc_type_default(db, &projection.trait_ref, &projection.name)?,
+            };
+            Some(ty)
+        }
+        Solution::Assumption => None,
+    }
+}
+
+/// The type of the associated type `name` defined in `impl_block`, in terms
+/// of the impl's type parameters.
+pub(crate) fn assoc_type_in_impl(
+    db: &impl HirDatabase,
+    impl_block: &ImplBlock,
+    name: &Name,
+) -> Option<Ty> {
+    let type_ref = impl_block.items().iter().find_map(|item| match item {
+        ImplItem::Type(it) if it.name(db).as_ref() == Some(name) => it.type_ref(db),
+        _ => None,
+    })?;
+    let module = impl_block.module();
+    let generics = impl_block.generic_params();
+    Some(Ty::from_hir(
+        db,
+        &module,
+        Some(impl_block),
+        generics,
+        &type_ref,
+    ))
+}
+
+/// The default given for the associated type `name` in the trait, if any.
+fn assoc_type_default(db: &impl HirDatabase, trait_ref: &TraitRef, name: &Name) -> Option<Ty> {
+    let type_ref = trait_ref
+        .trait_
+        .items(db)
+        .into_iter()
+        .find_map(|item| match item {
+            TraitItem::Type(it) if it.name(db).as_ref() == Some(name) => it.type_ref(db),
+            _ => None,
+        })?;
+    let module = trait_ref.trait_.module(db);
+    let generics = trait_ref.trait_.generic_params(db);
+    let ty = Ty::from_hir(db, &module, None, &generics, &type_ref);
+    let mut substs = vec![trait_ref.self_ty.clone()];
+    substs.extend(trait_ref.substs.0.iter().cloned());
+    Some(ty.subst(&Substs(substs.into())))
+}
+
+/// Whether `trait_` declares an associated type called `name`.
+pub(crate) fn has_assoc_type(db: &impl HirDatabase, trait_: Trait, name: &Name) -> bool {
+    trait_.items(db).into_iter().any(|item| match item {
+        TraitItem::Type(it) => it.name(db).as_ref() == Some(name),
+        _ => false,
+    })
+}
+
+/// Finds a trait of the standard library by its path relative to `std` (or
+/// `core`), e.g. `["iter", "IntoIterator"]`.
+pub(crate) fn std_trait(db: &impl HirDatabase, module: Module, path: &[&str]) -> Option<Trait> {
+    match std_item(db, module, path)? {
+        ModuleDef::Trait(it) => Some(it),
+        _ => None,
+    }
+}
+
+/// Finds an item of the standard library by its path relative to `std` (or
+/// `core`), e.g. `["ops", "Range"]`.
+pub(crate) fn std_item(db: &impl HirDatabase, module: Module, path: &[&str]) -> Option<ModuleDef> {
+    let crate_root = module.crate_root(db);
+    ["std", "core"].iter().find_map(|krate| {
+        let segments = std::iter::once(krate)
+            .chain(path.iter())
+            .map(|name| PathSegment {
+                name: Name::new((*name).into()),
+                args_and_bindings: None,
+            })
+            .collect();
+        let path = Path {
+            kind: PathKind::Plain,
+            segments,
+        };
+        crate_root.resolve_path(db, &path).take_types()
+    })
+}
+
+struct Solver<'a, D: HirDatabase> {
+    db: &'a D,
+    env: &'a Environment,
+}
+
+impl<'a, D: HirDatabase> Solver<'a, D> {
+    fn solve(&self, goal: &TraitRef, depth: usize) -> Option<Solution> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let from_env = self
+            .env
+            .predicates
+            .iter()
+            .any(|pred| pred.trait_ == goal.trait_ && match_trait_ref(pred, goal, &mut []));
+        // `dyn Trait` and `impl Trait` types implement their bounds
+        let from_bounds = goal.self_ty.trait_bounds().iter().any(|bound| {
+            bound.trait_ == goal.trait_
+                && match_trait_ref(&bound.trait_ref(goal.self_ty.clone()), goal, &mut [])
+        });
+        if from_env || from_bounds {
+            return Some(Solution::Assumption);
+        }
+        for impl_block in candidate_impls(self.db, self.env, goal) {
+            if let Some(substs) = self.match_impl(&impl_block, goal, depth) {
+                return Some(Solution::Impl(impl_block, substs));
+            }
+        }
+        None
+    }
+
+    /// Checks whether `impl_block` implements `goal`, returning the values of
+    /// the impl's type parameters if so.
+    fn match_impl(&self, impl_block: &ImplBlock, goal: &TraitRef, depth: usize) -> Option<Substs> {
+        let impl_trait_ref = TraitRef::for_impl(self.db, impl_block)?;
+        if impl_trait_ref.trait_ != goal.trait_ {
+            return None;
+        }
+        let generics = impl_block.generic_params();
+        let mut subst = vec![None; generics.params.len()];
+        if !match_trait_ref(&impl_trait_ref, goal, &mut subst) {
+            return None;
+        }
+        let substs = Substs(
+            subst
+                .into_iter()
+                .map(|ty| ty.unwrap_or(Ty::Unknown))
+                .collect::<Vec<_>>()
+                .into(),
+        );
+        let module = impl_block.module();
+        for pred in generics.where_predicates.iter() {
+            let pred = match TraitRef::from_where_predicate(
+                self.db,
+                &module,
+                Some(impl_block),
+                generics,
+                pred,
+            ) {
+                Some(it) => it.subst(&substs),
+                None => continue,
+            };
+            // We can't check anything about unknown types, so we are
+            // optimistic here.
+            match pred.self_ty {
+                Ty::Unknown | Ty::Infer(_) => continue,
+                _ => {}
+            }
+            self.solve(&pred, depth + 1)?;
+        }
+        Some(substs)
+    }
+}
+
+/// The values of the type parameters of `impl_block` for which its self type
+/// is `self_ty`. Parameters which are not determined by the self type are
+/// unknown.
+pub(crate) fn impl_substs_for_self_ty(
+    db: &impl HirDatabase,
+    impl_block: &ImplBlock,
+    self_ty: &Ty,
+) -> Substs {
+    let module = impl_block.module();
+    let generics = impl_block.generic_params();
+    let target_ty = Ty::from_hir(
+        db,
+        &module,
+        Some(impl_block),
+        generics,
+        impl_block.target_type(),
+    );
+    let mut subst = vec![None; generics.params.len()];
+    match_ty(&target_ty, self_ty, &mut subst);
+    Substs(
+        subst
+            .into_iter()
+            .map(|ty| ty.unwrap_or(Ty::Unknown))
+            .collect::<Vec<_>>()
+            .into(),
+    )
+}
+
+/// The impls which could implement `goal`. Because of the orphan rules, they
+/// have to be in the crate of the trait or of one of the types in `goal`.
+/// Impls for types of other crates, like primitives, can only be in the
+/// current crate, if they are not in the trait's.
+fn candidate_impls(db: &impl HirDatabase, env: &Environment, goal: &TraitRef) -> Vec<ImplBlock> {
+    let mut crates: Vec<Crate> = Vec::new();
+    crates.extend(goal.trait_.module(db).krate(db));
+    crates.extend(env.krate);
+    crates.extend(type_crate(db, &goal.self_ty));
+    crates.extend(goal.substs.0.iter().filter_map(|ty| type_crate(db, ty)));
+    crates.sort_by_key(|krate| krate.crate_id());
+    crates.dedup();
+    crates
+        .into_iter()
+        .flat_map(|krate| {
+            db.impls_in_crate(krate)
+                .lookup_impl_blocks_for_trait(db, goal.trait_)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The crate defining `ty`, looking through references, which are
+/// fundamental: `impl Trait for &Foo` is allowed in the crate of `Foo`.
+fn type_crate(db: &impl HirDatabase, ty: &Ty) -> Option<Crate> {
+    match ty {
+        Ty::Ref(inner, _) => type_crate(db, inner),
+        _ => def_crate(db, ty),
+    }
+}
+
+fn match_trait_ref(pattern: &TraitRef, goal: &TraitRef, subst: &mut [Option<Ty>]) -> bool {
+    match_ty(&pattern.self_ty, &goal.self_ty, subst)
+        && pattern
+            .substs
+            .0
+            .iter()
+            .zip(goal.substs.0.iter())
+            .all(|(p, g)| match_ty(p, g, subst))
+}
+
+/// Matches `pattern`, which contains the type parameters of an impl, against
+/// `ty`, recording the values of the parameters in `subst`. Type parameters
+/// which are not covered by `subst` only match themselves; unknown types match
+/// anything.
+fn match_ty(pattern: &Ty, ty: &Ty, subst: &mut [Option<Ty>]) -> bool {
+    match (pattern, ty) {
+        (Ty::Param { idx, .. }, _) if (*idx as usize) < subst.len() => {
+            match &subst[*idx as usize] {
+                Some(value) => match_ty(value, ty, &mut []),
+                None => {
+                    subst[*idx as usize] = Some(ty.clone());
+                    true
+                }
+            }
+        }
+        (Ty::Unknown, _) | (_, Ty::Unknown) | (Ty::Infer(_), _) | (_, Ty::Infer(_)) => true,
+        (Ty::Param { idx: idx1, .. }, Ty::Param { idx: idx2, .. }) => idx1 == idx2,
+        (Ty::Int(int1), Ty::Int(int2)) => {
+            use super::primitive::UncertainIntTy::Unknown;
+            *int1 == Unknown || *int2 == Unknown || int1 == int2
+        }
+        (Ty::Float(float1), Ty::Float(float2)) => {
+            use super::primitive::UncertainFloatTy::Unknown;
+            *float1 == Unknown || *float2 == Unknown || float1 == float2
+        }
+        (
+            Ty::Adt {
+                def_id: def_id1,
+                substs: substs1,
+                ..
+            },
+            Ty::Adt {
+                def_id: def_id2,
+                substs: substs2,
+                ..
+            },
+        ) => def_id1 == def_id2 && match_tys(&substs1.0, &substs2.0, subst),
+        (Ty::Slice(t1), Ty::Slice(t2)) => match_ty(t1, t2, subst),
+        (Ty::Array(t1, len1), Ty::Array(t2, len2)) => len1 == len2 && match_ty(t1, t2, subst),
+        (Ty::RawPtr(t1, mut1), Ty::RawPtr(t2, mut2)) | (Ty::Ref(t1, mut1), Ty::Ref(t2, mut2)) => {
+            mut1 == mut2 && match_ty(t1, t2, subst)
+        }
+        (Ty::Tuple(ts1), Ty::Tuple(ts2)) => match_tys(ts1, ts2, subst),
+        (Ty::FnPtr(sig1), Ty::FnPtr(sig2)) => {
+            match_tys(&sig1.input, &sig2.input, subst)
+                && match_ty(&sig1.output, &sig2.output, subst)
+        }
+        _ => pattern == ty,
+    }
+}
+
+fn match_tys(patterns: &[Ty], tys: &[Ty], subst: &mut [Option<Ty>]) -> bool {
+    patterns.len() == tys.len()
+        && patterns
+            .iter()
+            .zip(tys.iter())
+            .all(|(pattern, ty)| match_ty(pattern, ty, subst))
+}
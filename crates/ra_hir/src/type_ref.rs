@@ -1,9 +1,19 @@
 //! HIR for references to types. Paths in these are not yet resolved. They can
 //! be directly created from an ast::TypeRef, without further queries.
 
+use std::{
+    hash::{Hash, Hasher},
+    mem,
+    sync::Arc,
+};
+
 use ra_syntax::ast;
 
-use crate::Path;
+use crate::{
+    Path, PathKind,
+    path::GenericArg,
+    expr::{self, Body},
+};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Mutability {
@@ -35,6 +45,31 @@ impl Mutability {
     }
 }
 
+/// The length of an array type, like `N * 2` in `[u8; N * 2]`. The expression
+/// is lowered into a body of its own, which is evaluated when lowering the
+/// type.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ArrayLen {
+    Expr(Arc<Body>),
+    Unknown,
+}
+
+impl ArrayLen {
+    fn from_ast_opt(expr: Option<&ast::Expr>) -> ArrayLen {
+        match expr {
+            Some(expr) => ArrayLen::Expr(expr::collect_type_expr(expr)),
+            None => ArrayLen::Unknown,
+        }
+    }
+}
+
+// `Body` can't be hashed, but lengths which are equal still hash the same.
+impl Hash for ArrayLen {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+    }
+}
+
 /// Compare ty::Ty
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum TypeRef {
@@ -44,13 +79,15 @@ pub enum TypeRef {
     Path(Path),
     RawPtr(Box<TypeRef>, Mutability),
     Reference(Box<TypeRef>, Mutability),
-    Array(Box<TypeRef> /*, Expr*/),
+    Array(Box<TypeRef>, ArrayLen),
     Slice(Box<TypeRef>),
     /// A fn pointer. Last element of the vector is the return type.
     Fn(Vec<TypeRef>),
     // For
-    // ImplTrait,
-    // DynTrait,
+    /// An opaque type with the given trait bounds, like `impl Iterator<Item = u32>`.
+    ImplTrait(Vec<Path>),
+    /// A trait object with the given trait bounds, like `dyn Fn(i32) + Send`.
+    DynTrait(Vec<Path>),
     Error,
 }
 
@@ -72,7 +109,10 @@ impl TypeRef {
                 let mutability = Mutability::from_mutable(inner.is_mut());
                 TypeRef::RawPtr(Box::new(inner_ty), mutability)
             }
-            ArrayType(inner) => TypeRef::Array(Box::new(TypeRef::from_ast_opt(inner.type_ref()))),
+            ArrayType(inner) => TypeRef::Array(
+                Box::new(TypeRef::from_ast_opt(inner.type_ref())),
+                ArrayLen::from_ast_opt(inner.expr()),
+            ),
             SliceType(inner) => TypeRef::Slice(Box::new(TypeRef::from_ast_opt(inner.type_ref()))),
             ReferenceType(inner) => {
                 let inner_ty = TypeRef::from_ast_opt(inner.type_ref());
@@ -95,8 +135,8 @@ impl TypeRef {
             }
             // for types are close enough for our purposes to the inner type for now...
             ForType(inner) => TypeRef::from_ast_opt(inner.type_ref()),
-            ImplTraitType(_inner) => TypeRef::Error,
-            DynTraitType(_inner) => TypeRef::Error,
+            ImplTraitType(inner) => TypeRef::ImplTrait(bound_paths(inner.bounds())),
+            DynTraitType(inner) => TypeRef::DynTrait(bound_paths(inner.bounds())),
         }
     }
 
@@ -111,4 +151,59 @@ impl TypeRef {
     pub fn unit() -> TypeRef {
         TypeRef::Tuple(Vec::new())
     }
+
+    /// Calls `f` for each path in the type, including the ones in generic
+    /// arguments, like `Foo` and `Bar` in `Vec<(Foo, Bar)>`.
+    pub(crate) fn walk_paths(&self, f: &mut impl FnMut(&Path)) {
+        match self {
+            TypeRef::Path(path) => walk_path(path, f),
+            TypeRef::ImplTrait(paths) | TypeRef::DynTrait(paths) => {
+                for path in paths {
+                    walk_path(path, f);
+                }
+            }
+            TypeRef::Tuple(inner) | TypeRef::Fn(inner) => {
+                for type_ref in inner {
+                    type_ref.walk_paths(f);
+                }
+            }
+            TypeRef::RawPtr(inner, _)
+            | TypeRef::Reference(inner, _)
+            | TypeRef::Array(inner, _)
+            | TypeRef::Slice(inner) => inner.walk_paths(f),
+            TypeRef::Never | TypeRef::Placeholder | TypeRef::Error => {}
+        }
+    }
+}
+
+fn walk_path(path: &Path, f: &mut impl FnMut(&Path)) {
+    f(path);
+    if let PathKind::Type {
+        self_type,
+        trait_ref,
+    } = &path.kind
+    {
+        self_type.walk_paths(f);
+        if let Some(trait_ref) = trait_ref {
+            walk_path(trait_ref, f);
+        }
+    }
+    for segment in &path.segments {
+        let args = match &segment.args_and_bindings {
+            Some(it) => it,
+            None => continue,
+        };
+        for arg in &args.args {
+            match arg {
+                GenericArg::Type(type_ref) => type_ref.walk_paths(f),
+            }
+        }
+        for (_, type_ref) in &args.bindings {
+            type_ref.walk_paths(f);
+        }
+    }
+}
+
+fn bound_paths(bounds: Vec<&ast::Path>) -> Vec<Path> {
+    bounds.into_iter().filter_map(Path::from_ast).collect()
 }
@@ -0,0 +1,121 @@
+//! Finds the operations in a body which are only allowed in unsafe code:
+//! calls to `unsafe fn`s, dereferences of raw pointers, uses of `static mut`s
+//! and reads of union fields.
+use std::sync::Arc;
+
+use crate::{
+    HirDatabase, DefWithBody, ModuleDef,
+    adt::VariantDef,
+    expr::{Body, Expr, ExprId, UnaryOp, BinaryOp},
+    ty::{Ty, InferenceResult},
+};
+
+/// An operation which is only allowed in unsafe code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsafeOp {
+    /// A call to an `unsafe fn` or an `unsafe` method.
+    CallUnsafeFn,
+    /// A dereference of a raw pointer, like `*ptr`.
+    DerefRawPtr,
+    /// A use of a `static mut`.
+    UseMutStatic,
+    /// A read of a union field. Assigning to a union field is safe.
+    ReadUnionField,
+}
+
+/// An unsafe operation in a body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsafeExpr {
+    pub expr: ExprId,
+    pub op: UnsafeOp,
+    /// Whether the operation is inside an `unsafe` block or `unsafe fn`,
+    /// where it is allowed.
+    pub in_unsafe_context: bool,
+}
+
+pub(crate) fn unsafe_exprs_query(db: &impl HirDatabase, def: DefWithBody) -> Arc<Vec<UnsafeExpr>> {
+    let body = def.body(db);
+    let infer = def.infer(db);
+    let in_unsafe_fn = match def {
+        DefWithBody::Function(func) => func.signature(db).is_unsafe(),
+        _ => false,
+    };
+    let mut res = Vec::new();
+    walk_unsafe(db, &body, &infer, body.body_expr(), in_unsafe_fn, &mut res);
+    Arc::new(res)
+}
+
+fn walk_unsafe(
+    db: &impl HirDatabase,
+    body: &Body,
+    infer: &InferenceResult,
+    expr: ExprId,
+    in_unsafe_context: bool,
+    acc: &mut Vec<UnsafeExpr>,
+) {
+    let op = match &body[expr] {
+        Expr::Call { callee, .. } => match infer.path_resolution(*callee) {
+            Some(ModuleDef::Function(func)) if func.signature(db).is_unsafe() => {
+                Some(UnsafeOp::CallUnsafeFn)
+            }
+            _ => None,
+        },
+        Expr::MethodCall { .. } => match infer.method_resolution(expr) {
+            Some(func) if func.signature(db).is_unsafe() => Some(UnsafeOp::CallUnsafeFn),
+            _ => None,
+        },
+        Expr::UnaryOp {
+            expr: inner,
+            op: UnaryOp::Deref,
+        } => match infer[*inner] {
+            Ty::RawPtr(..) => Some(UnsafeOp::DerefRawPtr),
+            _ => None,
+        },
+        Expr::Path(_) => match infer.path_resolution(expr) {
+            Some(ModuleDef::Static(it)) if it.is_mut(db) => Some(UnsafeOp::UseMutStatic),
+            _ => None,
+        },
+        Expr::Field { .. } if is_union_field(db, infer, expr) => Some(UnsafeOp::ReadUnionField),
+        _ => None,
+    };
+    if let Some(op) = op {
+        acc.push(UnsafeExpr {
+            expr,
+            op,
+            in_unsafe_context,
+        });
+    }
+
+    let in_unsafe_context = in_unsafe_context
+        || match &body[expr] {
+            Expr::Block { is_unsafe, .. } => *is_unsafe,
+            _ => false,
+        };
+    match &body[expr] {
+        // Assigning to a union field doesn't read it, so only the union
+        // itself is visited.
+        Expr::BinaryOp {
+            lhs,
+            rhs,
+            op: Some(BinaryOp::Assignment),
+        } if is_union_field(db, infer, *lhs) => {
+            if let Expr::Field { expr: union, .. } = &body[*lhs] {
+                walk_unsafe(db, body, infer, *union, in_unsafe_context, acc);
+            }
+            walk_unsafe(db, body, infer, *rhs, in_unsafe_context, acc);
+        }
+        it => {
+            it.walk_child_exprs(|child| walk_unsafe(db, body, infer, child, in_unsafe_context, acc))
+        }
+    }
+}
+
+fn is_union_field(db: &impl HirDatabase, infer: &InferenceResult, expr: ExprId) -> bool {
+    match infer.field_resolution(expr) {
+        Some(field) => match field.parent {
+            VariantDef::Struct(s) => s.is_union(db),
+            VariantDef::EnumVariant(_) => false,
+        },
+        None => false,
+    }
+}
@@ -1,4 +1,5 @@
-use hir::{Ty, AdtDef, Docs};
+use rustc_hash::FxHashSet;
+use hir::{Ty, AdtDef, Docs, Function};
 
 use crate::completion::{CompletionContext, Completions, CompletionItem, CompletionItemKind};
 use crate::completion::completion_item::CompletionKind;
@@ -19,7 +20,7 @@ pub(super) fn complete_dot(acc: &mut Completions, ctx: &CompletionContext) {
     if !ctx.is_call {
         complete_fields(acc, ctx, receiver_ty.clone());
     }
-    complete_methods(acc, ctx, receiver_ty);
+    complete_methods(acc, ctx, function, receiver_ty);
 }
 
 fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty) {
@@ -64,10 +65,23 @@ fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty)
     }
 }
 
-fn complete_methods(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty) {
-    receiver.iterate_methods(ctx.db, |func| {
+fn complete_methods(
+    acc: &mut Completions,
+    ctx: &CompletionContext,
+    function: &Function,
+    receiver: Ty,
+) {
+    let module = match ctx.module {
+        Some(it) => it,
+        None => return,
+    };
+    let env = function.environment(ctx.db);
+    // The same trait method can be found for several autoderef and autoref
+    // steps.
+    let mut seen_methods = FxHashSet::default();
+    receiver.iterate_methods(ctx.db, module, &env, |_ty, func| {
         let sig = func.signature(ctx.db);
-        if sig.has_self_param() {
+        if sig.has_self_param() && seen_methods.insert(func) {
             CompletionItem::new(
                 CompletionKind::Reference,
                 ctx.source_range(),
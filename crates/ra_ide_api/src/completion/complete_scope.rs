@@ -48,6 +48,8 @@ fn complete_fn(
     scopes
         .scope_chain_for_offset(ctx.offset)
         .flat_map(|scope| scopes.scopes.entries(scope).iter())
+        // Bindings introduced by macros are not visible in the source file.
+        .filter(|entry| entry.hygiene() == hir::SyntaxContext::ROOT)
         .filter(|entry| shadowed.insert(entry.name()))
         .for_each(|entry| {
             CompletionItem::new(
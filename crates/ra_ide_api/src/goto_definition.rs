@@ -1,7 +1,8 @@
 use ra_db::{FileId, SyntaxDatabase};
 use ra_syntax::{
-    AstNode, ast,
-    algo::find_node_at_offset,
+    AstNode, SyntaxNode, TextUnit, ast,
+    SyntaxKind::{IDENT, TOKEN_TREE, LIFETIME, BREAK_EXPR, CONTINUE_EXPR, BLOCK},
+    algo::{find_node_at_offset, find_leaf_at_offset},
 };
 use test_utils::tested_by;
 
@@ -21,9 +22,65 @@ pub(crate) fn goto_definition(
         let navs = name_definition(db, position.file_id, name)?;
         return Some(RangeInfo::new(name.syntax().range(), navs));
     }
+    if let Some(lifetime) = find_label_ref(syntax, position.offset) {
+        let nav = label_definition(db, position.file_id, lifetime)?;
+        return Some(RangeInfo::new(lifetime.range(), vec![nav]));
+    }
+    if let Some(ident) = find_ident_in_macro_call(syntax, position.offset) {
+        let def = hir::source_binder::def_with_body_from_child_node(db, position.file_id, ident)?;
+        let entry = def.scopes(db).resolve_local_name_in_macro(ident)?;
+        let nav = NavigationTarget::from_scope_entry(position.file_id, &entry);
+        return Some(RangeInfo::new(ident.range(), vec![nav]));
+    }
     None
 }
 
+/// Finds an identifier inside the token tree of a macro call, like `x` in
+/// `println!("{}", x)`.
+pub(crate) fn find_ident_in_macro_call(
+    syntax: &SyntaxNode,
+    offset: TextUnit,
+) -> Option<&SyntaxNode> {
+    find_leaf_at_offset(syntax, offset)
+        .find(|leaf| leaf.kind() == IDENT && leaf.parent().map(|it| it.kind()) == Some(TOKEN_TREE))
+}
+
+/// Finds the label of a `break` or `continue`, like `'outer` in
+/// `break 'outer`.
+fn find_label_ref(syntax: &SyntaxNode, offset: TextUnit) -> Option<&SyntaxNode> {
+    find_leaf_at_offset(syntax, offset).find(|leaf| {
+        leaf.kind() == LIFETIME
+            && leaf.parent().map_or(false, |it| {
+                it.kind() == BREAK_EXPR || it.kind() == CONTINUE_EXPR
+            })
+    })
+}
+
+/// Finds the label of the loop or block which the `break` or `continue`
+/// with the label `lifetime` leaves.
+fn label_definition(
+    db: &RootDatabase,
+    file_id: FileId,
+    lifetime: &SyntaxNode,
+) -> Option<NavigationTarget> {
+    let expr = lifetime.parent().and_then(ast::Expr::cast)?;
+    let def = hir::source_binder::def_with_body_from_child_node(db, file_id, expr.syntax())?;
+    let syntax_mapping = def.body_syntax_mapping(db);
+    let target = syntax_mapping
+        .body()
+        .break_target(syntax_mapping.node_expr(expr)?)?;
+    let file = db.source_file(file_id);
+    let target = syntax_mapping.expr_syntax(target)?.to_node(&file);
+    // labeled blocks are mapped to the block, without the label
+    let target = if target.kind() == BLOCK {
+        target.parent()?
+    } else {
+        target
+    };
+    let label = target.children().find_map(ast::Label::cast)?;
+    Some(NavigationTarget::from_label(file_id, label))
+}
+
 pub(crate) enum ReferenceResult {
     Exact(NavigationTarget),
     Approximate(Vec<NavigationTarget>),
@@ -45,10 +102,10 @@ pub(crate) fn reference_definition(
     name_ref: &ast::NameRef,
 ) -> ReferenceResult {
     use self::ReferenceResult::*;
-    if let Some(function) =
-        hir::source_binder::function_from_child_node(db, file_id, name_ref.syntax())
+    if let Some(def) =
+        hir::source_binder::def_with_body_from_child_node(db, file_id, name_ref.syntax())
     {
-        let scope = function.scopes(db);
+        let scope = def.scopes(db);
         // First try to resolve the symbol locally
         if let Some(entry) = scope.resolve_local_name(name_ref) {
             let nav = NavigationTarget::from_scope_entry(file_id, &entry);
@@ -62,8 +119,8 @@ pub(crate) fn reference_definition(
             .and_then(ast::MethodCallExpr::cast)
         {
             tested_by!(goto_definition_works_for_methods);
-            let infer_result = function.infer(db);
-            let syntax_mapping = function.body_syntax_mapping(db);
+            let infer_result = def.infer(db);
+            let syntax_mapping = def.body_syntax_mapping(db);
             let expr = ast::Expr::cast(method_call.syntax()).unwrap();
             if let Some(func) = syntax_mapping
                 .node_expr(expr)
@@ -75,8 +132,8 @@ pub(crate) fn reference_definition(
         // It could also be a field access
         if let Some(field_expr) = name_ref.syntax().parent().and_then(ast::FieldExpr::cast) {
             tested_by!(goto_definition_works_for_fields);
-            let infer_result = function.infer(db);
-            let syntax_mapping = function.body_syntax_mapping(db);
+            let infer_result = def.infer(db);
+            let syntax_mapping = def.body_syntax_mapping(db);
             let expr = ast::Expr::cast(field_expr.syntax()).unwrap();
             if let Some(field) = syntax_mapping
                 .node_expr(expr)
@@ -130,9 +187,13 @@ fn name_definition(
 
 #[cfg(test)]
 mod tests {
+    use ra_db::CrateGraph;
     use test_utils::covers;
 
-    use crate::mock_analysis::analysis_and_position;
+    use crate::{
+        AnalysisChange,
+        mock_analysis::{analysis_and_position, MockAnalysis},
+    };
 
     fn check_goto(fixuture: &str, expected: &str) {
         let (analysis, pos) = analysis_and_position(fixuture);
@@ -155,6 +216,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_definition_works_in_builtin_macros() {
+        check_goto(
+            "
+            //- /lib.rs
+            fn foo() {
+                let x = 92;
+                println!(\"{}\", x<|>);
+            }
+            ",
+            "x NAME FileId(1) [19; 20)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_respects_macro_hygiene() {
+        check_goto(
+            "
+            //- /lib.rs
+            fn foo() {
+                let left_val = 92;
+                assert_eq!(1, 2, \"{}\", left_val<|>);
+            }
+            ",
+            "left_val NAME FileId(1) [19; 27)",
+        );
+    }
+
     #[test]
     fn goto_definition_resolves_correct_name() {
         check_goto(
@@ -215,6 +304,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_definition_works_for_derived_methods() {
+        let (mock, pos) = MockAnalysis::with_files_and_position(
+            "
+            //- /main.rs
+            #[derive(Clone)]
+            struct Foo;
+            fn bar(foo: &Foo) {
+                foo.clone<|>();
+            }
+
+            //- /std.rs
+            pub mod prelude {
+                pub mod v1 {
+                    pub use crate::clone::Clone;
+                }
+            }
+            pub mod clone {
+                pub trait Clone {
+                    fn clone(&self) -> Self;
+                }
+            }
+            ",
+        );
+        let main = mock.id_of("/main.rs");
+        let std = mock.id_of("/std.rs");
+        let mut host = mock.analysis_host();
+
+        // the derived impls come from the `std` in the extern prelude
+        let mut crate_graph = CrateGraph::default();
+        let main_crate = crate_graph.add_crate_root(main);
+        let std_crate = crate_graph.add_crate_root(std);
+        crate_graph
+            .add_dep(main_crate, "std".into(), std_crate)
+            .unwrap();
+        let mut change = AnalysisChange::new();
+        change.set_crate_graph(crate_graph);
+        host.apply_change(change);
+
+        let mut navs = host.analysis().goto_definition(pos).unwrap().unwrap().info;
+        assert_eq!(navs.len(), 1);
+        navs.pop()
+            .unwrap()
+            .assert_match("clone FN_DEF FileId(2) [126; 150) [129; 134)");
+    }
+
+    #[test]
+    fn goto_definition_works_for_trait_methods_in_scope() {
+        check_goto(
+            "
+            //- /lib.rs
+            mod ext {
+                pub trait Ext {
+                    fn ext(&self) {}
+                }
+                impl Ext for u32 {}
+            }
+            use ext::Ext;
+            fn bar(x: u32) {
+                x.ext<|>();
+            }
+            ",
+            "ext FN_DEF FileId(1) [38; 54) [41; 44)",
+        );
+    }
+
     #[test]
     fn goto_definition_works_for_fields() {
         covers!(goto_definition_works_for_fields);
@@ -232,4 +387,32 @@ mod tests {
             "spam NAMED_FIELD_DEF FileId(1) [17; 26) [17; 21)",
         );
     }
+
+    #[test]
+    fn goto_definition_works_for_labels() {
+        check_goto(
+            "
+            //- /lib.rs
+            fn foo() {
+                'outer: loop {
+                    'inner: loop {
+                        break 'outer<|>;
+                    }
+                }
+            }
+            ",
+            "'outer LABEL FileId(1) [15; 22) [15; 21)",
+        );
+        check_goto(
+            "
+            //- /lib.rs
+            fn foo() {
+                let x = 'blk: {
+                    break 'blk<|> 1;
+                };
+            }
+            ",
+            "'blk LABEL FileId(1) [23; 28) [23; 27)",
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use ra_db::{SyntaxDatabase};
+use ra_db::{SyntaxDatabase, FileId};
 use ra_syntax::{
     AstNode, SyntaxNode, TreeArc, ast,
     algo::{find_covering_node, find_node_at_offset, find_leaf_at_offset, visit::{visitor, Visitor}},
@@ -62,10 +62,9 @@ pub(crate) fn type_of(db: &RootDatabase, frange: FileRange) -> Option<String> {
         .take_while(|it| it.range() == leaf_node.range())
         .find(|&it| ast::Expr::cast(it).is_some() || ast::Pat::cast(it).is_some())
         .unwrap_or(leaf_node);
-    let parent_fn = node.ancestors().find_map(ast::FnDef::cast)?;
-    let function = hir::source_binder::function_from_source(db, frange.file_id, parent_fn)?;
-    let infer = function.infer(db);
-    let syntax_mapping = function.body_syntax_mapping(db);
+    let def = hir::source_binder::def_with_body_from_child_node(db, frange.file_id, node)?;
+    let infer = def.infer(db);
+    let syntax_mapping = def.body_syntax_mapping(db);
     if let Some(expr) = ast::Expr::cast(node).and_then(|e| syntax_mapping.node_expr(e)) {
         Some(infer[expr].to_string())
     } else if let Some(pat) = ast::Pat::cast(node).and_then(|p| syntax_mapping.node_pat(p)) {
@@ -147,12 +146,34 @@ impl NavigationTarget {
             .visit(|node: &ast::TraitDef| visit_node(node, "trait "))
             .visit(|node: &ast::Module| visit_node(node, "mod "))
             .visit(|node: &ast::TypeDef| visit_node(node, "type "))
-            .visit(|node: &ast::ConstDef| visit_node(node, "const "))
+            .visit(|node: &ast::ConstDef| {
+                let mut string = visit_node(node, "const ")?;
+                if let Some(type_ref) = node.type_ref() {
+                    string.push_str(": ");
+                    type_ref.syntax().text().push_to(&mut string);
+                }
+                if let Some(value) = const_value(db, self.file_id(), node) {
+                    string.push_str(&format!(" = {}", value));
+                }
+                Some(string)
+            })
             .visit(|node: &ast::StaticDef| visit_node(node, "static "))
             .accept(&node)?
     }
 }
 
+/// Evaluates the value of a constant, so it can be shown alongside its name.
+fn const_value(
+    db: &RootDatabase,
+    file_id: FileId,
+    node: &ast::ConstDef,
+) -> Option<hir::ConstValue> {
+    match hir::source_binder::def_with_body_from_child_node(db, file_id, node.syntax())? {
+        hir::DefWithBody::Const(c) => c.value(db),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ra_syntax::TextRange;
@@ -188,6 +209,29 @@ mod tests {
         assert_eq!(hover.info, "i32");
     }
 
+    #[test]
+    fn hover_inside_static_initializer() {
+        let (analysis, position) = single_file_with_position("static TABLE: [u8; 4] = [0<|>; 4];");
+        let hover = analysis.hover(position).unwrap().unwrap();
+        assert_eq!(hover.info, "u8");
+    }
+
+    #[test]
+    fn hover_shows_value_of_a_const() {
+        let (analysis, position) = single_file_with_position(
+            "
+            const WORD: usize = 4 * 8;
+            const BITS: usize = WORD * 8;
+
+            fn main() {
+                let bits = BI<|>TS;
+            }
+            ",
+        );
+        let hover = analysis.hover(position).unwrap().unwrap();
+        assert_eq!(hover.info, "```rust\nconst BITS: usize = 256\n```");
+    }
+
     #[test]
     fn test_type_of_for_function() {
         let (analysis, range) = single_file_with_range(
@@ -1,23 +1,30 @@
 use std::sync::Arc;
 
 use hir::{
-    self, Problem, source_binder
+    self, Problem, BodyProblem, InferenceDiagnostic, UnsafeOp, MutabilityDiagnostic, Mutation,
+    source_binder
 };
 use ra_db::{
     FilesDatabase, SourceRoot, SourceRootId, SyntaxDatabase,
     salsa::{Database, SweepStrategy},
 };
-use ra_ide_api_light::{self, assists, LocalEdit, Severity};
+use ra_ide_api_light::{self, assists, leading_indent, LocalEdit, Severity};
 use ra_syntax::{
     algo::find_node_at_offset, ast::{self, NameOwner}, AstNode,
-    SourceFile,
+    SourceFile, SyntaxNode,
     TextRange,
+    SyntaxKind::{
+        FN_DEF, CONST_DEF, STATIC_DEF, ENUM_VARIANT, LIFETIME, WHITESPACE, COMMENT, COMMA,
+        BLOCK_EXPR, PATH_EXPR, SELF_KW, MUT_KW,
+    },
+    Direction,
 };
+use ra_text_edit::TextEditBuilder;
 
 use crate::{
     AnalysisChange,
     CrateId, db, Diagnostic, FileId, FilePosition, FileRange, FileSystemEdit,
-    Query, RootChange, SourceChange, SourceFileEdit,
+    Query, RootChange, SourceChange, SourceFileEdit, goto_definition,
     symbol_index::{FileSymbol, SymbolsDatabase},
 };
 
@@ -35,6 +42,11 @@ impl db::RootDatabase {
             }
             self.set_local_roots(Arc::new(local_roots));
         }
+        for (root_id, path) in change.root_paths {
+            let mut source_root = SourceRoot::clone(&self.source_root(root_id));
+            source_root.path = Some(path);
+            self.set_source_root(root_id, Arc::new(source_root));
+        }
 
         for (root_id, root_change) in change.roots_changed {
             self.apply_root_change(root_id, root_change);
@@ -46,7 +58,6 @@ impl db::RootDatabase {
             let mut libraries = Vec::clone(&self.library_roots());
             for library in change.libraries_added {
                 libraries.push(library.root_id);
-                self.set_source_root(library.root_id, Default::default());
                 self.set_constant_library_symbols(library.root_id, Arc::new(library.symbol_index));
                 self.apply_root_change(library.root_id, library.root_change);
             }
@@ -138,13 +149,24 @@ impl db::RootDatabase {
                 )?;
                 return Some((binding, descr));
             };
-            let name_ref = find_node_at_offset::<ast::NameRef>(syntax, position.offset)?;
-            let descr =
-                source_binder::function_from_child_node(db, position.file_id, name_ref.syntax())?;
-            let scope = descr.scopes(db);
-            let resolved = scope.resolve_local_name(name_ref)?;
-            let resolved = resolved.ptr().to_node(source_file);
-            let binding = find_node_at_offset::<ast::BindPat>(syntax, resolved.range().end())?;
+            let (resolved, descr) = if let Some(name_ref) =
+                find_node_at_offset::<ast::NameRef>(syntax, position.offset)
+            {
+                let descr = source_binder::function_from_child_node(
+                    db,
+                    position.file_id,
+                    name_ref.syntax(),
+                )?;
+                (descr.scopes(db).resolve_local_name(name_ref)?, descr)
+            } else {
+                let ident = goto_definition::find_ident_in_macro_call(syntax, position.offset)?;
+                let descr = source_binder::function_from_child_node(db, position.file_id, ident)?;
+                (descr.scopes(db).resolve_local_name_in_macro(ident)?, descr)
+            };
+            // The binding might come from a macro expansion, so don't use
+            // `to_node` here.
+            let binding =
+                find_node_at_offset::<ast::BindPat>(syntax, resolved.ptr().range().end())?;
             Some((binding, descr))
         }
     }
@@ -210,6 +232,306 @@ impl db::RootDatabase {
                 res.push(diag)
             }
         };
+        res.extend(self.body_diagnostics(file_id, &syntax));
+        res
+    }
+
+    /// The problems in the bodies of the functions, consts, statics and enum
+    /// discriminants of the file.
+    fn body_diagnostics(&self, file_id: FileId, file: &SourceFile) -> Vec<Diagnostic> {
+        let mut res = Vec::new();
+        let def_kinds = [FN_DEF, CONST_DEF, STATIC_DEF, ENUM_VARIANT];
+        for node in file
+            .syntax()
+            .descendants()
+            .filter(|it| def_kinds.contains(&it.kind()))
+        {
+            let def = match source_binder::def_with_body_from_child_node(self, file_id, node) {
+                Some(it) => it,
+                None => continue,
+            };
+            let syntax_mapping = def.body_syntax_mapping(self);
+            for problem in syntax_mapping.body().problems() {
+                let (expr, message) = match problem {
+                    BodyProblem::UnresolvedLabel { expr, label } => {
+                        (expr, format!("use of undeclared label `{}`", label))
+                    }
+                    BodyProblem::ContinueToBlock { expr, label } => (
+                        expr,
+                        format!("`continue` pointing to the labeled block `{}`", label),
+                    ),
+                };
+                let expr = match syntax_mapping.expr_syntax(*expr) {
+                    Some(ptr) => ptr.to_node(file),
+                    None => continue,
+                };
+                let range = expr
+                    .children()
+                    .find(|it| it.kind() == LIFETIME)
+                    .unwrap_or(expr)
+                    .range();
+                res.push(Diagnostic {
+                    range,
+                    message,
+                    severity: Severity::Error,
+                    fix: None,
+                });
+            }
+            for diagnostic in def.infer(self).diagnostics() {
+                let (range, message, fix) = match diagnostic {
+                    InferenceDiagnostic::MismatchedType {
+                        expr,
+                        expected,
+                        actual,
+                    } => {
+                        let expr = match syntax_mapping.expr_syntax(*expr) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        let message = format!(
+                            "mismatched types: expected `{}`, found `{}`",
+                            expected, actual
+                        );
+                        (expr.range(), message, None)
+                    }
+                    InferenceDiagnostic::WrongArgCount {
+                        expr,
+                        expected,
+                        actual,
+                    } => {
+                        let expr = match syntax_mapping.expr_syntax(*expr) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        let message = format!(
+                            "this function takes {} argument{} but {} {} supplied",
+                            expected,
+                            if *expected == 1 { "" } else { "s" },
+                            actual,
+                            if *actual == 1 { "was" } else { "were" }
+                        );
+                        (expr.range(), message, None)
+                    }
+                    InferenceDiagnostic::NoSuchField { expr, field, ty } => {
+                        let expr = match syntax_mapping.expr_syntax(*expr) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        let field_name = field.to_string();
+                        let name_ref = if let Some(field_expr) = ast::FieldExpr::cast(expr) {
+                            field_expr.name_ref()
+                        } else {
+                            ast::StructLit::cast(expr)
+                                .and_then(|it| it.named_field_list())
+                                .and_then(|it| {
+                                    it.fields()
+                                        .filter_map(|it| it.name_ref())
+                                        .find(|it| it.text() == field_name.as_str())
+                                })
+                        };
+                        let range = name_ref.map_or(expr.range(), |it| it.syntax().range());
+                        (
+                            range,
+                            format!("no field `{}` on type `{}`", field, ty),
+                            None,
+                        )
+                    }
+                    InferenceDiagnostic::NoSuchPatField { pat, field, ty } => {
+                        let pat = match syntax_mapping.pat_syntax(*pat) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        let field_name = field.to_string();
+                        let name = ast::StructPat::cast(pat)
+                            .and_then(|it| it.field_pat_list())
+                            .and_then(|it| {
+                                let bind_pats = it.bind_pats().filter_map(|it| it.name());
+                                let field_pats = it.field_pats().filter_map(|it| it.name());
+                                bind_pats
+                                    .chain(field_pats)
+                                    .find(|it| it.text() == field_name.as_str())
+                            });
+                        let range = name.map_or(pat.range(), |it| it.syntax().range());
+                        (
+                            range,
+                            format!("no field `{}` on type `{}`", field, ty),
+                            None,
+                        )
+                    }
+                    InferenceDiagnostic::NoSuchMethod {
+                        expr,
+                        method,
+                        receiver,
+                    } => {
+                        let expr = match syntax_mapping.expr_syntax(*expr) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        let range = ast::MethodCallExpr::cast(expr)
+                            .and_then(|it| it.name_ref())
+                            .map_or(expr.range(), |it| it.syntax().range());
+                        let message =
+                            format!("no method named `{}` found for type `{}`", method, receiver);
+                        (range, message, None)
+                    }
+                    InferenceDiagnostic::NotCallable { callee, ty } => {
+                        let callee = match syntax_mapping.expr_syntax(*callee) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        (
+                            callee.range(),
+                            format!("expected function, found `{}`", ty),
+                            None,
+                        )
+                    }
+                    InferenceDiagnostic::MissingMatchArms { expr, missing } => {
+                        let expr = match syntax_mapping.expr_syntax(*expr) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        let match_expr = match ast::MatchExpr::cast(expr) {
+                            Some(it) => it,
+                            None => continue,
+                        };
+                        let range = match_expr.expr().map_or(expr, |it| it.syntax()).range();
+                        let message = format!(
+                            "non-exhaustive patterns: {} not covered",
+                            describe_patterns(missing)
+                        );
+                        (
+                            range,
+                            message,
+                            fill_match_arms(file_id, match_expr, missing),
+                        )
+                    }
+                    InferenceDiagnostic::UnreachablePattern { pat } => {
+                        let pat = match syntax_mapping.pat_syntax(*pat) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        (pat.range(), "unreachable pattern".to_string(), None)
+                    }
+                };
+                let severity = match diagnostic {
+                    InferenceDiagnostic::UnreachablePattern { .. } => Severity::WeakWarning,
+                    _ => Severity::Error,
+                };
+                res.push(Diagnostic {
+                    range,
+                    message,
+                    severity,
+                    fix,
+                });
+            }
+            for unsafe_expr in def.unsafe_exprs(self).iter() {
+                if unsafe_expr.in_unsafe_context {
+                    continue;
+                }
+                let expr = match syntax_mapping.expr_syntax(unsafe_expr.expr) {
+                    Some(ptr) => ptr.to_node(file),
+                    None => continue,
+                };
+                let op = match unsafe_expr.op {
+                    UnsafeOp::CallUnsafeFn => "call to unsafe function",
+                    UnsafeOp::DerefRawPtr => "dereference of raw pointer",
+                    UnsafeOp::UseMutStatic => "use of mutable static",
+                    UnsafeOp::ReadUnionField => "access to union field",
+                };
+                res.push(Diagnostic {
+                    range: expr.range(),
+                    message: format!("{} is unsafe and requires unsafe function or block", op),
+                    severity: Severity::Error,
+                    fix: None,
+                });
+            }
+            for diagnostic in def.mutability_diagnostics(self) {
+                let diag = match diagnostic {
+                    MutabilityDiagnostic::MutatedImmutable {
+                        expr,
+                        place,
+                        binding,
+                        mutation,
+                    } => {
+                        let (expr, place, binding_node) = match (
+                            syntax_mapping.expr_syntax(expr),
+                            syntax_mapping.expr_syntax(place),
+                            syntax_mapping.pat_syntax(binding),
+                        ) {
+                            (Some(expr), Some(place), Some(binding)) => (
+                                expr.to_node(file),
+                                place.to_node(file),
+                                binding.to_node(file),
+                            ),
+                            _ => continue,
+                        };
+                        // `mut` goes right before the name, so that a `ref x`
+                        // binding becomes `ref mut x`.
+                        let (name, mut_offset) = match ast::BindPat::cast(binding_node) {
+                            Some(pat) => match pat.name() {
+                                Some(name) => {
+                                    (name.text().to_string(), name.syntax().range().start())
+                                }
+                                None => continue,
+                            },
+                            None => (
+                                binding_node.text().to_string(),
+                                binding_node.range().start(),
+                            ),
+                        };
+                        let place_text = place.text();
+                        let message = match (mutation, place.kind() == PATH_EXPR) {
+                            (Mutation::Assign, true) => {
+                                format!("cannot assign twice to immutable variable `{}`", name)
+                            }
+                            (Mutation::Assign, false) => format!(
+                                "cannot assign to `{}`, as `{}` is not declared as mutable",
+                                place_text, name
+                            ),
+                            (Mutation::BorrowMut, true) => format!(
+                                "cannot borrow `{}` as mutable, as it is not declared as mutable",
+                                name
+                            ),
+                            (Mutation::BorrowMut, false) => format!(
+                                "cannot borrow `{}` as mutable, as `{}` is not declared as mutable",
+                                place_text, name
+                            ),
+                        };
+                        let mut edit = TextEditBuilder::default();
+                        edit.insert(mut_offset, "mut ".to_string());
+                        let edit = LocalEdit {
+                            label: format!("make `{}` mutable", name),
+                            edit: edit.finish(),
+                            cursor_position: None,
+                        };
+                        Diagnostic {
+                            range: expr.range(),
+                            message,
+                            severity: Severity::Error,
+                            fix: Some(SourceChange::from_local_edit(file_id, edit)),
+                        }
+                    }
+                    MutabilityDiagnostic::UnusedMut { binding } => {
+                        let node = match syntax_mapping.pat_syntax(binding) {
+                            Some(ptr) => ptr.to_node(file),
+                            None => continue,
+                        };
+                        // The pattern of `mut self` is just the `self` keyword.
+                        let node = match node.kind() {
+                            SELF_KW => node.parent().unwrap_or(node),
+                            _ => node,
+                        };
+                        Diagnostic {
+                            range: node.range(),
+                            message: "variable does not need to be mutable".to_string(),
+                            severity: Severity::WeakWarning,
+                            fix: remove_mut(file_id, node),
+                        }
+                    }
+                };
+                res.push(diag);
+            }
+        }
         res
     }
 
@@ -230,6 +552,97 @@ impl db::RootDatabase {
     }
 }
 
+/// Removes the `mut` keyword of a binding pattern or `self` parameter.
+fn remove_mut(file_id: FileId, node: &SyntaxNode) -> Option<SourceChange> {
+    let mut_kw = node.children().find(|it| it.kind() == MUT_KW)?;
+    let end = match mut_kw.next_sibling() {
+        Some(ws) if ws.kind() == WHITESPACE => ws.range().end(),
+        _ => mut_kw.range().end(),
+    };
+    let mut edit = TextEditBuilder::default();
+    edit.delete(TextRange::from_to(mut_kw.range().start(), end));
+    let edit = LocalEdit {
+        label: "remove `mut`".to_string(),
+        edit: edit.finish(),
+        cursor_position: None,
+    };
+    Some(SourceChange::from_local_edit(file_id, edit))
+}
+
+/// Lists the patterns in a diagnostic, like "`A`, `B` and 3 more".
+fn describe_patterns(pats: &[String]) -> String {
+    const LISTED: usize = 3;
+    let quoted: Vec<String> = pats.iter().map(|it| format!("`{}`", it)).collect();
+    match quoted.len() {
+        0 => String::new(),
+        1 => quoted[0].clone(),
+        n if n <= LISTED => format!("{} and {}", quoted[..n - 1].join(", "), quoted[n - 1]),
+        n => format!("{} and {} more", quoted[..LISTED].join(", "), n - LISTED),
+    }
+}
+
+/// A fix adding an arm for each of the `missing` patterns at the end of
+/// `match_expr`.
+fn fill_match_arms(
+    file_id: FileId,
+    match_expr: &ast::MatchExpr,
+    missing: &[String],
+) -> Option<SourceChange> {
+    let arm_list = match_expr.match_arm_list()?;
+    let mut edit = TextEditBuilder::default();
+    match arm_list.arms().last() {
+        Some(last_arm) => {
+            let indent = leading_indent(last_arm.syntax());
+            let comma = last_arm
+                .syntax()
+                .siblings(Direction::Next)
+                .skip(1)
+                .find(|it| it.kind() != WHITESPACE && it.kind() != COMMENT)
+                .filter(|it| it.kind() == COMMA);
+            let mut buf = String::new();
+            let offset = match comma {
+                Some(comma) => comma.range().end(),
+                None => {
+                    let is_block = last_arm
+                        .expr()
+                        .map_or(false, |it| it.syntax().kind() == BLOCK_EXPR);
+                    if !is_block {
+                        buf.push(',');
+                    }
+                    last_arm.syntax().range().end()
+                }
+            };
+            for pat in missing {
+                match indent {
+                    Some(indent) => buf.push_str(&format!("\n{}{} => (),", indent, pat)),
+                    None => buf.push_str(&format!(" {} => (),", pat)),
+                }
+            }
+            edit.insert(offset, buf);
+        }
+        None => {
+            let l_curly = arm_list.syntax().first_child()?;
+            let r_curly = arm_list.syntax().last_child()?;
+            let indent = leading_indent(match_expr.syntax()).unwrap_or("");
+            let mut buf = String::new();
+            for pat in missing {
+                buf.push_str(&format!("\n{}    {} => (),", indent, pat));
+            }
+            buf.push_str(&format!("\n{}", indent));
+            edit.replace(
+                TextRange::from_to(l_curly.range().end(), r_curly.range().start()),
+                buf,
+            );
+        }
+    }
+    let edit = LocalEdit {
+        label: "fill match arms".to_string(),
+        edit: edit.finish(),
+        cursor_position: None,
+    };
+    Some(SourceChange::from_local_edit(file_id, edit))
+}
+
 impl SourceChange {
     pub(crate) fn from_local_edit(file_id: FileId, edit: LocalEdit) -> SourceChange {
         let file_edit = SourceFileEdit {
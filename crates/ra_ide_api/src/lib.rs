@@ -29,7 +29,7 @@ mod rename;
 #[cfg(test)]
 mod marks;
 
-use std::{fmt, sync::Arc};
+use std::{fmt, path::PathBuf, sync::Arc};
 
 use ra_syntax::{SourceFile, TreeArc, TextRange, TextUnit};
 use ra_text_edit::TextEdit;
@@ -56,7 +56,7 @@ pub use ra_ide_api_light::{
     LineIndex, LineCol, translate_offset_with_edit,
 };
 pub use ra_db::{
-    Canceled, CrateGraph, CrateId, FileId, FilePosition, FileRange, SourceRootId
+    Canceled, CfgOptions, CrateGraph, CrateId, Edition, FileId, FilePosition, FileRange, SourceRootId
 };
 
 pub type Cancelable<T> = Result<T, Canceled>;
@@ -64,6 +64,7 @@ pub type Cancelable<T> = Result<T, Canceled>;
 #[derive(Default)]
 pub struct AnalysisChange {
     new_roots: Vec<(SourceRootId, bool)>,
+    root_paths: Vec<(SourceRootId, PathBuf)>,
     roots_changed: FxHashMap<SourceRootId, RootChange>,
     files_changed: Vec<(FileId, Arc<String>)>,
     libraries_added: Vec<LibraryData>,
@@ -95,6 +96,9 @@ impl fmt::Debug for AnalysisChange {
         if !self.new_roots.is_empty() {
             d.field("new_roots", &self.new_roots);
         }
+        if !self.root_paths.is_empty() {
+            d.field("root_paths", &self.root_paths);
+        }
         if !self.roots_changed.is_empty() {
             d.field("roots_changed", &self.roots_changed);
         }
@@ -129,6 +133,12 @@ impl AnalysisChange {
         self.new_roots.push((root_id, is_local));
     }
 
+    /// Records the absolute path of the root's directory. This is needed only
+    /// to resolve module paths which point into another source root.
+    pub fn set_root_path(&mut self, root_id: SourceRootId, path: PathBuf) {
+        self.root_paths.push((root_id, path));
+    }
+
     pub fn add_file(
         &mut self,
         root_id: SourceRootId,
@@ -161,6 +171,8 @@ impl AnalysisChange {
             .push(file);
     }
 
+    /// Adds the files of a library root. The root itself should be added with
+    /// `add_root` beforehand.
     pub fn add_library(&mut self, data: LibraryData) {
         self.libraries_added.push(data)
     }
@@ -445,6 +457,12 @@ impl Analysis {
         self.with_db(|db| syntax_highlighting::highlight(db, file_id))
     }
 
+    /// Computes the highlighting of the operations which are only allowed in
+    /// unsafe code in the given file.
+    pub fn highlight_unsafe(&self, file_id: FileId) -> Cancelable<Vec<HighlightedRange>> {
+        self.with_db(|db| syntax_highlighting::highlight_unsafe(db, file_id))
+    }
+
     /// Computes completions at the given position.
     pub fn completions(&self, position: FilePosition) -> Cancelable<Option<Vec<CompletionItem>>> {
         self.with_db(|db| completion::completions(db, position).map(Into::into))
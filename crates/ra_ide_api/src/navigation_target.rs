@@ -1,6 +1,6 @@
 use ra_db::FileId;
 use ra_syntax::{
-    SyntaxNode, AstNode, SmolStr, TextRange, ast,
+    SyntaxNode, AstNode, SmolStr, TextRange, ast::{self, AstToken},
     SyntaxKind::{self, NAME},
 };
 use hir::{ModuleSource, FieldSource};
@@ -69,6 +69,13 @@ impl NavigationTarget {
         }
     }
 
+    pub(crate) fn from_label(file_id: FileId, label: &ast::Label) -> NavigationTarget {
+        let lifetime = label.lifetime();
+        let name = lifetime.map(|it| it.text().clone()).unwrap_or_default();
+        let focus_range = lifetime.map(|it| it.syntax().range());
+        NavigationTarget::from_syntax(file_id, name, focus_range, label.syntax())
+    }
+
     pub(crate) fn from_module(db: &RootDatabase, module: hir::Module) -> NavigationTarget {
         let (file_id, source) = module.definition_source(db);
         let name = module
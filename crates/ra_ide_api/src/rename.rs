@@ -4,14 +4,16 @@ use hir::{
     self, ModuleSource, source_binder::module_from_declaration,
 };
 use ra_syntax::{
-    algo::find_node_at_offset,
+    algo::{find_node_at_offset, find_leaf_at_offset},
     ast,
     AstNode,
-    SyntaxNode
+    SyntaxNode, SyntaxNodePtr,
+    SyntaxKind::{LIFETIME, LABEL, BREAK_EXPR, CONTINUE_EXPR, BLOCK},
 };
 
 use crate::{
     db::RootDatabase,
+    FileId,
     FilePosition,
     FileSystemEdit,
     SourceChange,
@@ -30,6 +32,8 @@ pub(crate) fn rename(
 
     if let Some((ast_name, ast_module)) = find_name_and_module_at_offset(syntax, position) {
         rename_mod(db, ast_name, ast_module, position, new_name)
+    } else if let Some(lifetime) = find_label_at_offset(syntax, position) {
+        rename_label(db, position.file_id, lifetime, new_name)
     } else {
         rename_reference(db, position, new_name)
     }
@@ -103,6 +107,77 @@ fn rename_mod(
     });
 }
 
+/// Finds a label, either where it is declared, like `'outer: loop {}`, or
+/// where it is used, like `break 'outer`.
+fn find_label_at_offset(syntax: &SyntaxNode, position: FilePosition) -> Option<&SyntaxNode> {
+    find_leaf_at_offset(syntax, position.offset).find(|leaf| {
+        leaf.kind() == LIFETIME
+            && leaf.parent().map_or(false, |it| {
+                it.kind() == LABEL || it.kind() == BREAK_EXPR || it.kind() == CONTINUE_EXPR
+            })
+    })
+}
+
+fn rename_label(
+    db: &RootDatabase,
+    file_id: FileId,
+    lifetime: &SyntaxNode,
+    new_name: &str,
+) -> Option<SourceChange> {
+    let def = hir::source_binder::def_with_body_from_child_node(db, file_id, lifetime)?;
+    let syntax_mapping = def.body_syntax_mapping(db);
+    let body = syntax_mapping.body();
+    let parent = lifetime.parent()?;
+    let target = if parent.kind() == LABEL {
+        let labeled = parent.parent()?;
+        // labeled blocks are mapped to the block, without the label
+        let labeled = match ast::BlockExpr::cast(labeled) {
+            Some(block_expr) => block_expr.block()?.syntax(),
+            None => labeled,
+        };
+        syntax_mapping.syntax_expr(SyntaxNodePtr::new(labeled))?
+    } else {
+        body.break_target(syntax_mapping.node_expr(ast::Expr::cast(parent)?)?)?
+    };
+
+    let file = db.source_file(file_id);
+    let target_node = syntax_mapping.expr_syntax(target)?.to_node(&file);
+    let target_node = if target_node.kind() == BLOCK {
+        target_node.parent()?
+    } else {
+        target_node
+    };
+    let label = target_node
+        .children()
+        .find(|it| it.kind() == LABEL)?
+        .children()
+        .find(|it| it.kind() == LIFETIME)?;
+    let break_labels = body.breaks_of(target).filter_map(|expr| {
+        let node = syntax_mapping.expr_syntax(expr)?.to_node(&file);
+        node.children().find(|it| it.kind() == LIFETIME)
+    });
+
+    // The new name may be given with or without the leading `'`.
+    let new_name = if new_name.starts_with('\'') {
+        new_name.to_string()
+    } else {
+        format!("'{}", new_name)
+    };
+    let mut builder = ra_text_edit::TextEditBuilder::default();
+    for lifetime in std::iter::once(label).chain(break_labels) {
+        builder.replace(lifetime.range(), new_name.clone());
+    }
+    Some(SourceChange {
+        label: "rename".to_string(),
+        source_file_edits: vec![SourceFileEdit {
+            file_id,
+            edit: builder.finish(),
+        }],
+        file_system_edits: Vec::new(),
+        cursor_position: None,
+    })
+}
+
 fn rename_reference(
     db: &RootDatabase,
     position: FilePosition,
@@ -173,6 +248,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rename_label_from_break() {
+        test_rename(
+            r#"
+    fn main() {
+        'outer: loop {
+            'inner: while true {
+                'outer: for x in xs {
+                    break 'outer;
+                }
+                continue 'outer<|>;
+            }
+            break 'outer;
+        }
+    }"#,
+            "'done",
+            r#"
+    fn main() {
+        'done: loop {
+            'inner: while true {
+                'outer: for x in xs {
+                    break 'outer;
+                }
+                continue 'done;
+            }
+            break 'done;
+        }
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_label_of_block() {
+        test_rename(
+            r#"
+    fn main() {
+        let x = 'bl<|>k: {
+            if true {
+                break 'blk 1;
+            }
+            2
+        };
+    }"#,
+            "result",
+            r#"
+    fn main() {
+        let x = 'result: {
+            if true {
+                break 'result 1;
+            }
+            2
+        };
+    }"#,
+        );
+    }
+
     #[test]
     fn test_rename_for_param_inside() {
         test_rename(
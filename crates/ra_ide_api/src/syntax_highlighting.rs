@@ -1,5 +1,9 @@
-use ra_syntax::{ast, AstNode,};
-use ra_db::SyntaxDatabase;
+use ra_syntax::{
+    ast, AstNode, SyntaxNode,
+    SyntaxKind::{FN_DEF, CONST_DEF, STATIC_DEF, ENUM_VARIANT},
+};
+use ra_db::{SyntaxDatabase, FilesDatabase, CfgExpr, CfgOptions};
+use hir::source_binder;
 
 use crate::{
     FileId, HighlightedRange,
@@ -28,11 +32,75 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
             res.extend(mapped_ranges);
         }
     }
+    if let Some(&krate) = db.crate_for(file_id).first() {
+        let crate_graph = db.crate_graph();
+        res.extend(highlight_inactive(
+            crate_graph.cfg_options(krate),
+            source_file.syntax(),
+        ));
+    }
+    res
+}
+
+/// Highlights the operations which are only allowed in unsafe code, both
+/// inside and outside of `unsafe` blocks. Unlike `highlight`, this needs type
+/// inference of all the bodies in the file, so it is a separate request.
+pub(crate) fn highlight_unsafe(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRange> {
+    let source_file = db.source_file(file_id);
+    let def_kinds = [FN_DEF, CONST_DEF, STATIC_DEF, ENUM_VARIANT];
+    let mut res = Vec::new();
+    for node in source_file
+        .syntax()
+        .descendants()
+        .filter(|it| def_kinds.contains(&it.kind()))
+    {
+        let def = match source_binder::def_with_body_from_child_node(db, file_id, node) {
+            Some(it) => it,
+            None => continue,
+        };
+        let syntax_mapping = def.body_syntax_mapping(db);
+        for unsafe_expr in def.unsafe_exprs(db).iter() {
+            if let Some(ptr) = syntax_mapping.expr_syntax(unsafe_expr.expr) {
+                res.push(HighlightedRange {
+                    range: ptr.range(),
+                    tag: "unsafe",
+                });
+            }
+        }
+    }
+    res
+}
+
+/// Highlights items, fields, match arms and statements which are compiled out
+/// by a `#[cfg]` attribute.
+fn highlight_inactive(cfg_options: &CfgOptions, root: &SyntaxNode) -> Vec<HighlightedRange> {
+    let mut res: Vec<HighlightedRange> = Vec::new();
+    for node in root.descendants() {
+        if let Some(last) = res.last() {
+            if node.range().is_subrange(&last.range) {
+                continue;
+            }
+        }
+        let is_inactive = node
+            .children()
+            .filter_map(ast::Attr::cast)
+            .filter_map(CfgExpr::from_attr)
+            .any(|cfg| cfg_options.check(&cfg) == Some(false));
+        if is_inactive {
+            res.push(HighlightedRange {
+                range: node.range(),
+                tag: "inactive",
+            });
+        }
+    }
     res
 }
 
 #[cfg(test)]
 mod tests {
+    use ra_syntax::TextRange;
+    use test_utils::extract_ranges;
+
     use crate::mock_analysis::single_file;
 
     use insta::assert_debug_snapshot_matches;
@@ -51,6 +119,65 @@ mod tests {
         assert_debug_snapshot_matches!("highlights_code_inside_macros", &highlights);
     }
 
+    #[test]
+    fn highlights_inactive_code() {
+        let (ranges, text) = extract_ranges(
+            "
+            <inactive>#[cfg(test)]
+            fn foo() {}</inactive>
+            #[cfg(not(test))]
+            fn bar() {
+                <inactive>#[cfg(test)]
+                let x = 92;</inactive>
+                match () {
+                    <inactive>#[cfg(all(test, unix))] () => ()</inactive>,
+                    #[cfg(any(test, not(unix)))] () => (),
+                }
+            }
+            struct S {
+                <inactive>#[cfg(test)] x: i32</inactive>,
+            }
+            ",
+            "inactive",
+        );
+        let (analysis, file_id) = single_file(&text);
+        let inactive: Vec<TextRange> = analysis
+            .highlight(file_id)
+            .unwrap()
+            .into_iter()
+            .filter(|it| it.tag == "inactive")
+            .map(|it| it.range)
+            .collect();
+        assert_eq!(inactive, ranges);
+    }
+
+    #[test]
+    fn highlights_unsafe_operations() {
+        let (ranges, text) = extract_ranges(
+            "
+            unsafe fn danger() {}
+            static mut COUNTER: u32 = 0;
+            union U { a: u32 }
+            fn foo(p: *mut u32, u: U) {
+                unsafe {
+                    <unsafe>danger()</unsafe>;
+                    <unsafe>*p</unsafe> = <unsafe>u.a</unsafe>;
+                }
+                <unsafe>COUNTER</unsafe> += 1;
+            }
+            ",
+            "unsafe",
+        );
+        let (analysis, file_id) = single_file(&text);
+        let highlighted: Vec<TextRange> = analysis
+            .highlight_unsafe(file_id)
+            .unwrap()
+            .into_iter()
+            .map(|it| it.range)
+            .collect();
+        assert_eq!(highlighted, ranges);
+    }
+
     // FIXME: this test is not really necessary: artifact of the inital hacky
     // macros implementation.
     #[test]
@@ -12,6 +12,198 @@ fn test_unresolved_module_diagnostic() {
     assert_debug_snapshot_matches!("unresolved_module_diagnostic", &diagnostics);
 }
 
+#[test]
+fn test_unresolved_label_diagnostic() {
+    let (analysis, file_id) = single_file(
+        "
+fn foo() {
+    'a: loop {
+        break 'b;
+        continue 'a;
+    }
+    let f = || loop { continue 'a; };
+    'blk: { continue 'blk; }
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_debug_snapshot_matches!("unresolved_label_diagnostic", &diagnostics);
+}
+
+#[test]
+fn test_type_error_diagnostics() {
+    let (analysis, file_id) = single_file(
+        "
+struct S { a: u32 }
+impl S {
+    fn method(&self, x: u32) -> u32 { x }
+}
+fn takes_two(a: u32, b: u32) {}
+fn foo(s: S) -> bool {
+    let x: u32 = true;
+    takes_two(1);
+    s.method();
+    s.missing();
+    s.b;
+    S { a: 1, c: 2 };
+    let S { d, .. } = s;
+    let y = 1u32;
+    y();
+    1u32
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_debug_snapshot_matches!("type_error_diagnostics", &diagnostics);
+}
+
+#[test]
+fn test_no_such_method_diagnostic_not_reported_for_primitives() {
+    let (analysis, file_id) = single_file(
+        "
+fn foo(x: u8, slice: &[u32], s: &&str) {
+    \"a\".len();
+    1u32.to_string();
+    slice.iter();
+    x.clone();
+    s.chars();
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn test_match_check_diagnostics() {
+    let (analysis, file_id) = single_file(
+        "
+enum Option<T> { None, Some(T) }
+enum E { A, B(u32), C { x: bool, y: u8 } }
+struct S { a: bool, b: bool }
+const ZERO: i32 = 0;
+fn f(e: E, o: Option<E>, b: bool, t: (bool, bool), s: S, i: i32, u: u8, c: char, st: &str, r: &E) {
+    match e { E::A => (), E::B(1) => (), E::C { x: true, .. } => (), _ => () }
+    match o { Option::None => (), Option::Some(E::A) => () }
+    match o { Option::None => (), Option::Some(_) => (), Option::Some(E::A) => () }
+    match b { true => () }
+    match t { (true, _) => (), (_, true) => () }
+    match s { S { a: true, b } => () }
+    match i { 0 => (), 1..=10 => (), ZERO => () }
+    match u { 0..=100 => (), 200..=255 => () }
+    match c { 'a'..='z' => () }
+    match st { \"a\" => () }
+    match r { E::A => (), E::B(_) => (), E::C { .. } => () }
+    match e { E::A => (), _ if b => () }
+    match e { E::A | E::A => (), x => () }
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    let diagnostics: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.range, d.severity, d.message.as_str()))
+        .collect();
+    assert_debug_snapshot_matches!("match_check_diagnostics", &diagnostics);
+}
+
+#[test]
+fn test_fill_match_arms_fix() {
+    let (analysis, file_id) = single_file(
+        "
+enum E { A, B(u32), C { x: bool } }
+fn f(e: E) {
+    match e {
+        E::A => {}
+    }
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_debug_snapshot_matches!("fill_match_arms_fix", &diagnostics);
+}
+
+#[test]
+fn test_unsafe_diagnostics() {
+    let (analysis, file_id) = single_file(
+        "
+unsafe fn danger() {}
+struct S;
+impl S {
+    unsafe fn danger(&self) {}
+}
+union U { a: u32, b: f32 }
+static mut COUNTER: u32 = 0;
+unsafe fn unsafe_context(p: *const u32) -> u32 {
+    danger();
+    *p
+}
+fn foo(p: *const u32, u: U, s: S) {
+    danger();
+    s.danger();
+    let x = *p;
+    COUNTER += 1;
+    let y = u.a;
+    let mut v = U { a: 1 };
+    v.b = 1.0;
+    unsafe {
+        danger();
+        let z = *p + u.a + COUNTER;
+    }
+    let f = || *p;
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_debug_snapshot_matches!("unsafe_diagnostics", &diagnostics);
+}
+
+#[test]
+fn test_mutability_diagnostics() {
+    let (analysis, file_id) = single_file(
+        "
+struct S { a: u32 }
+impl S {
+    fn set(&mut self, a: u32) { self.a = a; }
+    fn get(mut self) -> u32 { self.a }
+}
+fn foo(r: &mut S) {
+    let x = 1;
+    x = 2;
+    let s = S { a: 1 };
+    s.a += 1;
+    let b = &mut s.a;
+    s.set(3);
+    r.set(4);
+    let y;
+    y = 5;
+    let mut unused = 6;
+    let mut used = 7;
+    used += 1;
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_debug_snapshot_matches!("mutability_diagnostics", &diagnostics);
+}
+
+#[test]
+fn test_mutability_diagnostics_for_ref_bindings() {
+    let (analysis, file_id) = single_file(
+        "
+struct S { a: u32 }
+fn foo(s: S) {
+    let ref r = s;
+    r.a = 1;
+    let ref mut m = s;
+    m.a = 2;
+}
+",
+    );
+    let diagnostics = analysis.diagnostics(file_id).unwrap();
+    assert_debug_snapshot_matches!("mutability_diagnostics_for_ref_bindings", &diagnostics);
+}
+
 // FIXME: move this test to hir
 #[test]
 fn test_unresolved_module_diagnostic_no_diag_for_inline_mode() {
@@ -26,12 +218,12 @@ fn test_resolve_crate_root() {
         "
         //- /bar.rs
         mod foo;
-        //- /bar/foo.rs
+        //- /foo.rs
         // emtpy <|>
     ",
     );
     let root_file = mock.id_of("/bar.rs");
-    let mod_file = mock.id_of("/bar/foo.rs");
+    let mod_file = mock.id_of("/foo.rs");
     let mut host = mock.analysis_host();
     assert!(host.analysis().crate_for(mod_file).unwrap().is_empty());
 
@@ -90,6 +282,19 @@ fn test_find_all_refs_for_fn_param() {
     assert_eq!(refs.len(), 2);
 }
 
+#[test]
+fn test_find_all_refs_inside_builtin_macros() {
+    let code = r#"
+    fn foo(i<|> : u32) -> u32 {
+        println!("{} {}", i, i + 1);
+        assert_eq!(i, 92);
+        i
+    }"#;
+
+    let refs = get_all_refs(code);
+    assert_eq!(refs.len(), 5);
+}
+
 #[test]
 fn world_symbols_include_stuff_from_macros() {
     let (analysis, _) = single_file(
@@ -6,7 +6,7 @@ use ra_syntax::{
 };
 
 /// If the node is on the beginning of the line, calculate indent.
-pub(crate) fn leading_indent(node: &SyntaxNode) -> Option<&str> {
+pub fn leading_indent(node: &SyntaxNode) -> Option<&str> {
     for leaf in prev_leaves(node) {
         if let Some(ws) = ast::Whitespace::cast(leaf) {
             let ws_text = ws.text();
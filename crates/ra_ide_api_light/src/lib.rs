@@ -24,6 +24,7 @@ pub use self::{
     line_index_utils::translate_offset_with_edit,
     structure::{file_structure, StructureNode},
     diagnostics::diagnostics,
+    formatting::leading_indent,
     join_lines::join_lines,
     typing::{on_enter, on_dot_typed, on_eq_typed},
 
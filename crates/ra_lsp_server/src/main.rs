@@ -31,6 +31,8 @@ struct InitializationOptions {
     // This is different to the highlightingOn setting, which is whether the user
     // wants our custom highlighting to be used.
     publish_decorations: Option<bool>,
+    // Whether `cfg(test)` code of the workspace members is analyzed.
+    cfg_test: Option<bool>,
 }
 
 fn main_inner() -> Result<()> {
@@ -45,12 +47,13 @@ fn main_inner() -> Result<()> {
                 .root_uri
                 .and_then(|it| it.to_file_path().ok())
                 .unwrap_or(cwd);
-            let supports_decorations = params
+            let options = params
                 .initialization_options
-                .and_then(|v| InitializationOptions::deserialize(v).ok())
-                .and_then(|it| it.publish_decorations)
-                == Some(true);
-            ra_lsp_server::main_loop(false, root, supports_decorations, r, s)
+                .and_then(|v| InitializationOptions::deserialize(v).ok());
+            let supports_decorations =
+                options.as_ref().and_then(|it| it.publish_decorations) == Some(true);
+            let cfg_test = options.and_then(|it| it.cfg_test).unwrap_or(true);
+            ra_lsp_server::main_loop(false, root, supports_decorations, cfg_test, r, s)
         },
     )?;
     log::info!("shutting down IO...");
@@ -52,6 +52,7 @@ pub fn main_loop(
     internal_mode: bool,
     ws_root: PathBuf,
     supports_decorations: bool,
+    cfg_test: bool,
     msg_receiver: &Receiver<RawMessage>,
     msg_sender: &Sender<RawMessage>,
 ) -> Result<()> {
@@ -72,7 +73,7 @@ pub fn main_loop(
     ws_watcher
         .shutdown()
         .map_err(|_| format_err!("ws watcher died"))?;
-    let mut state = ServerWorldState::new(ws_root.clone(), workspaces);
+    let mut state = ServerWorldState::new(ws_root.clone(), workspaces, cfg_test);
 
     log::info!("server initialized, serving requests");
 
@@ -307,6 +308,7 @@ fn on_request(
         .on::<req::ParentModule>(handlers::handle_parent_module)?
         .on::<req::Runnables>(handlers::handle_runnables)?
         .on::<req::DecorationsRequest>(handlers::handle_decorations)?
+        .on::<req::UnsafeDecorationsRequest>(handlers::handle_unsafe_decorations)?
         .on::<req::Completion>(handlers::handle_completion)?
         .on::<req::CodeActionRequest>(handlers::handle_code_action)?
         .on::<req::CodeLensRequest>(handlers::handle_code_lens)?
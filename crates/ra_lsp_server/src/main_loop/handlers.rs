@@ -308,6 +308,24 @@ pub fn handle_decorations(
     highlight(&world, file_id)
 }
 
+pub fn handle_unsafe_decorations(
+    world: ServerWorld,
+    params: TextDocumentIdentifier,
+) -> Result<Vec<Decoration>> {
+    let file_id = params.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id);
+    let res = world
+        .analysis()
+        .highlight_unsafe(file_id)?
+        .into_iter()
+        .map(|h| Decoration {
+            range: h.range.conv_with(&line_index),
+            tag: h.tag,
+        })
+        .collect();
+    Ok(res)
+}
+
 pub fn handle_completion(
     world: ServerWorld,
     params: req::CompletionParams,
@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use cargo_metadata::{MetadataCommand, CargoOpt};
+use ra_ide_api::Edition;
 use ra_syntax::SmolStr;
 use ra_arena::{Arena, RawId, impl_arena_id};
 use rustc_hash::FxHashMap;
@@ -33,9 +34,11 @@ impl_arena_id!(Target);
 struct PackageData {
     name: SmolStr,
     manifest: PathBuf,
+    edition: Edition,
     targets: Vec<Target>,
     is_member: bool,
     dependencies: Vec<PackageDependency>,
+    features: Vec<SmolStr>,
 }
 
 #[derive(Debug, Clone)]
@@ -88,10 +91,15 @@ impl Package {
     pub fn targets<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = Target> + 'a {
         ws.packages[self].targets.iter().cloned()
     }
-    #[allow(unused)]
     pub fn is_member(self, ws: &CargoWorkspace) -> bool {
         ws.packages[self].is_member
     }
+    pub fn edition(self, ws: &CargoWorkspace) -> Edition {
+        ws.packages[self].edition
+    }
+    pub fn features(self, ws: &CargoWorkspace) -> &[SmolStr] {
+        &ws.packages[self].features
+    }
     pub fn dependencies<'a>(
         self,
         ws: &'a CargoWorkspace,
@@ -133,9 +141,11 @@ impl CargoWorkspace {
             let pkg = packages.alloc(PackageData {
                 name: meta_pkg.name.into(),
                 manifest: meta_pkg.manifest_path.clone(),
+                edition: Edition::from_string(&meta_pkg.edition),
                 targets: Vec::new(),
                 is_member,
                 dependencies: Vec::new(),
+                features: Vec::new(),
             });
             let pkg_data = &mut packages[pkg];
             pkg_by_id.insert(meta_pkg.id.clone(), pkg);
@@ -159,6 +169,9 @@ impl CargoWorkspace {
                 };
                 packages[source].dependencies.push(dep);
             }
+            packages[source]
+                .features
+                .extend(node.features.into_iter().map(SmolStr::from));
         }
 
         Ok(CargoWorkspace { packages, targets })
@@ -25,6 +25,10 @@ struct SysrootCrateData {
 }
 
 impl Sysroot {
+    pub(crate) fn core(&self) -> Option<SysrootCrate> {
+        self.by_name("core")
+    }
+
     pub(crate) fn std(&self) -> Option<SysrootCrate> {
         self.by_name("std")
     }
@@ -85,6 +85,16 @@ impl Request for DecorationsRequest {
     const METHOD: &'static str = "m/decorationsRequest";
 }
 
+/// Highlights unsafe operations. Unlike the other decorations, these need type
+/// inference, so they are only computed when the client asks for them.
+pub enum UnsafeDecorationsRequest {}
+
+impl Request for UnsafeDecorationsRequest {
+    type Params = TextDocumentIdentifier;
+    type Result = Vec<Decoration>;
+    const METHOD: &'static str = "m/unsafeDecorationsRequest";
+}
+
 pub enum PublishDecorations {}
 
 impl Notification for PublishDecorations {
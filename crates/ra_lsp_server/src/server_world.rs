@@ -5,7 +5,7 @@ use std::{
 
 use lsp_types::Url;
 use ra_ide_api::{
-    Analysis, AnalysisChange, AnalysisHost, CrateGraph, FileId, LibraryData,
+    Analysis, AnalysisChange, AnalysisHost, CfgOptions, CrateGraph, FileId, LibraryData,
     SourceRootId
 };
 use ra_vfs::{Vfs, VfsChange, VfsFile, VfsRoot};
@@ -35,7 +35,11 @@ pub struct ServerWorld {
 }
 
 impl ServerWorldState {
-    pub fn new(root: PathBuf, workspaces: Vec<ProjectWorkspace>) -> ServerWorldState {
+    pub fn new(
+        root: PathBuf,
+        workspaces: Vec<ProjectWorkspace>,
+        cfg_test: bool,
+    ) -> ServerWorldState {
         let mut change = AnalysisChange::new();
 
         let mut roots = Vec::new();
@@ -55,8 +59,10 @@ impl ServerWorldState {
         for r in roots {
             let is_local = vfs.root2path(r).starts_with(&root);
             change.add_root(SourceRootId(r.0.into()), is_local);
+            change.set_root_path(SourceRootId(r.0.into()), vfs.root2path(r));
         }
 
+        let default_cfg_options = default_cfg_options();
         let mut crate_graph = CrateGraph::default();
         for ws in workspaces.iter() {
             // First, load std
@@ -64,7 +70,9 @@ impl ServerWorldState {
             for krate in ws.sysroot.crates() {
                 if let Some(file_id) = vfs.load(krate.root(&ws.sysroot)) {
                     let file_id = FileId(file_id.0.into());
-                    sysroot_crates.insert(krate, crate_graph.add_crate_root(file_id));
+                    let crate_id = crate_graph.add_crate_root(file_id);
+                    crate_graph.set_cfg_options(crate_id, default_cfg_options.clone());
+                    sysroot_crates.insert(krate, crate_id);
                 }
             }
             for from in ws.sysroot.crates() {
@@ -80,6 +88,10 @@ impl ServerWorldState {
                 }
             }
 
+            let libcore = ws
+                .sysroot
+                .core()
+                .and_then(|it| sysroot_crates.get(&it).map(|&it| it));
             let libstd = ws
                 .sysroot
                 .std()
@@ -89,12 +101,26 @@ impl ServerWorldState {
             let mut pkg_crates = FxHashMap::default();
             // Next, create crates for each package, target pair
             for pkg in ws.cargo.packages() {
+                let mut cfg_options = default_cfg_options.clone();
+                for feature in pkg.features(&ws.cargo) {
+                    cfg_options.insert_key_value("feature".into(), feature.clone());
+                }
                 let mut lib_tgt = None;
                 for tgt in pkg.targets(&ws.cargo) {
                     let root = tgt.root(&ws.cargo);
                     if let Some(file_id) = vfs.load(root) {
                         let file_id = FileId(file_id.0.into());
                         let crate_id = crate_graph.add_crate_root(file_id);
+                        let mut cfg_options = cfg_options.clone();
+                        if target_has_cfg_test(
+                            tgt.kind(&ws.cargo),
+                            pkg.is_member(&ws.cargo),
+                            cfg_test,
+                        ) {
+                            cfg_options.insert_atom("test".into());
+                        }
+                        crate_graph.set_cfg_options(crate_id, cfg_options);
+                        crate_graph.set_edition(crate_id, pkg.edition(&ws.cargo));
                         if tgt.kind(&ws.cargo) == TargetKind::Lib {
                             lib_tgt = Some(crate_id);
                             pkg_to_lib_crate.insert(pkg, crate_id);
@@ -106,7 +132,7 @@ impl ServerWorldState {
                     }
                 }
 
-                // Set deps to the std and to the lib target of the current package
+                // Set deps to the core, std and to the lib target of the current package
                 for &from in pkg_crates.get(&pkg).into_iter().flatten() {
                     if let Some(to) = lib_tgt {
                         if to != from {
@@ -120,6 +146,13 @@ impl ServerWorldState {
                             }
                         }
                     }
+                    // `core` is always in the extern prelude, even in
+                    // `no_std` crates.
+                    if let Some(core) = libcore {
+                        if let Err(_) = crate_graph.add_dep(from, "core".into(), core) {
+                            log::error!("cyclic dependency on core for {}", pkg.name(&ws.cargo))
+                        }
+                    }
                     if let Some(std) = libstd {
                         if let Err(_) = crate_graph.add_dep(from, "std".into(), std) {
                             log::error!("cyclic dependency on std for {}", pkg.name(&ws.cargo))
@@ -237,6 +270,31 @@ impl ServerWorldState {
     }
 }
 
+/// Cfg flags of the host platform. We don't know the actual target the code
+/// will be compiled for, so the host is our best guess.
+fn default_cfg_options() -> CfgOptions {
+    let mut res = CfgOptions::default();
+    res.insert_atom("debug_assertions".into());
+    res.insert_atom(std::env::consts::FAMILY.into());
+    res.insert_key_value("target_family".into(), std::env::consts::FAMILY.into());
+    res.insert_key_value("target_os".into(), std::env::consts::OS.into());
+    res.insert_key_value("target_arch".into(), std::env::consts::ARCH.into());
+    let pointer_width = (std::mem::size_of::<usize>() * 8).to_string();
+    res.insert_key_value("target_pointer_width".into(), pointer_width.into());
+    res
+}
+
+/// Whether `cfg(test)` is enabled for a target. Test and bench targets are
+/// always compiled as tests, while the tests inside libraries and binaries
+/// are only interesting in the workspace members, if the user wants them.
+fn target_has_cfg_test(kind: TargetKind, is_member: bool, cfg_test: bool) -> bool {
+    match kind {
+        TargetKind::Test | TargetKind::Bench => true,
+        TargetKind::Lib | TargetKind::Bin => is_member && cfg_test,
+        TargetKind::Example | TargetKind::Other => false,
+    }
+}
+
 impl ServerWorld {
     pub fn analysis(&self) -> &Analysis {
         &self.analysis
@@ -7,8 +7,9 @@ use itertools::Itertools;
 pub use self::generated::*;
 use crate::{
     yellow::{SyntaxNode, SyntaxNodeChildren, TreeArc, RaTypes},
-    SmolStr,
+    SmolStr, SyntaxKind,
     SyntaxKind::*,
+    string_lexing::{self, StringComponentKind},
 };
 
 /// The main trait to go from untyped `SyntaxNode`  to a typed ast. The
@@ -137,6 +138,22 @@ impl FnDef {
     pub fn has_atom_attr(&self, atom: &str) -> bool {
         self.attrs().filter_map(|x| x.as_atom()).any(|x| x == atom)
     }
+
+    pub fn is_unsafe(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == UNSAFE_KW)
+    }
+}
+
+impl TraitDef {
+    pub fn is_unsafe(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == UNSAFE_KW)
+    }
+}
+
+impl StaticDef {
+    pub fn is_mut(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == MUT_KW)
+    }
 }
 
 impl Attr {
@@ -150,6 +167,21 @@ impl Attr {
         }
     }
 
+    pub fn as_key_value(&self) -> Option<(SmolStr, SmolStr)> {
+        let tt = self.value()?;
+        let tokens: Vec<_> = tt.syntax().children().filter(|it| !it.kind().is_trivia()).collect();
+        match tokens.as_slice() {
+            [_bra, key, eq, value, _ket]
+                if key.kind() == IDENT && eq.kind() == EQ && value.kind() == STRING =>
+            {
+                let value = value.leaf_text().unwrap();
+                let value = value.trim_start_matches('"').trim_end_matches('"');
+                Some((key.leaf_text().unwrap().clone(), SmolStr::new(value)))
+            }
+            _ => None,
+        }
+    }
+
     pub fn as_call(&self) -> Option<(SmolStr, &TokenTree)> {
         let tt = self.value()?;
         let (_bra, attr, args, _ket) = tt.syntax().children().collect_tuple()?;
@@ -265,6 +297,10 @@ impl ImplBlock {
         let second = types.next();
         (first, second)
     }
+
+    pub fn is_unsafe(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == UNSAFE_KW)
+    }
 }
 
 impl Module {
@@ -309,6 +345,12 @@ impl ExprStmt {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PathSegmentKind<'a> {
     Name(&'a NameRef),
+    /// A qualified self type, like `<T>` or `<T as Trait>` in
+    /// `<T as Trait>::Item`.
+    Type {
+        type_ref: Option<&'a TypeRef>,
+        trait_ref: Option<&'a PathType>,
+    },
     SelfKw,
     SuperKw,
     CrateKw,
@@ -330,6 +372,20 @@ impl PathSegment {
                 SELF_KW => PathSegmentKind::SelfKw,
                 SUPER_KW => PathSegmentKind::SuperKw,
                 CRATE_KW => PathSegmentKind::CrateKw,
+                L_ANGLE => {
+                    // `<T as Trait>`: the first type is the self type, the
+                    // one after `as` is the trait.
+                    let mut type_refs = self
+                        .syntax()
+                        .children()
+                        .filter(|node| TypeRef::cast(node).is_some());
+                    let type_ref = type_refs.next().and_then(TypeRef::cast);
+                    let trait_ref = type_refs.next().and_then(PathType::cast);
+                    PathSegmentKind::Type {
+                        type_ref,
+                        trait_ref,
+                    }
+                }
                 _ => return None,
             }
         };
@@ -428,6 +484,10 @@ impl StructDef {
     pub fn flavor(&self) -> StructFlavor {
         StructFlavor::from_node(self)
     }
+
+    pub fn is_union(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == UNION_KW)
+    }
 }
 
 impl EnumVariant {
@@ -461,6 +521,12 @@ impl RefExpr {
     }
 }
 
+impl BlockExpr {
+    pub fn is_unsafe(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == UNSAFE_KW)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PrefixOp {
     /// The `*` operator for dereferencing
@@ -546,6 +612,26 @@ pub enum BinOp {
     BitXorAssign,
 }
 
+impl BinOp {
+    /// Whether this is `=` or a compound assignment like `+=`.
+    pub fn is_assignment(self) -> bool {
+        match self {
+            BinOp::Assignment
+            | BinOp::AddAssign
+            | BinOp::SubAssign
+            | BinOp::DivAssign
+            | BinOp::MulAssign
+            | BinOp::RemAssign
+            | BinOp::ShrAssign
+            | BinOp::ShlAssign
+            | BinOp::BitAndAssign
+            | BinOp::BitOrAssign
+            | BinOp::BitXorAssign => true,
+            _ => false,
+        }
+    }
+}
+
 impl BinExpr {
     pub fn op(&self) -> Option<BinOp> {
         self.syntax()
@@ -602,6 +688,64 @@ impl BinExpr {
     }
 }
 
+impl IndexExpr {
+    pub fn base(&self) -> Option<&Expr> {
+        children(self).nth(0)
+    }
+
+    pub fn index(&self) -> Option<&Expr> {
+        children(self).nth(1)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RangeOp {
+    /// The `..` operator for half-open ranges
+    Exclusive,
+    /// The `..=` operator for closed ranges
+    Inclusive,
+}
+
+impl RangeExpr {
+    fn op_details(&self) -> Option<(usize, RangeOp)> {
+        self.syntax()
+            .children()
+            .enumerate()
+            .find_map(|(ix, child)| match child.kind() {
+                DOTDOT => Some((ix, RangeOp::Exclusive)),
+                DOTDOTEQ => Some((ix, RangeOp::Inclusive)),
+                _ => None,
+            })
+    }
+
+    pub fn op_kind(&self) -> Option<RangeOp> {
+        self.op_details().map(|(_, op)| op)
+    }
+
+    /// The start of the range, like the `a` in `a..b`; `None` for `..b`.
+    pub fn start(&self) -> Option<&Expr> {
+        let (op_ix, _) = self.op_details()?;
+        self.syntax().children().take(op_ix).find_map(Expr::cast)
+    }
+
+    /// The end of the range, like the `b` in `a..b`; `None` for `a..`.
+    pub fn end(&self) -> Option<&Expr> {
+        let (op_ix, _) = self.op_details()?;
+        self.syntax()
+            .children()
+            .skip(op_ix + 1)
+            .find_map(Expr::cast)
+    }
+}
+
+impl ArrayExpr {
+    /// Whether this is a repeat expression like `[0; 4]`, whose two `exprs`
+    /// are the repeated value and the length, rather than a list of elements.
+    pub fn is_repeat(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == SEMI)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SelfParamFlavor {
     /// self
@@ -679,6 +823,88 @@ impl LiteralExpr {
     }
 }
 
+impl LiteralExpr {
+    /// The value of an integer or byte literal, like `255` for `0xff_u8` or
+    /// `b'\xff'`.
+    pub fn int_value(&self) -> Option<u128> {
+        match self.flavor() {
+            LiteralFlavor::IntNumber { suffix } => {
+                let text = self.syntax().text().to_string();
+                let text = &text[..text.len() - suffix.map_or(0, |it| it.len())];
+                let digits = text.replace('_', "");
+                let (digits, radix) = match digits.get(..2) {
+                    Some("0x") => (&digits[2..], 16),
+                    Some("0o") => (&digits[2..], 8),
+                    Some("0b") => (&digits[2..], 2),
+                    _ => (&digits[..], 10),
+                };
+                u128::from_str_radix(digits, radix).ok()
+            }
+            LiteralFlavor::Byte => {
+                let text = self.syntax().text().to_string();
+                let components = string_lexing::parse_byte_literal(&text);
+                char_literal_value(&text, components).map(|c| c as u128)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of a char literal, with escapes like `'\n'` resolved.
+    pub fn char_value(&self) -> Option<char> {
+        match self.flavor() {
+            LiteralFlavor::Char => {
+                let text = self.syntax().text().to_string();
+                let components = string_lexing::parse_char_literal(&text);
+                char_literal_value(&text, components)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn bool_value(&self) -> Option<bool> {
+        match self.syntax().kind() {
+            TRUE_KW => Some(true),
+            FALSE_KW => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// The value of a char or byte literal consisting of exactly one (possibly
+/// escaped) character.
+fn char_literal_value(
+    text: &str,
+    mut components: string_lexing::StringComponentIterator,
+) -> Option<char> {
+    let component = components.next()?;
+    if components.next().is_some() || !components.has_closing_quote {
+        return None;
+    }
+    let component_text = &text[component.range];
+    match component.kind {
+        StringComponentKind::CodePoint => component_text.chars().next(),
+        StringComponentKind::AsciiEscape => match component_text.chars().nth(1)? {
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '0' => Some('\0'),
+            c @ '\\' | c @ '\'' | c @ '"' => Some(c),
+            _ => None,
+        },
+        StringComponentKind::AsciiCodeEscape => {
+            let code = u8::from_str_radix(component_text.get(2..)?, 16).ok()?;
+            Some(code as char)
+        }
+        StringComponentKind::UnicodeEscape => {
+            let digits = component_text
+                .get(3..component_text.len() - 1)?
+                .replace('_', "");
+            std::char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+        }
+        StringComponentKind::IgnoreNewline => None,
+    }
+}
+
 impl BindPat {
     pub fn is_mutable(&self) -> bool {
         self.syntax().children().any(|n| n.kind() == MUT_KW)
@@ -689,6 +915,239 @@ impl BindPat {
     }
 }
 
+impl LiteralPat {
+    /// Whether the literal is negated, like in `-1`.
+    pub fn is_negative(&self) -> bool {
+        self.syntax().children().any(|n| n.kind() == MINUS)
+    }
+}
+
+impl RangePat {
+    /// The lower bound of the range, like `1` in `1..=5`.
+    pub fn start(&self) -> Option<&Pat> {
+        children(self).next()
+    }
+
+    /// The upper bound of the range, like `5` in `1..=5`.
+    pub fn end(&self) -> Option<&Pat> {
+        children(self).nth(1)
+    }
+
+    /// Whether the range includes its upper bound, like `1..=5` and the
+    /// older `1...5` do.
+    pub fn is_inclusive(&self) -> bool {
+        self.syntax()
+            .children()
+            .any(|n| n.kind() == DOTDOTEQ || n.kind() == DOTDOTDOT)
+    }
+}
+
+impl TypeParam {
+    /// The trait bounds of the parameter, like `Clone` and `Foo<u32>` in
+    /// `T: Clone + Foo<u32>`. Lifetimes and `?Sized` bounds are skipped.
+    pub fn bounds(&self) -> Vec<&Path> {
+        bounds(self.syntax(), COLON)
+    }
+
+    /// The default type of the parameter, like `i32` in `T = i32`.
+    pub fn default_type(&self) -> Option<&TypeRef> {
+        self.syntax()
+            .children()
+            .skip_while(|it| it.kind() != EQ)
+            .find_map(TypeRef::cast)
+    }
+}
+
+impl WherePred {
+    /// The trait bounds of the predicate, like `Clone` in `T: Clone`.
+    pub fn bounds(&self) -> Vec<&Path> {
+        bounds(self.syntax(), COLON)
+    }
+}
+
+impl ImplTraitType {
+    /// The trait bounds of the type, like `Iterator<Item = u32>` in
+    /// `impl Iterator<Item = u32> + 'a`.
+    pub fn bounds(&self) -> Vec<&Path> {
+        bounds(self.syntax(), IMPL_KW)
+    }
+}
+
+impl DynTraitType {
+    /// The trait bounds of the type, like `Fn(i32)` and `Send` in
+    /// `dyn Fn(i32) + Send`.
+    pub fn bounds(&self) -> Vec<&Path> {
+        bounds(self.syntax(), DYN_KW)
+    }
+}
+
+/// Bounds are not wrapped into a node of their own: the parser produces a
+/// chain of nested `PATH_TYPE`s after the `start` token (the colon, or the
+/// `impl` or `dyn` keyword).
+fn bounds(node: &SyntaxNode, start: SyntaxKind) -> Vec<&Path> {
+    fn collect<'a>(
+        nodes: impl Iterator<Item = &'a SyntaxNode>,
+        mut is_maybe: bool,
+        res: &mut Vec<&'a Path>,
+    ) {
+        for node in nodes {
+            match node.kind() {
+                QUESTION => {
+                    is_maybe = true;
+                    continue;
+                }
+                PATH if !is_maybe => res.extend(Path::cast(node)),
+                PATH_TYPE => collect(node.children(), is_maybe, res),
+                _ => (),
+            }
+            is_maybe = false;
+        }
+    }
+    let mut res = Vec::new();
+    let nodes = node
+        .children()
+        .skip_while(|it| it.kind() != start)
+        .take_while(|it| it.kind() != EQ);
+    collect(nodes, false, &mut res);
+    res
+}
+
+#[test]
+fn test_bounds() {
+    let file = SourceFile::parse(
+        "fn f<T: ?Sized + Clone + Foo<u32>, U: 'a + Bar = i32>() where Vec<T>: Baz {}",
+    );
+    let bounds = file
+        .syntax()
+        .descendants()
+        .filter_map(TypeParam::cast)
+        .map(|it| it.bounds().iter().map(|it| it.syntax().text().to_string()).join(", "))
+        .collect::<Vec<_>>();
+    assert_eq!(bounds, ["Clone, Foo<u32>", "Bar"]);
+    let defaults = file
+        .syntax()
+        .descendants()
+        .filter_map(TypeParam::cast)
+        .map(|it| it.default_type().map(|it| it.syntax().text().to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(defaults, [None, Some("i32".to_string())]);
+    let pred = file.syntax().descendants().find_map(WherePred::cast).unwrap();
+    assert_eq!(pred.type_ref().unwrap().syntax().text().to_string(), "Vec<T>");
+    assert_eq!(pred.bounds()[0].syntax().text().to_string(), "Baz");
+}
+
+#[test]
+fn test_impl_and_dyn_trait_bounds() {
+    let file =
+        SourceFile::parse("fn f(x: &dyn Fn(i32) + Send) -> impl Iterator<Item = u32> + 'static {}");
+    let dyn_bounds = file
+        .syntax()
+        .descendants()
+        .find_map(DynTraitType::cast)
+        .unwrap()
+        .bounds()
+        .iter()
+        .map(|it| it.syntax().text().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(dyn_bounds, ["Fn(i32)", "Send"]);
+    let impl_bounds = file
+        .syntax()
+        .descendants()
+        .find_map(ImplTraitType::cast)
+        .unwrap()
+        .bounds()
+        .iter()
+        .map(|it| it.syntax().text().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(impl_bounds, ["Iterator<Item = u32>"]);
+}
+
+#[test]
+fn test_qualified_path_segment() {
+    let file = SourceFile::parse("type X = <Foo as Bar>::Item;");
+    let segment = file
+        .syntax()
+        .descendants()
+        .find_map(PathSegment::cast)
+        .unwrap();
+    match segment.kind().unwrap() {
+        PathSegmentKind::Type {
+            type_ref,
+            trait_ref,
+        } => {
+            assert_eq!(type_ref.unwrap().syntax().text().to_string(), "Foo");
+            assert_eq!(trait_ref.unwrap().syntax().text().to_string(), "Bar");
+        }
+        _ => panic!("expected a qualified segment"),
+    }
+}
+
+#[test]
+fn test_range_expr() {
+    let file = SourceFile::parse("fn f() { a..b; c..; ..=d; ..; }");
+    let ranges = file
+        .syntax()
+        .descendants()
+        .filter_map(RangeExpr::cast)
+        .map(|it| {
+            let text = |e: Option<&Expr>| e.map(|e| e.syntax().text().to_string());
+            (text(it.start()), it.op_kind().unwrap(), text(it.end()))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ranges,
+        [
+            (
+                Some("a".to_string()),
+                RangeOp::Exclusive,
+                Some("b".to_string())
+            ),
+            (Some("c".to_string()), RangeOp::Exclusive, None),
+            (None, RangeOp::Inclusive, Some("d".to_string())),
+            (None, RangeOp::Exclusive, None),
+        ]
+    );
+}
+
+#[test]
+fn test_array_expr_is_repeat() {
+    let file = SourceFile::parse("fn f() { [1, 2]; [0; 4]; []; }");
+    let repeats = file
+        .syntax()
+        .descendants()
+        .filter_map(ArrayExpr::cast)
+        .map(|it| it.is_repeat())
+        .collect::<Vec<_>>();
+    assert_eq!(repeats, [false, true, false]);
+}
+
+#[test]
+fn test_literal_values() {
+    let file = SourceFile::parse(
+        r"fn f() { 1_000; 0xffu8; 0o17; 0b101; b'a'; b'\x7f'; 'x'; '\n'; '\u{1F600}'; true; }",
+    );
+    let literals = file
+        .syntax()
+        .descendants()
+        .filter_map(LiteralExpr::cast)
+        .collect::<Vec<_>>();
+    let ints = literals
+        .iter()
+        .filter_map(|it| it.int_value())
+        .collect::<Vec<_>>();
+    assert_eq!(ints, [1000, 255, 15, 5, 97, 127]);
+    let chars = literals
+        .iter()
+        .filter_map(|it| it.char_value())
+        .collect::<Vec<_>>();
+    assert_eq!(chars, ['x', '\n', '\u{1F600}']);
+    let bools = literals
+        .iter()
+        .filter_map(|it| it.bool_value())
+        .collect::<Vec<_>>();
+    assert_eq!(bools, [true]);
+}
+
 #[test]
 fn test_doc_comment_of_items() {
     let file = SourceFile::parse(
@@ -17,6 +17,35 @@ use crate::{
     ast::{self, AstNode},
 };
 
+// Alias
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Alias {
+    pub(crate) syntax: SyntaxNode,
+}
+unsafe impl TransparentNewType for Alias {
+    type Repr = rowan::SyntaxNode<RaTypes>;
+}
+
+impl AstNode for Alias {
+    fn cast(syntax: &SyntaxNode) -> Option<&Self> {
+        match syntax.kind() {
+            ALIAS => Some(Alias::from_repr(syntax.into_repr())),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode { &self.syntax }
+}
+
+impl ToOwned for Alias {
+    type Owned = TreeArc<Alias>;
+    fn to_owned(&self) -> TreeArc<Alias> { TreeArc::cast(self.syntax.to_owned()) }
+}
+
+
+impl ast::NameOwner for Alias {}
+impl Alias {}
+
 // ArgList
 #[derive(Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -272,6 +301,7 @@ impl ToOwned for Block {
 }
 
 
+impl ast::ModuleItemOwner for Block {}
 impl Block {
     pub fn statements(&self) -> impl Iterator<Item = &Stmt> {
         super::children(self)
@@ -309,6 +339,10 @@ impl ToOwned for BlockExpr {
 
 
 impl BlockExpr {
+    pub fn label(&self) -> Option<&Label> {
+        super::child_opt(self)
+    }
+
     pub fn block(&self) -> Option<&Block> {
         super::child_opt(self)
     }
@@ -341,6 +375,10 @@ impl ToOwned for BreakExpr {
 
 
 impl BreakExpr {
+    pub fn lifetime(&self) -> Option<&Lifetime> {
+        super::child_opt(self)
+    }
+
     pub fn expr(&self) -> Option<&Expr> {
         super::child_opt(self)
     }
@@ -602,6 +640,10 @@ impl ConstDef {
     pub fn type_ref(&self) -> Option<&TypeRef> {
         super::child_opt(self)
     }
+
+    pub fn expr(&self) -> Option<&Expr> {
+        super::child_opt(self)
+    }
 }
 
 // ContinueExpr
@@ -630,7 +672,11 @@ impl ToOwned for ContinueExpr {
 }
 
 
-impl ContinueExpr {}
+impl ContinueExpr {
+    pub fn lifetime(&self) -> Option<&Lifetime> {
+        super::child_opt(self)
+    }
+}
 
 // DynTraitType
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -725,6 +771,7 @@ impl ToOwned for EnumVariant {
 
 impl ast::NameOwner for EnumVariant {}
 impl ast::DocCommentsOwner for EnumVariant {}
+impl ast::AttrsOwner for EnumVariant {}
 impl EnumVariant {
     pub fn expr(&self) -> Option<&Expr> {
         super::child_opt(self)
@@ -802,6 +849,7 @@ pub enum ExprKind<'a> {
     RangeExpr(&'a RangeExpr),
     BinExpr(&'a BinExpr),
     Literal(&'a Literal),
+    MacroCall(&'a MacroCall),
 }
 
 impl AstNode for Expr {
@@ -833,7 +881,8 @@ impl AstNode for Expr {
             | PREFIX_EXPR
             | RANGE_EXPR
             | BIN_EXPR
-            | LITERAL => Some(Expr::from_repr(syntax.into_repr())),
+            | LITERAL
+            | MACRO_CALL => Some(Expr::from_repr(syntax.into_repr())),
             _ => None,
         }
     }
@@ -875,6 +924,7 @@ impl Expr {
             RANGE_EXPR => ExprKind::RangeExpr(RangeExpr::cast(&self.syntax).unwrap()),
             BIN_EXPR => ExprKind::BinExpr(BinExpr::cast(&self.syntax).unwrap()),
             LITERAL => ExprKind::Literal(Literal::cast(&self.syntax).unwrap()),
+            MACRO_CALL => ExprKind::MacroCall(MacroCall::cast(&self.syntax).unwrap()),
             _ => unreachable!(),
         }
     }
@@ -908,6 +958,7 @@ impl ToOwned for ExprStmt {
 }
 
 
+impl ast::AttrsOwner for ExprStmt {}
 impl ExprStmt {
     pub fn expr(&self) -> Option<&Expr> {
         super::child_opt(self)
@@ -940,7 +991,16 @@ impl ToOwned for ExternCrateItem {
 }
 
 
-impl ExternCrateItem {}
+impl ast::AttrsOwner for ExternCrateItem {}
+impl ExternCrateItem {
+    pub fn name_ref(&self) -> Option<&NameRef> {
+        super::child_opt(self)
+    }
+
+    pub fn alias(&self) -> Option<&Alias> {
+        super::child_opt(self)
+    }
+}
 
 // FalseKw
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -1214,6 +1274,10 @@ impl ToOwned for ForExpr {
 
 impl ast::LoopBodyOwner for ForExpr {}
 impl ForExpr {
+    pub fn label(&self) -> Option<&Label> {
+        super::child_opt(self)
+    }
+
     pub fn pat(&self) -> Option<&Pat> {
         super::child_opt(self)
     }
@@ -1313,6 +1377,8 @@ impl ToOwned for ImplBlock {
 }
 
 
+impl ast::AttrsOwner for ImplBlock {}
+impl ast::TypeParamsOwner for ImplBlock {}
 impl ImplBlock {
     pub fn item_list(&self) -> Option<&ItemList> {
         super::child_opt(self)
@@ -1364,6 +1430,7 @@ impl ImplItem {
     }
 }
 
+impl ast::AttrsOwner for ImplItem {}
 impl ImplItem {}
 
 // ImplTraitType
@@ -1511,7 +1578,11 @@ impl ToOwned for Label {
 }
 
 
-impl Label {}
+impl Label {
+    pub fn lifetime(&self) -> Option<&Lifetime> {
+        super::child_opt(self)
+    }
+}
 
 // LambdaExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -1575,6 +1646,7 @@ impl ToOwned for LetStmt {
 }
 
 
+impl ast::AttrsOwner for LetStmt {}
 impl LetStmt {
     pub fn pat(&self) -> Option<&Pat> {
         super::child_opt(self)
@@ -1782,6 +1854,38 @@ impl LiteralExpr {
 
 impl LiteralExpr {}
 
+// LiteralPat
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct LiteralPat {
+    pub(crate) syntax: SyntaxNode,
+}
+unsafe impl TransparentNewType for LiteralPat {
+    type Repr = rowan::SyntaxNode<RaTypes>;
+}
+
+impl AstNode for LiteralPat {
+    fn cast(syntax: &SyntaxNode) -> Option<&Self> {
+        match syntax.kind() {
+            LITERAL_PAT => Some(LiteralPat::from_repr(syntax.into_repr())),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode { &self.syntax }
+}
+
+impl ToOwned for LiteralPat {
+    type Owned = TreeArc<LiteralPat>;
+    fn to_owned(&self) -> TreeArc<LiteralPat> { TreeArc::cast(self.syntax.to_owned()) }
+}
+
+
+impl LiteralPat {
+    pub fn literal(&self) -> Option<&Literal> {
+        super::child_opt(self)
+    }
+}
+
 // LoopExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -1809,7 +1913,11 @@ impl ToOwned for LoopExpr {
 
 
 impl ast::LoopBodyOwner for LoopExpr {}
-impl LoopExpr {}
+impl LoopExpr {
+    pub fn label(&self) -> Option<&Label> {
+        super::child_opt(self)
+    }
+}
 
 // MacroCall
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -1873,6 +1981,7 @@ impl ToOwned for MatchArm {
 }
 
 
+impl ast::AttrsOwner for MatchArm {}
 impl MatchArm {
     pub fn pats(&self) -> impl Iterator<Item = &Pat> {
         super::children(self)
@@ -1981,7 +2090,11 @@ impl ToOwned for MatchGuard {
 }
 
 
-impl MatchGuard {}
+impl MatchGuard {
+    pub fn expr(&self) -> Option<&Expr> {
+        super::child_opt(self)
+    }
+}
 
 // MethodCallExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -2125,6 +2238,7 @@ impl ModuleItem {
     }
 }
 
+impl ast::AttrsOwner for ModuleItem {}
 impl ModuleItem {}
 
 // Name
@@ -2551,6 +2665,7 @@ pub enum PatKind<'a> {
     TuplePat(&'a TuplePat),
     SlicePat(&'a SlicePat),
     RangePat(&'a RangePat),
+    LiteralPat(&'a LiteralPat),
 }
 
 impl AstNode for Pat {
@@ -2564,7 +2679,8 @@ impl AstNode for Pat {
             | TUPLE_STRUCT_PAT
             | TUPLE_PAT
             | SLICE_PAT
-            | RANGE_PAT => Some(Pat::from_repr(syntax.into_repr())),
+            | RANGE_PAT
+            | LITERAL_PAT => Some(Pat::from_repr(syntax.into_repr())),
             _ => None,
         }
     }
@@ -2588,6 +2704,7 @@ impl Pat {
             TUPLE_PAT => PatKind::TuplePat(TuplePat::cast(&self.syntax).unwrap()),
             SLICE_PAT => PatKind::SlicePat(SlicePat::cast(&self.syntax).unwrap()),
             RANGE_PAT => PatKind::RangePat(RangePat::cast(&self.syntax).unwrap()),
+            LITERAL_PAT => PatKind::LiteralPat(LiteralPat::cast(&self.syntax).unwrap()),
             _ => unreachable!(),
         }
     }
@@ -2729,6 +2846,14 @@ impl PathSegment {
     pub fn type_arg_list(&self) -> Option<&TypeArgList> {
         super::child_opt(self)
     }
+
+    pub fn param_list(&self) -> Option<&ParamList> {
+        super::child_opt(self)
+    }
+
+    pub fn ret_type(&self) -> Option<&RetType> {
+        super::child_opt(self)
+    }
 }
 
 // PathType
@@ -3416,6 +3541,10 @@ impl StaticDef {
     pub fn type_ref(&self) -> Option<&TypeRef> {
         super::child_opt(self)
     }
+
+    pub fn expr(&self) -> Option<&Expr> {
+        super::child_opt(self)
+    }
 }
 
 // Stmt
@@ -3659,7 +3788,11 @@ impl ast::NameOwner for TraitDef {}
 impl ast::AttrsOwner for TraitDef {}
 impl ast::DocCommentsOwner for TraitDef {}
 impl ast::TypeParamsOwner for TraitDef {}
-impl TraitDef {}
+impl TraitDef {
+    pub fn item_list(&self) -> Option<&ItemList> {
+        super::child_opt(self)
+    }
+}
 
 // TrueKw
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -4131,6 +4264,7 @@ impl ToOwned for UseItem {
 }
 
 
+impl ast::AttrsOwner for UseItem {}
 impl UseItem {
     pub fn use_tree(&self) -> Option<&UseTree> {
         super::child_opt(self)
@@ -4171,6 +4305,10 @@ impl UseTree {
     pub fn use_tree_list(&self) -> Option<&UseTreeList> {
         super::child_opt(self)
     }
+
+    pub fn alias(&self) -> Option<&Alias> {
+        super::child_opt(self)
+    }
 }
 
 // UseTreeList
@@ -4259,7 +4397,43 @@ impl ToOwned for WhereClause {
 }
 
 
-impl WhereClause {}
+impl WhereClause {
+    pub fn predicates(&self) -> impl Iterator<Item = &WherePred> {
+        super::children(self)
+    }
+}
+
+// WherePred
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct WherePred {
+    pub(crate) syntax: SyntaxNode,
+}
+unsafe impl TransparentNewType for WherePred {
+    type Repr = rowan::SyntaxNode<RaTypes>;
+}
+
+impl AstNode for WherePred {
+    fn cast(syntax: &SyntaxNode) -> Option<&Self> {
+        match syntax.kind() {
+            WHERE_PRED => Some(WherePred::from_repr(syntax.into_repr())),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode { &self.syntax }
+}
+
+impl ToOwned for WherePred {
+    type Owned = TreeArc<WherePred>;
+    fn to_owned(&self) -> TreeArc<WherePred> { TreeArc::cast(self.syntax.to_owned()) }
+}
+
+
+impl WherePred {
+    pub fn type_ref(&self) -> Option<&TypeRef> {
+        super::child_opt(self)
+    }
+}
 
 // WhileExpr
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -4289,6 +4463,10 @@ impl ToOwned for WhileExpr {
 
 impl ast::LoopBodyOwner for WhileExpr {}
 impl WhileExpr {
+    pub fn label(&self) -> Option<&Label> {
+        super::child_opt(self)
+    }
+
     pub fn condition(&self) -> Option<&Condition> {
         super::child_opt(self)
     }
@@ -44,54 +44,64 @@ pub(crate) fn block(p: &mut Parser) {
     p.bump();
 
     while !p.at(EOF) && !p.at(R_CURLY) {
-        match p.current() {
-            LET_KW => let_stmt(p),
-            // test nocontentexpr
-            // fn foo(){
-            //     ;;;some_expr();;;;{;;;};;;;Ok(())
-            // }
-            SEMI => p.bump(),
-            _ => {
-                // test block_items
-                // fn a() { fn b() {} }
-                let m = p.start();
-                match items::maybe_item(p, items::ItemFlavor::Mod) {
-                    items::MaybeItem::Item(kind) => {
-                        m.complete(p, kind);
-                    }
-                    items::MaybeItem::Modifiers => {
-                        m.abandon(p);
-                        p.error("expected an item");
-                    }
-                    // test pub_expr
-                    // fn foo() { pub 92; } //FIXME
-                    items::MaybeItem::None => {
-                        let is_blocklike = expressions::expr_stmt(p) == BlockLike::Block;
-                        if p.at(R_CURLY) {
-                            m.abandon(p);
-                        } else {
-                            // test no_semi_after_block
-                            // fn foo() {
-                            //     if true {}
-                            //     loop {}
-                            //     match () {}
-                            //     while true {}
-                            //     for _ in () {}
-                            //     {}
-                            //     {}
-                            //     macro_rules! test {
-                            //          () => {}
-                            //     }
-                            //     test!{}
-                            // }
-                            if is_blocklike {
-                                p.eat(SEMI);
-                            } else {
-                                p.expect(SEMI);
-                            }
-                            m.complete(p, EXPR_STMT);
-                        }
+        // test nocontentexpr
+        // fn foo(){
+        //     ;;;some_expr();;;;{;;;};;;;Ok(())
+        // }
+        if p.at(SEMI) {
+            p.bump();
+            continue;
+        }
+        // test block_items
+        // fn a() { fn b() {} }
+        let m = p.start();
+        // test stmt_outer_attrs
+        // fn foo() {
+        //     #[cfg(test)]
+        //     let x = 1;
+        //     #[cfg(test)]
+        //     bar();
+        // }
+        attributes::outer_attributes(p);
+        if p.at(LET_KW) {
+            let_stmt(p, m);
+            continue;
+        }
+        match items::maybe_item(p, items::ItemFlavor::Mod) {
+            items::MaybeItem::Item(kind) => {
+                m.complete(p, kind);
+            }
+            items::MaybeItem::Modifiers => {
+                m.abandon(p);
+                p.error("expected an item");
+            }
+            // test pub_expr
+            // fn foo() { pub 92; } //FIXME
+            items::MaybeItem::None => {
+                let is_blocklike = expressions::expr_stmt(p) == BlockLike::Block;
+                if p.at(R_CURLY) {
+                    m.abandon(p);
+                } else {
+                    // test no_semi_after_block
+                    // fn foo() {
+                    //     if true {}
+                    //     loop {}
+                    //     match () {}
+                    //     while true {}
+                    //     for _ in () {}
+                    //     {}
+                    //     {}
+                    //     macro_rules! test {
+                    //          () => {}
+                    //     }
+                    //     test!{}
+                    // }
+                    if is_blocklike {
+                        p.eat(SEMI);
+                    } else {
+                        p.expect(SEMI);
                     }
+                    m.complete(p, EXPR_STMT);
                 }
             }
         }
@@ -106,9 +116,8 @@ pub(crate) fn block(p: &mut Parser) {
     //     let c = 92;
     //     let d: i32 = 92;
     // }
-    fn let_stmt(p: &mut Parser) {
+    fn let_stmt(p: &mut Parser, m: Marker) {
         assert!(p.at(LET_KW));
-        let m = p.start();
         p.bump();
         patterns::pattern(p);
         if p.at(COLON) {
@@ -355,13 +355,22 @@ pub(crate) fn match_arm_list(p: &mut Parser) {
 // }
 fn match_arm(p: &mut Parser) -> BlockLike {
     let m = p.start();
+    // test match_arm_outer_attrs
+    // fn foo() {
+    //     match () {
+    //         #[cfg(test)]
+    //         () => (),
+    //         _ => (),
+    //     }
+    // }
+    attributes::outer_attributes(p);
     p.eat(PIPE);
     patterns::pattern_r(p, TokenSet::empty());
     while p.eat(PIPE) {
         patterns::pattern(p);
     }
-    if p.eat(IF_KW) {
-        expr(p);
+    if p.at(IF_KW) {
+        match_guard(p);
     }
     p.expect(FAT_ARROW);
     let ret = expr_stmt(p);
@@ -369,6 +378,20 @@ fn match_arm(p: &mut Parser) -> BlockLike {
     ret
 }
 
+// test match_guard
+// fn foo() {
+//     match () {
+//         _ if foo => (),
+//     }
+// }
+fn match_guard(p: &mut Parser) -> CompletedMarker {
+    assert!(p.at(IF_KW));
+    let m = p.start();
+    p.bump();
+    expr(p);
+    m.complete(p, MATCH_GUARD)
+}
+
 // test block_expr
 // fn foo() {
 //     {};
@@ -247,7 +247,15 @@ fn extern_crate_item(p: &mut Parser) {
     p.bump();
     assert!(p.at(CRATE_KW));
     p.bump();
-    name(p);
+    // test extern_crate_self
+    // extern crate self as foo;
+    if p.at(SELF_KW) {
+        let m = p.start();
+        p.bump();
+        m.complete(p, NAME_REF);
+    } else {
+        name_ref(p);
+    }
     opt_alias(p);
     p.expect(SEMI);
 }
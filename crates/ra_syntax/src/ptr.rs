@@ -21,6 +21,12 @@ impl SyntaxNodePtr {
         }
     }
 
+    /// Creates a pointer to a node which doesn't necessarily exist in the file,
+    /// like a node from a macro expansion mapped back to the macro call.
+    pub fn from_range(range: TextRange, kind: SyntaxKind) -> SyntaxNodePtr {
+        SyntaxNodePtr { range, kind }
+    }
+
     pub fn to_node(self, source_file: &SourceFile) -> &SyntaxNode {
         generate(Some(source_file.syntax()), |&node| {
             node.children()
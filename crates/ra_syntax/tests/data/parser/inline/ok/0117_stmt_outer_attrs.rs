@@ -0,0 +1,6 @@
+fn foo() {
+    #[cfg(test)]
+    let x = 1;
+    #[cfg(test)]
+    bar();
+}
@@ -0,0 +1,7 @@
+fn foo() {
+    match () {
+        #[cfg(test)]
+        () => (),
+        _ => (),
+    }
+}
@@ -0,0 +1 @@
+extern crate self as foo;
@@ -6,6 +6,7 @@ const RA_LSP_DEBUG = process.env.__RA_LSP_SERVER_DEBUG;
 
 export class Config {
     public highlightingOn = true;
+    public cfgTest = true;
     public raLspServerPath = RA_LSP_DEBUG || 'ra_lsp_server';
 
     constructor() {
@@ -25,6 +26,10 @@ export class Config {
             Server.highlighter.removeHighlights();
         }
 
+        if (config.has('cfgTest')) {
+            this.cfgTest = config.get('cfgTest') as boolean;
+        }
+
         if (config.has('raLspServerPath')) {
             this.raLspServerPath =
                 RA_LSP_DEBUG || (config.get('raLspServerPath') as string);
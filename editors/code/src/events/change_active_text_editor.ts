@@ -20,4 +20,18 @@ export async function handle(editor: TextEditor | undefined) {
         params
     );
     Server.highlighter.setHighlights(editor, decorations);
+    await requestUnsafeHighlights(editor);
+}
+
+// Highlighting unsafe operations needs type inference, so unlike the syntax
+// highlighting it is only updated when switching to or saving a file.
+export async function requestUnsafeHighlights(editor: TextEditor) {
+    const params: TextDocumentIdentifier = {
+        uri: editor.document.uri.toString()
+    };
+    const decorations = await Server.client.sendRequest<Decoration[]>(
+        'm/unsafeDecorationsRequest',
+        params
+    );
+    Server.highlighter.setUnsafeHighlights(editor, decorations);
 }
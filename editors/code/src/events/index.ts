@@ -1,4 +1,5 @@
 import * as changeActiveTextEditor from './change_active_text_editor';
 import * as changeTextDocument from './change_text_document';
+import * as saveTextDocument from './save_text_document';
 
-export { changeActiveTextEditor, changeTextDocument };
+export { changeActiveTextEditor, changeTextDocument, saveTextDocument };
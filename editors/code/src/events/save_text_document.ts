@@ -0,0 +1,17 @@
+import * as vscode from 'vscode';
+
+import { Server } from '../server';
+import { requestUnsafeHighlights } from './change_active_text_editor';
+
+export async function handle(document: vscode.TextDocument) {
+    if (!Server.config.highlightingOn || document.languageId !== 'rust') {
+        return;
+    }
+    const editor = vscode.window.visibleTextEditors.find(
+        it => it.document === document
+    );
+    if (!editor) {
+        return;
+    }
+    await requestUnsafeHighlights(editor);
+}
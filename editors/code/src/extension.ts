@@ -90,6 +90,11 @@ export function activate(context: vscode.ExtensionContext) {
         null,
         context.subscriptions
     );
+    vscode.workspace.onDidSaveTextDocument(
+        events.saveTextDocument.handle,
+        null,
+        context.subscriptions
+    );
 
     // Start the language server, finally!
     Server.start(allNotifications);
@@ -29,7 +29,9 @@ export class Highlighter {
             ['text', decor('#DCDCCC')],
             ['attribute', decor('#BFEBBF')],
             ['literal', decor('#DFAF8F')],
-            ['macro', decor('#DFAF8F')]
+            ['macro', decor('#DFAF8F')],
+            ['inactive', decor('#7F7F7F')],
+            ['unsafe', decor('#BC8383')]
         ];
 
         return new Map<string, vscode.TextEditorDecorationType>(decorations);
@@ -54,6 +56,23 @@ export class Highlighter {
     }
 
     public setHighlights(editor: vscode.TextEditor, highlights: Decoration[]) {
+        // Unsafe operations are highlighted by a separate request, see
+        // `setUnsafeHighlights`
+        this.applyHighlights(editor, highlights, tag => tag !== 'unsafe');
+    }
+
+    public setUnsafeHighlights(
+        editor: vscode.TextEditor,
+        highlights: Decoration[]
+    ) {
+        this.applyHighlights(editor, highlights, tag => tag === 'unsafe');
+    }
+
+    private applyHighlights(
+        editor: vscode.TextEditor,
+        highlights: Decoration[],
+        isOwnTag: (tag: string) => boolean
+    ) {
         // Initialize decorations if necessary
         //
         // Note: decoration objects need to be kept around so we can dispose them
@@ -64,7 +83,9 @@ export class Highlighter {
 
         const byTag: Map<string, vscode.Range[]> = new Map();
         for (const tag of this.decorations.keys()) {
-            byTag.set(tag, []);
+            if (isOwnTag(tag)) {
+                byTag.set(tag, []);
+            }
         }
 
         for (const d of highlights) {
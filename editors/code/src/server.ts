@@ -22,7 +22,8 @@ export class Server {
         const clientOptions: lc.LanguageClientOptions = {
             documentSelector: [{ scheme: 'file', language: 'rust' }],
             initializationOptions: {
-                publishDecorations: true
+                publishDecorations: true,
+                cfgTest: Server.config.cfgTest
             }
         };
 
@@ -43,7 +44,10 @@ export class Server {
                 if (typeof messageOrDataObject === 'string') {
                     if (
                         messageOrDataObject.includes('m/publishDecorations') ||
-                        messageOrDataObject.includes('m/decorationsRequest')
+                        messageOrDataObject.includes('m/decorationsRequest') ||
+                        messageOrDataObject.includes(
+                            'm/unsafeDecorationsRequest'
+                        )
                     ) {
                         // Don't log publish decorations requests
                     } else {